@@ -0,0 +1,163 @@
+//! Functional automatic differentiation transforms.
+//!
+//! These helpers operate on closures taking a slice of tensors and returning a single tensor,
+//! the inputs are turned into fresh variables before calling the closure so the caller does
+//! not have to handle the [`Var`] wrapping.
+//!
+//! ```rust
+//! use candle_core::{autograd, Device, Tensor};
+//! let x = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?;
+//! // f(x) = sum(x^3)
+//! let f = |xs: &[Tensor]| xs[0].powf(3.)?.sum_all();
+//! let jac = autograd::jacobian(f, &[x.clone()])?;
+//! assert_eq!(jac[0].to_vec1::<f32>()?, &[3., 12., 27.]);
+//! let hess = autograd::hessian(f, &[x])?;
+//! assert_eq!(hess[0][0].to_vec2::<f32>()?, &[[6., 0., 0.], [0., 12., 0.], [0., 0., 18.]]);
+//! # Ok::<(), candle_core::Error>(())
+//! ```
+use crate::backprop::GradStore;
+use crate::{Error, Result, Tensor, Var};
+
+fn make_vars(inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+    inputs
+        .iter()
+        .map(|t| Ok(Var::from_tensor(t)?.as_tensor().clone()))
+        .collect()
+}
+
+// Returns the gradient of each variable, zeros are used for the variables that do not
+// contribute to the differentiated value.
+fn grads_for(grads: &GradStore, vars: &[Tensor]) -> Result<Vec<Tensor>> {
+    vars.iter()
+        .map(|v| match grads.get(v) {
+            Some(grad) => Ok(grad.clone()),
+            None => v.zeros_like(),
+        })
+        .collect()
+}
+
+fn check_same_shape(lhs: &Tensor, rhs: &Tensor, op: &'static str) -> Result<()> {
+    if lhs.shape() != rhs.shape() {
+        Err(Error::ShapeMismatchBinaryOp {
+            lhs: lhs.shape().clone(),
+            rhs: rhs.shape().clone(),
+            op,
+        }
+        .bt())?
+    }
+    Ok(())
+}
+
+// Jacobian of `out` with respect to `vars`, the element `i` of the result has shape
+// `out.dims() ++ vars[i].dims()`. This uses one backward pass per element of `out`.
+fn jacobian_of(out: &Tensor, vars: &[Tensor]) -> Result<Vec<Tensor>> {
+    let flat_out = out.flatten_all()?;
+    let mut rows: Vec<Vec<Tensor>> = vec![vec![]; vars.len()];
+    for idx in 0..flat_out.elem_count() {
+        let grads = flat_out.narrow(0, idx, 1)?.sum_all()?.backward()?;
+        for (row, grad) in rows.iter_mut().zip(grads_for(&grads, vars)?) {
+            row.push(grad.flatten_all()?)
+        }
+    }
+    rows.iter()
+        .zip(vars.iter())
+        .map(|(row, var)| {
+            let mut dims = out.dims().to_vec();
+            dims.extend_from_slice(var.dims());
+            if row.is_empty() {
+                Tensor::zeros(dims, var.dtype(), var.device())
+            } else {
+                Tensor::stack(row, 0)?.reshape(dims)
+            }
+        })
+        .collect()
+}
+
+/// Vector-Jacobian product of `f` evaluated at `inputs` with the cotangent `v`, this is the
+/// gradient of `(f(inputs) * v).sum()` with respect to each input.
+///
+/// Returns the value of `f(inputs)` together with the products, one per input. The cotangent `v`
+/// must have the same shape as the output of `f`.
+pub fn vjp<F>(f: F, inputs: &[Tensor], v: &Tensor) -> Result<(Tensor, Vec<Tensor>)>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor>,
+{
+    let vars = make_vars(inputs)?;
+    let out = f(&vars)?;
+    check_same_shape(&out, v, "vjp")?;
+    let grads = out.mul(v)?.sum_all()?.backward()?;
+    Ok((out.detach()?, grads_for(&grads, &vars)?))
+}
+
+/// Jacobian-vector product of `f` evaluated at `inputs` with the tangents `tangents`, i.e. the
+/// directional derivative of `f` along `tangents`.
+///
+/// Returns the value of `f(inputs)` together with the product which has the same shape as the
+/// output of `f`. There must be one tangent per input, with the same shape as this input.
+///
+/// The product is computed with two reverse mode passes: the vector-Jacobian product
+/// `u -> J^T u` is linear in `u` so differentiating `(J^T u) . tangents` with respect to `u`
+/// yields `J tangents`.
+pub fn jvp<F>(f: F, inputs: &[Tensor], tangents: &[Tensor]) -> Result<(Tensor, Tensor)>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor>,
+{
+    if inputs.len() != tangents.len() {
+        crate::bail!(
+            "jvp: got {} inputs but {} tangents",
+            inputs.len(),
+            tangents.len()
+        )
+    }
+    for (input, tangent) in inputs.iter().zip(tangents.iter()) {
+        check_same_shape(input, tangent, "jvp")?
+    }
+    let vars = make_vars(inputs)?;
+    let out = f(&vars)?;
+    let u = Var::zeros(out.shape(), out.dtype(), out.device())?;
+    let grads = out.mul(&u)?.sum_all()?.backward_with_graph()?;
+    let mut dot = Tensor::zeros((), out.dtype(), out.device())?;
+    for (grad, tangent) in grads_for(&grads, &vars)?.iter().zip(tangents.iter()) {
+        dot = (dot + grad.mul(tangent)?.sum_all()?)?
+    }
+    let jvp = match dot.backward()?.get(&u) {
+        Some(grad) => grad.clone(),
+        None => out.zeros_like()?,
+    };
+    Ok((out.detach()?, jvp))
+}
+
+/// Jacobian of `f` evaluated at `inputs`, one tensor is returned per input and the tensor for
+/// input `i` has shape `f(inputs).dims() ++ inputs[i].dims()`.
+///
+/// This runs one backward pass per element of the output of `f`.
+pub fn jacobian<F>(f: F, inputs: &[Tensor]) -> Result<Vec<Tensor>>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor>,
+{
+    let vars = make_vars(inputs)?;
+    let out = f(&vars)?;
+    jacobian_of(&out, &vars)
+}
+
+/// Hessian of the scalar valued function `f` evaluated at `inputs`. The element `[i][j]` of
+/// the result contains the second order derivatives with respect to inputs `i` and `j` and has
+/// shape `inputs[i].dims() ++ inputs[j].dims()`.
+pub fn hessian<F>(f: F, inputs: &[Tensor]) -> Result<Vec<Vec<Tensor>>>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor>,
+{
+    let vars = make_vars(inputs)?;
+    let out = f(&vars)?;
+    if out.elem_count() != 1 {
+        crate::bail!(
+            "hessian: f should return a scalar, got shape {:?}",
+            out.shape()
+        )
+    }
+    let grads = out.sum_all()?.backward_with_graph()?;
+    grads_for(&grads, &vars)?
+        .iter()
+        .map(|grad| jacobian_of(grad, &vars))
+        .collect()
+}
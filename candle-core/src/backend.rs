@@ -1,4 +1,4 @@
-use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, ScanOp, UnaryOpT};
 use crate::{CpuStorage, DType, Layout, Result, Shape};
 
 pub trait BackendStorage: Sized {
@@ -21,6 +21,21 @@ pub trait BackendStorage: Sized {
 
     fn reduce_op(&self, _: ReduceOp, _: &Layout, _: &[usize]) -> Result<Self>;
 
+    /// Cumulative scan along a dimension, the result is contiguous and has the same shape as the
+    /// input.
+    fn scan(&self, _: &Layout, _: ScanOp, _: usize) -> Result<Self>;
+
+    /// Linear recurrence `h_i = a_i * h_{i-1} + b_i` along a dimension, starting from
+    /// `h_{-1} = 0`. `self` holds the `a` values and the argument the `b` values, both have the
+    /// same shape and the result is contiguous.
+    fn linear_recurrence(&self, _: &Layout, _: &Self, _: &Layout, _: usize) -> Result<Self>;
+
+    fn arg_sort_last_dim(&self, _: &Layout, _: bool) -> Result<Self>;
+
+    /// Discrete Fourier transform over the last dimension of a contiguous complex tensor. The
+    /// inverse transform is normalized by the size of this dimension.
+    fn fft_last_dim(&self, _: &Layout, _: bool) -> Result<Self>;
+
     fn cmp(&self, _: CmpOp, _: &Self, _: &Layout, _: &Layout) -> Result<Self>;
 
     fn to_dtype(&self, _: &Layout, _: DType) -> Result<Self>;
@@ -63,8 +78,36 @@ pub trait BackendStorage: Sized {
         _params: &crate::conv::ParamsConvTranspose2D,
     ) -> Result<Self>;
 
+    fn conv3d(
+        &self,
+        _l: &Layout,
+        _kernel: &Self,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConv3D,
+    ) -> Result<Self>;
+
+    fn conv_transpose3d(
+        &self,
+        _l: &Layout,
+        _kernel: &Self,
+        _kernel_l: &Layout,
+        _params: &crate::conv::ParamsConvTranspose3D,
+    ) -> Result<Self>;
+
     fn avg_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self>;
     fn max_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self>;
+    fn avg_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self>;
+    fn max_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self>;
     fn upsample_nearest1d(&self, _: &Layout, _: usize) -> Result<Self>;
     fn upsample_nearest2d(&self, _: &Layout, _: usize, _: usize) -> Result<Self>;
 
@@ -1,5 +1,5 @@
-use crate::op::{BinaryOp, Op, ReduceOp, UnaryOp};
-use crate::{Error, Result, Tensor, TensorId};
+use crate::op::{BinaryOp, Op, ReduceOp, ScanOp, UnaryOp};
+use crate::{Device, Error, Result, Tensor, TensorId, D};
 use std::collections::HashMap;
 
 // arg has been reduced to node via reduce_dims, expand it back to arg.
@@ -15,6 +15,164 @@ fn broadcast_back(arg: &Tensor, node: &Tensor, reduced_dims: &[usize]) -> Result
     }
 }
 
+// The source indexes used by nearest-neighbor upsampling from `src_sz` to `dst_sz` elements,
+// this follows the computation done in the backends.
+fn upsample_nearest_ids(src_sz: usize, dst_sz: usize, device: &Device) -> Result<Tensor> {
+    let scale = src_sz as f64 / dst_sz as f64;
+    let ids = (0..dst_sz)
+        .map(|idx| usize::min(src_sz - 1, (idx as f64 * scale) as usize) as u32)
+        .collect::<Vec<_>>();
+    Tensor::from_vec(ids, dst_sz, device)
+}
+
+// The gradient of `arg` for `node`, the cumulative scan of `arg` along `dim`.
+fn scan_grad(arg: &Tensor, node: &Tensor, grad: &Tensor, op: ScanOp, dim: usize) -> Result<Tensor> {
+    let rev_cumsum = |xs: &Tensor| xs.flip(dim)?.cumsum(dim)?.flip(dim);
+    match op {
+        ScanOp::Sum => rev_cumsum(grad),
+        ScanOp::Prod => {
+            // The elements before the first zero can use a division by the element value, the
+            // first zero gets the gradient of the products where it is replaced by one, and all
+            // the following elements have a zero gradient.
+            let is_zero = arg.eq(0.)?.to_dtype(arg.dtype())?;
+            let n_zeros = is_zero.cumsum(dim)?;
+            let before_zero = n_zeros.eq(0.)?;
+            let first_zero = is_zero.mul(&n_zeros)?.eq(1.)?;
+            let ones = arg.ones_like()?;
+            let grad_before =
+                rev_cumsum(&grad.mul(node)?)?.div(&before_zero.where_cond(arg, &ones)?)?;
+            let prod_without_zero = first_zero.where_cond(&ones, arg)?.cumprod(dim)?;
+            let grad_first = rev_cumsum(&grad.mul(&prod_without_zero)?)?;
+            let grad_first = first_zero.where_cond(&grad_first, &arg.zeros_like()?)?;
+            before_zero.where_cond(&grad_before, &grad_first)
+        }
+        ScanOp::Max | ScanOp::Min => {
+            // Each output element gets its gradient from the last element that reached the
+            // running extremum.
+            let size = arg.dim(dim)?;
+            let mut dims = vec![1; arg.rank()];
+            dims[dim] = size;
+            let positions = Tensor::arange(0u32, size as u32, arg.device())?
+                .reshape(dims)?
+                .broadcast_as(arg.shape())?;
+            let indexes = node
+                .eq(arg)?
+                .where_cond(&positions, &positions.zeros_like()?)?
+                .cummax(dim)?;
+            arg.zeros_like()?
+                .scatter_add(&indexes, &grad.contiguous()?, dim)
+        }
+        ScanOp::LogSumExp => {
+            // The partial derivative of node_i with respect to arg_j is exp(arg_j - node_i) for
+            // j <= i. The positive and negative parts of the gradient are accumulated separately
+            // in log space for numerical stability.
+            let rev_logcumsumexp = |xs: &Tensor| xs.flip(dim)?.logcumsumexp(dim)?.flip(dim);
+            let neg_inf = Tensor::full(f64::NEG_INFINITY, arg.shape(), arg.device())?
+                .to_dtype(arg.dtype())?;
+            let log_pos = grad.gt(0.)?.where_cond(&grad.log()?.sub(node)?, &neg_inf)?;
+            let log_neg = grad
+                .lt(0.)?
+                .where_cond(&grad.neg()?.log()?.sub(node)?, &neg_inf)?;
+            let pos = rev_logcumsumexp(&log_pos)?.add(arg)?.exp()?;
+            let neg = rev_logcumsumexp(&log_neg)?.add(arg)?.exp()?;
+            pos.sub(&neg)
+        }
+    }
+}
+
+// The indexes `offset + stride * i` for `i < len`.
+fn strided_ids(offset: usize, stride: usize, len: usize, device: &Device) -> Result<Tensor> {
+    let ids = (0..len)
+        .map(|i| (offset + stride * i) as u32)
+        .collect::<Vec<_>>();
+    Tensor::from_vec(ids, len, device)
+}
+
+// Adds the elements of `src` along `dim` to a zero tensor with `size` elements along this
+// dimension, the position of each element is given by `ids`.
+fn scatter_back(src: &Tensor, ids: &Tensor, dim: usize, size: usize) -> Result<Tensor> {
+    let mut dims = src.dims().to_vec();
+    dims[dim] = size;
+    Tensor::zeros(dims, src.dtype(), src.device())?.index_add(ids, &src.contiguous()?, dim)
+}
+
+// Gradient of an average pooling over the dimensions following the batch and channel ones. The
+// windows can overlap or leave some elements uncovered.
+fn avg_pool_backward(
+    grad: &Tensor,
+    arg: &Tensor,
+    kernel_size: &[usize],
+    stride: &[usize],
+) -> Result<Tensor> {
+    let mut grad = grad.clone();
+    for (i, (&k, &s)) in kernel_size.iter().zip(stride.iter()).enumerate() {
+        let dim = i + 2;
+        let (size, len) = (arg.dim(dim)?, grad.dim(dim)?);
+        let mut sum_grad: Option<Tensor> = None;
+        for offset in 0..k {
+            let ids = strided_ids(offset, s, len, arg.device())?;
+            let g = scatter_back(&grad, &ids, dim, size)?;
+            sum_grad = Some(match sum_grad {
+                None => g,
+                Some(sum_grad) => (sum_grad + g)?,
+            })
+        }
+        if let Some(sum_grad) = sum_grad {
+            grad = sum_grad
+        }
+    }
+    let volume = kernel_size.iter().product::<usize>();
+    grad * (1f64 / volume as f64)
+}
+
+// Gradient of a max pooling over the dimensions following the batch and channel ones. The
+// gradient of each window is split evenly between the elements reaching the maximum value.
+fn max_pool_backward(
+    grad: &Tensor,
+    node: &Tensor,
+    arg: &Tensor,
+    kernel_size: &[usize],
+    stride: &[usize],
+) -> Result<Tensor> {
+    let arg = arg.contiguous()?;
+    let device = arg.device();
+    let volume = kernel_size.iter().product::<usize>();
+    // The positions of the kernel elements, one index per spatial dimension.
+    let offsets = (0..volume)
+        .map(|mut idx| {
+            let mut offset = vec![0; kernel_size.len()];
+            for (o, &k) in offset.iter_mut().zip(kernel_size.iter()).rev() {
+                *o = idx % k;
+                idx /= k;
+            }
+            offset
+        })
+        .collect::<Vec<_>>();
+    let mut masks = Vec::with_capacity(volume);
+    let mut count = node.zeros_like()?;
+    for offset in offsets.iter() {
+        let mut xs = arg.clone();
+        for (i, (&o, &s)) in offset.iter().zip(stride.iter()).enumerate() {
+            let ids = strided_ids(o, s, node.dim(i + 2)?, device)?;
+            xs = xs.contiguous()?.index_select(&ids, i + 2)?;
+        }
+        let mask = xs.eq(node)?.to_dtype(arg.dtype())?;
+        count = (count + &mask)?;
+        masks.push(mask)
+    }
+    let grad = (grad / count)?;
+    let mut sum_grad = arg.zeros_like()?;
+    for (offset, mask) in offsets.iter().zip(masks.iter()) {
+        let mut g = (&grad * mask)?;
+        for (i, (&o, &s)) in offset.iter().zip(stride.iter()).enumerate() {
+            let ids = strided_ids(o, s, node.dim(i + 2)?, device)?;
+            g = scatter_back(&g, &ids, i + 2, arg.dim(i + 2)?)?;
+        }
+        sum_grad = (sum_grad + g)?;
+    }
+    Ok(sum_grad)
+}
+
 thread_local! {
     static CANDLE_GRAD_DO_NOT_DETACH: bool = {
         match std::env::var("CANDLE_GRAD_DO_NOT_DETACH") {
@@ -26,18 +184,114 @@ thread_local! {
     }
 }
 
+/// A callback run on the gradient of a tensor during the backward pass, see
+/// [`Tensor::register_hook`].
+pub(crate) type GradHook = std::sync::Arc<dyn Fn(&Tensor) -> Result<Option<Tensor>> + Send + Sync>;
+
+fn apply_hooks(hooks: &[GradHook], mut grad: Tensor) -> Result<Tensor> {
+    for hook in hooks.iter() {
+        if let Some(new_grad) = hook(&grad)? {
+            if new_grad.shape() != grad.shape() {
+                Err(Error::ShapeMismatchBinaryOp {
+                    lhs: grad.shape().clone(),
+                    rhs: new_grad.shape().clone(),
+                    op: "register_hook",
+                }
+                .bt())?
+            }
+            grad = new_grad
+        }
+    }
+    Ok(grad)
+}
+
+// Errors out if a tensor needed to compute the gradients of the arguments of `node` has been
+// modified in place since `node` was created.
+fn check_versions(node: &Tensor) -> Result<()> {
+    let op = match node.op() {
+        Some(op) => op.name(),
+        None => return Ok(()),
+    };
+    for (arg, expected) in node.saved_versions().iter() {
+        let version = arg.version();
+        if version != *expected {
+            Err(Error::ModifiedInPlace {
+                op,
+                expected: *expected,
+                version,
+            }
+            .bt())?
+        }
+    }
+    Ok(())
+}
+
+fn is_before(node: &Tensor, boundary: Option<TensorId>) -> bool {
+    match boundary {
+        None => false,
+        Some(boundary) => node.id() < boundary,
+    }
+}
+
+/// Runs `f` on `xs` without keeping the intermediate values of the computation alive, these
+/// get recomputed by running `f` again when backpropagating through the result. This trades
+/// some compute for a lower memory usage when training.
+///
+/// The closure can use variables and other tensors that it captures, the gradients are
+/// propagated to these as well. It should be deterministic as it gets called once in the
+/// forward pass and once per backward pass, e.g. dropout masks would differ between the calls.
+///
+/// ```rust
+/// use candle_core::{Device, Tensor, Var};
+/// let w = Var::new(&[1f32, 2., 3.], &Device::Cpu)?;
+/// let x = Var::new(&[0.5f32, 1., -1.], &Device::Cpu)?;
+/// let w_ = w.as_tensor().clone();
+/// let f = move |xs: &[Tensor]| xs[0].mul(&w_)?.exp()?.sum_all();
+/// let y = candle_core::checkpoint(f, &[x.as_tensor().clone()])?;
+/// let grads = y.backward()?;
+/// assert!(grads.get(&w).is_some());
+/// assert!(grads.get(&x).is_some());
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn checkpoint<F>(f: F, xs: &[Tensor]) -> Result<Tensor>
+where
+    F: Fn(&[Tensor]) -> Result<Tensor> + Send + Sync + 'static,
+{
+    let boundary = TensorId::new();
+    let out = f(xs)?;
+    if !out.track_op() || is_before(&out, Some(boundary)) {
+        return Ok(out);
+    }
+    let deps: Vec<Tensor> = out
+        .sorted_nodes(Some(boundary))
+        .into_iter()
+        .filter(|node| is_before(node, Some(boundary)))
+        .cloned()
+        .collect();
+    let f: std::sync::Arc<crate::op::CheckpointFn> = std::sync::Arc::new(f);
+    let op = crate::op::BackpropOp::new(&deps, |deps| Op::Checkpoint {
+        inputs: xs.to_vec(),
+        deps,
+        f: f.clone(),
+    });
+    // Dropping `out` releases the intermediate values, only its storage is kept around.
+    Ok(out.with_op(op))
+}
+
 impl Tensor {
     /// Return all the nodes that lead to this value in a topologically sorted vec, the first
     /// elements having dependencies on the latter ones, e.g. the first element if any is the
     /// argument.
     /// This assumes that the op graph is a DAG.
-    fn sorted_nodes(&self) -> Vec<&Tensor> {
+    /// When `boundary` is set, the nodes created before it are considered as leaves.
+    fn sorted_nodes(&self, boundary: Option<TensorId>) -> Vec<&Tensor> {
         // The vec of sorted nodes is passed as an owned value rather than a mutable reference
         // to get around some lifetime limitations.
         fn walk<'a>(
             node: &'a Tensor,
             nodes: Vec<&'a Tensor>,
             already_seen: &mut HashMap<TensorId, bool>,
+            boundary: Option<TensorId>,
         ) -> (bool, Vec<&'a Tensor>) {
             if let Some(&tg) = already_seen.get(&node.id()) {
                 return (tg, nodes);
@@ -47,7 +301,10 @@ impl Tensor {
                 // Do not call recursively on the "leaf" nodes.
                 track_grad = true;
                 nodes
-            } else if node.dtype().is_int() {
+            } else if is_before(node, boundary) {
+                track_grad = node.track_op();
+                nodes
+            } else if node.dtype().is_int() || node.dtype().is_bool() {
                 nodes
             } else if let Some(op) = node.op() {
                 match op {
@@ -55,11 +312,11 @@ impl Tensor {
                     | Op::ScatterAdd(t1, t2, t3, _)
                     | Op::CustomOp3(t1, t2, t3, _)
                     | Op::WhereCond(t1, t2, t3) => {
-                        let (tg, nodes) = walk(t1, nodes, already_seen);
+                        let (tg, nodes) = walk(t1, nodes, already_seen, boundary);
                         track_grad |= tg;
-                        let (tg, nodes) = walk(t2, nodes, already_seen);
+                        let (tg, nodes) = walk(t2, nodes, already_seen, boundary);
                         track_grad |= tg;
-                        let (tg, nodes) = walk(t3, nodes, already_seen);
+                        let (tg, nodes) = walk(t3, nodes, already_seen, boundary);
                         track_grad |= tg;
                         nodes
                     }
@@ -83,28 +340,41 @@ impl Tensor {
                         kernel: rhs,
                         ..
                     }
+                    | Op::Conv3D {
+                        arg: lhs,
+                        kernel: rhs,
+                        ..
+                    }
+                    | Op::ConvTranspose3D {
+                        arg: lhs,
+                        kernel: rhs,
+                        ..
+                    }
                     | Op::CustomOp2(lhs, rhs, _)
                     | Op::Binary(lhs, rhs, _)
                     | Op::Gather(lhs, rhs, _)
                     | Op::IndexSelect(lhs, rhs, _)
                     | Op::Matmul(lhs, rhs)
+                    | Op::LinearRecurrence(lhs, rhs, _)
                     | Op::SliceScatter0(lhs, rhs, _) => {
-                        let (tg, nodes) = walk(lhs, nodes, already_seen);
+                        let (tg, nodes) = walk(lhs, nodes, already_seen, boundary);
                         track_grad |= tg;
-                        let (tg, nodes) = walk(rhs, nodes, already_seen);
+                        let (tg, nodes) = walk(rhs, nodes, already_seen, boundary);
                         track_grad |= tg;
                         nodes
                     }
-                    Op::Cat(args, _) => args.iter().fold(nodes, |nodes, arg| {
-                        let (tg, nodes) = walk(arg, nodes, already_seen);
-                        track_grad |= tg;
-                        nodes
-                    }),
+                    Op::Cat(args, _) | Op::Checkpoint { deps: args, .. } => {
+                        args.iter().fold(nodes, |nodes, arg| {
+                            let (tg, nodes) = walk(arg, nodes, already_seen, boundary);
+                            track_grad |= tg;
+                            nodes
+                        })
+                    }
                     Op::Affine { arg, mul, .. } => {
                         if *mul == 0. {
                             nodes
                         } else {
-                            let (tg, nodes) = walk(arg, nodes, already_seen);
+                            let (tg, nodes) = walk(arg, nodes, already_seen, boundary);
                             track_grad |= tg;
                             nodes
                         }
@@ -117,25 +387,30 @@ impl Tensor {
                     | Op::UpsampleNearest2D { arg: node, .. }
                     | Op::AvgPool2D { arg: node, .. }
                     | Op::MaxPool2D { arg: node, .. }
+                    | Op::AvgPool3D { arg: node, .. }
+                    | Op::MaxPool3D { arg: node, .. }
                     | Op::Copy(node)
                     | Op::Broadcast(node)
                     | Op::Cmp(node, _)
                     | Op::Reduce(node, ReduceOp::Min | ReduceOp::Sum | ReduceOp::Max, _)
+                    | Op::Scan(node, _, _)
                     | Op::ToDevice(node)
                     | Op::Transpose(node, _, _)
                     | Op::Permute(node, _)
+                    | Op::AsStrided(node, _)
                     | Op::Narrow(node, _, _, _)
                     | Op::Unary(node, _)
                     | Op::Elu(node, _)
                     | Op::Powf(node, _)
+                    | Op::Fft(node, _)
                     | Op::CustomOp1(node, _) => {
-                        let (tg, nodes) = walk(node, nodes, already_seen);
+                        let (tg, nodes) = walk(node, nodes, already_seen, boundary);
                         track_grad |= tg;
                         nodes
                     }
                     Op::ToDType(node) => {
-                        if node.dtype().is_float() {
-                            let (tg, nodes) = walk(node, nodes, already_seen);
+                        if node.dtype().is_float() || node.dtype().is_complex() {
+                            let (tg, nodes) = walk(node, nodes, already_seen, boundary);
                             track_grad |= tg;
                             nodes
                         } else {
@@ -153,17 +428,64 @@ impl Tensor {
             }
             (track_grad, nodes)
         }
-        let (_tg, mut nodes) = walk(self, vec![], &mut HashMap::new());
+        let (_tg, mut nodes) = walk(self, vec![], &mut HashMap::new(), boundary);
         nodes.reverse();
         nodes
     }
 
+    /// Computes the gradients of this tensor with respect to all the variables it depends on.
+    /// The resulting gradients are detached from the computation graph.
     pub fn backward(&self) -> Result<GradStore> {
-        let sorted_nodes = self.sorted_nodes();
+        let do_not_detach = CANDLE_GRAD_DO_NOT_DETACH.with(|b| *b);
+        self.backward_impl(self.ones_like()?.contiguous()?, !do_not_detach, None)
+    }
+
+    /// Similar to [`Tensor::backward`] but the gradient computation itself is recorded in the
+    /// computation graph, so the returned gradients can be differentiated again. This can be
+    /// used for higher order derivatives, Hessian-vector products, or losses that depend on
+    /// gradients such as gradient penalties.
+    ///
+    /// ```rust
+    /// use candle_core::{Var, Device};
+    /// let x = Var::new(2f64, &Device::Cpu)?;
+    /// let y = x.powf(3.)?;
+    /// let dy = y.backward_with_graph()?.get(&x).unwrap().clone();
+    /// let d2y = dy.backward()?.get(&x).unwrap().clone();
+    /// assert_eq!(dy.to_scalar::<f64>()?, 12.);
+    /// assert_eq!(d2y.to_scalar::<f64>()?, 12.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn backward_with_graph(&self) -> Result<GradStore> {
+        self.backward_impl(self.ones_like()?.contiguous()?, false, None)
+    }
+
+    // Backpropagates `grad` from this tensor. When `boundary` is set, the gradients are not
+    // propagated past the nodes created before it and these nodes get their gradients in the
+    // returned store.
+    fn backward_impl(
+        &self,
+        grad: Tensor,
+        detach: bool,
+        boundary: Option<TensorId>,
+    ) -> Result<GradStore> {
+        let sorted_nodes = self.sorted_nodes(boundary);
         let mut grads = GradStore::new();
-        grads.insert(self, self.ones_like()?.contiguous()?);
+        grads.insert(self, grad);
         for node in sorted_nodes.iter() {
+            if is_before(node, boundary) {
+                // The hooks of these nodes are run by the outer backward pass.
+                continue;
+            }
             if node.is_variable() {
+                // All the nodes that use this variable have already been processed so its
+                // gradient is final at this point.
+                let hooks = node.hooks();
+                if !hooks.is_empty() {
+                    if let Some(grad) = grads.remove(node) {
+                        let grad = apply_hooks(&hooks, grad)?;
+                        grads.insert(node, grad);
+                    }
+                }
                 continue;
             }
             let grad = grads
@@ -172,10 +494,11 @@ impl Tensor {
             // https://github.com/huggingface/candle/issues/1241
             // Ideally, we would make these operations in place where possible to ensure that we
             // do not have to allocate too often. Here we just call `.detach` to avoid computing
-            // the backprop graph of the backprop itself. This would be an issue for second order
-            // derivatives but these are out of scope at the moment.
-            let do_not_detach = CANDLE_GRAD_DO_NOT_DETACH.with(|b| *b);
-            let grad = if do_not_detach { grad } else { grad.detach()? };
+            // the backprop graph of the backprop itself, this is skipped when the gradients
+            // have to remain differentiable.
+            let grad = if detach { grad.detach()? } else { grad };
+            let grad = apply_hooks(&node.hooks(), grad)?;
+            check_versions(node)?;
             if let Some(op) = node.op() {
                 match op {
                     Op::Binary(lhs, rhs, BinaryOp::Add) => {
@@ -190,19 +513,21 @@ impl Tensor {
                         let rhs_sum_grad = grads.or_insert(rhs)?;
                         *rhs_sum_grad = rhs_sum_grad.sub(&grad)?;
                     }
+                    // For complex values, the gradients use the conjugate of the partial
+                    // derivatives.
                     Op::Binary(lhs, rhs, BinaryOp::Mul) => {
-                        let lhs_grad = grad.mul(rhs)?;
+                        let lhs_grad = grad.mul(&rhs.conj()?)?;
                         let lhs_sum_grad = grads.or_insert(lhs)?;
                         *lhs_sum_grad = lhs_sum_grad.add(&lhs_grad)?;
-                        let rhs_grad = grad.mul(lhs)?;
+                        let rhs_grad = grad.mul(&lhs.conj()?)?;
                         let rhs_sum_grad = grads.or_insert(rhs)?;
                         *rhs_sum_grad = rhs_sum_grad.add(&rhs_grad)?;
                     }
                     Op::Binary(lhs, rhs, BinaryOp::Div) => {
-                        let lhs_grad = grad.div(rhs)?;
+                        let lhs_grad = grad.div(&rhs.conj()?)?;
                         let lhs_sum_grad = grads.or_insert(lhs)?;
                         *lhs_sum_grad = lhs_sum_grad.add(&lhs_grad)?;
-                        let rhs_grad = grad.mul(lhs)?.div(&rhs.sqr()?)?;
+                        let rhs_grad = grad.mul(&lhs.conj()?)?.div(&rhs.sqr()?.conj()?)?;
                         let rhs_sum_grad = grads.or_insert(rhs)?;
                         *rhs_sum_grad = rhs_sum_grad.sub(&rhs_grad)?;
                     }
@@ -306,24 +631,117 @@ impl Tensor {
                         };
                         *sum_grad = sum_grad.add(&grad_kernel)?;
                     }
-                    Op::ConvTranspose1D { .. } => Err(Error::BackwardNotSupported {
-                        op: "conv-transpose1d",
-                    })?,
-                    Op::ConvTranspose2D { .. } => Err(Error::BackwardNotSupported {
-                        op: "conv-transpose2d",
-                    })?,
+                    Op::ConvTranspose1D {
+                        arg,
+                        kernel,
+                        padding,
+                        output_padding: _,
+                        stride,
+                        dilation,
+                    } => {
+                        let grad_arg = grad.conv1d(kernel, *padding, *stride, *dilation, 1)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+
+                        let (_, _, k_size) = kernel.dims3()?;
+                        let grad_kernel = grad
+                            .transpose(0, 1)?
+                            .conv1d(&arg.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
+                            .transpose(0, 1)?
+                            .narrow(2, 0, k_size)?;
+                        let sum_grad = grads.or_insert(kernel)?;
+                        *sum_grad = sum_grad.add(&grad_kernel)?;
+                    }
+                    Op::ConvTranspose2D {
+                        arg,
+                        kernel,
+                        padding,
+                        output_padding: _,
+                        stride,
+                        dilation,
+                    } => {
+                        let grad_arg = grad.conv2d(kernel, *padding, *stride, *dilation, 1)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+
+                        let (_, _, k_h, k_w) = kernel.dims4()?;
+                        let grad_kernel = grad
+                            .transpose(0, 1)?
+                            .conv2d(&arg.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
+                            .transpose(0, 1)?
+                            .narrow(2, 0, k_h)?
+                            .narrow(3, 0, k_w)?;
+                        let sum_grad = grads.or_insert(kernel)?;
+                        *sum_grad = sum_grad.add(&grad_kernel)?;
+                    }
+                    Op::Conv3D {
+                        arg,
+                        kernel,
+                        padding,
+                        stride,
+                        dilation,
+                    } => {
+                        // The output padding can differ between the spatial dimensions, use the
+                        // largest one and narrow the result back to the input shape.
+                        let (_, _, k_d, k_h, k_w) = kernel.dims5()?;
+                        let (_, _, g_d, g_h, g_w) = grad.dims5()?;
+                        let (_, _, i_d, i_h, i_w) = arg.dims5()?;
+                        let out_padding = [(g_d, k_d, i_d), (g_h, k_h, i_h), (g_w, k_w, i_w)]
+                            .iter()
+                            .map(|&(g, k, i)| {
+                                i - ((g - 1) * stride + dilation * (k - 1) + 1 - 2 * padding)
+                            })
+                            .max()
+                            .unwrap_or(0);
+                        let grad_arg = grad
+                            .conv_transpose3d(kernel, *padding, out_padding, *stride, *dilation)?
+                            .narrow(2, 0, i_d)?
+                            .narrow(3, 0, i_h)?
+                            .narrow(4, 0, i_w)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+
+                        let grad_kernel = arg
+                            .transpose(0, 1)?
+                            .conv3d(&grad.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
+                            .transpose(0, 1)?
+                            .narrow(2, 0, k_d)?
+                            .narrow(3, 0, k_h)?
+                            .narrow(4, 0, k_w)?;
+                        let sum_grad = grads.or_insert(kernel)?;
+                        *sum_grad = sum_grad.add(&grad_kernel)?;
+                    }
+                    Op::ConvTranspose3D {
+                        arg,
+                        kernel,
+                        padding,
+                        output_padding: _,
+                        stride,
+                        dilation,
+                    } => {
+                        let grad_arg = grad.conv3d(kernel, *padding, *stride, *dilation, 1)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+
+                        let (_, _, k_d, k_h, k_w) = kernel.dims5()?;
+                        let grad_kernel = grad
+                            .transpose(0, 1)?
+                            .conv3d(&arg.transpose(0, 1)?, *padding, *dilation, *stride, 1)?
+                            .transpose(0, 1)?
+                            .narrow(2, 0, k_d)?
+                            .narrow(3, 0, k_h)?
+                            .narrow(4, 0, k_w)?;
+                        let sum_grad = grads.or_insert(kernel)?;
+                        *sum_grad = sum_grad.add(&grad_kernel)?;
+                    }
                     Op::AvgPool2D {
                         arg,
                         kernel_size,
                         stride,
                     } => {
-                        if kernel_size != stride {
-                            crate::bail!("backward not supported for avgpool2d if ksize {kernel_size:?} != stride {stride:?}")
-                        }
-                        let (_n, _c, h, w) = arg.dims4()?;
-                        let grad_arg = grad.upsample_nearest2d(h, w)?;
-                        let grad_arg =
-                            (grad_arg * (1f64 / (kernel_size.0 * kernel_size.1) as f64))?;
+                        let kernel_size = [kernel_size.0, kernel_size.1];
+                        let stride = [stride.0, stride.1];
+                        let grad_arg = avg_pool_backward(&grad, arg, &kernel_size, &stride)?;
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
                     }
@@ -332,44 +750,53 @@ impl Tensor {
                         kernel_size,
                         stride,
                     } => {
-                        if kernel_size != stride {
-                            crate::bail!("backward not supported for maxpool2d if ksize {kernel_size:?} != stride {stride:?}")
-                        }
-                        let (_n, _c, h, w) = arg.dims4()?;
-                        // For computing the max-pool gradient, we compute a mask where a 1 means
-                        // that the element is the maximum, then we apply this mask to the
-                        // upsampled gradient (taking into account that multiple max may exist so
-                        // we scale the gradient for this case).
-                        let node_upsampled = node.upsample_nearest2d(h, w)?;
-                        let mask = arg.eq(&node_upsampled)?.to_dtype(arg.dtype())?;
-                        let avg = mask.avg_pool2d_with_stride(*kernel_size, *stride)?;
-                        let grad_arg = ((grad * avg)?.upsample_nearest2d(h, w)? * mask)?;
+                        let kernel_size = [kernel_size.0, kernel_size.1];
+                        let stride = [stride.0, stride.1];
+                        let grad_arg = max_pool_backward(&grad, node, arg, &kernel_size, &stride)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+                    }
+                    Op::AvgPool3D {
+                        arg,
+                        kernel_size,
+                        stride,
+                    } => {
+                        let kernel_size = [kernel_size.0, kernel_size.1, kernel_size.2];
+                        let stride = [stride.0, stride.1, stride.2];
+                        let grad_arg = avg_pool_backward(&grad, arg, &kernel_size, &stride)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+                    }
+                    Op::MaxPool3D {
+                        arg,
+                        kernel_size,
+                        stride,
+                    } => {
+                        let kernel_size = [kernel_size.0, kernel_size.1, kernel_size.2];
+                        let stride = [stride.0, stride.1, stride.2];
+                        let grad_arg = max_pool_backward(&grad, node, arg, &kernel_size, &stride)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
+                    }
+                    Op::UpsampleNearest1D(arg) => {
+                        let (_n, _c, size) = arg.dims3()?;
+                        let ids = upsample_nearest_ids(size, grad.dim(2)?, arg.device())?;
+                        let grad_arg = scatter_back(&grad, &ids, 2, size)?;
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&grad_arg)?;
                     }
-                    Op::UpsampleNearest1D { .. } => Err(Error::BackwardNotSupported {
-                        op: "upsample-nearest1d",
-                    })?,
                     Op::UpsampleNearest2D {
                         arg,
                         target_h,
                         target_w,
                     } => {
-                        let (_n, c, h, w) = arg.dims4()?;
-                        if target_h % h != 0 || target_w % w != 0 {
-                            crate::bail!("backward not supported for non integer upscaling factors")
-                        }
-                        let scale_h = target_h / h;
-                        let scale_w = target_w / w;
-
-                        if scale_h != scale_w {
-                            crate::bail!("backward not supported for non uniform upscaling factors")
-                        };
-                        let kernel =
-                            Tensor::ones((c, 1, scale_h, scale_w), arg.dtype(), arg.device())?;
-                        let conv_sum = grad.conv2d(&kernel, 0, scale_h, 1, c)?;
+                        let (_n, _c, h, w) = arg.dims4()?;
+                        let ids_w = upsample_nearest_ids(w, *target_w, arg.device())?;
+                        let grad_arg = scatter_back(&grad, &ids_w, 3, w)?;
+                        let ids_h = upsample_nearest_ids(h, *target_h, arg.device())?;
+                        let grad_arg = scatter_back(&grad_arg, &ids_h, 2, h)?;
                         let sum_grad = grads.or_insert(arg)?;
-                        *sum_grad = conv_sum;
+                        *sum_grad = sum_grad.add(&grad_arg)?;
                     }
                     Op::SliceScatter0(lhs, rhs, start_rhs) => {
                         let rhs_sum_grad = grads.or_insert(rhs)?;
@@ -455,6 +882,31 @@ impl Tensor {
                         *sum_grad = sum_grad.add(&grad)?;
                     }
                     Op::Cmp(_args, _) => {}
+                    Op::Scan(arg, op, dim) => {
+                        let arg_grad = scan_grad(arg, node, &grad, *op, *dim)?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&arg_grad)?
+                    }
+                    Op::LinearRecurrence(a, b, dim) => {
+                        let dim = *dim;
+                        let size = node.dim(dim)?;
+                        if size > 0 {
+                            // The gradient of b_j is g_j + a_{j+1} * grad_b_{j+1}, a recurrence
+                            // running backward, and the gradient of a_i is grad_b_i * h_{i-1}.
+                            let a_next = a.narrow(dim, 1, size - 1)?.pad_with_zeros(dim, 0, 1)?;
+                            let grad_b = a_next
+                                .flip(dim)?
+                                .linear_recurrence(&grad.flip(dim)?, dim)?
+                                .flip(dim)?;
+                            let h_prev =
+                                node.narrow(dim, 0, size - 1)?.pad_with_zeros(dim, 1, 0)?;
+                            let grad_a = grad_b.mul(&h_prev)?;
+                            let sum_grad = grads.or_insert(a)?;
+                            *sum_grad = sum_grad.add(&grad_a)?;
+                            let sum_grad = grads.or_insert(b)?;
+                            *sum_grad = sum_grad.add(&grad_b)?;
+                        }
+                    }
                     Op::Reduce(arg, ReduceOp::Max, reduced_dims) => {
                         let node = broadcast_back(arg, node, reduced_dims)?;
                         let grad = broadcast_back(arg, &grad, reduced_dims)?;
@@ -604,6 +1056,31 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
+                    Op::Checkpoint { inputs, deps, f } => {
+                        // Run the closure again, this time recording the intermediate values,
+                        // and backpropagate through it down to the tensors it depends on.
+                        let boundary = TensorId::new();
+                        let out = f(inputs)?;
+                        let inner_grads = out.backward_impl(grad, detach, Some(boundary))?;
+                        for dep in deps.iter() {
+                            if let Some(dep_grad) = inner_grads.get(dep) {
+                                let sum_grad = grads.or_insert(dep)?;
+                                *sum_grad = sum_grad.add(dep_grad)?
+                            }
+                        }
+                    }
+                    Op::Fft(arg, inverse) => {
+                        // The forward transform is unnormalized whereas the inverse one is scaled
+                        // by 1/n, hence the scaling factors here.
+                        let n = arg.dim(D::Minus1)? as f64;
+                        let arg_grad = if *inverse {
+                            (grad.fft(D::Minus1)? / n)?
+                        } else {
+                            (grad.ifft(D::Minus1)? * n)?
+                        };
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&arg_grad)?
+                    }
                     Op::CustomOp1(arg, c) => {
                         if let Some(arg_grad) = c.bwd(arg, node, &grad)? {
                             let sum_grad = grads.or_insert(arg)?;
@@ -666,6 +1143,20 @@ impl Tensor {
                         let sum_grad = grads.or_insert(arg)?;
                         *sum_grad = sum_grad.add(&arg_grad)?
                     }
+                    Op::AsStrided(arg, layout) => {
+                        // Accumulate the gradient of each element of the view to the position
+                        // that it was taken from.
+                        let positions: Vec<u32> =
+                            layout.strided_index().map(|i| i as u32).collect();
+                        let positions =
+                            Tensor::from_vec(positions, grad.elem_count(), grad.device())?;
+                        let arg_grad =
+                            Tensor::zeros(arg.elem_count(), grad.dtype(), grad.device())?
+                                .index_add(&positions, &grad.flatten_all()?, 0)?
+                                .reshape(arg.shape())?;
+                        let sum_grad = grads.or_insert(arg)?;
+                        *sum_grad = sum_grad.add(&arg_grad)?
+                    }
                 };
             }
         }
@@ -1,58 +1,58 @@
 //! Implement conversion traits for tensors
-use crate::{DType, Device, Error, Tensor, WithDType};
+use crate::{DType, Device, ElemType, Error, Tensor};
 use half::{bf16, f16, slice::HalfFloatSliceExt};
 use std::convert::TryFrom;
 
-impl<T: WithDType> TryFrom<&Tensor> for Vec<T> {
+impl<T: ElemType> TryFrom<&Tensor> for Vec<T> {
     type Error = Error;
     fn try_from(tensor: &Tensor) -> Result<Self, Self::Error> {
         tensor.to_vec1::<T>()
     }
 }
 
-impl<T: WithDType> TryFrom<&Tensor> for Vec<Vec<T>> {
+impl<T: ElemType> TryFrom<&Tensor> for Vec<Vec<T>> {
     type Error = Error;
     fn try_from(tensor: &Tensor) -> Result<Self, Self::Error> {
         tensor.to_vec2::<T>()
     }
 }
 
-impl<T: WithDType> TryFrom<&Tensor> for Vec<Vec<Vec<T>>> {
+impl<T: ElemType> TryFrom<&Tensor> for Vec<Vec<Vec<T>>> {
     type Error = Error;
     fn try_from(tensor: &Tensor) -> Result<Self, Self::Error> {
         tensor.to_vec3::<T>()
     }
 }
 
-impl<T: WithDType> TryFrom<Tensor> for Vec<T> {
+impl<T: ElemType> TryFrom<Tensor> for Vec<T> {
     type Error = Error;
     fn try_from(tensor: Tensor) -> Result<Self, Self::Error> {
         Vec::<T>::try_from(&tensor)
     }
 }
 
-impl<T: WithDType> TryFrom<Tensor> for Vec<Vec<T>> {
+impl<T: ElemType> TryFrom<Tensor> for Vec<Vec<T>> {
     type Error = Error;
     fn try_from(tensor: Tensor) -> Result<Self, Self::Error> {
         Vec::<Vec<T>>::try_from(&tensor)
     }
 }
 
-impl<T: WithDType> TryFrom<Tensor> for Vec<Vec<Vec<T>>> {
+impl<T: ElemType> TryFrom<Tensor> for Vec<Vec<Vec<T>>> {
     type Error = Error;
     fn try_from(tensor: Tensor) -> Result<Self, Self::Error> {
         Vec::<Vec<Vec<T>>>::try_from(&tensor)
     }
 }
 
-impl<T: WithDType> TryFrom<&[T]> for Tensor {
+impl<T: ElemType> TryFrom<&[T]> for Tensor {
     type Error = Error;
     fn try_from(v: &[T]) -> Result<Self, Self::Error> {
         Tensor::from_slice(v, v.len(), &Device::Cpu)
     }
 }
 
-impl<T: WithDType> TryFrom<Vec<T>> for Tensor {
+impl<T: ElemType> TryFrom<Vec<T>> for Tensor {
     type Error = Error;
     fn try_from(v: Vec<T>) -> Result<Self, Self::Error> {
         let len = v.len();
@@ -93,8 +93,12 @@ from_tensor!(f32);
 from_tensor!(f16);
 from_tensor!(bf16);
 from_tensor!(i64);
+from_tensor!(i32);
+from_tensor!(i16);
+from_tensor!(i8);
 from_tensor!(u32);
 from_tensor!(u8);
+from_tensor!(bool);
 
 impl Tensor {
     pub fn write_bytes<W: std::io::Write>(&self, f: &mut W) -> crate::Result<()> {
@@ -135,10 +139,42 @@ impl Tensor {
                     f.write_i64::<LittleEndian>(v)?
                 }
             }
+            DType::I32 => {
+                for v in vs.to_vec1::<i32>()? {
+                    f.write_i32::<LittleEndian>(v)?
+                }
+            }
+            DType::I16 => {
+                for v in vs.to_vec1::<i16>()? {
+                    f.write_i16::<LittleEndian>(v)?
+                }
+            }
+            DType::I8 => {
+                for v in vs.to_vec1::<i8>()? {
+                    f.write_i8(v)?
+                }
+            }
             DType::U8 => {
                 let vs = vs.to_vec1::<u8>()?;
                 f.write_all(&vs)?;
             }
+            DType::Bool => {
+                for v in vs.to_vec1::<bool>()? {
+                    f.write_u8(u8::from(v))?
+                }
+            }
+            DType::C64 => {
+                for v in vs.to_vec1::<num_complex::Complex<f32>>()? {
+                    f.write_f32::<LittleEndian>(v.re)?;
+                    f.write_f32::<LittleEndian>(v.im)?
+                }
+            }
+            DType::C128 => {
+                for v in vs.to_vec1::<num_complex::Complex<f64>>()? {
+                    f.write_f64::<LittleEndian>(v.re)?;
+                    f.write_f64::<LittleEndian>(v.im)?
+                }
+            }
         }
         Ok(())
     }
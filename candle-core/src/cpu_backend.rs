@@ -1,7 +1,8 @@
 use crate::backend::{BackendDevice, BackendStorage};
-use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
-use crate::{DType, Error, IntDType, Layout, Result, Shape, WithDType};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, ScanOp, UnaryOpT};
+use crate::{DType, ElemType, Error, IntDType, Layout, Result, Shape, WithDType};
 use half::{bf16, f16};
+use num_complex::Complex;
 use rayon::prelude::*;
 
 const USE_IM2COL_CONV1D: bool = true;
@@ -11,25 +12,234 @@ const USE_IM2COL_CONV2D: bool = true;
 // intercept the oom errors to avoid panicking and provide a proper error.
 #[derive(Debug, Clone)]
 pub enum CpuStorage {
+    Bool(Vec<bool>),
     U8(Vec<u8>),
     U32(Vec<u32>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
     I64(Vec<i64>),
     BF16(Vec<bf16>),
     F16(Vec<f16>),
     F32(Vec<f32>),
     F64(Vec<f64>),
+    C64(Vec<Complex<f32>>),
+    C128(Vec<Complex<f64>>),
+}
+
+// The buffers that come from the caching allocator are handed back to it rather than being
+// freed by the vectors, see `memory::alloc_vec`.
+impl Drop for CpuStorage {
+    fn drop(&mut self) {
+        crate::memory::release(self)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CpuDevice;
 
+/// Configuration of the cpu backend, this carries a dedicated rayon thread pool. The tensors
+/// created on [`crate::Device::CpuPool`] with this configuration run their cpu ops on this pool
+/// and the tensors derived from them stay on the same device. The tensors on
+/// [`crate::Device::Cpu`] use the global rayon thread pool.
+///
+/// This makes it possible to serve several models from a single process without the ops of one
+/// model starving the others, each model gets its weights loaded on its own device.
+///
+/// ```rust
+/// use candle_core::{CpuDeviceConfig, DType, Device, Tensor};
+/// let dev = Device::CpuPool(CpuDeviceConfig::new(2)?);
+/// let xs = Tensor::ones((64, 64), DType::F32, &dev)?;
+/// let ys = xs.matmul(&xs)?;
+/// assert_eq!(ys.dims(), [64, 64]);
+/// assert!(ys.device().same_device(&dev));
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CpuDeviceConfig {
+    pool: std::sync::Arc<rayon::ThreadPool>,
+    affinity: Option<Vec<usize>>,
+}
+
+impl CpuDeviceConfig {
+    /// Creates a configuration with a pool of `num_threads` threads.
+    pub fn new(num_threads: usize) -> Result<Self> {
+        Self::build(num_threads, None)
+    }
+
+    /// Creates a configuration with a pool of `num_threads` threads, thread `i` gets pinned to
+    /// the cpu core `cores[i % cores.len()]`. The cores have to be part of the cores that the
+    /// current thread is allowed to run on. This is only supported on linux.
+    pub fn with_affinity(num_threads: usize, cores: &[usize]) -> Result<Self> {
+        let allowed = allowed_cores()?;
+        if cores.is_empty() || cores.iter().any(|core| !allowed.contains(core)) {
+            crate::bail!("invalid cpu cores {cores:?}, the allowed cores are {allowed:?}")
+        }
+        Self::build(num_threads, Some(cores.to_vec()))
+    }
+
+    fn build(num_threads: usize, affinity: Option<Vec<usize>>) -> Result<Self> {
+        if num_threads == 0 {
+            crate::bail!("the number of threads of a cpu device should be positive")
+        }
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|idx| format!("candle-cpu-{idx}"))
+            .build()
+            .map_err(Error::wrap)?;
+        if let Some(cores) = affinity.as_ref() {
+            let pinned = pool.broadcast(|ctx| pin_current_thread(cores[ctx.index() % cores.len()]));
+            pinned.into_iter().collect::<Result<Vec<_>>>()?;
+        }
+        Ok(Self {
+            pool: std::sync::Arc::new(pool),
+            affinity,
+        })
+    }
+
+    /// The number of threads in the pool.
+    pub fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// The cpu cores that the threads of the pool are pinned to, if any.
+    pub fn affinity(&self) -> Option<&[usize]> {
+        self.affinity.as_deref()
+    }
+
+    /// Returns true if both configurations use the same thread pool.
+    pub fn same_pool(&self, rhs: &Self) -> bool {
+        std::sync::Arc::ptr_eq(&self.pool, &rhs.pool)
+    }
+
+    /// Runs `f` on the thread pool of this configuration, all the cpu ops that `f` runs are
+    /// parallelized using this pool whatever the device of their tensors.
+    pub fn install<R: Send, F: FnOnce() -> R + Send>(&self, f: F) -> R {
+        self.pool.install(f)
+    }
+}
+
+// The number of tasks that the matmuls are split into in deterministic mode.
+const DETERMINISTIC_NUM_TASKS: usize = 8;
+
+// The seed of the cpu random number generator and the number of random ops that have used it.
+static CPU_SEED: std::sync::Mutex<Option<(u64, u64)>> = std::sync::Mutex::new(None);
+
+// Returns the random number generator for the next random op. When a seed has been set or in
+// deterministic mode, each op gets its own stream derived from the seed and the op index.
+fn cpu_rng() -> Result<rand::rngs::StdRng> {
+    use rand::SeedableRng;
+    let mut seed = CPU_SEED.lock().unwrap();
+    if seed.is_none() && crate::utils::is_deterministic() {
+        *seed = Some((0, 0))
+    }
+    match seed.as_mut() {
+        Some((seed, index)) => {
+            let stream = seed.wrapping_add(index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            *index += 1;
+            Ok(rand::rngs::StdRng::seed_from_u64(stream))
+        }
+        None => rand::rngs::StdRng::from_rng(rand::thread_rng()).map_err(Error::wrap),
+    }
+}
+
+// Returns the cpu cores that the current thread is allowed to run on.
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> Result<Vec<usize>> {
+    // SAFETY: the cpu set is a plain bitmask that is zero initialized and filled by the kernel,
+    // only the indexes below CPU_SETSIZE are read from it.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            let err = std::io::Error::last_os_error();
+            crate::bail!("cannot get the cpu affinity of the current thread: {err}")
+        }
+        let num_cores = libc::CPU_SETSIZE as usize;
+        Ok((0..num_cores)
+            .filter(|&c| libc::CPU_ISSET(c, &set))
+            .collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cores() -> Result<Vec<usize>> {
+    crate::bail!("cpu affinity is only supported on linux")
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> Result<()> {
+    // SAFETY: the cpu set is a plain bitmask that is fully initialized before being used, the
+    // core index has been checked against the allowed cores when creating the configuration.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            let err = std::io::Error::last_os_error();
+            crate::bail!("cannot pin a cpu thread to core {core}: {err}")
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_core: usize) -> Result<()> {
+    crate::bail!("cpu affinity is only supported on linux")
+}
+
+// Split some complex values in their real and imaginary parts, apply f on both parts and merge the
+// results back.
+fn map_complex<T: WithDType, F: Fn(&[T]) -> Result<Vec<T>>>(
+    vs: &[Complex<T>],
+    f: F,
+) -> Result<Vec<Complex<T>>> {
+    let re = f(&vs.iter().map(|v| v.re).collect::<Vec<_>>())?;
+    let im = f(&vs.iter().map(|v| v.im).collect::<Vec<_>>())?;
+    Ok(re
+        .into_iter()
+        .zip(im)
+        .map(|(re, im)| Complex::new(re, im))
+        .collect())
+}
+
+fn map_complex2<T: WithDType, F: Fn(&[T], &[T]) -> Result<Vec<T>>>(
+    vs1: &[Complex<T>],
+    vs2: &[Complex<T>],
+    f: F,
+) -> Result<Vec<Complex<T>>> {
+    let (re1, im1): (Vec<_>, Vec<_>) = vs1.iter().map(|v| (v.re, v.im)).unzip();
+    let (re2, im2): (Vec<_>, Vec<_>) = vs2.iter().map(|v| (v.re, v.im)).unzip();
+    let re = f(&re1, &re2)?;
+    let im = f(&im1, &im2)?;
+    Ok(re
+        .into_iter()
+        .zip(im)
+        .map(|(re, im)| Complex::new(re, im))
+        .collect())
+}
+
 pub trait Map1 {
+    const OP: &'static str;
+    /// Set to true when the op only moves values around or is linear, in which case it can be
+    /// applied to complex values by processing the real and imaginary parts separately.
+    const COMPLEX: bool = false;
     fn f<T: WithDType>(&self, vs: &[T], layout: &Layout) -> Result<Vec<T>>;
 
     fn map(&self, vs: &CpuStorage, layout: &Layout) -> Result<CpuStorage> {
         match vs {
+            CpuStorage::C64(vs) if Self::COMPLEX => {
+                Ok(CpuStorage::C64(map_complex(vs, |vs| self.f(vs, layout))?))
+            }
+            CpuStorage::C128(vs) if Self::COMPLEX => {
+                Ok(CpuStorage::C128(map_complex(vs, |vs| self.f(vs, layout))?))
+            }
+            CpuStorage::Bool(_) | CpuStorage::C64(_) | CpuStorage::C128(_) => {
+                Err(Error::UnsupportedDTypeForOp(vs.dtype(), Self::OP).bt())
+            }
             CpuStorage::U8(vs) => Ok(CpuStorage::U8(self.f(vs, layout)?)),
             CpuStorage::U32(vs) => Ok(CpuStorage::U32(self.f(vs, layout)?)),
+            CpuStorage::I8(vs) => Ok(CpuStorage::I8(self.f(vs, layout)?)),
+            CpuStorage::I16(vs) => Ok(CpuStorage::I16(self.f(vs, layout)?)),
+            CpuStorage::I32(vs) => Ok(CpuStorage::I32(self.f(vs, layout)?)),
             CpuStorage::I64(vs) => Ok(CpuStorage::I64(self.f(vs, layout)?)),
             CpuStorage::BF16(vs) => Ok(CpuStorage::BF16(self.f(vs, layout)?)),
             CpuStorage::F16(vs) => Ok(CpuStorage::F16(self.f(vs, layout)?)),
@@ -39,7 +249,37 @@ pub trait Map1 {
     }
 }
 
+/// Similar to [`Map1`] but the values get updated in place, only contiguous layouts are supported.
+pub trait Map1InPlace {
+    const OP: &'static str;
+    fn f<T: WithDType>(&self, vs: &mut [T]);
+
+    fn map_inplace(&self, vs: &mut CpuStorage, layout: &Layout) -> Result<()> {
+        let (o1, o2) = match layout.contiguous_offsets() {
+            Some(offsets) => offsets,
+            None => Err(Error::RequiresContiguous { op: Self::OP }.bt())?,
+        };
+        match vs {
+            CpuStorage::Bool(_) | CpuStorage::C64(_) | CpuStorage::C128(_) => {
+                Err(Error::UnsupportedDTypeForOp(vs.dtype(), Self::OP).bt())?
+            }
+            CpuStorage::U8(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::U32(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::I8(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::I16(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::I32(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::I64(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::BF16(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::F16(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::F32(vs) => self.f(&mut vs[o1..o2]),
+            CpuStorage::F64(vs) => self.f(&mut vs[o1..o2]),
+        }
+        Ok(())
+    }
+}
+
 pub trait Map1Any {
+    const OP: &'static str;
     fn f<T: WithDType, W: Fn(Vec<T>) -> CpuStorage>(
         &self,
         vs: &[T],
@@ -49,8 +289,14 @@ pub trait Map1Any {
 
     fn map(&self, vs: &CpuStorage, layout: &Layout) -> Result<CpuStorage> {
         match vs {
+            CpuStorage::Bool(_) | CpuStorage::C64(_) | CpuStorage::C128(_) => {
+                Err(Error::UnsupportedDTypeForOp(vs.dtype(), Self::OP).bt())
+            }
             CpuStorage::U8(vs) => Ok(self.f(vs, layout, CpuStorage::U8)?),
             CpuStorage::U32(vs) => Ok(self.f(vs, layout, CpuStorage::U32)?),
+            CpuStorage::I8(vs) => Ok(self.f(vs, layout, CpuStorage::I8)?),
+            CpuStorage::I16(vs) => Ok(self.f(vs, layout, CpuStorage::I16)?),
+            CpuStorage::I32(vs) => Ok(self.f(vs, layout, CpuStorage::I32)?),
             CpuStorage::I64(vs) => Ok(self.f(vs, layout, CpuStorage::I64)?),
             CpuStorage::BF16(vs) => Ok(self.f(vs, layout, CpuStorage::BF16)?),
             CpuStorage::F16(vs) => Ok(self.f(vs, layout, CpuStorage::F16)?),
@@ -63,6 +309,10 @@ pub trait Map1Any {
 type C = CpuStorage;
 pub trait Map2 {
     const OP: &'static str;
+    /// Set to true when the op only moves values around or is linear in both arguments, in which
+    /// case it can be applied to complex values by processing the real and imaginary parts
+    /// separately.
+    const COMPLEX: bool = false;
     fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, v2: &[T], l2: &Layout) -> Result<Vec<T>>;
 
     fn map(
@@ -73,8 +323,24 @@ pub trait Map2 {
         l2: &Layout,
     ) -> Result<CpuStorage> {
         match (v1, v2) {
+            (C::C64(v1), C::C64(v2)) if Self::COMPLEX => {
+                Ok(C::C64(map_complex2(v1, v2, |v1, v2| {
+                    self.f(v1, l1, v2, l2)
+                })?))
+            }
+            (C::C128(v1), C::C128(v2)) if Self::COMPLEX => {
+                Ok(C::C128(map_complex2(v1, v2, |v1, v2| {
+                    self.f(v1, l1, v2, l2)
+                })?))
+            }
+            (C::Bool(_), C::Bool(_)) | (C::C64(_), C::C64(_)) | (C::C128(_), C::C128(_)) => {
+                Err(Error::UnsupportedDTypeForOp(v1.dtype(), Self::OP).bt())
+            }
             (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
             (C::U32(v1), C::U32(v2)) => Ok(C::U32(self.f(v1, l1, v2, l2)?)),
+            (C::I8(v1), C::I8(v2)) => Ok(C::I8(self.f(v1, l1, v2, l2)?)),
+            (C::I16(v1), C::I16(v2)) => Ok(C::I16(self.f(v1, l1, v2, l2)?)),
+            (C::I32(v1), C::I32(v2)) => Ok(C::I32(self.f(v1, l1, v2, l2)?)),
             (C::I64(v1), C::I64(v2)) => Ok(C::I64(self.f(v1, l1, v2, l2)?)),
             (C::BF16(v1), C::BF16(v2)) => Ok(C::BF16(self.f(v1, l1, v2, l2)?)),
             (C::F16(v1), C::F16(v2)) => Ok(C::F16(self.f(v1, l1, v2, l2)?)),
@@ -90,9 +356,9 @@ pub trait Map2 {
     }
 }
 
-pub trait Map2U8 {
+pub trait Map2Bool {
     const OP: &'static str;
-    fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, v2: &[T], l2: &Layout) -> Result<Vec<u8>>;
+    fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, v2: &[T], l2: &Layout) -> Result<Vec<bool>>;
 
     fn map(
         &self,
@@ -102,13 +368,22 @@ pub trait Map2U8 {
         l2: &Layout,
     ) -> Result<CpuStorage> {
         match (v1, v2) {
-            (C::U8(v1), C::U8(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::U32(v1), C::U32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::I64(v1), C::I64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::BF16(v1), C::BF16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F16(v1), C::F16(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F32(v1), C::F32(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
-            (C::F64(v1), C::F64(v2)) => Ok(C::U8(self.f(v1, l1, v2, l2)?)),
+            (C::Bool(v1), C::Bool(v2)) => {
+                Ok(C::Bool(self.f(bool_as_u8(v1), l1, bool_as_u8(v2), l2)?))
+            }
+            (C::C64(_), C::C64(_)) | (C::C128(_), C::C128(_)) => {
+                Err(Error::UnsupportedDTypeForOp(v1.dtype(), Self::OP).bt())
+            }
+            (C::U8(v1), C::U8(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::U32(v1), C::U32(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::I8(v1), C::I8(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::I16(v1), C::I16(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::I32(v1), C::I32(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::I64(v1), C::I64(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::BF16(v1), C::BF16(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::F16(v1), C::F16(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::F32(v1), C::F32(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
+            (C::F64(v1), C::F64(v2)) => Ok(C::Bool(self.f(v1, l1, v2, l2)?)),
             _ => Err(Error::DTypeMismatchBinaryOp {
                 lhs: v1.dtype(),
                 rhs: v2.dtype(),
@@ -119,8 +394,15 @@ pub trait Map2U8 {
     }
 }
 
+// Boolean values are represented using a single byte that is either 0 or 1, so they can be
+// processed with the u8 kernels.
+fn bool_as_u8(vs: &[bool]) -> &[u8] {
+    // Safety: bool and u8 have the same size and alignment and a bool is always 0 or 1.
+    unsafe { std::slice::from_raw_parts(vs.as_ptr() as *const u8, vs.len()) }
+}
+
 struct Cmp(CmpOp);
-impl Map2U8 for Cmp {
+impl Map2Bool for Cmp {
     const OP: &'static str = "cmp";
     #[inline(always)]
     fn f<T: WithDType>(
@@ -129,14 +411,14 @@ impl Map2U8 for Cmp {
         lhs_l: &Layout,
         rhs: &[T],
         rhs_l: &Layout,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<Vec<bool>> {
         let dst = match self.0 {
-            CmpOp::Eq => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x == y)),
-            CmpOp::Ne => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x != y)),
-            CmpOp::Lt => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x < y)),
-            CmpOp::Le => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x <= y)),
-            CmpOp::Gt => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x > y)),
-            CmpOp::Ge => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| u8::from(x >= y)),
+            CmpOp::Eq => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x == y),
+            CmpOp::Ne => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x != y),
+            CmpOp::Lt => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x < y),
+            CmpOp::Le => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x <= y),
+            CmpOp::Gt => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x > y),
+            CmpOp::Ge => binary_map(lhs_l, rhs_l, lhs, rhs, |x, y| x >= y),
         };
         Ok(dst)
     }
@@ -146,6 +428,7 @@ struct WCond<'a, T: IntDType>(&'a [T], &'a Layout);
 
 impl<'a, I: IntDType> Map2 for WCond<'a, I> {
     const OP: &'static str = "where";
+    const COMPLEX: bool = true;
     #[inline(always)]
     fn f<T: WithDType>(&self, t: &[T], t_l: &Layout, f: &[T], f_l: &Layout) -> Result<Vec<T>> {
         let vs = match (
@@ -191,15 +474,15 @@ impl ReduceIndex {
     fn fold_impl<T, U, F, G>(&self, src: &[T], src_l: &Layout, f: F, g: G) -> Result<Vec<U>>
     where
         T: Clone + Copy,
-        U: Clone + Copy,
+        U: Clone + Copy + 'static,
         F: Fn(T, T) -> bool,
         G: Fn(T, usize) -> U,
     {
         let reduce_dim_size = src_l.dims()[self.reduce_dim_index];
         let reduce_dim_stride = src_l.stride()[self.reduce_dim_index];
         let dst_len = src_l.shape().elem_count() / reduce_dim_size;
-        let mut dst: Vec<U> = Vec::with_capacity(dst_len);
-        let dst_to_set = dst.spare_capacity_mut();
+        let mut dst: Vec<U> = crate::memory::alloc_vec(dst_len);
+        let dst_to_set = &mut dst.spare_capacity_mut()[..dst_len];
         let dst_to_set = unsafe { std::mem::transmute::<_, &mut [U]>(dst_to_set) };
         match src_l.contiguous_offsets() {
             Some((o1, o2)) => {
@@ -263,6 +546,8 @@ impl ReduceIndex {
 }
 
 impl Map1Any for ReduceIndex {
+    const OP: &'static str = "reduce-index";
+
     #[inline(always)]
     fn f<T: WithDType, W: Fn(Vec<T>) -> CpuStorage>(
         &self,
@@ -287,6 +572,80 @@ impl Map1Any for ReduceIndex {
     }
 }
 
+/// Returns true for values that are not comparable with themselves, i.e. float nans.
+#[inline(always)]
+fn is_nan<T: PartialOrd>(v: &T) -> bool {
+    v.partial_cmp(v).is_none()
+}
+
+/// A total order where nans compare greater than every other value regardless of `rev`, `rev`
+/// only reverses the order of the non-nan values.
+#[inline(always)]
+fn nan_last_cmp<T: PartialOrd>(a: &T, b: &T, rev: bool) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (is_nan(a), is_nan(b)) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => {
+            let ord = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+            if rev {
+                ord.reverse()
+            } else {
+                ord
+            }
+        }
+    }
+}
+
+struct ArgSort {
+    asc: bool,
+    last_dim: usize,
+}
+
+impl ArgSort {
+    fn asort<T: WithDType>(&self, vs: &[T]) -> Vec<u32> {
+        let mut sort_indexes = vec![0u32; vs.len()];
+        if self.last_dim == 0 {
+            return sort_indexes;
+        }
+        sort_indexes
+            .par_chunks_exact_mut(self.last_dim)
+            .zip(vs.par_chunks_exact(self.last_dim))
+            .for_each(|(indexes, vs)| {
+                indexes
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, v)| *v = i as u32);
+                // The sort is stable so equal values keep their original order, nans are pushed
+                // towards the end.
+                if self.asc {
+                    indexes.sort_by(|&i, &j| nan_last_cmp(&vs[i as usize], &vs[j as usize], false))
+                } else {
+                    indexes.sort_by(|&i, &j| nan_last_cmp(&vs[i as usize], &vs[j as usize], true))
+                }
+            });
+        sort_indexes
+    }
+}
+
+impl Map1Any for ArgSort {
+    const OP: &'static str = "arg-sort";
+
+    fn f<T: WithDType, W: Fn(Vec<T>) -> CpuStorage>(
+        &self,
+        vs: &[T],
+        layout: &Layout,
+        _wrap: W,
+    ) -> Result<CpuStorage> {
+        let vs = match layout.contiguous_offsets() {
+            None => Err(Error::RequiresContiguous { op: "arg_sort" }.bt())?,
+            Some((o1, o2)) => &vs[o1..o2],
+        };
+        Ok(CpuStorage::U32(self.asort(vs)))
+    }
+}
+
 struct ReduceSum<'a> {
     dst_shape: &'a Shape,
     reduce_dims: &'a [usize],
@@ -299,7 +658,7 @@ impl<'a> ReduceSum<'a> {
     where
         T: WithDType,
     {
-        let mut dst = vec![start_elt; self.dst_shape.elem_count()];
+        let mut dst = crate::memory::filled_vec(self.dst_shape.elem_count(), start_elt);
         match src_l.contiguous_offsets() {
             Some((o1, o2)) => {
                 let src = &src[o1..o2];
@@ -359,28 +718,137 @@ impl<'a> ReduceSum<'a> {
 }
 
 impl<'a> Map1 for ReduceSum<'a> {
+    const OP: &'static str = "sum";
+    const COMPLEX: bool = true;
+
     #[inline(always)]
     fn f<T: WithDType>(&self, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
         self.fold_impl(src, src_l, T::zero())
     }
 }
 
-pub fn unary_map<T: Copy, U: Copy, F: FnMut(T) -> U>(
+struct Scan {
+    op: ScanOp,
+    dim: usize,
+}
+
+impl Scan {
+    fn scan<T: WithDType, F: Fn(T, T) -> T + Sync>(
+        &self,
+        src: &[T],
+        layout: &Layout,
+        f: F,
+    ) -> Vec<T> {
+        let mut dst = unary_map(src, layout, |v| v);
+        let dims = layout.dims();
+        let dim_size = dims[self.dim];
+        let post = dims[self.dim + 1..].iter().product::<usize>();
+        if dim_size == 0 || post == 0 {
+            return dst;
+        }
+        // Each block holds the values of a single index of the leading dimensions, the scan is
+        // sequential along the scanned dimension and vectorizes over the trailing ones.
+        dst.par_chunks_exact_mut(dim_size * post).for_each(|block| {
+            for i in 1..dim_size {
+                let (prev, cur) = block[(i - 1) * post..(i + 1) * post].split_at_mut(post);
+                for (c, &p) in cur.iter_mut().zip(prev.iter()) {
+                    *c = f(p, *c)
+                }
+            }
+        });
+        dst
+    }
+}
+
+impl Map1 for Scan {
+    const OP: &'static str = "scan";
+
+    fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
+        let dst = match self.op {
+            ScanOp::Sum => self.scan(src, layout, |acc, v| acc + v),
+            ScanOp::Prod => self.scan(src, layout, |acc, v| acc * v),
+            // Once a nan has been encountered it is propagated to all the following values.
+            ScanOp::Max => self.scan(src, layout, |acc, v| {
+                if !is_nan(&acc) && (is_nan(&v) || v > acc) {
+                    v
+                } else {
+                    acc
+                }
+            }),
+            ScanOp::Min => self.scan(src, layout, |acc, v| {
+                if !is_nan(&acc) && (is_nan(&v) || v < acc) {
+                    v
+                } else {
+                    acc
+                }
+            }),
+            ScanOp::LogSumExp => {
+                if !T::DTYPE.is_float() {
+                    Err(Error::UnsupportedDTypeForOp(T::DTYPE, self.op.name()).bt())?
+                }
+                self.scan(src, layout, |acc, v| {
+                    let (acc, v) = (acc.to_f64(), v.to_f64());
+                    // Equal values are handled separately so that infinities do not produce nans.
+                    let res = if acc == v {
+                        acc + std::f64::consts::LN_2
+                    } else {
+                        acc.max(v) + (-(acc - v).abs()).exp().ln_1p()
+                    };
+                    T::from_f64(res)
+                })
+            }
+        };
+        Ok(dst)
+    }
+}
+
+struct LinearRecurrence(usize);
+
+impl Map2 for LinearRecurrence {
+    const OP: &'static str = "linear-recurrence";
+
+    fn f<T: WithDType>(&self, a: &[T], a_l: &Layout, b: &[T], b_l: &Layout) -> Result<Vec<T>> {
+        let a = unary_map(a, a_l, |v| v);
+        let mut dst = unary_map(b, b_l, |v| v);
+        let dims = b_l.dims();
+        let dim_size = dims[self.0];
+        let post = dims[self.0 + 1..].iter().product::<usize>();
+        if dim_size == 0 || post == 0 {
+            return Ok(dst);
+        }
+        // Same blocking as for the scans, `h_{-1}` is zero so the first values are the `b` ones.
+        let block_len = dim_size * post;
+        dst.par_chunks_exact_mut(block_len)
+            .zip(a.par_chunks_exact(block_len))
+            .for_each(|(block, a)| {
+                for i in 1..dim_size {
+                    let (prev, cur) = block[(i - 1) * post..(i + 1) * post].split_at_mut(post);
+                    let a = &a[i * post..(i + 1) * post];
+                    for ((c, &p), &a) in cur.iter_mut().zip(prev.iter()).zip(a.iter()) {
+                        *c = a * p + *c
+                    }
+                }
+            });
+        Ok(dst)
+    }
+}
+
+pub fn unary_map<T: Copy, U: Copy + 'static, F: FnMut(T) -> U>(
     vs: &[T],
     layout: &Layout,
     mut f: F,
 ) -> Vec<U> {
     match layout.strided_blocks() {
-        crate::StridedBlocks::SingleBlock { start_offset, len } => vs
-            [start_offset..start_offset + len]
-            .iter()
-            .map(|&v| f(v))
-            .collect(),
+        crate::StridedBlocks::SingleBlock { start_offset, len } => {
+            let mut result = crate::memory::alloc_vec(len);
+            result.extend(vs[start_offset..start_offset + len].iter().map(|&v| f(v)));
+            result
+        }
         crate::StridedBlocks::MultipleBlocks {
             block_start_index,
             block_len,
         } => {
-            let mut result = Vec::with_capacity(layout.shape().elem_count());
+            let mut result = crate::memory::alloc_vec(layout.shape().elem_count());
             // Specialize the case where block_len is one to avoid the second loop.
             if block_len == 1 {
                 for index in block_start_index {
@@ -400,7 +868,7 @@ pub fn unary_map<T: Copy, U: Copy, F: FnMut(T) -> U>(
     }
 }
 
-pub fn unary_map_vec<T: Copy, U: Copy, F: FnMut(T) -> U, FV: FnMut(&[T], &mut [U])>(
+pub fn unary_map_vec<T: Copy, U: Copy + 'static, F: FnMut(T) -> U, FV: FnMut(&[T], &mut [U])>(
     vs: &[T],
     layout: &Layout,
     mut f: F,
@@ -408,8 +876,8 @@ pub fn unary_map_vec<T: Copy, U: Copy, F: FnMut(T) -> U, FV: FnMut(&[T], &mut [U
 ) -> Vec<U> {
     match layout.strided_blocks() {
         crate::StridedBlocks::SingleBlock { start_offset, len } => {
-            let mut ys: Vec<U> = Vec::with_capacity(len);
-            let ys_to_set = ys.spare_capacity_mut();
+            let mut ys: Vec<U> = crate::memory::alloc_vec(len);
+            let ys_to_set = &mut ys.spare_capacity_mut()[..len];
             let ys_to_set = unsafe { std::mem::transmute::<_, &mut [U]>(ys_to_set) };
             f_vec(&vs[start_offset..start_offset + len], ys_to_set);
             // SAFETY: values are all set by f_vec.
@@ -423,15 +891,15 @@ pub fn unary_map_vec<T: Copy, U: Copy, F: FnMut(T) -> U, FV: FnMut(&[T], &mut [U
             let el_count = layout.shape().elem_count();
             // Specialize the case where block_len is one to avoid the second loop.
             if block_len == 1 {
-                let mut result = Vec::with_capacity(el_count);
+                let mut result = crate::memory::alloc_vec(el_count);
                 for index in block_start_index {
                     let v = unsafe { vs.get_unchecked(index) };
                     result.push(f(*v))
                 }
                 result
             } else {
-                let mut ys: Vec<U> = Vec::with_capacity(el_count);
-                let ys_to_set = ys.spare_capacity_mut();
+                let mut ys: Vec<U> = crate::memory::alloc_vec(el_count);
+                let ys_to_set = &mut ys.spare_capacity_mut()[..el_count];
                 let ys_to_set = unsafe { std::mem::transmute::<_, &mut [U]>(ys_to_set) };
                 let mut dst_index = 0;
                 for src_index in block_start_index {
@@ -449,46 +917,46 @@ pub fn unary_map_vec<T: Copy, U: Copy, F: FnMut(T) -> U, FV: FnMut(&[T], &mut [U
 }
 
 // This function maps over two strided index sequences.
-pub fn binary_map<T: Copy, U: Copy, F: FnMut(T, T) -> U>(
+pub fn binary_map<T: Copy, U: Copy + 'static, F: FnMut(T, T) -> U>(
     lhs_l: &Layout,
     rhs_l: &Layout,
     lhs: &[T],
     rhs: &[T],
     mut f: F,
 ) -> Vec<U> {
+    let mut dst = crate::memory::alloc_vec(lhs_l.shape().elem_count());
     match (lhs_l.contiguous_offsets(), rhs_l.contiguous_offsets()) {
-        (Some((o_l1, o_l2)), Some((o_r1, o_r2))) => lhs[o_l1..o_l2]
-            .iter()
-            .zip(rhs[o_r1..o_r2].iter())
-            .map(|(&l, &r)| f(l, r))
-            .collect(),
+        (Some((o_l1, o_l2)), Some((o_r1, o_r2))) => dst.extend(
+            lhs[o_l1..o_l2]
+                .iter()
+                .zip(rhs[o_r1..o_r2].iter())
+                .map(|(&l, &r)| f(l, r)),
+        ),
         (Some((o_l1, o_l2)), None) => {
             // TODO: Maybe we want to avoid going through the layout twice.
             match rhs_l.offsets_b() {
                 Some(ob) => {
                     let mut i_in_block = 0;
                     let mut i_right_broadcast = 0;
-                    lhs[o_l1..o_l2]
-                        .iter()
-                        .map(|&l| {
-                            let r = unsafe { rhs.get_unchecked(i_in_block + ob.start) };
-                            i_right_broadcast += 1;
-                            if i_right_broadcast >= ob.right_broadcast {
-                                i_in_block += 1;
-                                i_right_broadcast = 0;
-                            }
-                            if i_in_block >= ob.len {
-                                i_in_block = 0
-                            }
-                            f(l, *r)
-                        })
-                        .collect()
+                    dst.extend(lhs[o_l1..o_l2].iter().map(|&l| {
+                        let r = unsafe { rhs.get_unchecked(i_in_block + ob.start) };
+                        i_right_broadcast += 1;
+                        if i_right_broadcast >= ob.right_broadcast {
+                            i_in_block += 1;
+                            i_right_broadcast = 0;
+                        }
+                        if i_in_block >= ob.len {
+                            i_in_block = 0
+                        }
+                        f(l, *r)
+                    }))
                 }
-                None => lhs_l
-                    .strided_index()
-                    .zip(rhs_l.strided_index())
-                    .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i]))
-                    .collect(),
+                None => dst.extend(
+                    lhs_l
+                        .strided_index()
+                        .zip(rhs_l.strided_index())
+                        .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i])),
+                ),
             }
         }
         (None, Some((o_r1, o_r2))) => {
@@ -497,39 +965,69 @@ pub fn binary_map<T: Copy, U: Copy, F: FnMut(T, T) -> U>(
                 Some(ob) => {
                     let mut i_in_block = 0;
                     let mut i_right_broadcast = 0;
-                    rhs[o_r1..o_r2]
-                        .iter()
-                        .map(|&r| {
-                            let l = unsafe { lhs.get_unchecked(i_in_block + ob.start) };
-                            i_right_broadcast += 1;
-                            if i_right_broadcast >= ob.right_broadcast {
-                                i_in_block += 1;
-                                i_right_broadcast = 0;
-                            }
-                            if i_in_block >= ob.len {
-                                i_in_block = 0
-                            }
-                            f(*l, r)
-                        })
-                        .collect()
+                    dst.extend(rhs[o_r1..o_r2].iter().map(|&r| {
+                        let l = unsafe { lhs.get_unchecked(i_in_block + ob.start) };
+                        i_right_broadcast += 1;
+                        if i_right_broadcast >= ob.right_broadcast {
+                            i_in_block += 1;
+                            i_right_broadcast = 0;
+                        }
+                        if i_in_block >= ob.len {
+                            i_in_block = 0
+                        }
+                        f(*l, r)
+                    }))
                 }
-                None => lhs_l
-                    .strided_index()
-                    .zip(rhs_l.strided_index())
-                    .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i]))
-                    .collect(),
+                None => dst.extend(
+                    lhs_l
+                        .strided_index()
+                        .zip(rhs_l.strided_index())
+                        .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i])),
+                ),
+            }
+        }
+        _ => dst.extend(
+            lhs_l
+                .strided_index()
+                .zip(rhs_l.strided_index())
+                .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i])),
+        ),
+    }
+    dst
+}
+
+// Similar to binary_map but the result is written back into lhs, which must be contiguous.
+fn binary_map_inplace<T: Copy, F: FnMut(T, T) -> T>(
+    lhs_l: &Layout,
+    rhs_l: &Layout,
+    lhs: &mut [T],
+    rhs: &[T],
+    mut f: F,
+) -> Result<()> {
+    let lhs = match lhs_l.contiguous_offsets() {
+        Some((o1, o2)) => &mut lhs[o1..o2],
+        None => Err(Error::RequiresContiguous {
+            op: "binary-inplace",
+        }
+        .bt())?,
+    };
+    match rhs_l.contiguous_offsets() {
+        Some((o1, o2)) => {
+            for (l, &r) in lhs.iter_mut().zip(rhs[o1..o2].iter()) {
+                *l = f(*l, r)
+            }
+        }
+        None => {
+            for (l, r) in lhs.iter_mut().zip(rhs_l.strided_index()) {
+                *l = f(*l, rhs[r])
             }
         }
-        _ => lhs_l
-            .strided_index()
-            .zip(rhs_l.strided_index())
-            .map(|(lhs_i, rhs_i)| f(lhs[lhs_i], rhs[rhs_i]))
-            .collect(),
     }
+    Ok(())
 }
 
 // Similar to binary_map but with vectorized variants.
-pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [T])>(
+pub fn binary_map_vec<T: Copy + 'static, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [T])>(
     lhs_l: &Layout,
     rhs_l: &Layout,
     lhs: &[T],
@@ -540,8 +1038,8 @@ pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [
     let el_count = lhs_l.shape().elem_count();
     match (lhs_l.contiguous_offsets(), rhs_l.contiguous_offsets()) {
         (Some((o_l1, o_l2)), Some((o_r1, o_r2))) => {
-            let mut ys: Vec<T> = Vec::with_capacity(el_count);
-            let ys_to_set = ys.spare_capacity_mut();
+            let mut ys: Vec<T> = crate::memory::alloc_vec(el_count);
+            let ys_to_set = &mut ys.spare_capacity_mut()[..el_count];
             let ys_to_set = unsafe { std::mem::transmute::<_, &mut [T]>(ys_to_set) };
             f_vec(&lhs[o_l1..o_l2], &rhs[o_r1..o_r2], ys_to_set);
             // SAFETY: values are all set by f_vec.
@@ -551,8 +1049,8 @@ pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [
         (Some((o_l1, o_l2)), None) => match rhs_l.offsets_b() {
             Some(ob) if ob.right_broadcast == 1 => {
                 let rhs = &rhs[ob.start..ob.start + ob.len];
-                let mut ys: Vec<T> = Vec::with_capacity(el_count);
-                let ys_to_set = ys.spare_capacity_mut();
+                let mut ys: Vec<T> = crate::memory::alloc_vec(el_count);
+                let ys_to_set = &mut ys.spare_capacity_mut()[..el_count];
                 let ys_to_set = unsafe { std::mem::transmute::<_, &mut [T]>(ys_to_set) };
                 let mut dst_i = 0;
                 for src_i in (o_l1..o_l2).step_by(ob.len) {
@@ -590,8 +1088,8 @@ pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [
         (None, Some((o_r1, o_r2))) => match lhs_l.offsets_b() {
             Some(ob) if ob.right_broadcast == 1 => {
                 let lhs = &lhs[ob.start..ob.start + ob.len];
-                let mut ys: Vec<T> = Vec::with_capacity(el_count);
-                let ys_to_set = ys.spare_capacity_mut();
+                let mut ys: Vec<T> = crate::memory::alloc_vec(el_count);
+                let ys_to_set = &mut ys.spare_capacity_mut()[..el_count];
                 let ys_to_set = unsafe { std::mem::transmute::<_, &mut [T]>(ys_to_set) };
                 let mut dst_i = 0;
                 for src_i in (o_r1..o_r2).step_by(ob.len) {
@@ -637,6 +1135,8 @@ pub fn binary_map_vec<T: Copy, F: FnMut(T, T) -> T, FV: FnMut(&[T], &[T], &mut [
 struct Affine(f64, f64);
 
 impl Map1 for Affine {
+    const OP: &'static str = "affine";
+
     fn f<T: WithDType>(&self, vs: &[T], layout: &Layout) -> Result<Vec<T>> {
         let mul = T::from_f64(self.0);
         let add = T::from_f64(self.1);
@@ -644,9 +1144,32 @@ impl Map1 for Affine {
     }
 }
 
+impl Map1InPlace for Affine {
+    const OP: &'static str = "affine";
+
+    fn f<T: WithDType>(&self, vs: &mut [T]) {
+        let mul = T::from_f64(self.0);
+        let add = T::from_f64(self.1);
+        vs.iter_mut().for_each(|v| *v = *v * mul + add)
+    }
+}
+
+struct Fill(f64);
+
+impl Map1InPlace for Fill {
+    const OP: &'static str = "fill";
+
+    fn f<T: WithDType>(&self, vs: &mut [T]) {
+        vs.fill(T::from_f64(self.0))
+    }
+}
+
 struct AvgPool2D((usize, usize), (usize, usize));
 
 impl Map1 for AvgPool2D {
+    const OP: &'static str = "avg-pool2d";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
         // https://pytorch.org/docs/stable/generated/torch.nn.AvgPool2d.html
         let (k_h, k_w) = self.0;
@@ -688,6 +1211,8 @@ impl Map1 for AvgPool2D {
 struct MaxPool2D((usize, usize), (usize, usize));
 
 impl Map1 for MaxPool2D {
+    const OP: &'static str = "max-pool2d";
+
     fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
         // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool2d.html
         let (k_h, k_w) = self.0;
@@ -727,9 +1252,119 @@ impl Map1 for MaxPool2D {
     }
 }
 
+struct AvgPool3D((usize, usize, usize), (usize, usize, usize));
+
+impl Map1 for AvgPool3D {
+    const OP: &'static str = "avg-pool3d";
+    const COMPLEX: bool = true;
+
+    fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
+        // https://pytorch.org/docs/stable/generated/torch.nn.AvgPool3d.html
+        let (k_d, k_h, k_w) = self.0;
+        let (s_d, s_h, s_w) = self.1;
+        let (b_sz, c, d, h, w) = layout.shape().dims5()?;
+        let stride = layout.stride();
+        let (stride_d, stride_h, stride_w) = (stride[2], stride[3], stride[4]);
+        let d_out = (d - k_d) / s_d + 1;
+        let h_out = (h - k_h) / s_h + 1;
+        let w_out = (w - k_w) / s_w + 1;
+        let src_index = layout.start_offset();
+        let mut dst = vec![T::zero(); b_sz * c * d_out * h_out * w_out];
+        let scale = 1f64 / (k_d * k_h * k_w) as f64;
+        let scale = T::from_f64(scale);
+        for b_idx in 0..b_sz {
+            let dst = &mut dst[b_idx * c * d_out * h_out * w_out..];
+            let src_index = src_index + b_idx * stride[0];
+            for c_idx in 0..c {
+                let dst = &mut dst[c_idx * d_out * h_out * w_out..];
+                let src_index = src_index + c_idx * stride[1];
+                for d_idx in 0..d_out {
+                    for h_idx in 0..h_out {
+                        for w_idx in 0..w_out {
+                            let mut sum = T::zero();
+                            for l in 0..k_d {
+                                for m in 0..k_h {
+                                    for n in 0..k_w {
+                                        let l = s_d * d_idx + l;
+                                        let m = s_h * h_idx + m;
+                                        let n = s_w * w_idx + n;
+                                        sum += src
+                                            [src_index + l * stride_d + m * stride_h + n * stride_w]
+                                    }
+                                }
+                            }
+                            dst[(d_idx * h_out + h_idx) * w_out + w_idx] = sum * scale;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(dst)
+    }
+}
+
+struct MaxPool3D((usize, usize, usize), (usize, usize, usize));
+
+impl Map1 for MaxPool3D {
+    const OP: &'static str = "max-pool3d";
+
+    fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
+        // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool3d.html
+        let (k_d, k_h, k_w) = self.0;
+        let (s_d, s_h, s_w) = self.1;
+        let (b_sz, c, d, h, w) = layout.shape().dims5()?;
+        let stride = layout.stride();
+        let (stride_d, stride_h, stride_w) = (stride[2], stride[3], stride[4]);
+        let d_out = (d - k_d) / s_d + 1;
+        let h_out = (h - k_h) / s_h + 1;
+        let w_out = (w - k_w) / s_w + 1;
+        let src_index = layout.start_offset();
+        let mut dst = vec![T::zero(); b_sz * c * d_out * h_out * w_out];
+        for b_idx in 0..b_sz {
+            let dst = &mut dst[b_idx * c * d_out * h_out * w_out..];
+            let src_index = src_index + b_idx * stride[0];
+            for c_idx in 0..c {
+                let dst = &mut dst[c_idx * d_out * h_out * w_out..];
+                let src_index = src_index + c_idx * stride[1];
+                for d_idx in 0..d_out {
+                    for h_idx in 0..h_out {
+                        for w_idx in 0..w_out {
+                            let mut largest = src[src_index
+                                + s_d * d_idx * stride_d
+                                + s_h * h_idx * stride_h
+                                + s_w * w_idx * stride_w];
+                            for l in 0..k_d {
+                                for m in 0..k_h {
+                                    for n in 0..k_w {
+                                        let l = s_d * d_idx + l;
+                                        let m = s_h * h_idx + m;
+                                        let n = s_w * w_idx + n;
+                                        let v = src[src_index
+                                            + l * stride_d
+                                            + m * stride_h
+                                            + n * stride_w];
+                                        if largest < v {
+                                            largest = v
+                                        }
+                                    }
+                                }
+                            }
+                            dst[(d_idx * h_out + h_idx) * w_out + w_idx] = largest;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(dst)
+    }
+}
+
 struct UpsampleNearest1D(usize);
 
 impl Map1 for UpsampleNearest1D {
+    const OP: &'static str = "upsample-nearest1d";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
         // TODO: Specialized implementation for the case 2*sz?
         let dst_sz = self.0;
@@ -760,6 +1395,9 @@ impl Map1 for UpsampleNearest1D {
 struct UpsampleNearest2D(usize, usize);
 
 impl Map1 for UpsampleNearest2D {
+    const OP: &'static str = "upsample-nearest2d";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
         // TODO: Specialized implementation for the case 2*h, 2*w?
         let (dst_h, dst_w) = (self.0, self.1);
@@ -801,6 +1439,9 @@ struct Gather<'a, I: IntDType> {
 }
 
 impl<'a, I: IntDType> Map1 for Gather<'a, I> {
+    const OP: &'static str = "gather";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
         let ids = match self.ids_l.contiguous_offsets() {
             Some((a, b)) => &self.ids[a..b],
@@ -854,6 +1495,9 @@ struct IndexSelect<'a, T: IntDType> {
 }
 
 impl<'a, I: IntDType> Map1 for IndexSelect<'a, I> {
+    const OP: &'static str = "index-select";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, src: &[T], layout: &Layout) -> Result<Vec<T>> {
         let src = match layout.contiguous_offsets() {
             Some((a, b)) => &src[a..b],
@@ -908,6 +1552,7 @@ struct ScatterAdd<'a, I: IntDType> {
 
 impl<'a, I: IntDType> Map2 for ScatterAdd<'a, I> {
     const OP: &'static str = "scatter-add";
+    const COMPLEX: bool = true;
     fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
         let dst_len = l1.shape().elem_count();
         let mut dst = vec![T::zero(); dst_len];
@@ -964,6 +1609,7 @@ struct IndexAdd<'a, I: IntDType> {
 
 impl<'a, I: IntDType> Map2 for IndexAdd<'a, I> {
     const OP: &'static str = "index-add";
+    const COMPLEX: bool = true;
     // https://pytorch.org/docs/stable/generated/torch.Tensor.index_add_.html#torch.Tensor.index_add_
     // v1, l1 -> self
     fn f<T: WithDType>(&self, v1: &[T], l1: &Layout, src: &[T], src_l: &Layout) -> Result<Vec<T>> {
@@ -1022,28 +1668,138 @@ impl<'a, I: IntDType> Map2 for IndexAdd<'a, I> {
     }
 }
 
-fn copy_strided_src_<T: Copy>(src: &[T], dst: &mut [T], dst_offset: usize, src_l: &Layout) {
-    match src_l.strided_blocks() {
-        crate::StridedBlocks::SingleBlock { start_offset, len } => {
-            let to_copy = (dst.len() - dst_offset).min(len);
-            dst[dst_offset..dst_offset + to_copy]
-                .copy_from_slice(&src[start_offset..start_offset + to_copy])
+// Iterative radix-2 Cooley-Tukey transform, the length of xs has to be a power of two. The
+// result is not normalized.
+fn fft_pow2<T: WithDType + num_traits::Float + num_traits::FloatConst>(
+    xs: &mut [Complex<T>],
+    inverse: bool,
+) {
+    let n = xs.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
         }
-        crate::StridedBlocks::MultipleBlocks {
-            block_start_index,
-            block_len: 1,
-        } => {
-            for (dst_index, src_index) in block_start_index.enumerate() {
-                let dst_index = dst_index + dst_offset;
-                if dst_index >= dst.len() {
-                    break;
-                }
-                dst[dst_index] = src[src_index]
+        j ^= bit;
+        if i < j {
+            xs.swap(i, j)
+        }
+    }
+    let sign = if inverse { T::one() } else { -T::one() };
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let angle = sign * (T::PI() + T::PI()) / T::from_f64(len as f64);
+        let twiddles: Vec<_> = (0..half)
+            .map(|k| Complex::from_polar(T::one(), angle * T::from_f64(k as f64)))
+            .collect();
+        for chunk in xs.chunks_exact_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(half);
+            for ((lo, hi), w) in lo.iter_mut().zip(hi.iter_mut()).zip(twiddles.iter()) {
+                let u = *lo;
+                let v = *hi * w;
+                *lo = u + v;
+                *hi = u - v;
             }
         }
-        crate::StridedBlocks::MultipleBlocks {
-            block_start_index,
-            block_len,
+        len <<= 1;
+    }
+}
+
+// Bluestein's algorithm, expresses a transform of arbitrary length as a convolution that is
+// computed using power of two transforms. The result is not normalized.
+fn fft_bluestein<T: WithDType + num_traits::Float + num_traits::FloatConst>(
+    xs: &mut [Complex<T>],
+    inverse: bool,
+) {
+    let n = xs.len();
+    let m = (2 * n - 1).next_power_of_two();
+    let sign = if inverse { T::one() } else { -T::one() };
+    let zero = Complex::new(T::zero(), T::zero());
+    // Reduce k^2 modulo 2n before converting it to a float to preserve precision.
+    let chirp: Vec<_> = (0..n)
+        .map(|k| {
+            let k2 = T::from_f64(((k * k) % (2 * n)) as f64);
+            Complex::from_polar(T::one(), sign * T::PI() * k2 / T::from_f64(n as f64))
+        })
+        .collect();
+    let mut a = vec![zero; m];
+    for (a, (x, c)) in a.iter_mut().zip(xs.iter().zip(chirp.iter())) {
+        *a = x * c
+    }
+    let mut b = vec![zero; m];
+    b[0] = chirp[0].conj();
+    for k in 1..n {
+        b[k] = chirp[k].conj();
+        b[m - k] = chirp[k].conj();
+    }
+    fft_pow2(&mut a, false);
+    fft_pow2(&mut b, false);
+    for (a, b) in a.iter_mut().zip(b.iter()) {
+        *a *= b
+    }
+    fft_pow2(&mut a, true);
+    let scale = T::one() / T::from_f64(m as f64);
+    for (x, (a, c)) in xs.iter_mut().zip(a.iter().zip(chirp.iter())) {
+        *x = a * c * scale
+    }
+}
+
+fn fft_last_dim<T: WithDType + num_traits::Float + num_traits::FloatConst>(
+    vs: &[Complex<T>],
+    layout: &Layout,
+    inverse: bool,
+) -> Result<Vec<Complex<T>>> {
+    let vs = match layout.contiguous_offsets() {
+        Some((o1, o2)) => &vs[o1..o2],
+        None => Err(Error::RequiresContiguous { op: "fft" }.bt())?,
+    };
+    let n = match layout.dims().last() {
+        Some(&n) => n,
+        None => crate::bail!("fft requires a tensor with at least one dimension"),
+    };
+    let mut dst = vs.to_vec();
+    if n == 0 {
+        return Ok(dst);
+    }
+    let scale = T::one() / T::from_f64(n as f64);
+    dst.par_chunks_exact_mut(n).for_each(|xs| {
+        if n.is_power_of_two() {
+            fft_pow2(xs, inverse)
+        } else {
+            fft_bluestein(xs, inverse)
+        }
+        if inverse {
+            xs.iter_mut().for_each(|x| *x *= scale)
+        }
+    });
+    Ok(dst)
+}
+
+fn copy_strided_src_<T: Copy>(src: &[T], dst: &mut [T], dst_offset: usize, src_l: &Layout) {
+    match src_l.strided_blocks() {
+        crate::StridedBlocks::SingleBlock { start_offset, len } => {
+            let to_copy = (dst.len() - dst_offset).min(len);
+            dst[dst_offset..dst_offset + to_copy]
+                .copy_from_slice(&src[start_offset..start_offset + to_copy])
+        }
+        crate::StridedBlocks::MultipleBlocks {
+            block_start_index,
+            block_len: 1,
+        } => {
+            for (dst_index, src_index) in block_start_index.enumerate() {
+                let dst_index = dst_index + dst_offset;
+                if dst_index >= dst.len() {
+                    break;
+                }
+                dst[dst_index] = src[src_index]
+            }
+        }
+        crate::StridedBlocks::MultipleBlocks {
+            block_start_index,
+            block_len,
         } => {
             let mut dst_index = dst_offset;
             for src_index in block_start_index {
@@ -1136,6 +1892,9 @@ impl Im2Col1D {
 }
 
 impl Map1 for Im2Col1D {
+    const OP: &'static str = "im2col1d";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, vs: &[T], layout: &Layout) -> Result<Vec<T>> {
         let &Self {
             l_k,
@@ -1146,7 +1905,7 @@ impl Map1 for Im2Col1D {
         let (b, c, l) = layout.shape().dims3()?;
         let l_out = self.l_out(l);
         let src = &vs[layout.start_offset()..];
-        let mut dst = vec![T::zero(); b * l_out * c * l_k];
+        let mut dst = crate::memory::filled_vec(b * l_out * c * l_k, T::zero());
         let (src_s0, src_s1, src_s2) = {
             let s = layout.stride();
             (s[0], s[1], s[2])
@@ -1198,6 +1957,9 @@ impl Im2Col {
 }
 
 impl Map1 for Im2Col {
+    const OP: &'static str = "im2col";
+    const COMPLEX: bool = true;
+
     fn f<T: WithDType>(&self, vs: &[T], layout: &Layout) -> Result<Vec<T>> {
         let &Self {
             h_k,
@@ -1209,7 +1971,7 @@ impl Map1 for Im2Col {
         let (b, c, h, w) = layout.shape().dims4()?;
         let (h_out, w_out) = self.hw_out(h, w);
         let src = &vs[layout.start_offset()..];
-        let mut dst = vec![T::zero(); b * h_out * w_out * c * h_k * w_k];
+        let mut dst = crate::memory::filled_vec(b * h_out * w_out * c * h_k * w_k, T::zero());
         let (src_s0, src_s1, src_s2, src_s3) = {
             let s = layout.stride();
             (s[0], s[1], s[2], s[3])
@@ -1256,6 +2018,88 @@ impl Map1 for Im2Col {
     }
 }
 
+struct Im2Col3D {
+    d_k: usize,
+    h_k: usize,
+    w_k: usize,
+    stride: usize,
+    dilation: usize,
+    padding: usize,
+}
+
+impl Im2Col3D {
+    fn dhw_out(&self, d: usize, h: usize, w: usize) -> (usize, usize, usize) {
+        let out = |i: usize, k: usize| {
+            (i + 2 * self.padding - self.dilation * (k - 1) - 1) / self.stride + 1
+        };
+        (out(d, self.d_k), out(h, self.h_k), out(w, self.w_k))
+    }
+}
+
+impl Map1 for Im2Col3D {
+    const OP: &'static str = "im2col3d";
+    const COMPLEX: bool = true;
+
+    fn f<T: WithDType>(&self, vs: &[T], layout: &Layout) -> Result<Vec<T>> {
+        let &Self {
+            d_k,
+            h_k,
+            w_k,
+            stride,
+            dilation,
+            padding,
+        } = self;
+        let (b, c, d, h, w) = layout.shape().dims5()?;
+        let (d_out, h_out, w_out) = self.dhw_out(d, h, w);
+        let src = &vs[layout.start_offset()..];
+        let k_sz = c * d_k * h_k * w_k;
+        let (src_s0, src_s1, src_s2, src_s3, src_s4) = crate::shape::dims5(layout.stride())?;
+        let mut dst = crate::memory::filled_vec(b * d_out * h_out * w_out * k_sz, T::zero());
+        // Returns the source index along a spatial dimension, None when in the padding.
+        let src_pos = |idx: usize, k_idx: usize, size: usize| {
+            let pos = idx * stride + k_idx * dilation;
+            if pos < padding || pos >= size + padding {
+                None
+            } else {
+                Some(pos - padding)
+            }
+        };
+        dst.par_chunks_exact_mut(k_sz)
+            .enumerate()
+            .for_each(|(dst_idx, dst)| {
+                let w_idx = dst_idx % w_out;
+                let h_idx = (dst_idx / w_out) % h_out;
+                let d_idx = (dst_idx / (w_out * h_out)) % d_out;
+                let b_idx = dst_idx / (w_out * h_out * d_out);
+                for c_idx in 0..c {
+                    let src_idx = b_idx * src_s0 + c_idx * src_s1;
+                    let dst = &mut dst[c_idx * d_k * h_k * w_k..];
+                    for d_k_idx in 0..d_k {
+                        let src_d = match src_pos(d_idx, d_k_idx, d) {
+                            None => continue,
+                            Some(src_d) => src_d,
+                        };
+                        let src_idx = src_idx + src_d * src_s2;
+                        for h_k_idx in 0..h_k {
+                            let src_h = match src_pos(h_idx, h_k_idx, h) {
+                                None => continue,
+                                Some(src_h) => src_h,
+                            };
+                            let src_idx = src_idx + src_h * src_s3;
+                            let dst = &mut dst[(d_k_idx * h_k + h_k_idx) * w_k..];
+                            for (w_k_idx, dst) in dst[..w_k].iter_mut().enumerate() {
+                                if let Some(src_w) = src_pos(w_idx, w_k_idx, w) {
+                                    *dst = src[src_idx + src_w * src_s4]
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        Ok(dst)
+    }
+}
+
 struct ConvTranspose1D<'a>(&'a crate::conv::ParamsConvTranspose1D);
 
 impl<'a> Map2 for ConvTranspose1D<'a> {
@@ -1502,6 +2346,129 @@ impl<'a> Map2 for ConvTranspose2D<'a> {
     }
 }
 
+struct ConvTranspose3D<'a>(&'a crate::conv::ParamsConvTranspose3D);
+
+impl<'a> Map2 for ConvTranspose3D<'a> {
+    const OP: &'static str = "conv_transpose3d";
+    fn f<T: WithDType>(&self, inp: &[T], inp_l: &Layout, k: &[T], k_l: &Layout) -> Result<Vec<T>> {
+        let p = self.0;
+        let inp = &inp[inp_l.start_offset()..];
+        let (inp_s0, inp_s1, inp_s2, inp_s3, inp_s4) = crate::shape::dims5(inp_l.stride())?;
+        let k = &k[k_l.start_offset()..];
+        let (k_s0, k_s1, k_s2, k_s3, k_s4) = crate::shape::dims5(k_l.stride())?;
+        let (out_d, out_h, out_w) = (p.out_d(), p.out_h(), p.out_w());
+
+        // Output shape: [b_size, c_out, out_d, out_h, out_w].
+        let dst = vec![T::zero(); p.b_size * p.c_out * out_d * out_h * out_w];
+        let dst_s0 = p.c_out * out_d * out_h * out_w;
+        let dst_s1 = out_d * out_h * out_w;
+        let dst_s2 = out_h * out_w;
+        let dst_s3 = out_w;
+
+        // TODO: Avoid making this copy if `inp` already has the appropriate layout.
+        let mut inp_cont = vec![T::zero(); p.b_size * p.c_in * p.i_d * p.i_h * p.i_w];
+        let cont_s0 = p.i_d * p.i_h * p.i_w * p.c_in;
+        let cont_s1 = p.i_h * p.i_w * p.c_in;
+        let cont_s2 = p.i_w * p.c_in;
+        let cont_s3 = p.c_in;
+        for b_idx in 0..p.b_size {
+            for d_idx in 0..p.i_d {
+                for h_idx in 0..p.i_h {
+                    for w_idx in 0..p.i_w {
+                        for c_idx in 0..p.c_in {
+                            let src_idx = b_idx * inp_s0
+                                + c_idx * inp_s1
+                                + d_idx * inp_s2
+                                + h_idx * inp_s3
+                                + w_idx * inp_s4;
+                            let dst_idx = b_idx * cont_s0
+                                + d_idx * cont_s1
+                                + h_idx * cont_s2
+                                + w_idx * cont_s3
+                                + c_idx;
+                            inp_cont[dst_idx] = inp[src_idx]
+                        }
+                    }
+                }
+            }
+        }
+
+        // Returns the output index along a spatial dimension, None when out of bounds.
+        let out_pos = |inp_idx: usize, k_idx: usize, size: usize| {
+            let pos = inp_idx * p.stride + k_idx * p.dilation;
+            if pos < p.padding || pos - p.padding >= size {
+                None
+            } else {
+                Some(pos - p.padding)
+            }
+        };
+        for k_z in 0..p.k_d {
+            for k_y in 0..p.k_h {
+                for k_x in 0..p.k_w {
+                    (0..p.c_out).into_par_iter().for_each(|dst_c_idx| {
+                        let k_cont = (0..p.c_in)
+                            .map(|c_in_idx| {
+                                k[c_in_idx * k_s0
+                                    + dst_c_idx * k_s1
+                                    + k_z * k_s2
+                                    + k_y * k_s3
+                                    + k_x * k_s4]
+                            })
+                            .collect::<Vec<_>>();
+                        for b_idx in 0..p.b_size {
+                            for inp_z in 0..p.i_d {
+                                let out_z = match out_pos(inp_z, k_z, out_d) {
+                                    None => continue,
+                                    Some(out_z) => out_z,
+                                };
+                                for inp_y in 0..p.i_h {
+                                    let out_y = match out_pos(inp_y, k_y, out_h) {
+                                        None => continue,
+                                        Some(out_y) => out_y,
+                                    };
+                                    for inp_x in 0..p.i_w {
+                                        let out_x = match out_pos(inp_x, k_x, out_w) {
+                                            None => continue,
+                                            Some(out_x) => out_x,
+                                        };
+                                        let inp_cont = &inp_cont[b_idx * cont_s0
+                                            + inp_z * cont_s1
+                                            + inp_y * cont_s2
+                                            + inp_x * cont_s3..];
+                                        let dst_idx = b_idx * dst_s0
+                                            + dst_c_idx * dst_s1
+                                            + out_z * dst_s2
+                                            + out_y * dst_s3
+                                            + out_x;
+                                        let mut d = T::zero();
+                                        unsafe {
+                                            T::vec_dot(
+                                                inp_cont.as_ptr(),
+                                                k_cont.as_ptr(),
+                                                &mut d,
+                                                p.c_in,
+                                            )
+                                        }
+                                        let dst_p = dst.as_ptr();
+                                        // Safety: dst_idx are uniques per dst_c_idx which is used
+                                        // to parallelise the different tasks so no two threads
+                                        // can try to write at the same location.
+                                        unsafe {
+                                            let ptr = dst_p.add(dst_idx) as *mut T;
+                                            *ptr += d
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    })
+                }
+            }
+        }
+        Ok(dst)
+    }
+}
+
 struct MatMul((usize, usize, usize, usize));
 
 impl MatMul {
@@ -1566,8 +2533,14 @@ impl Map2 for MatMul {
         let dst_rs = dst_strides[0];
         let dst_cs = dst_strides[1];
 
-        let mut dst = vec![T::zero(); b * m * n];
-        let num_threads = crate::utils::get_num_threads();
+        let mut dst = crate::memory::filled_vec(b * m * n, T::zero());
+        let num_threads = if crate::utils::is_deterministic() {
+            // The way the work is split depends on the number of threads, use a fixed number of
+            // tasks so that the results do not depend on the size of the thread pool.
+            DETERMINISTIC_NUM_TASKS
+        } else {
+            crate::utils::get_num_threads()
+        };
         let parallelism = if num_threads > 1 {
             Parallelism::Rayon(num_threads)
         } else {
@@ -1655,7 +2628,7 @@ impl Map2 for MatMul {
             Err(self.striding_error(lhs_l, rhs_l, "non-contiguous lhs"))?
         };
 
-        let mut dst = vec![T::zero(); b * m * n];
+        let mut dst = crate::memory::filled_vec(b * m * n, T::zero());
         match T::DTYPE {
             DType::F16 => {
                 crate::bail!("the accelerate backend does not support f16 matmul")
@@ -1758,7 +2731,7 @@ impl Map2 for MatMul {
             Err(self.striding_error(lhs_l, rhs_l, "non-contiguous lhs"))?
         };
 
-        let mut dst = vec![T::zero(); b * m * n];
+        let mut dst = crate::memory::filled_vec(b * m * n, T::zero());
         match T::DTYPE {
             DType::F16 => {
                 for step in 0..b {
@@ -1851,9 +2824,83 @@ impl CpuStorage {
         D::cpu_storage_as_slice(self)
     }
 
+    // Ops that only move elements around without combining them can process boolean storages
+    // through their u8 representation.
+    fn map_bool_as_u8<F: FnOnce(&Self) -> Result<Self>>(&self, f: F) -> Result<Self> {
+        match self {
+            Self::Bool(vs) => {
+                let s = f(&Self::U8(bool_as_u8(vs).to_vec()))?;
+                match &s {
+                    Self::U8(vs) => Ok(Self::Bool(vs.iter().map(|&v| v != 0).collect())),
+                    _ => Ok(s),
+                }
+            }
+            _ => f(self),
+        }
+    }
+
+    pub(crate) fn binary_impl_inplace<B: BinaryOpT>(
+        &mut self,
+        rhs: &Self,
+        lhs_l: &Layout,
+        rhs_l: &Layout,
+    ) -> Result<()> {
+        let dtype = self.dtype();
+        match (self, rhs) {
+            (Self::BF16(lhs), Self::BF16(rhs)) => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::bf16)
+            }
+            (Self::F16(lhs), Self::F16(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f16),
+            (Self::F32(lhs), Self::F32(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f32),
+            (Self::F64(lhs), Self::F64(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::f64),
+            (Self::U8(lhs), Self::U8(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::u8),
+            (Self::U32(lhs), Self::U32(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::u32),
+            (Self::I8(lhs), Self::I8(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::i8),
+            (Self::I16(lhs), Self::I16(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::i16),
+            (Self::I32(lhs), Self::I32(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::i32),
+            (Self::I64(lhs), Self::I64(rhs)) => binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::i64),
+            (Self::C64(lhs), Self::C64(rhs)) if B::COMPLEX => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::c64)
+            }
+            (Self::C128(lhs), Self::C128(rhs)) if B::COMPLEX => {
+                binary_map_inplace(lhs_l, rhs_l, lhs, rhs, B::c128)
+            }
+            (Self::Bool(_), Self::Bool(_))
+            | (Self::C64(_), Self::C64(_))
+            | (Self::C128(_), Self::C128(_)) => {
+                Err(Error::UnsupportedDTypeForOp(dtype, B::NAME).bt())
+            }
+            (_, rhs) => Err(Error::DTypeMismatchBinaryOp {
+                lhs: dtype,
+                rhs: rhs.dtype(),
+                op: B::NAME,
+            }
+            .bt()),
+        }
+    }
+
+    pub(crate) fn affine_inplace(&mut self, layout: &Layout, mul: f64, add: f64) -> Result<()> {
+        Affine(mul, add).map_inplace(self, layout)
+    }
+
+    pub(crate) fn fill_inplace(&mut self, layout: &Layout, value: f64) -> Result<()> {
+        Fill(value).map_inplace(self, layout)
+    }
+
     pub fn concat(storages: &[CpuStorage]) -> Result<CpuStorage> {
         let storage0 = &storages[0];
         let s = match storage0 {
+            Self::Bool(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::Bool(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::Bool(storages)
+            }
             Self::U8(_) => {
                 let storages = storages
                     .iter()
@@ -1876,6 +2923,39 @@ impl CpuStorage {
                     .concat();
                 Self::U32(storages)
             }
+            Self::I8(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::I8(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::I8(storages)
+            }
+            Self::I16(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::I16(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::I16(storages)
+            }
+            Self::I32(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::I32(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::I32(storages)
+            }
             Self::I64(_) => {
                 let storages = storages
                     .iter()
@@ -1931,6 +3011,28 @@ impl CpuStorage {
                     .concat();
                 Self::F64(storages)
             }
+            Self::C64(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::C64(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::C64(storages)
+            }
+            Self::C128(_) => {
+                let storages = storages
+                    .iter()
+                    .map(|s| match s {
+                        Self::C128(s) => Ok(s.as_slice()),
+                        _ => crate::bail!("dtype mismatch"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .concat();
+                Self::C128(storages)
+            }
         };
         Ok(s)
     }
@@ -1941,13 +3043,19 @@ impl BackendStorage for CpuStorage {
 
     fn dtype(&self) -> DType {
         match self {
+            Self::Bool(_) => DType::Bool,
             Self::U8(_) => DType::U8,
             Self::U32(_) => DType::U32,
+            Self::I8(_) => DType::I8,
+            Self::I16(_) => DType::I16,
+            Self::I32(_) => DType::I32,
             Self::I64(_) => DType::I64,
             Self::BF16(_) => DType::BF16,
             Self::F16(_) => DType::F16,
             Self::F32(_) => DType::F32,
             Self::F64(_) => DType::F64,
+            Self::C64(_) => DType::C64,
+            Self::C128(_) => DType::C128,
         }
     }
 
@@ -2150,6 +3258,337 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| v);
                 Ok(Self::F64(data))
             }
+            (Self::Bool(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::Bool(data))
+            }
+            (Self::U8(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::U32(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::I8(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::I16(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::I32(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::I64(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0);
+                Ok(Self::Bool(data))
+            }
+            (Self::BF16(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != bf16::ZERO);
+                Ok(Self::Bool(data))
+            }
+            (Self::F16(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != f16::ZERO);
+                Ok(Self::Bool(data))
+            }
+            (Self::F32(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0.);
+                Ok(Self::Bool(data))
+            }
+            (Self::F64(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v != 0.);
+                Ok(Self::Bool(data))
+            }
+            (Self::Bool(storage), DType::U8) => {
+                let data = unary_map(storage, layout, u8::from);
+                Ok(Self::U8(data))
+            }
+            (Self::I8(storage), DType::U8) => {
+                let data = unary_map(storage, layout, |v| v as u8);
+                Ok(Self::U8(data))
+            }
+            (Self::I16(storage), DType::U8) => {
+                let data = unary_map(storage, layout, |v| v as u8);
+                Ok(Self::U8(data))
+            }
+            (Self::I32(storage), DType::U8) => {
+                let data = unary_map(storage, layout, |v| v as u8);
+                Ok(Self::U8(data))
+            }
+            (Self::Bool(storage), DType::U32) => {
+                let data = unary_map(storage, layout, u32::from);
+                Ok(Self::U32(data))
+            }
+            (Self::I8(storage), DType::U32) => {
+                let data = unary_map(storage, layout, |v| v as u32);
+                Ok(Self::U32(data))
+            }
+            (Self::I16(storage), DType::U32) => {
+                let data = unary_map(storage, layout, |v| v as u32);
+                Ok(Self::U32(data))
+            }
+            (Self::I32(storage), DType::U32) => {
+                let data = unary_map(storage, layout, |v| v as u32);
+                Ok(Self::U32(data))
+            }
+            (Self::Bool(storage), DType::I8) => {
+                let data = unary_map(storage, layout, i8::from);
+                Ok(Self::I8(data))
+            }
+            (Self::U8(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::U32(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::I8(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::I8(data))
+            }
+            (Self::I16(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::I32(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::I64(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::BF16(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::F16(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::F32(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::F64(storage), DType::I8) => {
+                let data = unary_map(storage, layout, |v| v as i8);
+                Ok(Self::I8(data))
+            }
+            (Self::Bool(storage), DType::I16) => {
+                let data = unary_map(storage, layout, i16::from);
+                Ok(Self::I16(data))
+            }
+            (Self::U8(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::U32(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::I8(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::I16(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::I16(data))
+            }
+            (Self::I32(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::I64(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::BF16(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::F16(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::F32(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::F64(storage), DType::I16) => {
+                let data = unary_map(storage, layout, |v| v as i16);
+                Ok(Self::I16(data))
+            }
+            (Self::Bool(storage), DType::I32) => {
+                let data = unary_map(storage, layout, i32::from);
+                Ok(Self::I32(data))
+            }
+            (Self::U8(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::U32(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::I8(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::I16(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::I32(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::I32(data))
+            }
+            (Self::I64(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::BF16(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::F16(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v.to_f32() as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::F32(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::F64(storage), DType::I32) => {
+                let data = unary_map(storage, layout, |v| v as i32);
+                Ok(Self::I32(data))
+            }
+            (Self::Bool(storage), DType::I64) => {
+                let data = unary_map(storage, layout, i64::from);
+                Ok(Self::I64(data))
+            }
+            (Self::I8(storage), DType::I64) => {
+                let data = unary_map(storage, layout, |v| v as i64);
+                Ok(Self::I64(data))
+            }
+            (Self::I16(storage), DType::I64) => {
+                let data = unary_map(storage, layout, |v| v as i64);
+                Ok(Self::I64(data))
+            }
+            (Self::I32(storage), DType::I64) => {
+                let data = unary_map(storage, layout, |v| v as i64);
+                Ok(Self::I64(data))
+            }
+            (Self::Bool(storage), DType::BF16) => {
+                let data = unary_map(storage, layout, |v| if v { bf16::ONE } else { bf16::ZERO });
+                Ok(Self::BF16(data))
+            }
+            (Self::I8(storage), DType::BF16) => {
+                let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
+                Ok(Self::BF16(data))
+            }
+            (Self::I16(storage), DType::BF16) => {
+                let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
+                Ok(Self::BF16(data))
+            }
+            (Self::I32(storage), DType::BF16) => {
+                let data = unary_map(storage, layout, |v| bf16::from_f32(v as f32));
+                Ok(Self::BF16(data))
+            }
+            (Self::Bool(storage), DType::F16) => {
+                let data = unary_map(storage, layout, |v| if v { f16::ONE } else { f16::ZERO });
+                Ok(Self::F16(data))
+            }
+            (Self::I8(storage), DType::F16) => {
+                let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
+                Ok(Self::F16(data))
+            }
+            (Self::I16(storage), DType::F16) => {
+                let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
+                Ok(Self::F16(data))
+            }
+            (Self::I32(storage), DType::F16) => {
+                let data = unary_map(storage, layout, |v| f16::from_f32(v as f32));
+                Ok(Self::F16(data))
+            }
+            (Self::Bool(storage), DType::F32) => {
+                let data = unary_map(storage, layout, |v| if v { 1. } else { 0. });
+                Ok(Self::F32(data))
+            }
+            (Self::I8(storage), DType::F32) => {
+                let data = unary_map(storage, layout, |v| v as f32);
+                Ok(Self::F32(data))
+            }
+            (Self::I16(storage), DType::F32) => {
+                let data = unary_map(storage, layout, |v| v as f32);
+                Ok(Self::F32(data))
+            }
+            (Self::I32(storage), DType::F32) => {
+                let data = unary_map(storage, layout, |v| v as f32);
+                Ok(Self::F32(data))
+            }
+            (Self::Bool(storage), DType::F64) => {
+                let data = unary_map(storage, layout, |v| if v { 1. } else { 0. });
+                Ok(Self::F64(data))
+            }
+            (Self::I8(storage), DType::F64) => {
+                let data = unary_map(storage, layout, |v| v as f64);
+                Ok(Self::F64(data))
+            }
+            (Self::I16(storage), DType::F64) => {
+                let data = unary_map(storage, layout, |v| v as f64);
+                Ok(Self::F64(data))
+            }
+            (Self::I32(storage), DType::F64) => {
+                let data = unary_map(storage, layout, |v| v as f64);
+                Ok(Self::F64(data))
+            }
+            (Self::C64(storage), DType::C64) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::C64(data))
+            }
+            (Self::C128(storage), DType::C64) => {
+                let data = unary_map(storage, layout, |v| Complex::new(v.re as f32, v.im as f32));
+                Ok(Self::C64(data))
+            }
+            (Self::C64(storage), DType::C128) => {
+                let data = unary_map(storage, layout, |v| Complex::new(v.re as f64, v.im as f64));
+                Ok(Self::C128(data))
+            }
+            (Self::C128(storage), DType::C128) => {
+                let data = unary_map(storage, layout, |v| v);
+                Ok(Self::C128(data))
+            }
+            (Self::C64(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v.re != 0. || v.im != 0.);
+                Ok(Self::Bool(data))
+            }
+            (Self::C128(storage), DType::Bool) => {
+                let data = unary_map(storage, layout, |v| v.re != 0. || v.im != 0.);
+                Ok(Self::Bool(data))
+            }
+            // Converting complex values to a real dtype only retains the real part.
+            (Self::C64(storage), dtype) => {
+                let data = unary_map(storage, layout, |v| v.re);
+                Self::F32(data).to_dtype(&Layout::contiguous(layout.shape()), dtype)
+            }
+            (Self::C128(storage), dtype) => {
+                let data = unary_map(storage, layout, |v| v.re);
+                Self::F64(data).to_dtype(&Layout::contiguous(layout.shape()), dtype)
+            }
+            (_, DType::C64) => {
+                let data = f32::cpu_storage_data(self.to_dtype(layout, DType::F32)?)?;
+                let data = data.into_iter().map(|v| Complex::new(v, 0.)).collect();
+                Ok(Self::C64(data))
+            }
+            (_, DType::C128) => {
+                let data = f64::cpu_storage_data(self.to_dtype(layout, DType::F64)?)?;
+                let data = data.into_iter().map(|v| Complex::new(v, 0.)).collect();
+                Ok(Self::C128(data))
+            }
         }
     }
 
@@ -2209,12 +3648,47 @@ impl BackendStorage for CpuStorage {
         }
     }
 
+    fn scan(&self, layout: &Layout, op: ScanOp, dim: usize) -> Result<Self> {
+        Scan { op, dim }.map(self, layout)
+    }
+
+    fn linear_recurrence(&self, l: &Layout, b: &Self, b_l: &Layout, dim: usize) -> Result<Self> {
+        LinearRecurrence(dim).map(self, l, b, b_l)
+    }
+
+    fn arg_sort_last_dim(&self, layout: &Layout, asc: bool) -> Result<Self> {
+        let last_dim = match layout.dims().last() {
+            Some(&last_dim) => last_dim,
+            None => crate::bail!("arg_sort requires a tensor with at least one dimension"),
+        };
+        ArgSort { asc, last_dim }.map(self, layout)
+    }
+
+    fn fft_last_dim(&self, layout: &Layout, inverse: bool) -> Result<Self> {
+        match self {
+            Self::C64(storage) => Ok(Self::C64(fft_last_dim(storage, layout, inverse)?)),
+            Self::C128(storage) => Ok(Self::C128(fft_last_dim(storage, layout, inverse)?)),
+            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "fft").bt()),
+        }
+    }
+
     fn cmp(&self, op: CmpOp, rhs: &Self, lhs_l: &Layout, rhs_l: &Layout) -> Result<Self> {
         Cmp(op).map(self, lhs_l, rhs, rhs_l)
     }
 
     fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
-        Affine(mul, add).map(self, layout)
+        // For complex values, add only applies to the real part.
+        match self {
+            Self::C64(storage) => {
+                let data = unary_map(storage, layout, |v| v * mul as f32 + add as f32);
+                Ok(Self::C64(data))
+            }
+            Self::C128(storage) => {
+                let data = unary_map(storage, layout, |v| v * mul + add);
+                Ok(Self::C128(data))
+            }
+            _ => Affine(mul, add).map(self, layout),
+        }
     }
 
     fn avg_pool2d(
@@ -2235,12 +3709,30 @@ impl BackendStorage for CpuStorage {
         MaxPool2D(kernel_size, stride).map(self, layout)
     }
 
+    fn avg_pool3d(
+        &self,
+        layout: &Layout,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    ) -> Result<Self> {
+        AvgPool3D(kernel_size, stride).map(self, layout)
+    }
+
+    fn max_pool3d(
+        &self,
+        layout: &Layout,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    ) -> Result<Self> {
+        MaxPool3D(kernel_size, stride).map(self, layout)
+    }
+
     fn upsample_nearest1d(&self, layout: &Layout, sz: usize) -> Result<Self> {
-        UpsampleNearest1D(sz).map(self, layout)
+        self.map_bool_as_u8(|s| UpsampleNearest1D(sz).map(s, layout))
     }
 
     fn upsample_nearest2d(&self, layout: &Layout, h: usize, w: usize) -> Result<Self> {
-        UpsampleNearest2D(h, w).map(self, layout)
+        self.map_bool_as_u8(|s| UpsampleNearest2D(h, w).map(s, layout))
     }
 
     fn powf(&self, layout: &Layout, e: f64) -> Result<Self> {
@@ -2263,9 +3755,15 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| v.powf(e));
                 Ok(Self::F64(data))
             }
-            Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
-            Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
-            Self::I64(_) => Err(Error::UnsupportedDTypeForOp(DType::I64, "elu").bt()),
+            Self::Bool(_)
+            | Self::U8(_)
+            | Self::U32(_)
+            | Self::I8(_)
+            | Self::I16(_)
+            | Self::I32(_)
+            | Self::I64(_)
+            | Self::C64(_)
+            | Self::C128(_) => Err(Error::UnsupportedDTypeForOp(self.dtype(), "powf").bt()),
         }
     }
 
@@ -2288,9 +3786,15 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, |v| elu(v, alpha));
                 Ok(Self::F64(data))
             }
-            Self::U8(_) => Err(Error::UnsupportedDTypeForOp(DType::U8, "elu").bt()),
-            Self::U32(_) => Err(Error::UnsupportedDTypeForOp(DType::U32, "elu").bt()),
-            Self::I64(_) => Err(Error::UnsupportedDTypeForOp(DType::I64, "elu").bt()),
+            Self::Bool(_)
+            | Self::U8(_)
+            | Self::U32(_)
+            | Self::I8(_)
+            | Self::I16(_)
+            | Self::I32(_)
+            | Self::I64(_)
+            | Self::C64(_)
+            | Self::C128(_) => Err(Error::UnsupportedDTypeForOp(self.dtype(), "elu").bt()),
         }
     }
 
@@ -2340,10 +3844,33 @@ impl BackendStorage for CpuStorage {
                 let data = unary_map(storage, layout, B::u32);
                 Ok(Self::U32(data))
             }
+            Self::I8(storage) => {
+                let data = unary_map(storage, layout, B::i8);
+                Ok(Self::I8(data))
+            }
+            Self::I16(storage) => {
+                let data = unary_map(storage, layout, B::i16);
+                Ok(Self::I16(data))
+            }
+            Self::I32(storage) => {
+                let data = unary_map(storage, layout, B::i32);
+                Ok(Self::I32(data))
+            }
             Self::I64(storage) => {
                 let data = unary_map(storage, layout, B::i64);
                 Ok(Self::I64(data))
             }
+            Self::C64(storage) if B::COMPLEX => {
+                let data = unary_map(storage, layout, B::c64);
+                Ok(Self::C64(data))
+            }
+            Self::C128(storage) if B::COMPLEX => {
+                let data = unary_map(storage, layout, B::c128);
+                Ok(Self::C128(data))
+            }
+            Self::Bool(_) | Self::C64(_) | Self::C128(_) => {
+                Err(Error::UnsupportedDTypeForOp(self.dtype(), B::NAME).bt())
+            }
         }
     }
 
@@ -2394,6 +3921,18 @@ impl BackendStorage for CpuStorage {
                 };
                 Ok(Self::U32(data))
             }
+            (Self::I8(lhs), Self::I8(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::i8);
+                Ok(Self::I8(data))
+            }
+            (Self::I16(lhs), Self::I16(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::i16);
+                Ok(Self::I16(data))
+            }
+            (Self::I32(lhs), Self::I32(rhs)) => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::i32);
+                Ok(Self::I32(data))
+            }
             (Self::I64(lhs), Self::I64(rhs)) => {
                 let data = if B::I64_VEC {
                     binary_map_vec(lhs_l, rhs_l, lhs, rhs, B::i64, B::i64_vec)
@@ -2410,6 +3949,19 @@ impl BackendStorage for CpuStorage {
                 };
                 Ok(Self::U8(data))
             }
+            (Self::C64(lhs), Self::C64(rhs)) if B::COMPLEX => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::c64);
+                Ok(Self::C64(data))
+            }
+            (Self::C128(lhs), Self::C128(rhs)) if B::COMPLEX => {
+                let data = binary_map(lhs_l, rhs_l, lhs, rhs, B::c128);
+                Ok(Self::C128(data))
+            }
+            (Self::Bool(_), Self::Bool(_))
+            | (Self::C64(_), Self::C64(_))
+            | (Self::C128(_), Self::C128(_)) => {
+                Err(Error::UnsupportedDTypeForOp(self.dtype(), B::NAME).bt())
+            }
             _ => {
                 // This should be covered by the dtype check above.
                 Err(Error::DTypeMismatchBinaryOp {
@@ -2424,13 +3976,19 @@ impl BackendStorage for CpuStorage {
 
     fn copy_strided_src(&self, dst: &mut Self, dst_offset: usize, src_l: &Layout) -> Result<()> {
         match (self, dst) {
+            (Self::Bool(src), Self::Bool(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::U8(src), Self::U8(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::U32(src), Self::U32(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::I8(src), Self::I8(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::I16(src), Self::I16(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::I32(src), Self::I32(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::I64(src), Self::I64(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::BF16(src), Self::BF16(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::F16(src), Self::F16(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::F32(src), Self::F32(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (Self::F64(src), Self::F64(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::C64(src), Self::C64(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
+            (Self::C128(src), Self::C128(dst)) => copy_strided_src_(src, dst, dst_offset, src_l),
             (_, dst) => {
                 // This should be covered by the dtype check above.
                 return Err(Error::DTypeMismatchBinaryOp {
@@ -2452,9 +4010,23 @@ impl BackendStorage for CpuStorage {
         f: &Self,
         f_l: &Layout,
     ) -> Result<Self> {
+        if let (Self::Bool(t), Self::Bool(f)) = (t, f) {
+            // Boolean values are selected through their u8 representation.
+            let t = Self::U8(bool_as_u8(t).to_vec());
+            let f = Self::U8(bool_as_u8(f).to_vec());
+            let s = self.where_cond(layout, &t, t_l, &f, f_l)?;
+            return match &s {
+                Self::U8(vs) => Ok(Self::Bool(vs.iter().map(|&v| v != 0).collect())),
+                _ => Ok(s),
+            };
+        }
         match self {
+            Self::Bool(pred) => WCond(bool_as_u8(pred), layout).map(t, t_l, f, f_l),
             Self::U8(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             Self::U32(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
+            Self::I8(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
+            Self::I16(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
+            Self::I32(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             Self::I64(pred) => WCond(pred, layout).map(t, t_l, f, f_l),
             _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "where-cond")),
         }
@@ -2492,10 +4064,10 @@ impl BackendStorage for CpuStorage {
             // Make the kernel contiguous if not already the case.
             let mut kernel_c = self.device().zeros_impl(kernel_l.shape(), kernel.dtype())?;
             kernel.copy_strided_src(&mut kernel_c, 0, kernel_l)?;
-            let kernel_l = Layout::contiguous_with_offset((1, n, k), kernel_l.start_offset())
+            let kernel_l = Layout::contiguous((1, n, k))
                 .transpose(1, 2)?
                 .broadcast_as((b, k, n))?;
-            col.matmul(kernel, (b, m, n, k), &col_l, &kernel_l)?
+            col.matmul(&kernel_c, (b, m, n, k), &col_l, &kernel_l)?
         };
         let res_l = Layout::contiguous((b, l_out, params.c_out)).transpose(1, 2)?;
         let mut res_t = self.device().zeros_impl(res_l.shape(), res.dtype())?;
@@ -2546,10 +4118,10 @@ impl BackendStorage for CpuStorage {
             // Make the kernel contiguous if not already the case.
             let mut kernel_c = self.device().zeros_impl(kernel_l.shape(), kernel.dtype())?;
             kernel.copy_strided_src(&mut kernel_c, 0, kernel_l)?;
-            let kernel_l = Layout::contiguous_with_offset((1, n, k), kernel_l.start_offset())
+            let kernel_l = Layout::contiguous((1, n, k))
                 .transpose(1, 2)?
                 .broadcast_as((b, k, n))?;
-            col.matmul(kernel, (b, m, n, k), &col_l, &kernel_l)?
+            col.matmul(&kernel_c, (b, m, n, k), &col_l, &kernel_l)?
         };
         let res_l = Layout::contiguous((b, h_out, w_out, params.c_out))
             .transpose(1, 2)?
@@ -2569,22 +4141,81 @@ impl BackendStorage for CpuStorage {
         ConvTranspose2D(params).map(self, l, kernel, kernel_l)
     }
 
+    fn conv3d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv3D,
+    ) -> Result<Self> {
+        let op = Im2Col3D {
+            d_k: params.k_d,
+            h_k: params.k_h,
+            w_k: params.k_w,
+            padding: params.padding,
+            stride: params.stride,
+            dilation: params.dilation,
+        };
+        let col = op.map(self, l)?;
+        let b = params.b_size;
+        let n = params.c_out;
+        let (d_out, h_out, w_out) = (params.out_d(), params.out_h(), params.out_w());
+        let k = op.d_k * op.h_k * op.w_k * params.c_in;
+        let m = d_out * h_out * w_out;
+        let col_l = Layout::contiguous((b, m, k));
+        let res = if kernel_l.is_contiguous() {
+            let kernel_l = Layout::contiguous_with_offset((1, n, k), kernel_l.start_offset())
+                .transpose(1, 2)?
+                .broadcast_as((b, k, n))?;
+            col.matmul(kernel, (b, m, n, k), &col_l, &kernel_l)?
+        } else {
+            // Make the kernel contiguous if not already the case.
+            let mut kernel_c = self.device().zeros_impl(kernel_l.shape(), kernel.dtype())?;
+            kernel.copy_strided_src(&mut kernel_c, 0, kernel_l)?;
+            let kernel_l = Layout::contiguous((1, n, k))
+                .transpose(1, 2)?
+                .broadcast_as((b, k, n))?;
+            col.matmul(&kernel_c, (b, m, n, k), &col_l, &kernel_l)?
+        };
+        let res_l =
+            Layout::contiguous((b, d_out, h_out, w_out, params.c_out)).permute(&[0, 4, 1, 2, 3])?;
+        let mut res_t = self.device().zeros_impl(res_l.shape(), res.dtype())?;
+        res.copy_strided_src(&mut res_t, 0, &res_l)?;
+        Ok(res_t)
+    }
+
+    fn conv_transpose3d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConvTranspose3D,
+    ) -> Result<Self> {
+        ConvTranspose3D(params).map(self, l, kernel, kernel_l)
+    }
+
     fn index_select(&self, ids: &Self, l: &Layout, ids_l: &Layout, dim: usize) -> Result<Self> {
-        match ids {
-            Self::U8(ids) => IndexSelect { ids, ids_l, dim }.map(self, l),
-            Self::U32(ids) => IndexSelect { ids, ids_l, dim }.map(self, l),
-            Self::I64(ids) => IndexSelect { ids, ids_l, dim }.map(self, l),
-            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "index-select")),
-        }
+        self.map_bool_as_u8(|s| match ids {
+            Self::U8(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            Self::U32(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            Self::I8(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            Self::I16(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            Self::I32(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            Self::I64(ids) => IndexSelect { ids, ids_l, dim }.map(s, l),
+            _ => Err(Error::UnsupportedDTypeForOp(ids.dtype(), "index-select")),
+        })
     }
 
     fn gather(&self, l: &Layout, ids: &Self, ids_l: &Layout, dim: usize) -> Result<Self> {
-        match ids {
-            Self::U8(ids) => Gather { ids, ids_l, dim }.map(self, l),
-            Self::U32(ids) => Gather { ids, ids_l, dim }.map(self, l),
-            Self::I64(ids) => Gather { ids, ids_l, dim }.map(self, l),
-            _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "gather")),
-        }
+        self.map_bool_as_u8(|s| match ids {
+            Self::U8(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            Self::U32(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            Self::I8(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            Self::I16(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            Self::I32(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            Self::I64(ids) => Gather { ids, ids_l, dim }.map(s, l),
+            _ => Err(Error::UnsupportedDTypeForOp(ids.dtype(), "gather")),
+        })
     }
 
     fn scatter_add(
@@ -2599,6 +4230,9 @@ impl BackendStorage for CpuStorage {
         match ids {
             Self::U8(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
             Self::U32(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
+            Self::I8(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
+            Self::I16(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
+            Self::I32(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
             Self::I64(ids) => ScatterAdd { ids, ids_l, dim }.map(self, l, src, src_l),
             _ => Err(Error::UnsupportedDTypeForOp(self.dtype(), "scatter-add")),
         }
@@ -2628,6 +4262,27 @@ impl BackendStorage for CpuStorage {
                 };
                 IndexAdd { ids, dim }.map(self, l, src, src_l)
             }
+            Self::I8(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-add" }.bt())?,
+                };
+                IndexAdd { ids, dim }.map(self, l, src, src_l)
+            }
+            Self::I16(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-add" }.bt())?,
+                };
+                IndexAdd { ids, dim }.map(self, l, src, src_l)
+            }
+            Self::I32(ids) => {
+                let ids = match ids_l.contiguous_offsets() {
+                    Some((a, b)) => &ids[a..b],
+                    None => Err(Error::RequiresContiguous { op: "index-add" }.bt())?,
+                };
+                IndexAdd { ids, dim }.map(self, l, src, src_l)
+            }
             Self::I64(ids) => {
                 let ids = match ids_l.contiguous_offsets() {
                     Some((a, b)) => &ids[a..b],
@@ -2681,19 +4336,26 @@ impl BackendDevice for CpuDevice {
         Ok(Self)
     }
 
-    fn set_seed(&self, _seed: u64) -> Result<()> {
-        crate::bail!("cannot seed the CPU rng with set_seed")
+    fn set_seed(&self, seed: u64) -> Result<()> {
+        *CPU_SEED.lock().unwrap() = Some((seed, 0));
+        Ok(())
     }
 
     fn rand_uniform(&self, shape: &Shape, dtype: DType, min: f64, max: f64) -> Result<CpuStorage> {
         use rand::prelude::*;
 
         let elem_count = shape.elem_count();
-        let mut rng = rand::thread_rng();
+        let mut rng = cpu_rng()?;
         match dtype {
-            DType::U8 | DType::U32 | DType::I64 => {
-                Err(Error::UnsupportedDTypeForOp(dtype, "rand_uniform").bt())
-            }
+            DType::Bool
+            | DType::U8
+            | DType::U32
+            | DType::I8
+            | DType::I16
+            | DType::I32
+            | DType::I64
+            | DType::C64
+            | DType::C128 => Err(Error::UnsupportedDTypeForOp(dtype, "rand_uniform").bt()),
             DType::BF16 => {
                 let mut data = Vec::with_capacity(elem_count);
                 let uniform =
@@ -2735,11 +4397,17 @@ impl BackendDevice for CpuDevice {
         use rand::prelude::*;
 
         let elem_count = shape.elem_count();
-        let mut rng = rand::thread_rng();
+        let mut rng = cpu_rng()?;
         match dtype {
-            DType::U8 | DType::U32 | DType::I64 => {
-                Err(Error::UnsupportedDTypeForOp(dtype, "rand_normal").bt())
-            }
+            DType::Bool
+            | DType::U8
+            | DType::U32
+            | DType::I8
+            | DType::I16
+            | DType::I32
+            | DType::I64
+            | DType::C64
+            | DType::C128 => Err(Error::UnsupportedDTypeForOp(dtype, "rand_normal").bt()),
             DType::BF16 => {
                 let mut data = Vec::with_capacity(elem_count);
                 let normal = rand_distr::Normal::new(bf16::from_f64(mean), bf16::from_f64(std))
@@ -2781,13 +4449,25 @@ impl BackendDevice for CpuDevice {
     fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<CpuStorage> {
         let elem_count = shape.elem_count();
         let storage = match dtype {
-            DType::U8 => CpuStorage::U8(vec![1u8; elem_count]),
-            DType::U32 => CpuStorage::U32(vec![1u32; elem_count]),
-            DType::I64 => CpuStorage::I64(vec![1i64; elem_count]),
-            DType::BF16 => CpuStorage::BF16(vec![bf16::ONE; elem_count]),
-            DType::F16 => CpuStorage::F16(vec![f16::ONE; elem_count]),
-            DType::F32 => CpuStorage::F32(vec![1f32; elem_count]),
-            DType::F64 => CpuStorage::F64(vec![1f64; elem_count]),
+            DType::Bool => CpuStorage::Bool(crate::memory::filled_vec(elem_count, true)),
+            DType::U8 => CpuStorage::U8(crate::memory::filled_vec(elem_count, 1u8)),
+            DType::U32 => CpuStorage::U32(crate::memory::filled_vec(elem_count, 1u32)),
+            DType::I8 => CpuStorage::I8(crate::memory::filled_vec(elem_count, 1i8)),
+            DType::I16 => CpuStorage::I16(crate::memory::filled_vec(elem_count, 1i16)),
+            DType::I32 => CpuStorage::I32(crate::memory::filled_vec(elem_count, 1i32)),
+            DType::I64 => CpuStorage::I64(crate::memory::filled_vec(elem_count, 1i64)),
+            DType::BF16 => CpuStorage::BF16(crate::memory::filled_vec(elem_count, bf16::ONE)),
+            DType::F16 => CpuStorage::F16(crate::memory::filled_vec(elem_count, f16::ONE)),
+            DType::F32 => CpuStorage::F32(crate::memory::filled_vec(elem_count, 1f32)),
+            DType::F64 => CpuStorage::F64(crate::memory::filled_vec(elem_count, 1f64)),
+            DType::C64 => CpuStorage::C64(crate::memory::filled_vec(
+                elem_count,
+                Complex::new(1f32, 0f32),
+            )),
+            DType::C128 => CpuStorage::C128(crate::memory::filled_vec(
+                elem_count,
+                Complex::new(1f64, 0f64),
+            )),
         };
         Ok(storage)
     }
@@ -2795,13 +4475,25 @@ impl BackendDevice for CpuDevice {
     fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<CpuStorage> {
         let elem_count = shape.elem_count();
         let storage = match dtype {
-            DType::U8 => CpuStorage::U8(vec![0u8; elem_count]),
-            DType::U32 => CpuStorage::U32(vec![0u32; elem_count]),
-            DType::I64 => CpuStorage::I64(vec![0i64; elem_count]),
-            DType::BF16 => CpuStorage::BF16(vec![bf16::ZERO; elem_count]),
-            DType::F16 => CpuStorage::F16(vec![f16::ZERO; elem_count]),
-            DType::F32 => CpuStorage::F32(vec![0f32; elem_count]),
-            DType::F64 => CpuStorage::F64(vec![0f64; elem_count]),
+            DType::Bool => CpuStorage::Bool(crate::memory::filled_vec(elem_count, false)),
+            DType::U8 => CpuStorage::U8(crate::memory::filled_vec(elem_count, 0u8)),
+            DType::U32 => CpuStorage::U32(crate::memory::filled_vec(elem_count, 0u32)),
+            DType::I8 => CpuStorage::I8(crate::memory::filled_vec(elem_count, 0i8)),
+            DType::I16 => CpuStorage::I16(crate::memory::filled_vec(elem_count, 0i16)),
+            DType::I32 => CpuStorage::I32(crate::memory::filled_vec(elem_count, 0i32)),
+            DType::I64 => CpuStorage::I64(crate::memory::filled_vec(elem_count, 0i64)),
+            DType::BF16 => CpuStorage::BF16(crate::memory::filled_vec(elem_count, bf16::ZERO)),
+            DType::F16 => CpuStorage::F16(crate::memory::filled_vec(elem_count, f16::ZERO)),
+            DType::F32 => CpuStorage::F32(crate::memory::filled_vec(elem_count, 0f32)),
+            DType::F64 => CpuStorage::F64(crate::memory::filled_vec(elem_count, 0f64)),
+            DType::C64 => CpuStorage::C64(crate::memory::filled_vec(
+                elem_count,
+                Complex::new(0f32, 0f32),
+            )),
+            DType::C128 => CpuStorage::C128(crate::memory::filled_vec(
+                elem_count,
+                Complex::new(0f64, 0f64),
+            )),
         };
         Ok(storage)
     }
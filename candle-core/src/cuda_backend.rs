@@ -1,5 +1,5 @@
 use crate::backend::{BackendDevice, BackendStorage};
-use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, ScanOp, UnaryOpT};
 use crate::{CpuStorage, DType, Layout, Result, Shape, WithDType};
 pub use candle_kernels as kernels;
 pub use cudarc;
@@ -84,6 +84,7 @@ pub struct CudaDevice {
     device: Arc<cudarc::driver::CudaDevice>,
     blas: Arc<cudarc::cublas::CudaBlas>,
     curand: Arc<Mutex<CudaRng>>,
+    memory: Arc<crate::memory::MemoryCounters>,
 }
 
 impl std::fmt::Debug for CudaDevice {
@@ -119,10 +120,23 @@ impl CudaDevice {
         self.id
     }
 
+    /// The memory statistics of the storages allocated on this device.
+    pub(crate) fn memory_stats(&self) -> crate::memory::MemoryStats {
+        self.memory.stats()
+    }
+
     fn const_impl(&self, v: f64, shape: &Shape, dtype: DType) -> Result<CudaStorage> {
         let elem_count = shape.elem_count();
         let cfg = LaunchConfig::for_num_elems(elem_count as u32);
         let slice = match dtype {
+            DType::Bool => {
+                // SAFETY: Set later by running the fill kernel.
+                let data = unsafe { self.alloc::<u8>(elem_count) }.w()?;
+                let func = self.get_or_load_func("fill_u8", kernels::FILL)?;
+                let params = (&data, u8::from(v != 0.), elem_count);
+                unsafe { func.launch(cfg, params) }.w()?;
+                CudaStorageSlice::Bool(data)
+            }
             DType::U8 => {
                 // SAFETY: Set later by running the fill kernel.
                 let data = unsafe { self.alloc::<u8>(elem_count) }.w()?;
@@ -131,6 +145,9 @@ impl CudaDevice {
                 unsafe { func.launch(cfg, params) }.w()?;
                 CudaStorageSlice::U8(data)
             }
+            DType::I8 | DType::I16 | DType::I32 | DType::C64 | DType::C128 => {
+                Err(CudaError::UnsupportedDtype { dtype, op: "fill" }).w()?
+            }
             DType::U32 => {
                 // SAFETY: Set later by running the fill kernel.
                 let data = unsafe { self.alloc::<u32>(elem_count) }.w()?;
@@ -180,10 +197,7 @@ impl CudaDevice {
                 CudaStorageSlice::F64(data)
             }
         };
-        Ok(CudaStorage {
-            slice,
-            device: self.clone(),
-        })
+        Ok(CudaStorage::new(slice, self.clone()))
     }
 
     pub fn get_or_load_func(&self, module_name: &str, ptx: &'static str) -> Result<CudaFunction> {
@@ -220,6 +234,7 @@ impl BackendDevice for CudaDevice {
             device,
             blas: Arc::new(blas),
             curand: Arc::new(Mutex::new(CudaRng(curand))),
+            memory: Arc::new(crate::memory::MemoryCounters::new()),
         })
     }
 
@@ -244,10 +259,17 @@ impl BackendDevice for CudaDevice {
     fn zeros_impl(&self, shape: &Shape, dtype: DType) -> Result<CudaStorage> {
         let elem_count = shape.elem_count();
         let slice = match dtype {
+            DType::Bool => {
+                let data = self.alloc_zeros::<u8>(elem_count).w()?;
+                CudaStorageSlice::Bool(data)
+            }
             DType::U8 => {
                 let data = self.alloc_zeros::<u8>(elem_count).w()?;
                 CudaStorageSlice::U8(data)
             }
+            DType::I8 | DType::I16 | DType::I32 | DType::C64 | DType::C128 => {
+                Err(CudaError::UnsupportedDtype { dtype, op: "zeros" }).w()?
+            }
             DType::U32 => {
                 let data = self.alloc_zeros::<u32>(elem_count).w()?;
                 CudaStorageSlice::U32(data)
@@ -273,10 +295,7 @@ impl BackendDevice for CudaDevice {
                 CudaStorageSlice::F64(data)
             }
         };
-        Ok(CudaStorage {
-            slice,
-            device: self.clone(),
-        })
+        Ok(CudaStorage::new(slice, self.clone()))
     }
 
     fn rand_uniform(&self, shape: &Shape, dtype: DType, lo: f64, up: f64) -> Result<CudaStorage> {
@@ -285,13 +304,21 @@ impl BackendDevice for CudaDevice {
         let slice = match dtype {
             // TODO: Add support for F16 and BF16 though this is likely to require some upstream
             // cudarc changes.
-            DType::U8 | DType::U32 | DType::I64 | DType::F16 | DType::BF16 => {
-                Err(CudaError::UnsupportedDtype {
-                    dtype,
-                    op: "rand_uniform",
-                })
-                .w()?
-            }
+            DType::Bool
+            | DType::U8
+            | DType::U32
+            | DType::I8
+            | DType::I16
+            | DType::I32
+            | DType::I64
+            | DType::F16
+            | DType::BF16
+            | DType::C64
+            | DType::C128 => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "rand_uniform",
+            })
+            .w()?,
             DType::F32 => {
                 let mut data = unsafe { self.alloc::<f32>(elem_count) }.w()?;
                 curand.0.fill_with_uniform(&mut data).w()?;
@@ -309,10 +336,7 @@ impl BackendDevice for CudaDevice {
             let layout = Layout::contiguous(shape);
             Affine(up - lo, lo).map(&slice, self, &layout)?
         };
-        Ok(CudaStorage {
-            slice,
-            device: self.clone(),
-        })
+        Ok(CudaStorage::new(slice, self.clone()))
     }
 
     fn rand_normal(&self, shape: &Shape, dtype: DType, mean: f64, std: f64) -> Result<CudaStorage> {
@@ -328,13 +352,21 @@ impl BackendDevice for CudaDevice {
             elem_count
         };
         let slice = match dtype {
-            DType::U8 | DType::U32 | DType::I64 | DType::F16 | DType::BF16 => {
-                Err(CudaError::UnsupportedDtype {
-                    dtype,
-                    op: "rand_normal",
-                })
-                .w()?
-            }
+            DType::Bool
+            | DType::U8
+            | DType::U32
+            | DType::I8
+            | DType::I16
+            | DType::I32
+            | DType::I64
+            | DType::F16
+            | DType::BF16
+            | DType::C64
+            | DType::C128 => Err(CudaError::UnsupportedDtype {
+                dtype,
+                op: "rand_normal",
+            })
+            .w()?,
             DType::F32 => {
                 let mut data = unsafe { self.alloc::<f32>(elem_count_round) }.w()?;
                 curand
@@ -349,10 +381,7 @@ impl BackendDevice for CudaDevice {
                 CudaStorageSlice::F64(data)
             }
         };
-        Ok(CudaStorage {
-            slice,
-            device: self.clone(),
-        })
+        Ok(CudaStorage::new(slice, self.clone()))
     }
 
     fn ones_impl(&self, shape: &Shape, dtype: DType) -> Result<CudaStorage> {
@@ -361,10 +390,24 @@ impl BackendDevice for CudaDevice {
 
     fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<CudaStorage> {
         let slice = match storage {
+            CpuStorage::Bool(storage) => {
+                let storage: Vec<u8> = storage.iter().map(|&v| u8::from(v)).collect();
+                let data = self.htod_sync_copy(&storage).w()?;
+                CudaStorageSlice::Bool(data)
+            }
             CpuStorage::U8(storage) => {
                 let data = self.htod_sync_copy(storage).w()?;
                 CudaStorageSlice::U8(data)
             }
+            CpuStorage::I8(_)
+            | CpuStorage::I16(_)
+            | CpuStorage::I32(_)
+            | CpuStorage::C64(_)
+            | CpuStorage::C128(_) => Err(CudaError::UnsupportedDtype {
+                dtype: storage.dtype(),
+                op: "storage_from_cpu_storage",
+            })
+            .w()?,
             CpuStorage::U32(storage) => {
                 let data = self.htod_sync_copy(storage).w()?;
                 CudaStorageSlice::U32(data)
@@ -390,15 +433,14 @@ impl BackendDevice for CudaDevice {
                 CudaStorageSlice::F64(data)
             }
         };
-        Ok(CudaStorage {
-            slice,
-            device: self.clone(),
-        })
+        Ok(CudaStorage::new(slice, self.clone()))
     }
 }
 
 #[derive(Debug)]
 pub enum CudaStorageSlice {
+    // Booleans are stored using one byte per element, set to either 0 or 1.
+    Bool(CudaSlice<u8>),
     U8(CudaSlice<u8>),
     U32(CudaSlice<u32>),
     I64(CudaSlice<i64>),
@@ -409,7 +451,24 @@ pub enum CudaStorageSlice {
 }
 type S = CudaStorageSlice;
 
+impl CudaStorageSlice {
+    fn dtype(&self) -> DType {
+        match self {
+            Self::Bool(_) => DType::Bool,
+            Self::U8(_) => DType::U8,
+            Self::U32(_) => DType::U32,
+            Self::I64(_) => DType::I64,
+            Self::BF16(_) => DType::BF16,
+            Self::F16(_) => DType::F16,
+            Self::F32(_) => DType::F32,
+            Self::F64(_) => DType::F64,
+        }
+    }
+}
+
 pub trait Map1 {
+    const OP: &'static str;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src: &CudaSlice<T>,
@@ -419,6 +478,10 @@ pub trait Map1 {
 
     fn map(&self, s: &S, d: &CudaDevice, l: &Layout) -> Result<S> {
         let out = match s {
+            S::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: Self::OP,
+            })?,
             S::U8(s) => S::U8(self.f(s, d, l)?),
             S::U32(s) => S::U32(self.f(s, d, l)?),
             S::I64(s) => S::I64(self.f(s, d, l)?),
@@ -432,6 +495,8 @@ pub trait Map1 {
 }
 
 pub trait Map2 {
+    const OP: &'static str;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src1: &CudaSlice<T>,
@@ -443,6 +508,10 @@ pub trait Map2 {
 
     fn map(&self, s1: &S, l1: &Layout, s2: &S, l2: &Layout, d: &CudaDevice) -> Result<S> {
         let out = match (s1, s2) {
+            (S::Bool(_), S::Bool(_)) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: Self::OP,
+            })?,
             (S::U8(s1), S::U8(s2)) => S::U8(self.f(s1, l1, s2, l2, d)?),
             (S::U32(s1), S::U32(s2)) => S::U32(self.f(s1, l1, s2, l2, d)?),
             (S::I64(s1), S::I64(s2)) => S::I64(self.f(s1, l1, s2, l2, d)?),
@@ -457,6 +526,8 @@ pub trait Map2 {
 }
 
 pub trait Map2InPlace {
+    const OP: &'static str;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         dst: &mut CudaSlice<T>,
@@ -475,6 +546,10 @@ pub trait Map2InPlace {
         d: &CudaDevice,
     ) -> Result<()> {
         match (dst, src) {
+            (S::Bool(_), S::Bool(_)) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: Self::OP,
+            })?,
             (S::U8(dst), S::U8(src)) => self.f(dst, dst_s, src, src_l, d),
             (S::U32(dst), S::U32(src)) => self.f(dst, dst_s, src, src_l, d),
             (S::I64(dst), S::I64(src)) => self.f(dst, dst_s, src, src_l, d),
@@ -488,6 +563,8 @@ pub trait Map2InPlace {
 }
 
 pub trait Map1Any {
+    const OP: &'static str;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits, W: Fn(CudaSlice<T>) -> S>(
         &self,
         src: &CudaSlice<T>,
@@ -498,6 +575,10 @@ pub trait Map1Any {
 
     fn map(&self, s: &S, d: &CudaDevice, l: &Layout) -> Result<S> {
         let out = match s {
+            S::Bool(_) => Err(CudaError::UnsupportedDtype {
+                dtype: DType::Bool,
+                op: Self::OP,
+            })?,
             S::U8(s) => self.f(s, d, l, S::U8)?,
             S::U32(s) => self.f(s, d, l, S::U32)?,
             S::I64(s) => self.f(s, d, l, S::I64)?,
@@ -522,6 +603,7 @@ pub trait Map2Any {
 
     fn map(&self, s1: &S, l1: &Layout, s2: &S, l2: &Layout, d: &CudaDevice) -> Result<S> {
         let out = match (s1, s2) {
+            (S::Bool(s1), S::Bool(s2)) => self.f(s1, l1, s2, l2, d)?,
             (S::U8(s1), S::U8(s2)) => self.f(s1, l1, s2, l2, d)?,
             (S::U32(s1), S::U32(s2)) => self.f(s1, l1, s2, l2, d)?,
             (S::I64(s1), S::I64(s2)) => self.f(s1, l1, s2, l2, d)?,
@@ -537,6 +619,8 @@ pub trait Map2Any {
 
 struct Clone;
 impl Map1 for Clone {
+    const OP: &'static str = "clone";
+
     fn f<T: DeviceRepr>(
         &self,
         s: &CudaSlice<T>,
@@ -554,6 +638,8 @@ pub fn kernel_name<T: WithDType>(root: &str) -> String {
 
 struct Affine(f64, f64);
 impl Map1 for Affine {
+    const OP: &'static str = "affine";
+
     fn f<T: DeviceRepr + WithDType>(
         &self,
         src: &CudaSlice<T>,
@@ -586,6 +672,8 @@ impl Map1 for Affine {
 
 struct Elu(f64);
 impl Map1 for Elu {
+    const OP: &'static str = "elu";
+
     fn f<T: DeviceRepr + WithDType>(
         &self,
         src: &CudaSlice<T>,
@@ -622,6 +710,8 @@ impl Im2Col1D {
 }
 
 impl Map1 for Im2Col1D {
+    const OP: &'static str = "im2col1d";
+
     fn f<T: DeviceRepr + WithDType>(
         &self,
         src: &CudaSlice<T>,
@@ -672,6 +762,8 @@ impl Im2Col {
 }
 
 impl Map1 for Im2Col {
+    const OP: &'static str = "im2col";
+
     fn f<T: DeviceRepr + WithDType>(
         &self,
         src: &CudaSlice<T>,
@@ -709,6 +801,8 @@ impl Map1 for Im2Col {
 
 struct Powf(f64);
 impl Map1 for Powf {
+    const OP: &'static str = "powf";
+
     fn f<T: DeviceRepr + WithDType>(
         &self,
         src: &CudaSlice<T>,
@@ -733,6 +827,8 @@ impl Map1 for Powf {
 
 struct Sum<'a>(&'a [usize]);
 impl<'a> Map1 for Sum<'a> {
+    const OP: &'static str = "sum";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src: &CudaSlice<T>,
@@ -771,6 +867,8 @@ impl<'a> Map1 for Sum<'a> {
 
 struct FastReduce<'a>(&'a [usize], ReduceOp);
 impl<'a> Map1Any for FastReduce<'a> {
+    const OP: &'static str = "reduce";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits, W: Fn(CudaSlice<T>) -> S>(
         &self,
         src: &CudaSlice<T>,
@@ -842,6 +940,8 @@ impl<'a> Map1Any for FastReduce<'a> {
 }
 
 impl<U: UnaryOpT> Map1 for U {
+    const OP: &'static str = U::NAME;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src: &CudaSlice<T>,
@@ -866,6 +966,8 @@ impl<U: UnaryOpT> Map1 for U {
 
 struct IndexSelect<'a>(&'a CudaStorage, &'a Layout, usize);
 impl<'a> Map1 for IndexSelect<'a> {
+    const OP: &'static str = "index-select";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src: &CudaSlice<T>,
@@ -926,6 +1028,8 @@ impl<'a> Map1 for IndexSelect<'a> {
 
 struct Gather<'a>(&'a CudaStorage, &'a Layout, usize);
 impl<'a> Map1 for Gather<'a> {
+    const OP: &'static str = "gather";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         src: &CudaSlice<T>,
@@ -977,6 +1081,8 @@ impl<'a> Map1 for Gather<'a> {
 
 struct IndexAdd<'a>(&'a CudaStorage, &'a Layout, usize);
 impl<'a> Map2InPlace for IndexAdd<'a> {
+    const OP: &'static str = "index-add";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         dst: &mut CudaSlice<T>,
@@ -1025,6 +1131,8 @@ impl<'a> Map2InPlace for IndexAdd<'a> {
 
 struct ScatterAdd<'a>(&'a CudaStorage, &'a Layout, usize);
 impl<'a> Map2InPlace for ScatterAdd<'a> {
+    const OP: &'static str = "scatter-add";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         dst: &mut CudaSlice<T>,
@@ -1070,6 +1178,8 @@ impl<'a> Map2InPlace for ScatterAdd<'a> {
 
 struct Conv1D<'a>(&'a crate::conv::ParamsConv1D);
 impl<'a> Map2 for Conv1D<'a> {
+    const OP: &'static str = "conv1d";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         inp: &CudaSlice<T>,
@@ -1111,6 +1221,8 @@ impl<'a> Map2 for Conv1D<'a> {
 
 struct Conv2D<'a>(&'a crate::conv::ParamsConv2D);
 impl<'a> Map2 for Conv2D<'a> {
+    const OP: &'static str = "conv2d";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         inp: &CudaSlice<T>,
@@ -1151,6 +1263,8 @@ impl<'a> Map2 for Conv2D<'a> {
 
 struct ConvTranspose2D<'a>(&'a crate::conv::ParamsConvTranspose2D);
 impl<'a> Map2 for ConvTranspose2D<'a> {
+    const OP: &'static str = "conv_transpose2d";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         inp: &CudaSlice<T>,
@@ -1213,6 +1327,8 @@ struct Pool2D {
 }
 
 impl Map1 for Pool2D {
+    const OP: &'static str = "pool2d";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         inp: &CudaSlice<T>,
@@ -1259,6 +1375,8 @@ impl Map1 for Pool2D {
 
 struct UpsampleNearest2D(usize, usize);
 impl Map1 for UpsampleNearest2D {
+    const OP: &'static str = "upsample-nearest2d";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         inp: &CudaSlice<T>,
@@ -1292,6 +1410,8 @@ impl Map1 for UpsampleNearest2D {
 
 struct WhereCond<'a>(&'a CudaStorage, &'a Layout);
 impl<'a> Map2 for WhereCond<'a> {
+    const OP: &'static str = "where";
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         t: &CudaSlice<T>,
@@ -1302,7 +1422,7 @@ impl<'a> Map2 for WhereCond<'a> {
     ) -> Result<CudaSlice<T>> {
         let ids_l = &self.1;
         let (ids, name) = match &self.0.slice {
-            CudaStorageSlice::U8(slice) => {
+            CudaStorageSlice::Bool(slice) | CudaStorageSlice::U8(slice) => {
                 let ptr = *slice.slice(ids_l.start_offset()..).device_ptr();
                 (ptr, "where_u8")
             }
@@ -1315,7 +1435,7 @@ impl<'a> Map2 for WhereCond<'a> {
                 (ptr, "where_i64")
             }
             _ => Err(CudaError::UnexpectedDType {
-                msg: "where conditions should be u8/u32/i64",
+                msg: "where conditions should be bool/u8/u32/i64",
                 expected: DType::U32,
                 got: self.0.dtype(),
             })
@@ -1341,6 +1461,8 @@ impl<'a> Map2 for WhereCond<'a> {
 }
 
 impl<U: crate::op::BinaryOpT> Map2 for U {
+    const OP: &'static str = U::NAME;
+
     fn f<T: DeviceRepr + WithDType + ValidAsZeroBits>(
         &self,
         lhs: &CudaSlice<T>,
@@ -1401,7 +1523,7 @@ impl Map2Any for Cmp {
         let params = (elem_count, dims.len(), &dims_and_strides, lhs, rhs, &out);
         // SAFETY: ffi
         unsafe { func.launch(cfg, params) }.w()?;
-        Ok(S::U8(out))
+        Ok(S::Bool(out))
     }
 }
 
@@ -1428,6 +1550,8 @@ fn slice_src_and_dst<'a, T>(
 pub struct CudaStorage {
     pub slice: CudaStorageSlice,
     pub device: CudaDevice,
+    // Accounts for the slice in the memory statistics of the device until it gets freed.
+    _allocation: crate::memory::Allocation,
 }
 
 pub trait CudaDType: Sized {
@@ -1452,7 +1576,7 @@ macro_rules! cuda_dtype {
 
             fn wrap_cuda_slice(slice: CudaSlice<Self>, device: CudaDevice) -> CudaStorage {
                 let slice = CudaStorageSlice::$dtype(slice);
-                CudaStorage { slice, device }
+                CudaStorage::new(slice, device)
             }
         }
     };
@@ -1466,6 +1590,24 @@ cuda_dtype!(f32, F32);
 cuda_dtype!(f64, F64);
 
 impl CudaStorage {
+    pub fn new(slice: CudaStorageSlice, device: CudaDevice) -> Self {
+        let bytes = match &slice {
+            S::Bool(s) | S::U8(s) => s.len(),
+            S::U32(s) => s.len() * std::mem::size_of::<u32>(),
+            S::I64(s) => s.len() * std::mem::size_of::<i64>(),
+            S::BF16(s) => s.len() * std::mem::size_of::<bf16>(),
+            S::F16(s) => s.len() * std::mem::size_of::<f16>(),
+            S::F32(s) => s.len() * std::mem::size_of::<f32>(),
+            S::F64(s) => s.len() * std::mem::size_of::<f64>(),
+        };
+        let allocation = crate::memory::Allocation::new(&device.memory, slice.dtype(), bytes);
+        Self {
+            slice,
+            device,
+            _allocation: allocation,
+        }
+    }
+
     pub fn wrap_cuda_slice<T: CudaDType>(slice: CudaSlice<T>, device: CudaDevice) -> CudaStorage {
         T::wrap_cuda_slice(slice, device)
     }
@@ -1564,21 +1706,16 @@ impl BackendStorage for CudaStorage {
     type Device = CudaDevice;
 
     fn try_clone(&self, layout: &Layout) -> Result<Self> {
-        let slice = Clone.map(&self.slice, self.device(), layout)?;
+        let slice = match &self.slice {
+            S::Bool(s) => S::Bool(Clone.f(s, self.device(), layout)?),
+            s => Clone.map(s, self.device(), layout)?,
+        };
         let device = self.device.clone();
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn dtype(&self) -> DType {
-        match self.slice {
-            CudaStorageSlice::U8(_) => DType::U8,
-            CudaStorageSlice::U32(_) => DType::U32,
-            CudaStorageSlice::I64(_) => DType::I64,
-            CudaStorageSlice::BF16(_) => DType::BF16,
-            CudaStorageSlice::F16(_) => DType::F16,
-            CudaStorageSlice::F32(_) => DType::F32,
-            CudaStorageSlice::F64(_) => DType::F64,
-        }
+        self.slice.dtype()
     }
 
     fn device(&self) -> &CudaDevice {
@@ -1586,6 +1723,22 @@ impl BackendStorage for CudaStorage {
     }
 
     fn to_dtype(&self, layout: &Layout, dtype: DType) -> Result<Self> {
+        match dtype {
+            DType::Bool => {
+                // Non-zero values map to true, this is computed by comparing against zero.
+                let zero = self.device.zeros_impl(&Shape::from(()), self.dtype())?;
+                let zero_l = Layout::contiguous(()).broadcast_as(layout.shape())?;
+                return self.cmp(CmpOp::Ne, &zero, layout, &zero_l);
+            }
+            DType::I8 | DType::I16 | DType::I32 | DType::C64 | DType::C128 => {
+                Err(CudaError::UnsupportedDtype {
+                    dtype,
+                    op: "to_dtype",
+                })
+                .w()?
+            }
+            _ => {}
+        }
         let shape = layout.shape();
         let dims = shape.dims();
         let el = shape.elem_count();
@@ -1597,7 +1750,9 @@ impl BackendStorage for CudaStorage {
         // lifetime issue and is safe as long as self.slice does not go out of scope before inp
         // is used.
         let inp = match &self.slice {
-            CudaStorageSlice::U8(inp) => *inp.slice(start_o..).device_ptr(),
+            CudaStorageSlice::Bool(inp) | CudaStorageSlice::U8(inp) => {
+                *inp.slice(start_o..).device_ptr()
+            }
             CudaStorageSlice::U32(inp) => *inp.slice(start_o..).device_ptr(),
             CudaStorageSlice::I64(inp) => *inp.slice(start_o..).device_ptr(),
             CudaStorageSlice::BF16(inp) => *inp.slice(start_o..).device_ptr(),
@@ -1607,7 +1762,12 @@ impl BackendStorage for CudaStorage {
         };
         let inp = &inp;
 
-        let kernel_name = format!("cast_{}_{}", self.dtype().as_str(), dtype.as_str());
+        // Booleans are cast using their byte representation.
+        let src_dtype = match self.dtype() {
+            DType::Bool => DType::U8,
+            dtype => dtype,
+        };
+        let kernel_name = format!("cast_{}_{}", src_dtype.as_str(), dtype.as_str());
         let func = dev.get_or_load_func(&kernel_name, kernels::CAST)?;
         let slice = match dtype {
             DType::U8 => {
@@ -1652,47 +1812,73 @@ impl BackendStorage for CudaStorage {
                 unsafe { func.launch(cfg, params) }.w()?;
                 CudaStorageSlice::F64(out)
             }
+            DType::Bool | DType::I8 | DType::I16 | DType::I32 | DType::C64 | DType::C128 => {
+                unreachable!("unexpected dtype {dtype:?} in cast")
+            }
         };
-        Ok(Self {
-            slice,
-            device: dev.clone(),
-        })
+        Ok(Self::new(slice, dev.clone()))
     }
 
     fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
         let device = self.device().clone();
         let slice = Affine(mul, add).map(&self.slice, &device, layout)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn powf(&self, layout: &Layout, e: f64) -> Result<Self> {
         let device = self.device().clone();
         let slice = Powf(e).map(&self.slice, &device, layout)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn elu(&self, layout: &Layout, alpha: f64) -> Result<Self> {
         let device = self.device().clone();
         let slice = Elu(alpha).map(&self.slice, &device, layout)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn reduce_op(&self, op: ReduceOp, layout: &Layout, sum_dims: &[usize]) -> Result<Self> {
         let device = self.device().clone();
         let slice = FastReduce(sum_dims, op).map(&self.slice, &device, layout)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
+    }
+
+    fn scan(&self, layout: &Layout, op: ScanOp, dim: usize) -> Result<Self> {
+        // There is no scan kernel yet so the values are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.scan(layout, op, dim)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn linear_recurrence(&self, l: &Layout, b: &Self, b_l: &Layout, dim: usize) -> Result<Self> {
+        // There is no scan kernel yet so the values are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.linear_recurrence(l, &b.to_cpu_storage()?, b_l, dim)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn arg_sort_last_dim(&self, layout: &Layout, asc: bool) -> Result<Self> {
+        // There is no sorting kernel yet so the indexes are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.arg_sort_last_dim(layout, asc)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn fft_last_dim(&self, _: &Layout, _: bool) -> Result<Self> {
+        // Complex dtypes are only supported on the cpu for now.
+        Err(crate::Error::UnsupportedDTypeForOp(self.dtype(), "fft").bt())
     }
 
     fn cmp(&self, op: CmpOp, rhs: &Self, lhs_l: &Layout, rhs_l: &Layout) -> Result<Self> {
         let device = self.device().clone();
         let slice = Cmp(op).map(&self.slice, lhs_l, &rhs.slice, rhs_l, &device)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn unary_impl<U: UnaryOpT>(&self, layout: &Layout) -> Result<Self> {
         let device = self.device().clone();
         let slice = U::V.map(&self.slice, &device, layout)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn binary_impl<B: BinaryOpT>(
@@ -1703,11 +1889,17 @@ impl BackendStorage for CudaStorage {
     ) -> Result<Self> {
         let device = self.device().clone();
         let slice = B::V.map(&self.slice, lhs_l, &rhs.slice, rhs_l, &device)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn to_cpu_storage(&self) -> Result<CpuStorage> {
         match &self.slice {
+            CudaStorageSlice::Bool(slice) => {
+                let dev = slice.device();
+                let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
+                let cpu_storage = cpu_storage.into_iter().map(|v| v != 0).collect();
+                Ok(CpuStorage::Bool(cpu_storage))
+            }
             CudaStorageSlice::U8(slice) => {
                 let dev = slice.device();
                 let cpu_storage = dev.dtoh_sync_copy(slice).w()?;
@@ -1756,7 +1948,7 @@ impl BackendStorage for CudaStorage {
     ) -> Result<Self> {
         let device = self.device().clone();
         let slice = WhereCond(self, layout).map(&t.slice, t_l, &f.slice, f_l, &device)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn conv1d(
@@ -1771,7 +1963,7 @@ impl BackendStorage for CudaStorage {
         let device = self.device().clone();
         if !USE_IM2COL_CONV1D {
             let slice = Conv1D(params).map(&self.slice, l, &kernel.slice, kernel_l, &device)?;
-            return Ok(Self { slice, device });
+            return Ok(Self::new(slice, device));
         }
 
         let col = Im2Col1D {
@@ -1781,7 +1973,7 @@ impl BackendStorage for CudaStorage {
             padding: params.padding,
         }
         .map(&self.slice, &device, l)?;
-        let col = Self { slice: col, device };
+        let col = Self::new(col, device);
         let l_out = params.l_out();
         let b = params.b_size;
         let n = params.c_out;
@@ -1831,7 +2023,7 @@ impl BackendStorage for CudaStorage {
         let device = self.device().clone();
         if !USE_IM2COL_CONV2D {
             let slice = Conv2D(params).map(&self.slice, l, &kernel.slice, kernel_l, &device)?;
-            return Ok(Self { slice, device });
+            return Ok(Self::new(slice, device));
         }
 
         let col = Im2Col {
@@ -1842,7 +2034,7 @@ impl BackendStorage for CudaStorage {
             padding: params.padding,
         }
         .map(&self.slice, &device, l)?;
-        let col = Self { slice: col, device };
+        let col = Self::new(col, device);
         let h_out = params.out_h();
         let w_out = params.out_w();
         let b = params.b_size;
@@ -1883,7 +2075,7 @@ impl BackendStorage for CudaStorage {
         let device = self.device().clone();
         if !kernel_l.is_contiguous() {
             let slice = Conv2D(params).map(&self.slice, inp_l, &kernel.slice, kernel_l, &device)?;
-            return Ok(Self { slice, device });
+            return Ok(Self::new(slice, device));
         }
         let (out_w, out_h) = (params.out_w(), params.out_h());
         let dst_el = params.c_out * out_w * out_h * params.b_size;
@@ -1932,7 +2124,7 @@ impl BackendStorage for CudaStorage {
             (S::I64(_), S::I64(_)) => Err(CudaError::InternalError("conv2d does not support i64"))?,
             _ => Err(CudaError::InternalError("dtype mismatch in conv2d"))?,
         };
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn conv_transpose2d(
@@ -1945,7 +2137,27 @@ impl BackendStorage for CudaStorage {
         let device = self.device().clone();
         let slice =
             ConvTranspose2D(params).map(&self.slice, l, &kernel.slice, kernel_l, &device)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
+    }
+
+    fn conv3d(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConv3D,
+    ) -> Result<Self> {
+        crate::bail!("conv3d is not supported on cuda")
+    }
+
+    fn conv_transpose3d(
+        &self,
+        _: &Layout,
+        _: &Self,
+        _: &Layout,
+        _: &crate::conv::ParamsConvTranspose3D,
+    ) -> Result<Self> {
+        crate::bail!("conv-transpose3d is not supported on cuda")
     }
 
     fn avg_pool2d(&self, l: &Layout, k: (usize, usize), stride: (usize, usize)) -> Result<Self> {
@@ -1958,7 +2170,7 @@ impl BackendStorage for CudaStorage {
             op: PoolOp::Avg,
         }
         .map(&self.slice, &device, l)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn max_pool2d(&self, l: &Layout, k: (usize, usize), stride: (usize, usize)) -> Result<Self> {
@@ -1971,7 +2183,25 @@ impl BackendStorage for CudaStorage {
             op: PoolOp::Max,
         }
         .map(&self.slice, &device, l)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
+    }
+
+    fn avg_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self> {
+        crate::bail!("avg-pool3d is not supported on cuda")
+    }
+
+    fn max_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self> {
+        crate::bail!("max-pool3d is not supported on cuda")
     }
 
     fn upsample_nearest1d(&self, _: &Layout, _out_sz: usize) -> Result<Self> {
@@ -1981,18 +2211,18 @@ impl BackendStorage for CudaStorage {
     fn upsample_nearest2d(&self, l: &Layout, out_w: usize, out_h: usize) -> Result<Self> {
         let device = self.device().clone();
         let slice = UpsampleNearest2D(out_w, out_h).map(&self.slice, &device, l)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn index_select(&self, ids: &Self, l: &Layout, ids_l: &Layout, dim: usize) -> Result<Self> {
         let device = self.device().clone();
         let slice = IndexSelect(ids, ids_l, dim).map(&self.slice, &device, l)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
     fn gather(&self, l: &Layout, ids: &Self, ids_l: &Layout, dim: usize) -> Result<Self> {
         let device = self.device().clone();
         let slice = Gather(ids, ids_l, dim).map(&self.slice, &device, l)?;
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
     fn scatter_add(
         &self,
@@ -2090,7 +2320,7 @@ impl BackendStorage for CudaStorage {
             _ => Err(CudaError::InternalError("dtype mismatch in matmul op"))?,
         };
         let device = dev.clone();
-        Ok(Self { slice, device })
+        Ok(Self::new(slice, device))
     }
 
     fn copy_strided_src(&self, dst: &mut Self, dst_offset: usize, src_l: &Layout) -> Result<()> {
@@ -2140,7 +2370,8 @@ impl BackendStorage for CudaStorage {
                     unsafe { func.launch(cfg, params) }.w()?
                 }
             }
-            (CudaStorageSlice::U8(src), CudaStorageSlice::U8(dst)) => {
+            (CudaStorageSlice::Bool(src), CudaStorageSlice::Bool(dst))
+            | (CudaStorageSlice::U8(src), CudaStorageSlice::U8(dst)) => {
                 let (src, mut dst) = slice_src_and_dst(src, src_l, dst, dst_offset);
                 if src_l.is_contiguous() {
                     dev.dtod_copy(&src, &mut dst).w()?
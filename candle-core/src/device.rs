@@ -1,6 +1,6 @@
 use crate::backend::BackendDevice;
 use crate::cpu_backend::CpuDevice;
-use crate::{CpuStorage, DType, Result, Shape, Storage, WithDType};
+use crate::{CpuStorage, DType, ElemType, Result, Shape, Storage};
 
 /// A `DeviceLocation` represents a physical device whereas multiple `Device`
 /// can live on the same location (typically for cuda devices).
@@ -14,6 +14,8 @@ pub enum DeviceLocation {
 #[derive(Debug, Clone)]
 pub enum Device {
     Cpu,
+    /// A cpu device whose ops run on the thread pool of the configuration.
+    CpuPool(crate::CpuDeviceConfig),
     Cuda(crate::CudaDevice),
     Metal(crate::MetalDevice),
 }
@@ -24,7 +26,7 @@ pub trait NdArray {
     fn to_cpu_storage(&self) -> CpuStorage;
 }
 
-impl<S: WithDType> NdArray for S {
+impl<S: ElemType> NdArray for S {
     fn shape(&self) -> Result<Shape> {
         Ok(Shape::from(()))
     }
@@ -34,7 +36,7 @@ impl<S: WithDType> NdArray for S {
     }
 }
 
-impl<S: WithDType, const N: usize> NdArray for &[S; N] {
+impl<S: ElemType, const N: usize> NdArray for &[S; N] {
     fn shape(&self) -> Result<Shape> {
         Ok(Shape::from(self.len()))
     }
@@ -44,7 +46,7 @@ impl<S: WithDType, const N: usize> NdArray for &[S; N] {
     }
 }
 
-impl<S: WithDType> NdArray for &[S] {
+impl<S: ElemType> NdArray for &[S] {
     fn shape(&self) -> Result<Shape> {
         Ok(Shape::from(self.len()))
     }
@@ -54,7 +56,7 @@ impl<S: WithDType> NdArray for &[S] {
     }
 }
 
-impl<S: WithDType, const N: usize, const M: usize> NdArray for &[[S; N]; M] {
+impl<S: ElemType, const N: usize, const M: usize> NdArray for &[[S; N]; M] {
     fn shape(&self) -> Result<Shape> {
         Ok(Shape::from((M, N)))
     }
@@ -64,7 +66,7 @@ impl<S: WithDType, const N: usize, const M: usize> NdArray for &[[S; N]; M] {
     }
 }
 
-impl<S: WithDType, const N1: usize, const N2: usize, const N3: usize> NdArray
+impl<S: ElemType, const N1: usize, const N2: usize, const N3: usize> NdArray
     for &[[[S; N3]; N2]; N1]
 {
     fn shape(&self) -> Result<Shape> {
@@ -82,7 +84,7 @@ impl<S: WithDType, const N1: usize, const N2: usize, const N3: usize> NdArray
     }
 }
 
-impl<S: WithDType, const N1: usize, const N2: usize, const N3: usize, const N4: usize> NdArray
+impl<S: ElemType, const N1: usize, const N2: usize, const N3: usize, const N4: usize> NdArray
     for &[[[[S; N4]; N3]; N2]; N1]
 {
     fn shape(&self) -> Result<Shape> {
@@ -136,7 +138,7 @@ impl Device {
 
     pub fn set_seed(&self, seed: u64) -> Result<()> {
         match self {
-            Self::Cpu => CpuDevice.set_seed(seed),
+            Self::Cpu | Self::CpuPool(_) => CpuDevice.set_seed(seed),
             Self::Cuda(c) => c.set_seed(seed),
             Self::Metal(m) => m.set_seed(seed),
         }
@@ -145,6 +147,7 @@ impl Device {
     pub fn same_device(&self, rhs: &Self) -> bool {
         match (self, rhs) {
             (Self::Cpu, Self::Cpu) => true,
+            (Self::CpuPool(lhs), Self::CpuPool(rhs)) => lhs.same_pool(rhs),
             (Self::Cuda(lhs), Self::Cuda(rhs)) => lhs.same_device(rhs),
             (Self::Metal(lhs), Self::Metal(rhs)) => lhs.same_device(rhs),
             _ => false,
@@ -153,14 +156,74 @@ impl Device {
 
     pub fn location(&self) -> DeviceLocation {
         match self {
-            Self::Cpu => DeviceLocation::Cpu,
+            Self::Cpu | Self::CpuPool(_) => DeviceLocation::Cpu,
             Self::Cuda(device) => device.location(),
             Device::Metal(device) => device.location(),
         }
     }
 
+    /// Returns the memory statistics for this device. On cuda and metal these cover all the
+    /// storages allocated by the backend, on cpu they cover the buffers allocated by the ops,
+    /// including their intermediate buffers, and the storages owned by tensors.
+    pub fn memory_stats(&self) -> crate::memory::MemoryStats {
+        match self {
+            Self::Cpu | Self::CpuPool(_) => crate::memory::cpu_counters().stats(),
+            Self::Cuda(device) => device.memory_stats(),
+            Self::Metal(device) => device.memory_stats(),
+        }
+    }
+
+    /// Enables or disables the caching allocator for the storages of this device, this is only
+    /// supported on cpu where all the devices share the cache of [`crate::memory`]. When enabled,
+    /// the buffers of the dropped storages are kept around and reused for the outputs of the
+    /// following ops, disabling the allocator releases them.
+    ///
+    /// ```rust
+    /// use candle_core::{DType, Device, Tensor};
+    /// let device = Device::Cpu;
+    /// device.set_caching_allocator(true)?;
+    /// for _ in 0..4 {
+    ///     let xs = Tensor::ones((32, 32), DType::F32, &device)?;
+    ///     let _ys = xs.exp()?;
+    /// }
+    /// assert!(device.caching_allocator_stats().hits > 0);
+    /// device.set_caching_allocator(false)?;
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn set_caching_allocator(&self, enabled: bool) -> Result<()> {
+        match self {
+            Self::Cpu | Self::CpuPool(_) => {
+                crate::memory::set_cpu_caching(enabled);
+                Ok(())
+            }
+            Self::Cuda(_) | Self::Metal(_) => {
+                crate::bail!(
+                    "the caching allocator is only supported on cpu, got {:?}",
+                    self.location()
+                )
+            }
+        }
+    }
+
+    /// Releases the buffers held by the caching allocator of this device.
+    pub fn empty_cache(&self) {
+        if self.is_cpu() {
+            crate::memory::empty_cpu_cache()
+        }
+    }
+
+    /// Returns the statistics of the caching allocator of this device, including the number of
+    /// cache hits and misses since the start of the process.
+    pub fn caching_allocator_stats(&self) -> crate::memory::CacheStats {
+        if self.is_cpu() {
+            crate::memory::cpu_cache_stats()
+        } else {
+            crate::memory::CacheStats::default()
+        }
+    }
+
     pub fn is_cpu(&self) -> bool {
-        matches!(self, Self::Cpu)
+        matches!(self, Self::Cpu | Self::CpuPool(_))
     }
 
     pub fn is_cuda(&self) -> bool {
@@ -171,6 +234,24 @@ impl Device {
         matches!(self, Self::Metal(_))
     }
 
+    /// Runs `f` on the thread pool of the device for the cpu devices with a configuration, runs
+    /// it on the current thread otherwise.
+    pub(crate) fn install<R: Send, F: FnOnce() -> R + Send>(&self, f: F) -> R {
+        match self {
+            Self::CpuPool(config) => config.install(f),
+            Self::Cpu | Self::Cuda(_) | Self::Metal(_) => f(),
+        }
+    }
+
+    /// Wraps a cpu storage so that its ops run on the thread pool of this device, this is only
+    /// meant to be called on cpu devices.
+    pub(crate) fn cpu_storage(&self, storage: CpuStorage) -> Storage {
+        match self {
+            Self::CpuPool(config) => Storage::CpuPool(storage, config.clone()),
+            Self::Cpu | Self::Cuda(_) | Self::Metal(_) => Storage::Cpu(storage),
+        }
+    }
+
     pub fn cuda_if_available(ordinal: usize) -> Result<Self> {
         if crate::utils::cuda_is_available() {
             Self::new_cuda(ordinal)
@@ -187,9 +268,9 @@ impl Device {
         dtype: DType,
     ) -> Result<Storage> {
         match self {
-            Device::Cpu => {
+            Device::Cpu | Device::CpuPool(_) => {
                 let storage = CpuDevice.rand_uniform(shape, dtype, lo, up)?;
-                Ok(Storage::Cpu(storage))
+                Ok(self.cpu_storage(storage))
             }
             Device::Cuda(device) => {
                 // TODO: Remove the special case if we start supporting generating f16/bf16 directly.
@@ -225,9 +306,9 @@ impl Device {
         dtype: DType,
     ) -> Result<Storage> {
         match self {
-            Device::Cpu => {
+            Device::Cpu | Device::CpuPool(_) => {
                 let storage = CpuDevice.rand_normal(shape, dtype, mean, std)?;
-                Ok(Storage::Cpu(storage))
+                Ok(self.cpu_storage(storage))
             }
             Device::Cuda(device) => {
                 // TODO: Remove the special case if we start supporting generating f16/bf16 directly.
@@ -257,9 +338,9 @@ impl Device {
 
     pub(crate) fn ones(&self, shape: &Shape, dtype: DType) -> Result<Storage> {
         match self {
-            Device::Cpu => {
+            Device::Cpu | Device::CpuPool(_) => {
                 let storage = CpuDevice.ones_impl(shape, dtype)?;
-                Ok(Storage::Cpu(storage))
+                Ok(self.cpu_storage(storage))
             }
             Device::Cuda(device) => {
                 let storage = device.ones_impl(shape, dtype)?;
@@ -274,9 +355,9 @@ impl Device {
 
     pub(crate) fn zeros(&self, shape: &Shape, dtype: DType) -> Result<Storage> {
         match self {
-            Device::Cpu => {
+            Device::Cpu | Device::CpuPool(_) => {
                 let storage = CpuDevice.zeros_impl(shape, dtype)?;
-                Ok(Storage::Cpu(storage))
+                Ok(self.cpu_storage(storage))
             }
             Device::Cuda(device) => {
                 let storage = device.zeros_impl(shape, dtype)?;
@@ -291,7 +372,7 @@ impl Device {
 
     pub(crate) fn storage<A: NdArray>(&self, array: A) -> Result<Storage> {
         match self {
-            Device::Cpu => Ok(Storage::Cpu(array.to_cpu_storage())),
+            Device::Cpu | Device::CpuPool(_) => Ok(self.cpu_storage(array.to_cpu_storage())),
             Device::Cuda(device) => {
                 let storage = array.to_cpu_storage();
                 let storage = device.storage_from_cpu_storage(&storage)?;
@@ -305,9 +386,9 @@ impl Device {
         }
     }
 
-    pub(crate) fn storage_owned<S: WithDType>(&self, data: Vec<S>) -> Result<Storage> {
+    pub(crate) fn storage_owned<S: ElemType>(&self, data: Vec<S>) -> Result<Storage> {
         match self {
-            Device::Cpu => Ok(Storage::Cpu(S::to_cpu_storage_owned(data))),
+            Device::Cpu | Device::CpuPool(_) => Ok(self.cpu_storage(S::to_cpu_storage_owned(data))),
             Device::Cuda(device) => {
                 let storage = S::to_cpu_storage_owned(data);
                 let storage = device.storage_from_cpu_storage(&storage)?;
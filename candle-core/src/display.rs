@@ -1,14 +1,12 @@
 /// Pretty printing of tensors
 /// This implementation should be in line with the PyTorch version.
 /// https://github.com/pytorch/pytorch/blob/7b419e8513a024e172eae767e24ec1b849976b13/torch/_tensor_str.py
-use crate::{DType, Result, Tensor, WithDType};
+use crate::{DType, ElemType, Result, Tensor, WithDType};
 use half::{bf16, f16};
+use num_complex::Complex;
 
 impl Tensor {
-    fn fmt_dt<T: WithDType + std::fmt::Display>(
-        &self,
-        f: &mut std::fmt::Formatter,
-    ) -> std::fmt::Result {
+    fn fmt_dt<T: ElemType>(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let device_str = match self.device().location() {
             crate::DeviceLocation::Cpu => "".to_owned(),
             crate::DeviceLocation::Cuda { gpu_id } => {
@@ -53,13 +51,19 @@ impl Tensor {
 impl std::fmt::Debug for Tensor {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self.dtype() {
+            DType::Bool => self.fmt_dt::<bool>(f),
             DType::U8 => self.fmt_dt::<u8>(f),
             DType::U32 => self.fmt_dt::<u32>(f),
+            DType::I8 => self.fmt_dt::<i8>(f),
+            DType::I16 => self.fmt_dt::<i16>(f),
+            DType::I32 => self.fmt_dt::<i32>(f),
             DType::I64 => self.fmt_dt::<i64>(f),
             DType::BF16 => self.fmt_dt::<bf16>(f),
             DType::F16 => self.fmt_dt::<f16>(f),
             DType::F32 => self.fmt_dt::<f32>(f),
             DType::F64 => self.fmt_dt::<f64>(f),
+            DType::C64 => self.fmt_dt::<Complex<f32>>(f),
+            DType::C128 => self.fmt_dt::<Complex<f64>>(f),
         }
     }
 }
@@ -139,7 +143,7 @@ impl std::fmt::Write for FmtSize {
 }
 
 trait TensorFormatter {
-    type Elem: WithDType;
+    type Elem: ElemType;
 
     fn fmt<T: std::fmt::Write>(&self, v: Self::Elem, max_w: usize, f: &mut T) -> std::fmt::Result;
 
@@ -358,11 +362,11 @@ where
     }
 }
 
-struct IntFormatter<S: WithDType> {
+struct IntFormatter<S: ElemType> {
     _phantom: std::marker::PhantomData<S>,
 }
 
-impl<S: WithDType> IntFormatter<S> {
+impl<S: ElemType> IntFormatter<S> {
     fn new() -> Self {
         Self {
             _phantom: std::marker::PhantomData,
@@ -372,7 +376,7 @@ impl<S: WithDType> IntFormatter<S> {
 
 impl<S> TensorFormatter for IntFormatter<S>
 where
-    S: WithDType + std::fmt::Display,
+    S: ElemType,
 {
     type Elem = S;
 
@@ -426,6 +430,12 @@ impl std::fmt::Display for Tensor {
             self.clone()
         };
         match self.dtype() {
+            DType::Bool => {
+                let tf: IntFormatter<bool> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
             DType::U8 => {
                 let tf: IntFormatter<u8> = IntFormatter::new();
                 let max_w = tf.max_width(&to_display);
@@ -438,6 +448,24 @@ impl std::fmt::Display for Tensor {
                 tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
                 writeln!(f)?;
             }
+            DType::I8 => {
+                let tf: IntFormatter<i8> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
+            DType::I16 => {
+                let tf: IntFormatter<i16> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
+            DType::I32 => {
+                let tf: IntFormatter<i32> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
             DType::I64 => {
                 let tf: IntFormatter<i64> = IntFormatter::new();
                 let max_w = tf.max_width(&to_display);
@@ -472,6 +500,18 @@ impl std::fmt::Display for Tensor {
                     writeln!(f)?;
                 }
             }
+            DType::C64 => {
+                let tf: IntFormatter<Complex<f32>> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
+            DType::C128 => {
+                let tf: IntFormatter<Complex<f64>> = IntFormatter::new();
+                let max_w = tf.max_width(&to_display);
+                tf.fmt_tensor(self, 1, max_w, summarize, &po, f)?;
+                writeln!(f)?;
+            }
         };
 
         let device_str = match self.device().location() {
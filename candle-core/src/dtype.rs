@@ -6,10 +6,18 @@ use crate::{CpuStorage, Error, Result};
 /// The different types of elements allowed in tensors.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum DType {
+    // Boolean, stored using one byte per element.
+    Bool,
     // Unsigned 8 bits integer.
     U8,
     // Unsigned 32 bits integer.
     U32,
+    // Signed 8 bits integer.
+    I8,
+    // Signed 16 bits integer.
+    I16,
+    // Signed 32 bits integer.
+    I32,
     // Signed 64 bits integer.
     I64,
     // Brain floating-point using half precision (16 bits).
@@ -20,6 +28,10 @@ pub enum DType {
     F32,
     // Floating-point using double precision (64 bits).
     F64,
+    // Complex number using single precision for the real and imaginary parts (64 bits).
+    C64,
+    // Complex number using double precision for the real and imaginary parts (128 bits).
+    C128,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -29,13 +41,19 @@ impl std::str::FromStr for DType {
     type Err = DTypeParseError;
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s {
+            "bool" => Ok(Self::Bool),
             "u8" => Ok(Self::U8),
             "u32" => Ok(Self::U32),
+            "i8" => Ok(Self::I8),
+            "i16" => Ok(Self::I16),
+            "i32" => Ok(Self::I32),
             "i64" => Ok(Self::I64),
             "bf16" => Ok(Self::BF16),
             "f16" => Ok(Self::F16),
             "f32" => Ok(Self::F32),
             "f64" => Ok(Self::F64),
+            "c64" => Ok(Self::C64),
+            "c128" => Ok(Self::C128),
             _ => Err(DTypeParseError),
         }
     }
@@ -45,59 +63,105 @@ impl DType {
     /// String representation for dtypes.
     pub fn as_str(&self) -> &'static str {
         match self {
+            Self::Bool => "bool",
             Self::U8 => "u8",
             Self::U32 => "u32",
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
             Self::I64 => "i64",
             Self::BF16 => "bf16",
             Self::F16 => "f16",
             Self::F32 => "f32",
             Self::F64 => "f64",
+            Self::C64 => "c64",
+            Self::C128 => "c128",
         }
     }
 
     /// The size used by each element in bytes, i.e. 1 for `U8`, 4 for `F32`.
     pub fn size_in_bytes(&self) -> usize {
         match self {
+            Self::Bool => 1,
             Self::U8 => 1,
             Self::U32 => 4,
+            Self::I8 => 1,
+            Self::I16 => 2,
+            Self::I32 => 4,
             Self::I64 => 8,
             Self::BF16 => 2,
             Self::F16 => 2,
             Self::F32 => 4,
             Self::F64 => 8,
+            Self::C64 => 8,
+            Self::C128 => 16,
         }
     }
 
     pub fn is_int(&self) -> bool {
         match self {
-            Self::U8 | Self::U32 | Self::I64 => true,
-            Self::BF16 | Self::F16 | Self::F32 | Self::F64 => false,
+            Self::U8 | Self::U32 | Self::I8 | Self::I16 | Self::I32 | Self::I64 => true,
+            Self::Bool
+            | Self::BF16
+            | Self::F16
+            | Self::F32
+            | Self::F64
+            | Self::C64
+            | Self::C128 => false,
         }
     }
 
     pub fn is_float(&self) -> bool {
         match self {
-            Self::U8 | Self::U32 | Self::I64 => false,
+            Self::Bool
+            | Self::U8
+            | Self::U32
+            | Self::I8
+            | Self::I16
+            | Self::I32
+            | Self::I64
+            | Self::C64
+            | Self::C128 => false,
             Self::BF16 | Self::F16 | Self::F32 | Self::F64 => true,
         }
     }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Self::Bool)
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Self::C64 | Self::C128)
+    }
+
+    /// The complex dtype with the same precision as this floating point dtype, i.e. `C64` for
+    /// `F32` and `C128` for `F64`. Complex dtypes map to themselves.
+    pub fn to_complex(&self) -> Result<Self> {
+        match self {
+            Self::F32 | Self::C64 => Ok(Self::C64),
+            Self::F64 | Self::C128 => Ok(Self::C128),
+            _ => Err(Error::UnsupportedDTypeForOp(*self, "to_complex").bt()),
+        }
+    }
+
+    /// The floating point dtype used for the real and imaginary parts of a complex dtype, i.e.
+    /// `F32` for `C64` and `F64` for `C128`. Floating point dtypes map to themselves.
+    pub fn to_real(&self) -> Result<Self> {
+        match self {
+            Self::F32 | Self::C64 => Ok(Self::F32),
+            Self::F64 | Self::C128 => Ok(Self::F64),
+            Self::BF16 | Self::F16 => Ok(*self),
+            _ => Err(Error::UnsupportedDTypeForOp(*self, "to_real").bt()),
+        }
+    }
 }
 
-pub trait WithDType:
-    Sized
-    + Copy
-    + num_traits::NumAssign
-    + std::cmp::PartialOrd
-    + std::fmt::Display
-    + 'static
-    + Send
-    + Sync
-    + crate::cpu::kernels::VecOps
-{
+/// Element types that can be stored in a tensor and copied back from it. This includes `bool`
+/// and the complex types which, unlike [`WithDType`] types, only support a subset of the tensor
+/// operations.
+pub trait ElemType: Sized + Copy + std::fmt::Display + 'static + Send + Sync {
     const DTYPE: DType;
 
-    fn from_f64(v: f64) -> Self;
-    fn to_f64(self) -> f64;
     fn to_cpu_storage_owned(data: Vec<Self>) -> CpuStorage;
 
     fn to_cpu_storage(data: &[Self]) -> CpuStorage {
@@ -108,29 +172,29 @@ pub trait WithDType:
     fn cpu_storage_data(s: CpuStorage) -> Result<Vec<Self>>;
 }
 
-macro_rules! with_dtype {
-    ($ty:ty, $dtype:ident, $from_f64:expr, $to_f64:expr) => {
-        impl WithDType for $ty {
-            const DTYPE: DType = DType::$dtype;
-
-            fn from_f64(v: f64) -> Self {
-                $from_f64(v)
-            }
+pub trait WithDType:
+    ElemType + std::cmp::PartialOrd + num_traits::NumAssign + crate::cpu::kernels::VecOps
+{
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
 
-            fn to_f64(self) -> f64 {
-                $to_f64(self)
-            }
+macro_rules! elem_type {
+    ($ty:ty, $dtype:ident) => {
+        impl ElemType for $ty {
+            const DTYPE: DType = DType::$dtype;
 
             fn to_cpu_storage_owned(data: Vec<Self>) -> CpuStorage {
                 CpuStorage::$dtype(data)
             }
 
-            fn cpu_storage_data(s: CpuStorage) -> Result<Vec<Self>> {
-                match s {
-                    CpuStorage::$dtype(data) => Ok(data),
+            fn cpu_storage_data(mut s: CpuStorage) -> Result<Vec<Self>> {
+                let got = s.dtype();
+                match &mut s {
+                    CpuStorage::$dtype(data) => Ok(crate::memory::take_vec(data)),
                     _ => Err(Error::UnexpectedDType {
                         expected: DType::$dtype,
-                        got: s.dtype(),
+                        got,
                         msg: "unexpected dtype",
                     }
                     .bt()),
@@ -151,10 +215,32 @@ macro_rules! with_dtype {
         }
     };
 }
+
+macro_rules! with_dtype {
+    ($ty:ty, $dtype:ident, $from_f64:expr, $to_f64:expr) => {
+        elem_type!($ty, $dtype);
+
+        impl WithDType for $ty {
+            fn from_f64(v: f64) -> Self {
+                $from_f64(v)
+            }
+
+            fn to_f64(self) -> f64 {
+                $to_f64(self)
+            }
+        }
+    };
+}
 use half::{bf16, f16};
 
+elem_type!(bool, Bool);
+elem_type!(num_complex::Complex<f32>, C64);
+elem_type!(num_complex::Complex<f64>, C128);
 with_dtype!(u8, U8, |v: f64| v as u8, |v: u8| v as f64);
 with_dtype!(u32, U32, |v: f64| v as u32, |v: u32| v as f64);
+with_dtype!(i8, I8, |v: f64| v as i8, |v: i8| v as f64);
+with_dtype!(i16, I16, |v: f64| v as i16, |v: i16| v as f64);
+with_dtype!(i32, I32, |v: f64| v as i32, |v: i32| v as f64);
 with_dtype!(i64, I64, |v: f64| v as i64, |v: i64| v as f64);
 with_dtype!(f16, F16, f16::from_f64, f16::to_f64);
 with_dtype!(bf16, BF16, bf16::from_f64, bf16::to_f64);
@@ -175,6 +261,33 @@ impl IntDType for i64 {
     }
 }
 
+impl IntDType for i32 {
+    fn is_true(&self) -> bool {
+        *self != 0
+    }
+    fn as_usize(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl IntDType for i16 {
+    fn is_true(&self) -> bool {
+        *self != 0
+    }
+    fn as_usize(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl IntDType for i8 {
+    fn is_true(&self) -> bool {
+        *self != 0
+    }
+    fn as_usize(&self) -> usize {
+        *self as usize
+    }
+}
+
 impl IntDType for u32 {
     fn is_true(&self) -> bool {
         *self != 0
@@ -0,0 +1,346 @@
+//! Einstein summation over tensors.
+//!
+//! The equation is parsed into one label per dimension, the operands are then contracted two at
+//! a time using permutations, reshapes and batched matrix multiplications so that gradients flow
+//! through the existing ops.
+use crate::{bail, Result, Tensor, D};
+use std::collections::{HashMap, HashSet};
+
+// The dimensions covered by an ellipsis get their own labels, aligned on the right so that they
+// broadcast in the same way as the other binary ops. These come first in the implicit output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Label {
+    Ellipsis(usize),
+    Char(char),
+}
+
+// The labels of a term before expanding the ellipsis, if any.
+struct Term {
+    chars: Vec<char>,
+    ellipsis_pos: Option<usize>,
+}
+
+impl Term {
+    fn parse(term: &str) -> Result<Self> {
+        let mut chars = vec![];
+        let mut ellipsis_pos = None;
+        let mut rest = term.trim();
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix("...") {
+                if ellipsis_pos.is_some() {
+                    bail!("einsum: more than one ellipsis in term '{term}'")
+                }
+                ellipsis_pos = Some(chars.len());
+                rest = r;
+            } else {
+                let c = rest.chars().next().unwrap_or_default();
+                if !c.is_ascii_alphabetic() {
+                    bail!("einsum: invalid character '{c}' in term '{term}'")
+                }
+                chars.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        Ok(Self {
+            chars,
+            ellipsis_pos,
+        })
+    }
+
+    fn labels(&self, ellipsis_rank: usize, max_ellipsis_rank: usize) -> Vec<Label> {
+        let mut labels: Vec<Label> = self.chars.iter().map(|&c| Label::Char(c)).collect();
+        if let Some(pos) = self.ellipsis_pos {
+            let ellipsis =
+                (max_ellipsis_rank - ellipsis_rank..max_ellipsis_rank).map(Label::Ellipsis);
+            labels.splice(pos..pos, ellipsis);
+        }
+        labels
+    }
+}
+
+struct Operand {
+    tensor: Tensor,
+    labels: Vec<Label>,
+}
+
+impl Operand {
+    // Takes the diagonal for labels that appear multiple times, e.g. `ii->i`.
+    fn diagonals(mut self) -> Result<Self> {
+        loop {
+            let pair = (0..self.labels.len()).find_map(|p| {
+                (p + 1..self.labels.len())
+                    .find(|&q| self.labels[p] == self.labels[q])
+                    .map(|q| (p, q))
+            });
+            let (p, q) = match pair {
+                None => return Ok(self),
+                Some(pair) => pair,
+            };
+            let n = self.tensor.dim(p)?;
+            if self.tensor.dim(q)? != n {
+                bail!(
+                    "einsum: repeated label {:?} with different sizes {n} and {}",
+                    self.labels[p],
+                    self.tensor.dim(q)?
+                )
+            }
+            let others: Vec<usize> = (0..self.labels.len())
+                .filter(|&i| i != p && i != q)
+                .collect();
+            let mut perm = others.clone();
+            perm.push(p);
+            perm.push(q);
+            let t = self.tensor.permute(perm)?;
+            let mut dims = t.dims()[..others.len()].to_vec();
+            dims.push(n * n);
+            let t = t.reshape(dims)?;
+            let ids: Vec<u32> = (0..n).map(|i| (i * n + i) as u32).collect();
+            let ids = Tensor::from_vec(ids, n, t.device())?;
+            let label = self.labels[p];
+            self.labels = others.iter().map(|&i| self.labels[i]).collect();
+            self.labels.push(label);
+            self.tensor = t.index_select(&ids, D::Minus1)?;
+        }
+    }
+
+    // Sums over the dimensions whose label is not part of `keep`.
+    fn sum_except(self, keep: &HashSet<Label>) -> Result<Self> {
+        let dims: Vec<usize> = (0..self.labels.len())
+            .filter(|&i| !keep.contains(&self.labels[i]))
+            .collect();
+        if dims.is_empty() {
+            return Ok(self);
+        }
+        let tensor = self.tensor.sum(dims)?;
+        let labels = self
+            .labels
+            .into_iter()
+            .filter(|l| keep.contains(l))
+            .collect();
+        Ok(Self { tensor, labels })
+    }
+
+    // Permutes the dimensions to follow the order of `labels` and broadcasts them to `sizes`.
+    fn arrange(&self, labels: &[Label], sizes: &HashMap<Label, usize>) -> Result<Tensor> {
+        let perm: Vec<usize> = labels
+            .iter()
+            .filter_map(|l| self.labels.iter().position(|s| s == l))
+            .collect();
+        let t = self.tensor.permute(perm)?;
+        let dims: Vec<usize> = labels.iter().map(|l| sizes[l]).collect();
+        if t.dims() == dims.as_slice() {
+            Ok(t)
+        } else {
+            t.broadcast_as(dims)
+        }
+    }
+}
+
+// Contracts two operands, the labels listed in `keep` are preserved in the result.
+fn contract(
+    lhs: Operand,
+    rhs: Operand,
+    keep: &HashSet<Label>,
+    sizes: &HashMap<Label, usize>,
+) -> Result<Operand> {
+    let mut batch = vec![];
+    let mut lhs_free = vec![];
+    let mut contracted = vec![];
+    for &l in lhs.labels.iter() {
+        if rhs.labels.contains(&l) {
+            if keep.contains(&l) {
+                batch.push(l)
+            } else {
+                contracted.push(l)
+            }
+        } else {
+            lhs_free.push(l)
+        }
+    }
+    let rhs_free: Vec<Label> = rhs
+        .labels
+        .iter()
+        .filter(|l| !lhs.labels.contains(l))
+        .copied()
+        .collect();
+    let size = |ls: &[Label]| ls.iter().map(|l| sizes[l]).product::<usize>();
+    let (b, m, k, n) = (
+        size(&batch),
+        size(&lhs_free),
+        size(&contracted),
+        size(&rhs_free),
+    );
+    let lhs_labels = [batch.as_slice(), &lhs_free, &contracted].concat();
+    let rhs_labels = [batch.as_slice(), &contracted, &rhs_free].concat();
+    let lhs_t = lhs.arrange(&lhs_labels, sizes)?.reshape((b, m, k))?;
+    let rhs_t = rhs.arrange(&rhs_labels, sizes)?.reshape((b, k, n))?;
+    let labels = [batch, lhs_free, rhs_free].concat();
+    let dims: Vec<usize> = labels.iter().map(|l| sizes[l]).collect();
+    let tensor = lhs_t.matmul(&rhs_t)?.reshape(dims)?;
+    Ok(Operand { tensor, labels })
+}
+
+impl Tensor {
+    /// Einstein summation, evaluates the tensor expression described by `equation` on the
+    /// given operands.
+    ///
+    /// The equation lists the labels for the dimensions of each operand separated by commas,
+    /// optionally followed by `->` and the labels of the output. Labels that do not appear in
+    /// the output are summed over. When the output is omitted, it is made of the labels that
+    /// appear only once, in alphabetical order. An ellipsis `...` stands for the dimensions
+    /// that are not explicitly labeled, these are broadcasted between operands.
+    ///
+    /// The operands are contracted two at a time, the pair leading to the smallest intermediate
+    /// result is picked first.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu)?;
+    /// let b = Tensor::new(&[[5f32, 6.], [7., 8.]], &Device::Cpu)?;
+    /// let c = Tensor::einsum("ij,jk->ik", &[&a, &b])?;
+    /// assert_eq!(c.to_vec2::<f32>()?, &[[19., 22.], [43., 50.]]);
+    /// let trace = Tensor::einsum("ii", &[&a])?;
+    /// assert_eq!(trace.to_scalar::<f32>()?, 5.);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn einsum(equation: &str, operands: &[&Tensor]) -> Result<Tensor> {
+        let (inputs, output) = match equation.split_once("->") {
+            Some((inputs, output)) => (inputs, Some(output)),
+            None => (equation, None),
+        };
+        let terms = inputs
+            .split(',')
+            .map(Term::parse)
+            .collect::<Result<Vec<_>>>()?;
+        if terms.len() != operands.len() {
+            bail!(
+                "einsum: the equation '{equation}' has {} operands but {} tensors were provided",
+                terms.len(),
+                operands.len()
+            )
+        }
+
+        let mut ellipsis_ranks = Vec::with_capacity(terms.len());
+        for (term, t) in terms.iter().zip(operands.iter()) {
+            let ellipsis_rank = match term.ellipsis_pos {
+                None if term.chars.len() == t.rank() => 0,
+                Some(_) if term.chars.len() <= t.rank() => t.rank() - term.chars.len(),
+                _ => bail!(
+                    "einsum: term '{}' does not match the operand shape {:?}",
+                    term.chars.iter().collect::<String>(),
+                    t.shape()
+                ),
+            };
+            ellipsis_ranks.push(ellipsis_rank)
+        }
+        let max_ellipsis_rank = ellipsis_ranks.iter().copied().max().unwrap_or(0);
+
+        let mut sizes: HashMap<Label, usize> = HashMap::new();
+        let mut counts: HashMap<Label, usize> = HashMap::new();
+        let mut ops = Vec::with_capacity(operands.len());
+        for ((term, t), ellipsis_rank) in terms.iter().zip(operands.iter()).zip(ellipsis_ranks) {
+            let labels = term.labels(ellipsis_rank, max_ellipsis_rank);
+            for (&label, &dim) in labels.iter().zip(t.dims().iter()) {
+                *counts.entry(label).or_default() += 1;
+                let size = sizes.entry(label).or_insert(dim);
+                if *size == 1 {
+                    *size = dim
+                } else if dim != 1 && dim != *size {
+                    bail!("einsum: incompatible sizes {dim} and {size} for label {label:?}")
+                }
+            }
+            ops.push(Operand {
+                tensor: (*t).clone(),
+                labels,
+            })
+        }
+
+        let output: Vec<Label> = match output {
+            Some(output) => {
+                let term = Term::parse(output)?;
+                if term.ellipsis_pos.is_none() && max_ellipsis_rank > 0 {
+                    bail!("einsum: the output of '{equation}' has to include the ellipsis")
+                }
+                let labels = term.labels(max_ellipsis_rank, max_ellipsis_rank);
+                for (i, label) in labels.iter().enumerate() {
+                    if !sizes.contains_key(label) {
+                        bail!("einsum: output label {label:?} does not appear in the inputs")
+                    }
+                    if labels[..i].contains(label) {
+                        bail!("einsum: output label {label:?} appears more than once")
+                    }
+                }
+                labels
+            }
+            None => {
+                let mut labels: Vec<Label> = counts
+                    .iter()
+                    .filter(|(l, &c)| c == 1 || matches!(l, Label::Ellipsis(_)))
+                    .map(|(&l, _)| l)
+                    .collect();
+                labels.sort();
+                labels
+            }
+        };
+
+        // Labels that are still needed, either by the output or by other operands.
+        let keep_for = |ops: &[Operand], skip: &[usize]| {
+            let mut keep: HashSet<Label> = output.iter().copied().collect();
+            for (i, op) in ops.iter().enumerate() {
+                if !skip.contains(&i) {
+                    keep.extend(op.labels.iter().copied())
+                }
+            }
+            keep
+        };
+
+        // Take the diagonals and sum over the labels that only appear in a single operand first.
+        let mut ops = ops
+            .into_iter()
+            .map(|op| op.diagonals())
+            .collect::<Result<Vec<_>>>()?;
+        for i in 0..ops.len() {
+            let keep = keep_for(&ops, &[i]);
+            let op = ops.remove(i).sum_except(&keep)?;
+            ops.insert(i, op)
+        }
+
+        while ops.len() > 1 {
+            // Greedily contract the pair of operands with the smallest result.
+            let mut best: Option<(usize, usize, usize)> = None;
+            for i in 0..ops.len() {
+                for j in i + 1..ops.len() {
+                    let keep = keep_for(&ops, &[i, j]);
+                    let result_size: usize = ops[i]
+                        .labels
+                        .iter()
+                        .chain(ops[j].labels.iter())
+                        .filter(|l| keep.contains(l))
+                        .collect::<HashSet<_>>()
+                        .into_iter()
+                        .map(|l| sizes[l])
+                        .product();
+                    match best {
+                        Some((_, _, s)) if s <= result_size => {}
+                        _ => best = Some((i, j, result_size)),
+                    }
+                }
+            }
+            let (i, j) = match best {
+                Some((i, j, _)) => (i, j),
+                None => bail!("einsum: no operands to contract"),
+            };
+            let keep = keep_for(&ops, &[i, j]);
+            let rhs = ops.remove(j);
+            let lhs = ops.remove(i);
+            ops.push(contract(lhs, rhs, &keep, &sizes)?);
+        }
+
+        let op = match ops.pop() {
+            Some(op) => op,
+            None => bail!("einsum: no operands provided"),
+        };
+        let op = op.sum_except(&output.iter().copied().collect())?;
+        op.arrange(&output, &sizes)
+    }
+}
@@ -37,6 +37,13 @@ pub enum Error {
         op: &'static str,
     },
 
+    #[error("{op}: no dimension named {name} in shape {shape:?}")]
+    UnknownDimName {
+        shape: Shape,
+        name: String,
+        op: &'static str,
+    },
+
     #[error("{op}: duplicate dim index {dims:?} for shape {shape:?}")]
     DuplicateDimIndex {
         shape: Shape,
@@ -64,7 +71,10 @@ pub enum Error {
     )]
     ShapeMismatch { buffer_size: usize, shape: Shape },
 
-    #[error("shape mismatch in {op}, lhs: {lhs:?}, rhs: {rhs:?}")]
+    #[error(
+        "shape mismatch in {op}, lhs: {lhs:?}, rhs: {rhs:?}{}",
+        crate::shape::mismatch_details(lhs, rhs)
+    )]
     ShapeMismatchBinaryOp {
         lhs: Shape,
         rhs: Shape,
@@ -100,6 +110,9 @@ pub enum Error {
         op: &'static str,
     },
 
+    #[error("cpu thread pool mismatch in {op}, the tensors are bound to different pools")]
+    CpuPoolMismatchBinaryOp { op: &'static str },
+
     // === Op Specific Errors ===
     #[error("narrow invalid args {msg}: {shape:?}, dim: {dim}, start: {start}, len:{len}")]
     NarrowInvalidArgs {
@@ -132,6 +145,13 @@ pub enum Error {
     #[error("cannot set variable {msg}")]
     CannotSetVar { msg: &'static str },
 
+    #[error("{op}: a tensor needed for the gradient computation has been modified by an in-place operation, expected version {expected} but got {version}")]
+    ModifiedInPlace {
+        op: &'static str,
+        expected: usize,
+        version: usize,
+    },
+
     // Box indirection to avoid large variant.
     #[error("{0:?}")]
     MatMulUnexpectedStriding(Box<MatMulUnexpectedStriding>),
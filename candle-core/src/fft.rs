@@ -0,0 +1,286 @@
+//! Discrete Fourier transforms and complex number helpers.
+//!
+//! The transforms follow the PyTorch conventions: the forward transforms are unnormalized and the
+//! inverse transforms are scaled by `1/n`.
+use crate::op::{BackpropOp, Op};
+use crate::shape::Dim;
+use crate::{bail, DType, Device, Result, Tensor, D};
+use num_complex::Complex;
+
+fn complex_scalar(re: f64, im: f64, dtype: DType, device: &Device) -> Result<Tensor> {
+    match dtype {
+        DType::C64 => Tensor::new(Complex::new(re as f32, im as f32), device),
+        DType::C128 => Tensor::new(Complex::new(re, im), device),
+        dtype => Err(crate::Error::UnsupportedDTypeForOp(dtype, "complex").bt()),
+    }
+}
+
+// Indexes of the frames used by stft/istft, the frames are laid out one after the other.
+fn frame_indexes(
+    n_frames: usize,
+    n_fft: usize,
+    hop_length: usize,
+    device: &Device,
+) -> Result<Tensor> {
+    let ids = (0..n_frames)
+        .flat_map(|f| (0..n_fft).map(move |j| (f * hop_length + j) as u32))
+        .collect::<Vec<_>>();
+    Tensor::from_vec(ids, n_frames * n_fft, device)
+}
+
+impl Tensor {
+    /// Creates a complex tensor from its real and imaginary parts. Both parts must have the same
+    /// shape and use either `f32` or `f64` elements.
+    pub fn complex(re: &Self, im: &Self) -> Result<Self> {
+        if re.dtype() != im.dtype() {
+            Err(crate::Error::DTypeMismatchBinaryOp {
+                lhs: re.dtype(),
+                rhs: im.dtype(),
+                op: "complex",
+            }
+            .bt())?
+        }
+        let dtype = re.dtype().to_complex()?;
+        let i = complex_scalar(0., 1., dtype, re.device())?;
+        re.to_dtype(dtype)?
+            .add(&im.to_dtype(dtype)?.broadcast_mul(&i)?)
+    }
+
+    /// The real part of a complex tensor, real tensors are returned unchanged.
+    pub fn real(&self) -> Result<Self> {
+        if self.dtype().is_complex() {
+            self.to_dtype(self.dtype().to_real()?)
+        } else {
+            Ok(self.clone())
+        }
+    }
+
+    /// The imaginary part of a complex tensor, this is zero for real tensors.
+    pub fn imag(&self) -> Result<Self> {
+        if self.dtype().is_complex() {
+            let minus_i = complex_scalar(0., -1., self.dtype(), self.device())?;
+            self.broadcast_mul(&minus_i)?.real()
+        } else {
+            self.zeros_like()
+        }
+    }
+
+    /// The complex conjugate of a tensor, real tensors are returned unchanged.
+    pub fn conj(&self) -> Result<Self> {
+        if self.dtype().is_complex() {
+            Self::complex(&self.real()?, &self.imag()?.neg()?)
+        } else {
+            Ok(self.clone())
+        }
+    }
+
+    fn fft_impl<D: Dim>(&self, dim: D, inverse: bool, op: &'static str) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), op)?;
+        let xs = self.to_dtype(self.dtype().to_complex()?)?;
+        let last_dim = xs.rank() - 1;
+        let xs = if dim == last_dim {
+            xs.contiguous()?
+        } else {
+            xs.transpose(dim, last_dim)?.contiguous()?
+        };
+        let storage = xs.storage().fft_last_dim(xs.layout(), inverse)?;
+        let bop = BackpropOp::new1(&xs, |arg| Op::Fft(arg, inverse));
+        let ys = crate::tensor::from_storage(storage, xs.shape(), bop, false);
+        if dim == last_dim {
+            Ok(ys)
+        } else {
+            ys.transpose(dim, last_dim)
+        }
+    }
+
+    /// The one dimensional discrete Fourier transform along the given dimension. Real tensors are
+    /// converted to complex tensors first.
+    ///
+    /// ```rust
+    /// use candle_core::{Complex, Tensor, Device};
+    /// let t = Tensor::new(&[1f32, 2., 3., 4.], &Device::Cpu)?;
+    /// let t = t.fft(0)?;
+    /// assert_eq!(t.dims(), &[4]);
+    /// assert_eq!(t.get(0)?.to_scalar::<Complex<f32>>()?, Complex::new(10., 0.));
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn fft<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.fft_impl(dim, false, "fft")
+    }
+
+    /// The inverse of [`Tensor::fft`], the result is scaled by `1/n` where `n` is the size of the
+    /// transformed dimension.
+    pub fn ifft<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.fft_impl(dim, true, "ifft")
+    }
+
+    /// The Fourier transform of a real tensor along the given dimension. Only the non-negative
+    /// frequencies are returned so the output has `n / 2 + 1` elements along this dimension.
+    pub fn rfft<D: Dim>(&self, dim: D) -> Result<Self> {
+        if self.dtype().is_complex() {
+            bail!("rfft expects a real tensor, got {:?}", self.dtype())
+        }
+        let dim = dim.to_index(self.shape(), "rfft")?;
+        let n = self.dim(dim)?;
+        self.fft(dim)?.narrow(dim, 0, n / 2 + 1)
+    }
+
+    /// The inverse of [`Tensor::rfft`], returns a real tensor with `n` elements along the given
+    /// dimension. The input is interpreted as the non-negative frequencies of a Hermitian
+    /// signal, it is truncated or zero-padded to `n / 2 + 1` elements.
+    pub fn irfft<D: Dim>(&self, n: usize, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "irfft")?;
+        if n == 0 {
+            bail!("irfft requires a positive output size")
+        }
+        let xs = self.to_dtype(self.dtype().to_complex()?)?;
+        let n_bins = n / 2 + 1;
+        let m = xs.dim(dim)?;
+        let xs = if m >= n_bins {
+            xs.narrow(dim, 0, n_bins)?
+        } else {
+            let mut pad_dims = xs.dims().to_vec();
+            pad_dims[dim] = n_bins - m;
+            let pad = Tensor::zeros(pad_dims, xs.dtype(), xs.device())?;
+            Tensor::cat(&[&xs, &pad], dim)?
+        };
+        // The negative frequencies are the conjugates of the positive ones in reverse order.
+        let n_neg = n - n_bins;
+        let xs = if n_neg == 0 {
+            xs
+        } else {
+            let ids = (1..=n_neg).rev().map(|i| i as u32).collect::<Vec<_>>();
+            let ids = Tensor::from_vec(ids, n_neg, xs.device())?;
+            let neg = xs.contiguous()?.index_select(&ids, dim)?.conj()?;
+            Tensor::cat(&[&xs, &neg], dim)?
+        };
+        xs.ifft(dim)?.real()
+    }
+
+    /// The short-time Fourier transform of a real signal over its last dimension.
+    ///
+    /// The signal is split in frames of `n_fft` samples every `hop_length` samples, each frame is
+    /// multiplied by `window` (a rectangular window is used when `None`) and the Fourier
+    /// transform of the result is computed. When `center` is true, the signal is reflect-padded
+    /// by `n_fft / 2` on both sides so that frame `t` is centered on sample `t * hop_length`.
+    ///
+    /// For an input of shape `(..., time)` the result is a complex tensor of shape
+    /// `(..., n_fft / 2 + 1, n_frames)`.
+    pub fn stft(
+        &self,
+        n_fft: usize,
+        hop_length: usize,
+        window: Option<&Tensor>,
+        center: bool,
+    ) -> Result<Self> {
+        if n_fft == 0 || hop_length == 0 {
+            bail!("stft requires positive n_fft and hop_length, got {n_fft} {hop_length}")
+        }
+        let xs = if center {
+            let len = self.dim(D::Minus1)?;
+            let pad = n_fft / 2;
+            if len <= pad {
+                bail!("stft with center requires more than {pad} samples, got {len}")
+            }
+            let ids = (0..len + 2 * pad)
+                .map(|i| {
+                    let i = i as i64 - pad as i64;
+                    let i = if i < 0 { -i } else { i };
+                    let last = len as i64 - 1;
+                    let i = if i > last { 2 * last - i } else { i };
+                    i as u32
+                })
+                .collect::<Vec<_>>();
+            let ids = Tensor::from_vec(ids, len + 2 * pad, self.device())?;
+            self.index_select(&ids, D::Minus1)?
+        } else {
+            self.clone()
+        };
+        let len = xs.dim(D::Minus1)?;
+        if len < n_fft {
+            bail!("stft requires at least {n_fft} samples, got {len}")
+        }
+        let n_frames = 1 + (len - n_fft) / hop_length;
+        let ids = frame_indexes(n_frames, n_fft, hop_length, xs.device())?;
+        let mut frames_dims = xs.dims().to_vec();
+        frames_dims.pop();
+        frames_dims.push(n_frames);
+        frames_dims.push(n_fft);
+        let frames = xs.index_select(&ids, D::Minus1)?.reshape(frames_dims)?;
+        let frames = match window {
+            None => frames,
+            Some(window) => frames.broadcast_mul(window)?,
+        };
+        frames.rfft(D::Minus1)?.transpose(D::Minus1, D::Minus2)
+    }
+
+    /// The inverse of [`Tensor::stft`], the frames are recovered with an inverse Fourier
+    /// transform and combined using overlap-add with the squared window normalization.
+    ///
+    /// The input is expected to have a shape `(..., n_fft / 2 + 1, n_frames)`, the result has a
+    /// shape `(..., length)`. When `length` is `None`, all the available samples are returned.
+    pub fn istft(
+        &self,
+        n_fft: usize,
+        hop_length: usize,
+        window: Option<&Tensor>,
+        center: bool,
+        length: Option<usize>,
+    ) -> Result<Self> {
+        if n_fft == 0 || hop_length == 0 {
+            bail!("istft requires positive n_fft and hop_length, got {n_fft} {hop_length}")
+        }
+        let n_frames = self.dim(D::Minus1)?;
+        if n_frames == 0 {
+            bail!("istft requires at least one frame")
+        }
+        let frames = self
+            .transpose(D::Minus1, D::Minus2)?
+            .irfft(n_fft, D::Minus1)?;
+        let dtype = frames.dtype();
+        let device = frames.device();
+        let window = match window {
+            None => Tensor::ones(n_fft, dtype, device)?,
+            Some(window) => window.clone(),
+        };
+        let frames = frames.broadcast_mul(&window)?;
+        let full_len = n_fft + hop_length * (n_frames - 1);
+        let ids = frame_indexes(n_frames, n_fft, hop_length, device)?;
+        let mut frames_dims = frames.dims().to_vec();
+        frames_dims.pop();
+        frames_dims.pop();
+        frames_dims.push(n_frames * n_fft);
+        let frames = frames.reshape(frames_dims.as_slice())?;
+        let mut out_dims = frames_dims;
+        out_dims.pop();
+        out_dims.push(full_len);
+        let ys = Tensor::zeros(out_dims, dtype, device)?.index_add(&ids, &frames, D::Minus1)?;
+
+        // Normalize by the overlapping squared windows, the samples not covered by the window
+        // are set to zero.
+        let window_sq = window.sqr()?.repeat(n_frames)?;
+        let envelope = Tensor::zeros(full_len, dtype, device)?.index_add(&ids, &window_sq, 0)?;
+        let covered = envelope.gt(1e-11)?;
+        let envelope = covered.where_cond(&envelope, &envelope.ones_like()?)?;
+        let norm = covered.to_dtype(dtype)?.div(&envelope)?;
+        let ys = ys.broadcast_mul(&norm)?;
+
+        let start = if center { n_fft / 2 } else { 0 };
+        let available = if center {
+            full_len - 2 * (n_fft / 2)
+        } else {
+            full_len
+        };
+        let length = length.unwrap_or(available);
+        let ys = ys.narrow(D::Minus1, start, usize::min(length, full_len - start))?;
+        let len = ys.dim(D::Minus1)?;
+        if len < length {
+            let mut pad_dims = ys.dims().to_vec();
+            *pad_dims.last_mut().unwrap() = length - len;
+            let pad = Tensor::zeros(pad_dims, dtype, device)?;
+            Tensor::cat(&[&ys, &pad], D::Minus1)
+        } else {
+            Ok(ys)
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! Sampling of tensors at fractional coordinates, following PyTorch's `F.grid_sample` and
+//! `F.affine_grid`.
+use crate::{DType, Result, Shape, Tensor};
+
+/// The interpolation mode used by [`Tensor::grid_sample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridSampleMode {
+    /// Bilinear interpolation, or trilinear interpolation for volumetric inputs.
+    Bilinear,
+    /// Take the value of the nearest element, halfway values are rounded to the nearest even
+    /// index. The gradient with respect to the grid is zero.
+    Nearest,
+    /// Cubic convolution with `a = -0.75`, only supported for 2D inputs.
+    Bicubic,
+}
+
+/// How [`Tensor::grid_sample`] handles the coordinates that fall outside of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridPaddingMode {
+    /// Out of bounds values are zeros.
+    Zeros,
+    /// Out of bounds coordinates are clamped to the border.
+    Border,
+    /// Out of bounds coordinates are reflected by the border.
+    Reflection,
+}
+
+// The sampling taps along a single axis, `indexes` and `masks` have one entry per tap, each of
+// these containing one element per sampled point.
+struct AxisTaps {
+    indexes: Vec<Vec<usize>>,
+    masks: Vec<Vec<f64>>,
+    weights: Vec<Tensor>,
+}
+
+// https://github.com/pytorch/pytorch/blob/main/aten/src/ATen/native/GridSampler.h
+fn unnormalize(size: usize, align_corners: bool) -> (f64, f64) {
+    // Returns (mul, add) such that the coordinate in pixels is `mul * c + add`.
+    if align_corners {
+        ((size as f64 - 1.) / 2., (size as f64 - 1.) / 2.)
+    } else {
+        (size as f64 / 2., (size as f64 - 1.) / 2.)
+    }
+}
+
+// Reflects `c` by the borders and returns the reflected coordinate together with the derivative
+// of the reflection, i.e. `1` or `-1`.
+fn reflect(c: f64, size: usize, align_corners: bool) -> (f64, f64) {
+    let (twice_low, twice_high) = if align_corners {
+        (0., 2. * (size as f64 - 1.))
+    } else {
+        (-1., 2. * size as f64 - 1.)
+    };
+    if twice_low == twice_high {
+        return (0., 0.);
+    }
+    let min = twice_low / 2.;
+    let span = (twice_high - twice_low) / 2.;
+    let sign = if c < min { -1. } else { 1. };
+    let c = (c - min).abs();
+    let extra = c % span;
+    let flips = (c / span).floor() as i64;
+    if flips % 2 == 0 {
+        (extra + min, sign)
+    } else {
+        (span - extra + min, -sign)
+    }
+}
+
+// Applies the padding mode to a coordinate, returns the new coordinate and its derivative.
+fn pad_coordinate(
+    c: f64,
+    size: usize,
+    padding: GridPaddingMode,
+    align_corners: bool,
+) -> (f64, f64) {
+    let (c, d) = match padding {
+        GridPaddingMode::Zeros => return (c, 1.),
+        GridPaddingMode::Border => (c, 1.),
+        GridPaddingMode::Reflection => reflect(c, size, align_corners),
+    };
+    let max = size as f64 - 1.;
+    if c <= 0. {
+        (0., 0.)
+    } else if c >= max {
+        (max, 0.)
+    } else {
+        (c, d)
+    }
+}
+
+// Applies the padding mode to a tap index, returns `None` if the tap falls out of the input.
+fn pad_index(
+    index: i64,
+    size: usize,
+    padding: GridPaddingMode,
+    align_corners: bool,
+) -> Option<usize> {
+    let index = match padding {
+        GridPaddingMode::Zeros => index,
+        _ => pad_coordinate(index as f64, size, padding, align_corners).0 as i64,
+    };
+    if index >= 0 && (index as usize) < size {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+fn round_half_even(c: f64) -> f64 {
+    let r = c.round();
+    if (c - c.trunc()).abs() == 0.5 && r % 2. != 0. {
+        r - c.signum()
+    } else {
+        r
+    }
+}
+
+fn cubic_convolution1(x: &Tensor, a: f64) -> Result<Tensor> {
+    // ((a + 2) x - (a + 3)) x x + 1
+    (x.affine(a + 2., -(a + 3.))? * x.sqr()?)? + 1.
+}
+
+fn cubic_convolution2(x: &Tensor, a: f64) -> Result<Tensor> {
+    // ((a x - 5 a) x + 8 a) x - 4 a
+    ((x.affine(a, -5. * a)? * x)? + 8. * a)?.mul(x)? - 4. * a
+}
+
+fn axis_taps(
+    coords: &Tensor,
+    size: usize,
+    mode: GridSampleMode,
+    padding: GridPaddingMode,
+    align_corners: bool,
+) -> Result<AxisTaps> {
+    let (mul, add) = unnormalize(size, align_corners);
+    let coords = coords.affine(mul, add)?;
+    let values = coords.to_dtype(DType::F64)?.to_vec1::<f64>()?;
+    let n = values.len();
+    // The padding of the bicubic mode applies to the taps rather than to the coordinates.
+    let values = if mode == GridSampleMode::Bicubic {
+        values
+    } else {
+        let (padded, derivatives): (Vec<_>, Vec<_>) = values
+            .iter()
+            .map(|&c| pad_coordinate(c, size, padding, align_corners))
+            .unzip();
+        if padding != GridPaddingMode::Zeros {
+            let offsets: Vec<_> = values
+                .iter()
+                .zip(padded.iter().zip(derivatives.iter()))
+                .map(|(c, (p, d))| p - d * c)
+                .collect();
+            let device = coords.device();
+            let derivatives = Tensor::from_vec(derivatives, n, device)?.to_dtype(coords.dtype())?;
+            let offsets = Tensor::from_vec(offsets, n, device)?.to_dtype(coords.dtype())?;
+            // Keep the graph so that the gradient flows through the padded coordinates.
+            let padded_coords = ((&coords * derivatives)? + offsets)?;
+            return taps_from(&padded_coords, padded, size, mode, padding, align_corners);
+        }
+        padded
+    };
+    taps_from(&coords, values, size, mode, padding, align_corners)
+}
+
+fn taps_from(
+    coords: &Tensor,
+    values: Vec<f64>,
+    size: usize,
+    mode: GridSampleMode,
+    padding: GridPaddingMode,
+    align_corners: bool,
+) -> Result<AxisTaps> {
+    let n = values.len();
+    let base: Vec<f64> = match mode {
+        GridSampleMode::Nearest => values.iter().map(|&c| round_half_even(c)).collect(),
+        GridSampleMode::Bilinear | GridSampleMode::Bicubic => {
+            values.iter().map(|c| c.floor()).collect()
+        }
+    };
+    let offsets: &[i64] = match mode {
+        GridSampleMode::Nearest => &[0],
+        GridSampleMode::Bilinear => &[0, 1],
+        GridSampleMode::Bicubic => &[-1, 0, 1, 2],
+    };
+    // Only the bicubic taps get padded, the other modes have already padded the coordinates.
+    let padding = match mode {
+        GridSampleMode::Bicubic => padding,
+        GridSampleMode::Nearest | GridSampleMode::Bilinear => GridPaddingMode::Zeros,
+    };
+    let mut indexes = Vec::with_capacity(offsets.len());
+    let mut masks = Vec::with_capacity(offsets.len());
+    for &offset in offsets.iter() {
+        let (index, mask): (Vec<_>, Vec<_>) = base
+            .iter()
+            .map(
+                |&b| match pad_index(b as i64 + offset, size, padding, align_corners) {
+                    Some(index) => (index, 1.),
+                    None => (0, 0.),
+                },
+            )
+            .unzip();
+        indexes.push(index);
+        masks.push(mask);
+    }
+    let base = Tensor::from_vec(base, n, coords.device())?.to_dtype(coords.dtype())?;
+    let t = (coords - base)?;
+    let weights = match mode {
+        GridSampleMode::Nearest => vec![t.ones_like()?],
+        GridSampleMode::Bilinear => vec![t.affine(-1., 1.)?, t],
+        GridSampleMode::Bicubic => {
+            let a = -0.75;
+            vec![
+                cubic_convolution2(&(&t + 1.)?, a)?,
+                cubic_convolution1(&t, a)?,
+                cubic_convolution1(&t.affine(-1., 1.)?, a)?,
+                cubic_convolution2(&t.affine(-1., 2.)?, a)?,
+            ]
+        }
+    };
+    Ok(AxisTaps {
+        indexes,
+        masks,
+        weights,
+    })
+}
+
+// Returns `(-1, 1)` spaced coordinates for the centers of the elements along an axis.
+fn base_coordinates(size: usize, align_corners: bool) -> Vec<f64> {
+    if size <= 1 {
+        return vec![0.; size];
+    }
+    let step = 2. / (size - 1) as f64;
+    let scale = if align_corners {
+        1.
+    } else {
+        (size - 1) as f64 / size as f64
+    };
+    (0..size).map(|i| (-1. + i as f64 * step) * scale).collect()
+}
+
+impl Tensor {
+    /// Samples the input tensor at the locations given by `grid`.
+    ///
+    /// For 2D inputs of shape `(batch, channels, h, w)`, the grid has shape
+    /// `(batch, h_out, w_out, 2)` and the result has shape `(batch, channels, h_out, w_out)`.
+    /// For volumetric inputs of shape `(batch, channels, d, h, w)`, the grid has shape
+    /// `(batch, d_out, h_out, w_out, 3)` and the result has shape
+    /// `(batch, channels, d_out, h_out, w_out)`.
+    ///
+    /// The last dimension of the grid holds the `x`, `y` (and `z`) coordinates, normalized so that
+    /// `-1` and `1` are the left and right borders of the input. When `align_corners` is set,
+    /// these refer to the centers of the corner elements, otherwise to their outer edges. The
+    /// results match PyTorch's `F.grid_sample` and the gradients flow to both the input and the
+    /// grid.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, GridSampleMode, GridPaddingMode};
+    /// let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], &Device::Cpu)?;
+    /// let grid = Tensor::new(&[[[[0f32, 0.], [1., -1.]]]], &Device::Cpu)?;
+    /// let t = t.grid_sample(&grid, GridSampleMode::Bilinear, GridPaddingMode::Zeros, true)?;
+    /// assert_eq!(t.flatten_all()?.to_vec1::<f32>()?, &[2.5, 2.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn grid_sample(
+        &self,
+        grid: &Self,
+        mode: GridSampleMode,
+        padding: GridPaddingMode,
+        align_corners: bool,
+    ) -> Result<Self> {
+        let rank = self.rank();
+        if rank != 4 && rank != 5 {
+            crate::bail!(
+                "grid_sample expects a 4D or 5D input, got {:?}",
+                self.shape()
+            )
+        }
+        if mode == GridSampleMode::Bicubic && rank != 4 {
+            crate::bail!("grid_sample: bicubic mode only supports 4D inputs")
+        }
+        if !self.dtype().is_float() {
+            Err(crate::Error::UnsupportedDTypeForOp(self.dtype(), "grid_sample").bt())?
+        }
+        let spatial = &self.dims()[2..];
+        let (b, c) = (self.dim(0)?, self.dim(1)?);
+        let grid_dims = grid.dims();
+        if grid_dims.len() != rank || grid_dims[0] != b || grid_dims[rank - 1] != rank - 2 {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: grid.shape().clone(),
+                op: "grid_sample",
+            }
+            .bt())?
+        }
+        let out_dims = &grid_dims[1..rank - 1];
+        let n = grid.elem_count() / (rank - 2);
+        let grid = grid.to_dtype(self.dtype())?.reshape((n, rank - 2))?;
+        // The grid coordinates are ordered from the last spatial dimension to the first one.
+        let taps = spatial
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| {
+                let coords = grid.narrow(1, rank - 3 - i, 1)?.squeeze(1)?;
+                axis_taps(&coords, size, mode, padding, align_corners)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let numel = spatial.iter().product::<usize>();
+        let xs = self.reshape((b, c, numel))?;
+        let per_batch = n / b;
+        let num_taps = taps[0].indexes.len();
+        let mut ys: Option<Tensor> = None;
+        for combination in 0..num_taps.pow(spatial.len() as u32) {
+            let mut tap_ids = Vec::with_capacity(spatial.len());
+            let mut rem = combination;
+            for _ in spatial.iter() {
+                tap_ids.push(rem % num_taps);
+                rem /= num_taps;
+            }
+            let mut index = vec![0u32; n];
+            let mut mask = vec![1f64; n];
+            let mut weight: Option<Tensor> = None;
+            for ((axis, &size), &tap) in taps.iter().zip(spatial.iter()).zip(tap_ids.iter()) {
+                for (j, (idx, m)) in index.iter_mut().zip(mask.iter_mut()).enumerate() {
+                    *idx = *idx * size as u32 + axis.indexes[tap][j] as u32;
+                    *m *= axis.masks[tap][j];
+                }
+                weight = Some(match weight {
+                    None => axis.weights[tap].clone(),
+                    Some(w) => (w * &axis.weights[tap])?,
+                });
+            }
+            // Unwrapping is safe as there are at least two spatial dimensions.
+            let weight = weight.unwrap();
+            let mask = Tensor::from_vec(mask, n, self.device())?.to_dtype(self.dtype())?;
+            let weight = (weight * mask)?.reshape((b, 1, per_batch))?;
+            let index = Tensor::from_vec(index, (b, 1, per_batch), self.device())?
+                .broadcast_as((b, c, per_batch))?
+                .contiguous()?;
+            let values = xs.gather(&index, 2)?.broadcast_mul(&weight)?;
+            ys = Some(match ys {
+                None => values,
+                Some(ys) => (ys + values)?,
+            });
+        }
+        let mut dims = vec![b, c];
+        dims.extend_from_slice(out_dims);
+        ys.unwrap().reshape(dims)
+    }
+
+    /// Generates a sampling grid for [`Tensor::grid_sample`] from a batch of affine matrices.
+    ///
+    /// `self` is the batch of matrices, with shape `(batch, 2, 3)` for 2D grids or
+    /// `(batch, 3, 4)` for 3D grids. `size` is the shape of the target, either
+    /// `(batch, channels, h, w)` or `(batch, channels, d, h, w)`, and the returned grid has shape
+    /// `(batch, h, w, 2)` or `(batch, d, h, w, 3)`. The results match PyTorch's `F.affine_grid`.
+    pub fn affine_grid<S: Into<Shape>>(&self, size: S, align_corners: bool) -> Result<Self> {
+        let size: Shape = size.into();
+        let dims = size.dims();
+        let spatial_rank = match dims.len() {
+            4 => 2,
+            5 => 3,
+            _ => crate::bail!("affine_grid expects a 4D or 5D size, got {size:?}"),
+        };
+        let (b, rows, cols) = self.dims3()?;
+        if b != dims[0] || rows != spatial_rank || cols != spatial_rank + 1 {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: size.clone(),
+                op: "affine_grid",
+            }
+            .bt())?
+        }
+        let spatial = &dims[2..];
+        let numel = spatial.iter().product::<usize>();
+        // The base grid holds the homogeneous coordinates (x, y, [z,] 1) of each target element.
+        let coords: Vec<_> = spatial
+            .iter()
+            .map(|&s| base_coordinates(s, align_corners))
+            .collect();
+        let mut base = Vec::with_capacity(numel * (spatial_rank + 1));
+        for i in 0..numel {
+            let mut rem = i;
+            let mut point = vec![0f64; spatial_rank];
+            for (axis, &s) in spatial.iter().enumerate().rev() {
+                // The x coordinate is the first element and corresponds to the last dimension.
+                point[spatial_rank - 1 - axis] = coords[axis][rem % s];
+                rem /= s;
+            }
+            base.extend_from_slice(&point);
+            base.push(1.);
+        }
+        let base = Tensor::from_vec(base, (1, numel, spatial_rank + 1), self.device())?
+            .to_dtype(self.dtype())?;
+        let grid = base.broadcast_matmul(&self.transpose(1, 2)?)?;
+        let mut grid_dims = vec![b];
+        grid_dims.extend_from_slice(spatial);
+        grid_dims.push(spatial_rank);
+        grid.reshape(grid_dims)
+    }
+}
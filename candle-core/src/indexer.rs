@@ -1,4 +1,4 @@
-use crate::{Error, Tensor};
+use crate::{DType, Error, Tensor};
 use std::ops::{
     Bound, Range, RangeBounds, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive,
 };
@@ -24,13 +24,43 @@ impl Tensor {
     ///
     /// # Ok::<(), candle_core::Error>(())
     /// ```
+    ///
+    /// Integer tensors of any rank and boolean masks can be used as indexes, following the NumPy
+    /// advanced indexing rules.
+    ///
+    /// ```
+    /// # use candle_core::{Tensor, Device, IndexOp, Idx, NewAxis, Ellipsis, Slice};
+    /// let a = Tensor::arange(0u32, 12, &Device::Cpu)?.reshape((3, 4))?;
+    ///
+    /// let ids = Tensor::new(&[[0u32, 2], [1, 1]], &Device::Cpu)?;
+    /// assert_eq!(a.i((.., &ids))?.shape().dims(), &[3, 2, 2]);
+    ///
+    /// let rows = Tensor::new(&[0u32, 2], &Device::Cpu)?;
+    /// let cols = Tensor::new(&[-1i64, 0], &Device::Cpu)?;
+    /// assert_eq!(a.i((&rows, &cols))?.to_vec1::<u32>()?, &[3, 8]);
+    ///
+    /// let mask = a.ge(6u32)?.to_dtype(candle_core::DType::Bool)?;
+    /// assert_eq!(a.i(&mask)?.to_vec1::<u32>()?, &[6, 7, 8, 9, 10, 11]);
+    ///
+    /// assert_eq!(a.i((Idx(-1), Slice::new(None, None, -2)))?.to_vec1::<u32>()?, &[11, 9]);
+    /// assert_eq!(a.i((None, Ellipsis, 0))?.shape().dims(), &[1, 3]);
+    /// assert_eq!(a.i((NewAxis, .., NewAxis))?.shape().dims(), &[1, 3, 1, 4]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
     fn index(&self, indexers: &[TensorIndexer]) -> Result<Self, Error> {
+        let rank = self.rank();
+        let consumed_dims = consumed_dims(rank, indexers)?;
         let mut x = self.clone();
-        let dims = self.shape().dims();
         let mut current_dim = 0;
-        for (i, indexer) in indexers.iter().enumerate() {
+        // The advanced indexes together with the dimension of `x` that they apply to.
+        let mut advanced = vec![];
+        for indexer in indexers.iter() {
             x = match indexer {
                 TensorIndexer::Select(n) => x.narrow(current_dim, *n, 1)?.squeeze(current_dim)?,
+                TensorIndexer::SelectSigned(n) => {
+                    let n = resolve_index(*n, x.dim(current_dim)?)?;
+                    x.narrow(current_dim, n, 1)?.squeeze(current_dim)?
+                }
                 TensorIndexer::Narrow(left_bound, right_bound) => {
                     let start = match left_bound {
                         Bound::Included(n) => *n,
@@ -40,24 +70,427 @@ impl Tensor {
                     let stop = match right_bound {
                         Bound::Included(n) => *n + 1,
                         Bound::Excluded(n) => *n,
-                        Bound::Unbounded => dims[i],
+                        Bound::Unbounded => x.dim(current_dim)?,
                     };
                     let out = x.narrow(current_dim, start, stop.saturating_sub(start))?;
                     current_dim += 1;
                     out
                 }
+                TensorIndexer::Slice(slice) => {
+                    let (start, len, step) = slice.resolve(x.dim(current_dim)?)?;
+                    let out = if step == 1 {
+                        x.narrow(current_dim, start as usize, len)?
+                    } else {
+                        let indexes: Vec<u32> = (0..len as isize)
+                            .map(|i| (start + i * step) as u32)
+                            .collect();
+                        let indexes = Tensor::from_vec(indexes, len, x.device())?;
+                        x.contiguous()?.index_select(&indexes, current_dim)?
+                    };
+                    current_dim += 1;
+                    out
+                }
+                TensorIndexer::NewAxis => {
+                    let out = x.unsqueeze(current_dim)?;
+                    current_dim += 1;
+                    out
+                }
+                TensorIndexer::Ellipsis => {
+                    current_dim += rank - consumed_dims;
+                    x
+                }
+                TensorIndexer::IndexSelect(indexes) if indexes.dtype() == DType::Bool => {
+                    let mask_dims = indexes.dims();
+                    let dims = &x.dims()[current_dim..current_dim + mask_dims.len()];
+                    if mask_dims != dims {
+                        crate::bail!(
+                            "boolean mask with shape {mask_dims:?} does not match the indexed dims {dims:?}"
+                        )
+                    }
+                    for (i, indexes) in nonzero(indexes)?.into_iter().enumerate() {
+                        advanced.push((current_dim + i, indexes))
+                    }
+                    current_dim += mask_dims.len();
+                    x
+                }
                 TensorIndexer::IndexSelect(indexes) => {
-                    if indexes.rank() != 1 {
-                        crate::bail!("multi-dimensional tensor indexing is not supported")
+                    if !indexes.dtype().is_int() {
+                        crate::bail!("tensors used as indexes must be integers or booleans")
                     }
-                    let out = x.index_select(&indexes.to_device(x.device())?, current_dim)?;
+                    advanced.push((current_dim, indexes.clone()));
                     current_dim += 1;
-                    out
+                    x
                 }
                 TensorIndexer::Err(e) => crate::bail!("indexing error {e:?}"),
             };
         }
-        Ok(x)
+        match advanced.as_slice() {
+            [] => Ok(x),
+            // A single unsigned index vector does not need to be checked on the host.
+            [(dim, indexes)]
+                if indexes.rank() == 1 && matches!(indexes.dtype(), DType::U8 | DType::U32) =>
+            {
+                x.contiguous()?
+                    .index_select(&indexes.to_device(x.device())?, *dim)
+            }
+            _ => advanced_index(&x, &advanced),
+        }
+    }
+}
+
+impl Tensor {
+    fn put(
+        &self,
+        indexers: &[TensorIndexer],
+        values: &Tensor,
+        accumulate: bool,
+    ) -> Result<Self, Error> {
+        if values.dtype() != self.dtype() {
+            Err(Error::DTypeMismatchBinaryOp {
+                lhs: self.dtype(),
+                rhs: values.dtype(),
+                op: "index_put",
+            }
+            .bt())?
+        }
+        let numel = self.elem_count();
+        if numel > u32::MAX as usize {
+            crate::bail!(
+                "index_put only supports tensors with at most {} elements, got {numel}",
+                u32::MAX
+            )
+        }
+        let (positions, shape) = put_positions(self.dims(), indexers)?;
+        let values = values.broadcast_as(shape)?.contiguous()?.flatten_all()?;
+        let xs = self.flatten_all()?;
+        let ys = if accumulate {
+            let positions: Vec<u32> = positions.iter().map(|&p| p as u32).collect();
+            let len = positions.len();
+            let positions = Tensor::from_vec(positions, len, self.device())?;
+            xs.index_add(&positions, &values, 0)?
+        } else {
+            // When a position appears multiple times, the last value is the one that is written.
+            let mut writes: Vec<(usize, usize)> = positions.into_iter().zip(0..).collect();
+            writes.sort_unstable();
+            let mut positions = Vec::with_capacity(writes.len());
+            let mut rows = Vec::with_capacity(writes.len());
+            for (i, &(p, row)) in writes.iter().enumerate() {
+                if writes.get(i + 1).is_none_or(|&(next, _)| next != p) {
+                    positions.push(p as u32);
+                    rows.push(row as u32);
+                }
+            }
+            let len = positions.len();
+            let positions = Tensor::from_vec(positions, len, self.device())?;
+            let rows = Tensor::from_vec(rows, len, self.device())?;
+            let values = values.index_select(&rows, 0)?;
+            let zeros = xs.zeros_like()?;
+            let written = zeros.index_add(&positions, &values, 0)?;
+            let mask = zeros
+                .index_add(&positions, &values.ones_like()?, 0)?
+                .ne(&zeros)?;
+            mask.where_cond(&written, &xs)?
+        };
+        ys.reshape(self.shape())
+    }
+
+    /// Returns a tensor with the same shape as `self` where the elements for which `mask` is
+    /// non-zero are replaced with `value`. The mask is broadcasted to the shape of `self`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, -2.], [-3., 4.]], &Device::Cpu)?;
+    /// let t = t.masked_fill(&t.lt(0f32)?, 0f32)?;
+    /// assert_eq!(t.to_vec2::<f32>()?, &[[1., 0.], [0., 4.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn masked_fill<T: crate::WithDType>(&self, mask: &Tensor, value: T) -> Result<Self, Error> {
+        let value = Tensor::new(value, self.device())?
+            .to_dtype(self.dtype())?
+            .broadcast_as(self.shape())?;
+        mask.broadcast_as(self.shape())?.where_cond(&value, self)
+    }
+
+    /// Returns a 1D tensor with the elements of `self` for which `mask` is non-zero, `self` and
+    /// `mask` are broadcasted together.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, -2.], [-3., 4.]], &Device::Cpu)?;
+    /// let t = t.masked_select(&t.gt(0f32)?)?;
+    /// assert_eq!(t.to_vec1::<f32>()?, &[1., 4.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn masked_select(&self, mask: &Tensor) -> Result<Self, Error> {
+        let shape = self
+            .shape()
+            .broadcast_shape_binary_op(mask.shape(), "masked_select")?;
+        let mask = mask.broadcast_as(&shape)?.to_dtype(DType::Bool)?;
+        self.broadcast_as(&shape)?
+            .index(&[TensorIndexer::IndexSelect(mask)])
+    }
+}
+
+// Checks that there is at most one ellipsis and returns the number of dimensions consumed by
+// the indexers.
+fn consumed_dims(rank: usize, indexers: &[TensorIndexer]) -> Result<usize, Error> {
+    let mut num_ellipsis = 0;
+    let mut consumed_dims = 0;
+    for indexer in indexers.iter() {
+        match indexer {
+            TensorIndexer::NewAxis => {}
+            TensorIndexer::Ellipsis => num_ellipsis += 1,
+            TensorIndexer::IndexSelect(t) if t.dtype() == DType::Bool => consumed_dims += t.rank(),
+            _ => consumed_dims += 1,
+        }
+    }
+    if num_ellipsis > 1 {
+        crate::bail!("an index can only have a single ellipsis")
+    }
+    if consumed_dims > rank {
+        crate::bail!("too many indices for a tensor of rank {rank}")
+    }
+    Ok(consumed_dims)
+}
+
+// Returns the flat positions of the elements selected by `indexers` in a contiguous tensor with
+// dimensions `dims`, in the order in which `index` returns them, together with the shape of the
+// selection. The positions are derived from the offset and strides of the selection so that only
+// the selected elements get visited.
+fn put_positions(
+    dims: &[usize],
+    indexers: &[TensorIndexer],
+) -> Result<(Vec<usize>, crate::Shape), Error> {
+    let rank = dims.len();
+    let consumed_dims = consumed_dims(rank, indexers)?;
+    let mut strides: Vec<isize> = crate::Shape::from(dims)
+        .stride_contiguous()
+        .into_iter()
+        .map(|s| s as isize)
+        .collect();
+    let mut dims = dims.to_vec();
+    let mut offset = 0isize;
+    let mut current_dim = 0;
+    let mut advanced = vec![];
+    for indexer in indexers.iter() {
+        match indexer {
+            TensorIndexer::Select(n) => {
+                let n = resolve_index(*n as isize, dims[current_dim])?;
+                offset += n as isize * strides[current_dim];
+                dims.remove(current_dim);
+                strides.remove(current_dim);
+            }
+            TensorIndexer::SelectSigned(n) => {
+                let n = resolve_index(*n, dims[current_dim])?;
+                offset += n as isize * strides[current_dim];
+                dims.remove(current_dim);
+                strides.remove(current_dim);
+            }
+            TensorIndexer::Narrow(left_bound, right_bound) => {
+                let size = dims[current_dim];
+                let start = match left_bound {
+                    Bound::Included(n) => *n,
+                    Bound::Excluded(n) => *n + 1,
+                    Bound::Unbounded => 0,
+                };
+                let stop = match right_bound {
+                    Bound::Included(n) => *n + 1,
+                    Bound::Excluded(n) => *n,
+                    Bound::Unbounded => size,
+                };
+                let len = stop.saturating_sub(start);
+                if start + len > size {
+                    crate::bail!(
+                        "range {start}..{stop} is out of bounds for a dimension with size {size}"
+                    )
+                }
+                offset += start as isize * strides[current_dim];
+                dims[current_dim] = len;
+                current_dim += 1;
+            }
+            TensorIndexer::Slice(slice) => {
+                let (start, len, step) = slice.resolve(dims[current_dim])?;
+                if len > 0 {
+                    offset += start * strides[current_dim];
+                }
+                dims[current_dim] = len;
+                strides[current_dim] *= step;
+                current_dim += 1;
+            }
+            TensorIndexer::NewAxis => {
+                dims.insert(current_dim, 1);
+                strides.insert(current_dim, 0);
+                current_dim += 1;
+            }
+            TensorIndexer::Ellipsis => current_dim += rank - consumed_dims,
+            TensorIndexer::IndexSelect(indexes) if indexes.dtype() == DType::Bool => {
+                let mask_dims = indexes.dims();
+                let indexed_dims = &dims[current_dim..current_dim + mask_dims.len()];
+                if mask_dims != indexed_dims {
+                    crate::bail!(
+                        "boolean mask with shape {mask_dims:?} does not match the indexed dims {indexed_dims:?}"
+                    )
+                }
+                for (i, indexes) in nonzero(indexes)?.into_iter().enumerate() {
+                    advanced.push((current_dim + i, indexes))
+                }
+                current_dim += mask_dims.len();
+            }
+            TensorIndexer::IndexSelect(indexes) => {
+                if !indexes.dtype().is_int() {
+                    crate::bail!("tensors used as indexes must be integers or booleans")
+                }
+                advanced.push((current_dim, indexes.clone()));
+                current_dim += 1;
+            }
+            TensorIndexer::Err(e) => crate::bail!("indexing error {e:?}"),
+        }
+    }
+    // The offsets along each dimension of the selection, in the order of `index`. The advanced
+    // indexes are broadcasted together and contribute a single block of offsets.
+    let strided =
+        |d: usize| -> Vec<isize> { (0..dims[d] as isize).map(|i| i * strides[d]).collect() };
+    let (axes, shape) = if advanced.is_empty() {
+        (
+            (0..dims.len()).map(strided).collect::<Vec<_>>(),
+            dims.clone(),
+        )
+    } else {
+        let mut shape = advanced[0].1.shape().clone();
+        for (_, indexes) in advanced[1..].iter() {
+            shape = shape.broadcast_shape_binary_op(indexes.shape(), "index_put")?
+        }
+        let mut offsets = vec![0isize; shape.elem_count()];
+        for (dim, indexes) in advanced.iter() {
+            let indexes = indexes
+                .broadcast_as(&shape)?
+                .to_dtype(DType::I64)?
+                .flatten_all()?
+                .to_vec1::<i64>()?;
+            for (o, &index) in offsets.iter_mut().zip(indexes.iter()) {
+                *o += resolve_index(index as isize, dims[*dim])? as isize * strides[*dim]
+            }
+        }
+        let indexed_dims: Vec<usize> = advanced.iter().map(|(d, _)| *d).collect();
+        let first = indexed_dims[0];
+        let adjacent = indexed_dims
+            .iter()
+            .enumerate()
+            .all(|(i, &d)| d == first + i);
+        let lead = if adjacent { first } else { 0 };
+        let other_dims: Vec<usize> = (0..dims.len())
+            .filter(|d| !indexed_dims.contains(d))
+            .collect();
+        let mut axes: Vec<Vec<isize>> = other_dims[..lead].iter().map(|&d| strided(d)).collect();
+        axes.push(offsets);
+        axes.extend(other_dims[lead..].iter().map(|&d| strided(d)));
+        let mut out_dims: Vec<usize> = other_dims[..lead].iter().map(|&d| dims[d]).collect();
+        out_dims.extend_from_slice(shape.dims());
+        out_dims.extend(other_dims[lead..].iter().map(|&d| dims[d]));
+        (axes, out_dims)
+    };
+    let mut positions = vec![offset];
+    for axis in axes.iter() {
+        positions = positions
+            .iter()
+            .flat_map(|&p| axis.iter().map(move |&o| p + o))
+            .collect();
+    }
+    let positions = positions.into_iter().map(|p| p as usize).collect();
+    Ok((positions, shape.into()))
+}
+
+fn resolve_index(index: isize, size: usize) -> Result<usize, Error> {
+    let resolved = if index < 0 {
+        index + size as isize
+    } else {
+        index
+    };
+    if resolved < 0 || resolved as usize >= size {
+        crate::bail!("index {index} is out of bounds for a dimension with size {size}")
+    }
+    Ok(resolved as usize)
+}
+
+// Returns one index vector per dimension of the mask, with the coordinates of the set elements.
+fn nonzero(mask: &Tensor) -> Result<Vec<Tensor>, Error> {
+    let dims = mask.dims();
+    let values = mask.flatten_all()?.to_dtype(DType::U8)?.to_vec1::<u8>()?;
+    let mut coords = vec![vec![]; dims.len()];
+    for (i, _) in values.iter().enumerate().filter(|(_, &v)| v != 0) {
+        let mut rem = i;
+        for (d, &size) in dims.iter().enumerate().rev() {
+            coords[d].push((rem % size) as u32);
+            rem /= size;
+        }
+    }
+    coords
+        .into_iter()
+        .map(|c| {
+            let len = c.len();
+            Tensor::from_vec(c, len, &crate::Device::Cpu)
+        })
+        .collect()
+}
+
+// Applies the advanced indexes, these are broadcasted together and the resulting dimensions
+// replace the indexed ones. When the indexed dimensions are not adjacent, the resulting
+// dimensions come first as in NumPy.
+fn advanced_index(x: &Tensor, advanced: &[(usize, Tensor)]) -> Result<Tensor, Error> {
+    let mut shape = advanced[0].1.shape().clone();
+    for (_, indexes) in advanced[1..].iter() {
+        shape = shape.broadcast_shape_binary_op(indexes.shape(), "index")?
+    }
+    let indexed_dims: Vec<usize> = advanced.iter().map(|(d, _)| *d).collect();
+    let other_dims: Vec<usize> = (0..x.rank())
+        .filter(|d| !indexed_dims.contains(d))
+        .collect();
+    let indexed_numel = indexed_dims.iter().map(|&d| x.dims()[d]).product::<usize>();
+    // The flat indexes are u32 so the indexed dimensions cannot have more elements than that.
+    if indexed_numel > u32::MAX as usize {
+        crate::bail!(
+            "advanced indexing only supports indexed dimensions with at most {} elements, got {indexed_numel}",
+            u32::MAX
+        )
+    }
+    let numel = shape.elem_count();
+    let mut flat_indexes = vec![0u32; numel];
+    for (dim, indexes) in advanced.iter() {
+        let size = x.dim(*dim)?;
+        let indexes = indexes
+            .broadcast_as(&shape)?
+            .to_dtype(DType::I64)?
+            .flatten_all()?
+            .to_vec1::<i64>()?;
+        for (flat, &index) in flat_indexes.iter_mut().zip(indexes.iter()) {
+            *flat = *flat * size as u32 + resolve_index(index as isize, size)? as u32;
+        }
+    }
+    let mut perm = indexed_dims.clone();
+    perm.extend_from_slice(&other_dims);
+    let mut dims = vec![indexed_numel];
+    dims.extend(other_dims.iter().map(|&d| x.dims()[d]));
+    let flat_indexes = Tensor::from_vec(flat_indexes, numel, x.device())?;
+    let ys = x
+        .permute(perm)?
+        .reshape(dims.as_slice())?
+        .index_select(&flat_indexes, 0)?;
+    let mut dims = shape.dims().to_vec();
+    dims.extend(other_dims.iter().map(|&d| x.dims()[d]));
+    let ys = ys.reshape(dims)?;
+    let first = indexed_dims[0];
+    let adjacent = indexed_dims
+        .iter()
+        .enumerate()
+        .all(|(i, &d)| d == first + i);
+    if adjacent && first > 0 {
+        let rank = shape.rank();
+        let mut perm: Vec<usize> = (rank..rank + first).collect();
+        perm.extend(0..rank);
+        perm.extend(rank + first..ys.rank());
+        ys.permute(perm)
+    } else {
+        Ok(ys)
     }
 }
 
@@ -68,11 +501,99 @@ pub enum TensorIndexer {
     Select(usize),
     /// This is a regular slice, purely indexing a chunk of the tensor
     Narrow(Bound<usize>, Bound<usize>),
-    /// Indexing via a 1d tensor
+    /// This selects the elements for which an index has some specific value, negative values
+    /// count from the end of the dimension.
+    SelectSigned(isize),
+    /// A slice with a step, see [`Slice`].
+    Slice(Slice),
+    /// Indexing via an integer tensor, or via a boolean mask which covers as many dimensions as
+    /// it has.
     IndexSelect(Tensor),
+    /// Inserts a new dimension of size 1.
+    NewAxis,
+    /// Expands to as many full slices as needed to index all the dimensions.
+    Ellipsis,
     Err(Error),
 }
 
+/// Selects a single element along a dimension, negative values count from the end of the
+/// dimension, e.g. `t.i(Idx(-1))` returns the last element of `t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Idx(pub isize);
+
+impl From<Idx> for TensorIndexer {
+    fn from(index: Idx) -> Self {
+        TensorIndexer::SelectSigned(index.0)
+    }
+}
+
+/// A NumPy style slice `start:stop:step`, negative bounds count from the end of the dimension
+/// and a negative step walks the dimension in reverse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slice {
+    pub start: Option<isize>,
+    pub stop: Option<isize>,
+    pub step: isize,
+}
+
+impl Slice {
+    pub fn new(start: Option<isize>, stop: Option<isize>, step: isize) -> Self {
+        Self { start, stop, step }
+    }
+
+    // Returns the start, the number of elements and the step of the slice.
+    fn resolve(&self, size: usize) -> Result<(isize, usize, isize), Error> {
+        let step = self.step;
+        if step == 0 {
+            crate::bail!("slice step cannot be zero")
+        }
+        let size = size as isize;
+        let normalize = |v: isize| if v < 0 { v + size } else { v };
+        let (start, len) = if step > 0 {
+            let start = self.start.map_or(0, normalize).clamp(0, size);
+            let stop = self.stop.map_or(size, normalize).clamp(0, size);
+            (start, (stop - start + step - 1).max(0) / step)
+        } else {
+            let start = self.start.map_or(size - 1, normalize).clamp(-1, size - 1);
+            let stop = self.stop.map_or(-1, normalize).clamp(-1, size - 1);
+            (start, (start - stop - step - 1).max(0) / -step)
+        };
+        Ok((start, len as usize, step))
+    }
+}
+
+impl From<Slice> for TensorIndexer {
+    fn from(slice: Slice) -> Self {
+        TensorIndexer::Slice(slice)
+    }
+}
+
+/// Inserts a new dimension of size 1, `None` can be used for the same purpose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewAxis;
+
+impl From<NewAxis> for TensorIndexer {
+    fn from(_: NewAxis) -> Self {
+        TensorIndexer::NewAxis
+    }
+}
+
+impl From<Option<NewAxis>> for TensorIndexer {
+    fn from(_: Option<NewAxis>) -> Self {
+        TensorIndexer::NewAxis
+    }
+}
+
+/// Expands to as many full slices as needed to index all the dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ellipsis;
+
+impl From<Ellipsis> for TensorIndexer {
+    fn from(_: Ellipsis) -> Self {
+        TensorIndexer::Ellipsis
+    }
+}
+
 impl From<usize> for TensorIndexer {
     fn from(index: usize) -> Self {
         TensorIndexer::Select(index)
@@ -135,6 +656,24 @@ pub trait IndexOp<T> {
     /// Returns a slicing iterator which are the chunks of data necessary to
     /// reconstruct the desired tensor.
     fn i(&self, index: T) -> Result<Tensor, Error>;
+
+    /// Returns a copy of the tensor where the elements selected by `index` are replaced by
+    /// `values`, or incremented by `values` when `accumulate` is set. The values are broadcasted
+    /// to the shape of the selection. When an element is selected multiple times, the values get
+    /// summed if `accumulate` is set, otherwise the last one is written.
+    ///
+    /// ```
+    /// # use candle_core::{Tensor, Device, IndexOp};
+    /// let a = Tensor::zeros((2, 3), candle_core::DType::F32, &Device::Cpu)?;
+    /// let ids = Tensor::new(&[2u32, 0, 2], &Device::Cpu)?;
+    /// let values = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// let b = a.index_put((1, &ids), &values, true)?;
+    /// assert_eq!(b.to_vec2::<f32>()?, &[[0., 0., 0.], [2., 0., 4.]]);
+    /// let b = a.index_put((1, &ids), &values, false)?;
+    /// assert_eq!(b.to_vec2::<f32>()?, &[[0., 0., 0.], [2., 0., 3.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    fn index_put(&self, index: T, values: &Tensor, accumulate: bool) -> Result<Tensor, Error>;
 }
 
 impl<T> IndexOp<T> for Tensor
@@ -144,6 +683,10 @@ where
     fn i(&self, index: T) -> Result<Tensor, Error> {
         self.index(&[index.into()])
     }
+
+    fn index_put(&self, index: T, values: &Tensor, accumulate: bool) -> Result<Tensor, Error> {
+        self.put(&[index.into()], values, accumulate)
+    }
 }
 
 macro_rules! index_op_tuple {
@@ -156,6 +699,15 @@ macro_rules! index_op_tuple {
             fn i(&self, ($($t,)*): ($($t,)*)) -> Result<Tensor, Error> {
                 self.index(&[$($t.into(),)*])
             }
+
+            fn index_put(
+                &self,
+                ($($t,)*): ($($t,)*),
+                values: &Tensor,
+                accumulate: bool,
+            ) -> Result<Tensor, Error> {
+                self.put(&[$($t.into(),)*], values, accumulate)
+            }
         }
     };
 }
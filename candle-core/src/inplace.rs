@@ -0,0 +1,119 @@
+//! In-place operations on tensors.
+//!
+//! These ops write their result to the storage of the tensor rather than allocating a new one,
+//! this requires the storage to be uniquely owned: tensors whose storage is shared with a view,
+//! e.g. the result of `reshape`, `narrow` or `detach`, cannot be modified in place. The clones of
+//! a tensor are the same tensor and see the update. Each in-place op increments the version of
+//! the storage, see [`Tensor::version`], and the backward pass fails if a tensor that it needs
+//! has been modified since it was recorded.
+use crate::op::{self, BinaryOpT};
+use crate::{Error, Layout, Result, Storage, Tensor};
+
+macro_rules! binary_op_inplace {
+    ($fn_name:ident, $op_name:ident, $doc:literal) => {
+        #[doc = $doc]
+        ///
+        /// `rhs` gets broadcasted to the shape of `self`.
+        pub fn $fn_name(&self, rhs: &Self) -> Result<()> {
+            self.binary_inplace::<op::$op_name>(rhs, stringify!($fn_name))
+        }
+    };
+}
+
+impl Tensor {
+    // Applies `f` to the storage of this tensor and bumps its version. Tensors that are the
+    // result of a tracked op cannot be modified as the op would not match their content anymore,
+    // variables can be modified as long as the graphs that use them are not backpropagated.
+    fn inplace_op<F>(&self, op: &'static str, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Storage, &Layout) -> Result<()>,
+    {
+        if self.op().is_some() {
+            crate::bail!(
+                "{op}: cannot modify a tensor that is part of a backward graph, use copy first"
+            )
+        }
+        if !self.is_contiguous() {
+            Err(Error::RequiresContiguous { op }.bt())?
+        }
+        // The sharing is checked while holding the write lock, views created after this point
+        // can only read the storage once the op is done.
+        let (mut storage, layout) = self.storage_mut_and_layout();
+        if self.storage_is_shared() {
+            crate::bail!(
+                "{op}: cannot modify a tensor that shares its storage with other tensors, use copy first"
+            )
+        }
+        f(&mut storage, layout)?;
+        self.bump_version();
+        Ok(())
+    }
+
+    fn binary_inplace<B: BinaryOpT>(&self, rhs: &Self, op: &'static str) -> Result<()> {
+        // The storage of self gets locked for writing so rhs cannot read from it, the broadcasted
+        // view is dropped so that it does not count as sharing the storage.
+        let rhs = {
+            let rhs = rhs.broadcast_as(self.shape())?;
+            if self.same_storage(&rhs) {
+                rhs.copy()?
+            } else {
+                rhs
+            }
+        };
+        let (rhs_storage, rhs_layout) = rhs.storage_and_layout();
+        self.inplace_op(op, |storage, layout| {
+            storage.binary_impl_inplace::<B>(&rhs_storage, layout, rhs_layout)
+        })
+    }
+
+    binary_op_inplace!(add_, Add, "In-place version of [`Tensor::broadcast_add`].");
+    binary_op_inplace!(sub_, Sub, "In-place version of [`Tensor::broadcast_sub`].");
+    binary_op_inplace!(mul_, Mul, "In-place version of [`Tensor::broadcast_mul`].");
+    binary_op_inplace!(div_, Div, "In-place version of [`Tensor::broadcast_div`].");
+
+    /// In-place version of [`Tensor::affine`], every element `x` is replaced by `x * mul + add`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// t.affine_(2., 1.)?;
+    /// assert_eq!(t.to_vec1::<f32>()?, [3., 5., 7.]);
+    /// assert_eq!(t.version(), 1);
+    /// // Tensors that share their storage with a view cannot be modified in place.
+    /// let _view = t.reshape((3, 1))?;
+    /// assert!(t.affine_(2., 1.).is_err());
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn affine_(&self, mul: f64, add: f64) -> Result<()> {
+        self.inplace_op("affine_", |storage, layout| {
+            storage.affine_inplace(layout, mul, add)
+        })
+    }
+
+    /// Sets all the elements of the tensor to `value`.
+    pub fn fill_(&self, value: f64) -> Result<()> {
+        self.inplace_op("fill_", |storage, layout| {
+            storage.fill_inplace(layout, value)
+        })
+    }
+
+    /// Copies the values of `src` to this tensor. `src` is converted to the dtype and device of
+    /// this tensor and gets broadcasted to its shape.
+    pub fn copy_from(&self, src: &Self) -> Result<()> {
+        let src = {
+            let src = src
+                .to_dtype(self.dtype())?
+                .to_device(self.device())?
+                .broadcast_as(self.shape())?;
+            if self.same_storage(&src) {
+                src.copy()?
+            } else {
+                src
+            }
+        };
+        let (src_storage, src_layout) = src.storage_and_layout();
+        self.inplace_op("copy_from", |storage, layout| {
+            src_storage.copy_strided_src(storage, layout.start_offset(), src_layout)
+        })
+    }
+}
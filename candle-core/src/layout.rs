@@ -93,7 +93,7 @@ impl Layout {
         let mut dims = dims.to_vec();
         dims[dim] = len;
         Ok(Self {
-            shape: Shape::from(dims),
+            shape: self.shape.resized(dims),
             stride: self.stride.clone(),
             start_offset: self.start_offset + self.stride[dim] * start,
         })
@@ -111,10 +111,12 @@ impl Layout {
         }
         let mut stride = self.stride().to_vec();
         let mut dims = self.shape().dims().to_vec();
+        let mut src = (0..rank).map(Some).collect::<Vec<_>>();
         dims.swap(dim1, dim2);
         stride.swap(dim1, dim2);
+        src.swap(dim1, dim2);
         Ok(Self {
-            shape: Shape::from(dims),
+            shape: self.shape.rearranged(dims, src),
             stride,
             start_offset: self.start_offset,
         })
@@ -139,12 +141,92 @@ impl Layout {
             perm_dims[i] = dims[idx];
         }
         Ok(Self {
-            shape: Shape::from(perm_dims),
+            shape: self
+                .shape
+                .rearranged(perm_dims, idxs.iter().map(|&i| Some(i))),
             stride: perm_stride,
             start_offset: self.start_offset,
         })
     }
 
+    /// Replaces the dimension `dim` by the number of windows of length `size` spaced by `step`
+    /// elements, and appends a new dimension of size `size` to index the elements of a window.
+    /// The windows can overlap.
+    pub(crate) fn unfold(&self, dim: usize, size: usize, step: usize) -> Result<Self> {
+        let dims = self.dims();
+        if dim >= dims.len() {
+            Err(Error::DimOutOfRange {
+                shape: self.shape().clone(),
+                dim: dim as i32,
+                op: "unfold",
+            }
+            .bt())?
+        }
+        if size > dims[dim] || step == 0 {
+            crate::bail!(
+                "unfold: invalid window size {size} or step {step} for dim {dim} of {:?}",
+                self.shape()
+            )
+        }
+        let rank = dims.len();
+        let mut dims = dims.to_vec();
+        let mut stride = self.stride.clone();
+        dims[dim] = (dims[dim] - size) / step + 1;
+        stride[dim] *= step;
+        dims.push(size);
+        stride.push(self.stride[dim]);
+        Ok(Self {
+            shape: self
+                .shape
+                .rearranged(dims, (0..rank).map(Some).chain([None])),
+            stride,
+            start_offset: self.start_offset,
+        })
+    }
+
+    /// Removes the dimensions `dim1` and `dim2` and appends a dimension for the diagonal formed
+    /// by these, `offset` selects a diagonal above the main one if positive, below if negative.
+    pub(crate) fn diagonal(&self, offset: isize, dim1: usize, dim2: usize) -> Result<Self> {
+        let rank = self.shape.rank();
+        if dim1 >= rank || dim2 >= rank || dim1 == dim2 {
+            crate::bail!(
+                "diagonal: invalid dims {dim1} and {dim2} for {:?}",
+                self.shape()
+            )
+        }
+        let (n1, n2) = (self.dims()[dim1] as isize, self.dims()[dim2] as isize);
+        let (s1, s2) = (self.stride[dim1], self.stride[dim2]);
+        let len = if offset >= 0 {
+            isize::min(n1, n2 - offset)
+        } else {
+            isize::min(n1 + offset, n2)
+        }
+        .max(0) as usize;
+        let start_offset = match (len, offset >= 0) {
+            (0, _) => self.start_offset,
+            (_, true) => self.start_offset + offset as usize * s2,
+            (_, false) => self.start_offset + offset.unsigned_abs() * s1,
+        };
+        let mut dims = vec![];
+        let mut stride = vec![];
+        let mut src = vec![];
+        for (i, (&d, &s)) in self.dims().iter().zip(self.stride.iter()).enumerate() {
+            if i != dim1 && i != dim2 {
+                dims.push(d);
+                stride.push(s);
+                src.push(Some(i));
+            }
+        }
+        dims.push(len);
+        stride.push(s1 + s2);
+        src.push(None);
+        Ok(Self {
+            shape: self.shape.rearranged(dims, src),
+            stride,
+            start_offset,
+        })
+    }
+
     pub fn broadcast_as<S: Into<Shape>>(&self, shape: S) -> Result<Self> {
         let shape = shape.into();
         if shape.rank() < self.shape().rank() {
@@ -173,6 +255,15 @@ impl Layout {
             };
             stride.push(s)
         }
+        // The broadcasted dimensions keep their names unless new names are provided.
+        let shape = if shape.names().is_none() && self.shape.names().is_some() {
+            let src = (0..added_dims)
+                .map(|_| None)
+                .chain((0..self.shape.rank()).map(Some));
+            self.shape.rearranged(shape.into_dims(), src)
+        } else {
+            shape
+        };
         Ok(Self {
             shape,
             stride,
@@ -0,0 +1,514 @@
+//! Lazy evaluation of elementwise computations.
+//!
+//! Tensor operations are usually run eagerly, each unary or binary op reading its inputs from
+//! memory and writing a new buffer. A [`LazyTensor`] instead records the graph of unary, binary
+//! and affine ops and evaluates it in a single pass when [`LazyTensor::eval`] is called. On cpu
+//! the whole chain of ops is fused in a single strided kernel that processes the output by
+//! blocks, so the intermediate values never get written to memory.
+//!
+//! ```rust
+//! use candle_core::{Device, Tensor};
+//! let xs = Tensor::new(&[[-1f32, 0., 2.], [3., -4., 0.5]], &Device::Cpu)?;
+//! // silu(xs) = xs / (1 + exp(-xs))
+//! let lazy = xs.lazy();
+//! let ys = lazy.div(&lazy.neg()?.exp()?.affine(1., 1.)?)?.eval()?;
+//! let expected = (&xs / (xs.neg()?.exp()? + 1.)?)?;
+//! assert_eq!(ys.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+//! # Ok::<(), candle_core::Error>(())
+//! ```
+//!
+//! Reductions are not fused, they evaluate their argument and act as a barrier for the fusion.
+//! When one of the inputs tracks gradients, or when the computation does not happen on a cpu
+//! device or does not use a float dtype, the recorded ops are replayed with the usual tensor ops
+//! instead.
+//!
+//! The graph nodes are the `UnaryOp` and `BinaryOp` ops from `op.rs`, plus affine transforms.
+//! The graph is not made of the `Op` nodes that tensors record for backpropagation: these nodes
+//! are only created once the op has run eagerly and only when gradients are tracked, whereas
+//! fusion has to see the graph before anything gets computed. `LazyOp` is a separate, smaller
+//! IR that only covers the ops the fused kernel can run and that owns its leaf tensors, so
+//! recording an op never allocates a buffer.
+//!
+//! Fusion is opt-in: `candle_nn::ops::silu_fused` as well as the `LayerNorm` and `RmsNorm`
+//! layers built with `with_fused(true)` use lazy tensors, the other ops stay eager.
+use crate::op::{BinaryOp, BinaryOpT, UnaryOp, UnaryOpT};
+use crate::shape::Dim;
+use crate::{CpuStorage, DType, Device, Error, Layout, Result, Shape, Storage, Tensor, WithDType};
+use half::{bf16, f16};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Number of output elements processed at once by the fused kernel, the intermediate values for
+// a block stay in the cache.
+const BLOCK_SIZE: usize = 1024;
+
+enum LazyOp {
+    Tensor(Tensor),
+    Unary(LazyTensor, UnaryOp),
+    Binary(LazyTensor, LazyTensor, BinaryOp),
+    Affine { arg: LazyTensor, mul: f64, add: f64 },
+}
+
+struct LazyTensor_ {
+    op: LazyOp,
+    shape: Shape,
+    dtype: DType,
+    device: Device,
+}
+
+/// A tensor whose value is described by a graph of elementwise ops, see the
+/// [module level documentation](crate::lazy).
+#[derive(Clone)]
+pub struct LazyTensor(Arc<LazyTensor_>);
+
+macro_rules! unary_op {
+    ($fn_name:ident, $op_name:ident) => {
+        pub fn $fn_name(&self) -> Result<Self> {
+            self.unary(UnaryOp::$op_name)
+        }
+    };
+}
+
+macro_rules! binary_op {
+    ($fn_name:ident, $broadcast_fn_name:ident, $op_name:ident) => {
+        pub fn $fn_name(&self, rhs: &Self) -> Result<Self> {
+            self.binary(rhs, BinaryOp::$op_name, false)
+        }
+
+        pub fn $broadcast_fn_name(&self, rhs: &Self) -> Result<Self> {
+            self.binary(rhs, BinaryOp::$op_name, true)
+        }
+    };
+}
+
+impl From<Tensor> for LazyTensor {
+    fn from(t: Tensor) -> Self {
+        let t_ = LazyTensor_ {
+            shape: t.shape().clone(),
+            dtype: t.dtype(),
+            device: t.device().clone(),
+            op: LazyOp::Tensor(t),
+        };
+        Self(Arc::new(t_))
+    }
+}
+
+impl Tensor {
+    /// Returns a lazy tensor with the same value as this tensor, the ops applied to the result
+    /// are recorded and only get evaluated on [`LazyTensor::eval`].
+    pub fn lazy(&self) -> LazyTensor {
+        LazyTensor::from(self.clone())
+    }
+}
+
+impl LazyTensor {
+    fn new(op: LazyOp, shape: Shape, dtype: DType, device: Device) -> Self {
+        let t_ = LazyTensor_ {
+            op,
+            shape,
+            dtype,
+            device,
+        };
+        Self(Arc::new(t_))
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.0.shape
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        self.0.shape.dims()
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.0.dtype
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.0.device
+    }
+
+    fn unary(&self, op: UnaryOp) -> Result<Self> {
+        if !self.dtype().is_float() {
+            Err(Error::UnsupportedDTypeForOp(self.dtype(), "lazy-unary").bt())?
+        }
+        let op = LazyOp::Unary(self.clone(), op);
+        Ok(Self::new(
+            op,
+            self.shape().clone(),
+            self.dtype(),
+            self.device().clone(),
+        ))
+    }
+
+    fn binary(&self, rhs: &Self, op: BinaryOp, broadcast: bool) -> Result<Self> {
+        let op_name = "lazy-binary";
+        if self.dtype() != rhs.dtype() {
+            Err(Error::DTypeMismatchBinaryOp {
+                lhs: self.dtype(),
+                rhs: rhs.dtype(),
+                op: op_name,
+            }
+            .bt())?
+        }
+        if !self.device().same_device(rhs.device()) {
+            Err(Error::DeviceMismatchBinaryOp {
+                lhs: self.device().location(),
+                rhs: rhs.device().location(),
+                op: op_name,
+            }
+            .bt())?
+        }
+        let shape = if broadcast {
+            self.shape()
+                .broadcast_shape_binary_op(rhs.shape(), op_name)?
+        } else if self.shape() != rhs.shape() {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: rhs.shape().clone(),
+                op: op_name,
+            }
+            .bt())?
+        } else {
+            self.shape().clone()
+        };
+        let op = LazyOp::Binary(self.clone(), rhs.clone(), op);
+        Ok(Self::new(op, shape, self.dtype(), self.device().clone()))
+    }
+
+    binary_op!(add, broadcast_add, Add);
+    binary_op!(sub, broadcast_sub, Sub);
+    binary_op!(mul, broadcast_mul, Mul);
+    binary_op!(div, broadcast_div, Div);
+    binary_op!(maximum, broadcast_maximum, Maximum);
+    binary_op!(minimum, broadcast_minimum, Minimum);
+
+    unary_op!(recip, Recip);
+    unary_op!(neg, Neg);
+    unary_op!(exp, Exp);
+    unary_op!(log, Log);
+    unary_op!(sin, Sin);
+    unary_op!(cos, Cos);
+    unary_op!(tanh, Tanh);
+    unary_op!(abs, Abs);
+    unary_op!(sqr, Sqr);
+    unary_op!(sqrt, Sqrt);
+    unary_op!(gelu, Gelu);
+    unary_op!(gelu_erf, GeluErf);
+    unary_op!(erf, Erf);
+    unary_op!(relu, Relu);
+    unary_op!(ceil, Ceil);
+    unary_op!(floor, Floor);
+    unary_op!(round, Round);
+
+    /// Applies `x * mul + add` elementwise.
+    pub fn affine(&self, mul: f64, add: f64) -> Result<Self> {
+        let op = LazyOp::Affine {
+            arg: self.clone(),
+            mul,
+            add,
+        };
+        Ok(Self::new(
+            op,
+            self.shape().clone(),
+            self.dtype(),
+            self.device().clone(),
+        ))
+    }
+
+    /// Sums the values over the specified dimension, the argument is evaluated first.
+    pub fn sum_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
+        Ok(self.eval()?.sum_keepdim(dim)?.lazy())
+    }
+
+    /// Averages the values over the specified dimension, the argument is evaluated first.
+    pub fn mean_keepdim<D: Dim>(&self, dim: D) -> Result<Self> {
+        Ok(self.eval()?.mean_keepdim(dim)?.lazy())
+    }
+
+    /// Evaluates the recorded ops and returns the resulting tensor.
+    pub fn eval(&self) -> Result<Tensor> {
+        let mut program = Program::default();
+        let out = program.push(self);
+        let fused = self.device().is_cpu() && self.dtype().is_float();
+        if !fused || program.leaves.iter().any(|t| t.track_op()) {
+            return self.eval_eager(&mut HashMap::new());
+        }
+        let out_reg = match out {
+            Reg::Leaf(idx) => return Ok(program.leaves[idx].clone()),
+            Reg::Instr(idx) => idx,
+        };
+        let (device, shape, dtype) = (self.device(), self.shape(), self.dtype());
+        let storage = device.install(|| match dtype {
+            DType::BF16 => program.run::<bf16>(shape, out_reg),
+            DType::F16 => program.run::<f16>(shape, out_reg),
+            DType::F32 => program.run::<f32>(shape, out_reg),
+            DType::F64 => program.run::<f64>(shape, out_reg),
+            dtype => Err(Error::UnsupportedDTypeForOp(dtype, "lazy-eval").bt()),
+        })?;
+        Ok(crate::tensor::from_storage(
+            device.cpu_storage(storage),
+            self.shape().clone(),
+            crate::op::BackpropOp::none(),
+            false,
+        ))
+    }
+
+    // Replays the recorded ops using the tensor api, shared sub-expressions are only computed
+    // once.
+    fn eval_eager(&self, cache: &mut HashMap<*const LazyTensor_, Tensor>) -> Result<Tensor> {
+        let key = Arc::as_ptr(&self.0);
+        if let Some(t) = cache.get(&key) {
+            return Ok(t.clone());
+        }
+        let t = match &self.0.op {
+            LazyOp::Tensor(t) => t.clone(),
+            LazyOp::Affine { arg, mul, add } => arg.eval_eager(cache)?.affine(*mul, *add)?,
+            LazyOp::Unary(arg, op) => {
+                let arg = arg.eval_eager(cache)?;
+                match op {
+                    UnaryOp::Exp => arg.exp()?,
+                    UnaryOp::Log => arg.log()?,
+                    UnaryOp::Sin => arg.sin()?,
+                    UnaryOp::Cos => arg.cos()?,
+                    UnaryOp::Abs => arg.abs()?,
+                    UnaryOp::Neg => arg.neg()?,
+                    UnaryOp::Recip => arg.recip()?,
+                    UnaryOp::Sqr => arg.sqr()?,
+                    UnaryOp::Sqrt => arg.sqrt()?,
+                    UnaryOp::Gelu => arg.gelu()?,
+                    UnaryOp::GeluErf => arg.gelu_erf()?,
+                    UnaryOp::Erf => arg.erf()?,
+                    UnaryOp::Relu => arg.relu()?,
+                    UnaryOp::Tanh => arg.tanh()?,
+                    UnaryOp::Floor => arg.floor()?,
+                    UnaryOp::Ceil => arg.ceil()?,
+                    UnaryOp::Round => arg.round()?,
+                }
+            }
+            LazyOp::Binary(lhs, rhs, op) => {
+                let lhs = lhs.eval_eager(cache)?;
+                let rhs = rhs.eval_eager(cache)?;
+                match op {
+                    BinaryOp::Add => lhs.broadcast_add(&rhs)?,
+                    BinaryOp::Sub => lhs.broadcast_sub(&rhs)?,
+                    BinaryOp::Mul => lhs.broadcast_mul(&rhs)?,
+                    BinaryOp::Div => lhs.broadcast_div(&rhs)?,
+                    BinaryOp::Maximum => lhs.broadcast_maximum(&rhs)?,
+                    BinaryOp::Minimum => lhs.broadcast_minimum(&rhs)?,
+                }
+            }
+        };
+        cache.insert(key, t.clone());
+        Ok(t)
+    }
+}
+
+impl std::fmt::Debug for LazyTensor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "LazyTensor[{:?}, {:?}]", self.dims(), self.dtype())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reg {
+    Leaf(usize),
+    Instr(usize),
+}
+
+enum Instr {
+    Unary(UnaryOp, Reg),
+    Binary(BinaryOp, Reg, Reg),
+    Affine(Reg, f64, f64),
+}
+
+// The linearized form of a lazy tensor graph, each instruction writes to its own register.
+#[derive(Default)]
+struct Program {
+    leaves: Vec<Tensor>,
+    instrs: Vec<Instr>,
+    // Keyed by the address of the nodes so that the program can be sent to a thread pool.
+    regs: HashMap<usize, Reg>,
+}
+
+impl Program {
+    fn push(&mut self, t: &LazyTensor) -> Reg {
+        let key = Arc::as_ptr(&t.0) as usize;
+        if let Some(reg) = self.regs.get(&key) {
+            return *reg;
+        }
+        let instr = match &t.0.op {
+            LazyOp::Tensor(t) => {
+                self.leaves.push(t.clone());
+                let reg = Reg::Leaf(self.leaves.len() - 1);
+                self.regs.insert(key, reg);
+                return reg;
+            }
+            LazyOp::Unary(arg, op) => Instr::Unary(*op, self.push(arg)),
+            LazyOp::Binary(lhs, rhs, op) => Instr::Binary(*op, self.push(lhs), self.push(rhs)),
+            LazyOp::Affine { arg, mul, add } => Instr::Affine(self.push(arg), *mul, *add),
+        };
+        self.instrs.push(instr);
+        let reg = Reg::Instr(self.instrs.len() - 1);
+        self.regs.insert(key, reg);
+        reg
+    }
+
+    fn run<T: FusedElem>(&self, shape: &Shape, out_reg: usize) -> Result<CpuStorage> {
+        let storages: Vec<_> = self.leaves.iter().map(|t| t.storage()).collect();
+        let mut srcs = Vec::with_capacity(self.leaves.len());
+        for (leaf, storage) in self.leaves.iter().zip(storages.iter()) {
+            let src = match &**storage {
+                Storage::Cpu(storage) | Storage::CpuPool(storage, _) => {
+                    T::cpu_storage_as_slice(storage)?
+                }
+                _ => crate::bail!("lazy-eval: unexpected non-cpu storage"),
+            };
+            srcs.push((src, leaf.layout().broadcast_as(shape)?));
+        }
+        let instrs = &self.instrs;
+        let n_leaves = srcs.len();
+        let mut dst = vec![T::zero(); shape.elem_count()];
+        // Each worker gets a scratch buffer with one block sized register per leaf and per
+        // instruction, this buffer is reused for all the blocks processed by the worker.
+        let n_regs = n_leaves + instrs.len();
+        dst.par_chunks_mut(BLOCK_SIZE).enumerate().for_each_init(
+            || (vec![T::zero(); n_regs * BLOCK_SIZE], Vec::new()),
+            |(scratch, index), (block_idx, dst)| {
+                let len = dst.len();
+                for (leaf_idx, (src, layout)) in srcs.iter().enumerate() {
+                    let leaf = &mut scratch[leaf_idx * BLOCK_SIZE..leaf_idx * BLOCK_SIZE + len];
+                    load(src, layout, block_idx * BLOCK_SIZE, leaf, index)
+                }
+                for (instr_idx, instr) in instrs.iter().enumerate() {
+                    // Instructions only read registers that have been written before, i.e. that
+                    // come before their own register in the scratch buffer.
+                    let (prev, cur) = scratch.split_at_mut((n_leaves + instr_idx) * BLOCK_SIZE);
+                    let ys = &mut cur[..len];
+                    let get = |reg: Reg| {
+                        let idx = match reg {
+                            Reg::Leaf(idx) => idx,
+                            Reg::Instr(idx) => n_leaves + idx,
+                        };
+                        &prev[idx * BLOCK_SIZE..idx * BLOCK_SIZE + len]
+                    };
+                    match instr {
+                        Instr::Unary(op, arg) => T::unary(*op, get(*arg), ys),
+                        Instr::Binary(op, lhs, rhs) => T::binary(*op, get(*lhs), get(*rhs), ys),
+                        Instr::Affine(arg, mul, add) => {
+                            let (mul, add) = (T::from_f64(*mul), T::from_f64(*add));
+                            for (y, &x) in ys.iter_mut().zip(get(*arg).iter()) {
+                                *y = x * mul + add
+                            }
+                        }
+                    }
+                }
+                let out = (n_leaves + out_reg) * BLOCK_SIZE;
+                dst.copy_from_slice(&scratch[out..out + len])
+            },
+        );
+        Ok(T::to_cpu_storage_owned(dst))
+    }
+}
+
+// Reads the elements `start..start + dst.len()` of the tensor with the given layout, `index` is
+// used as scratch space for the multi-dimensional index.
+fn load<T: Copy>(src: &[T], layout: &Layout, start: usize, dst: &mut [T], index: &mut Vec<usize>) {
+    if let Some((o1, _)) = layout.contiguous_offsets() {
+        dst.copy_from_slice(&src[o1 + start..o1 + start + dst.len()]);
+        return;
+    }
+    let dims = layout.dims();
+    let stride = layout.stride();
+    index.clear();
+    index.resize(dims.len(), 0);
+    let mut rem = start;
+    let mut offset = layout.start_offset();
+    for d in (0..dims.len()).rev() {
+        index[d] = rem % dims[d];
+        rem /= dims[d];
+        offset += index[d] * stride[d];
+    }
+    for v in dst.iter_mut() {
+        *v = src[offset];
+        for d in (0..dims.len()).rev() {
+            index[d] += 1;
+            offset += stride[d];
+            if index[d] < dims[d] {
+                break;
+            }
+            offset -= index[d] * stride[d];
+            index[d] = 0;
+        }
+    }
+}
+
+trait FusedElem: WithDType {
+    fn unary_op<O: UnaryOpT>(v: Self) -> Self;
+    fn binary_op<O: BinaryOpT>(v1: Self, v2: Self) -> Self;
+
+    fn unary(op: UnaryOp, xs: &[Self], ys: &mut [Self]) {
+        fn map<T: FusedElem, O: UnaryOpT>(xs: &[T], ys: &mut [T]) {
+            for (y, &x) in ys.iter_mut().zip(xs.iter()) {
+                *y = T::unary_op::<O>(x)
+            }
+        }
+        use crate::op as o;
+        match op {
+            UnaryOp::Exp => map::<Self, o::Exp>(xs, ys),
+            UnaryOp::Log => map::<Self, o::Log>(xs, ys),
+            UnaryOp::Sin => map::<Self, o::Sin>(xs, ys),
+            UnaryOp::Cos => map::<Self, o::Cos>(xs, ys),
+            UnaryOp::Abs => map::<Self, o::Abs>(xs, ys),
+            UnaryOp::Neg => map::<Self, o::Neg>(xs, ys),
+            UnaryOp::Recip => map::<Self, o::Recip>(xs, ys),
+            UnaryOp::Sqr => map::<Self, o::Sqr>(xs, ys),
+            UnaryOp::Sqrt => map::<Self, o::Sqrt>(xs, ys),
+            UnaryOp::Gelu => map::<Self, o::Gelu>(xs, ys),
+            UnaryOp::GeluErf => map::<Self, o::GeluErf>(xs, ys),
+            UnaryOp::Erf => map::<Self, o::Erf>(xs, ys),
+            UnaryOp::Relu => map::<Self, o::Relu>(xs, ys),
+            UnaryOp::Tanh => map::<Self, o::Tanh>(xs, ys),
+            UnaryOp::Floor => map::<Self, o::Floor>(xs, ys),
+            UnaryOp::Ceil => map::<Self, o::Ceil>(xs, ys),
+            UnaryOp::Round => map::<Self, o::Round>(xs, ys),
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: &[Self], rhs: &[Self], ys: &mut [Self]) {
+        fn map<T: FusedElem, O: BinaryOpT>(lhs: &[T], rhs: &[T], ys: &mut [T]) {
+            for ((y, &l), &r) in ys.iter_mut().zip(lhs.iter()).zip(rhs.iter()) {
+                *y = T::binary_op::<O>(l, r)
+            }
+        }
+        use crate::op as o;
+        match op {
+            BinaryOp::Add => map::<Self, o::Add>(lhs, rhs, ys),
+            BinaryOp::Sub => map::<Self, o::Sub>(lhs, rhs, ys),
+            BinaryOp::Mul => map::<Self, o::Mul>(lhs, rhs, ys),
+            BinaryOp::Div => map::<Self, o::Div>(lhs, rhs, ys),
+            BinaryOp::Maximum => map::<Self, o::Maximum>(lhs, rhs, ys),
+            BinaryOp::Minimum => map::<Self, o::Minimum>(lhs, rhs, ys),
+        }
+    }
+}
+
+macro_rules! fused_elem {
+    ($ty:ty, $fn_name:ident) => {
+        impl FusedElem for $ty {
+            fn unary_op<O: UnaryOpT>(v: Self) -> Self {
+                O::$fn_name(v)
+            }
+
+            fn binary_op<O: BinaryOpT>(v1: Self, v2: Self) -> Self {
+                O::$fn_name(v1, v2)
+            }
+        }
+    };
+}
+
+fused_elem!(bf16, bf16);
+fused_elem!(f16, f16);
+fused_elem!(f32, f32);
+fused_elem!(f64, f64);
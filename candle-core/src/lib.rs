@@ -35,6 +35,7 @@
 
 #[cfg(feature = "accelerate")]
 mod accelerate;
+pub mod autograd;
 pub mod backend;
 pub mod backprop;
 mod conv;
@@ -50,9 +51,15 @@ pub mod display;
 mod dtype;
 mod dummy_cuda_backend;
 mod dummy_metal_backend;
+mod einsum;
 pub mod error;
+mod fft;
+mod grid_sample;
 mod indexer;
+mod inplace;
 pub mod layout;
+pub mod lazy;
+pub mod memory;
 #[cfg(feature = "metal")]
 pub mod metal_backend;
 #[cfg(feature = "mkl")]
@@ -60,10 +67,13 @@ mod mkl;
 pub mod npy;
 mod op;
 pub mod pickle;
+pub mod profiler;
 pub mod quantized;
+mod resize;
 pub mod safetensors;
 pub mod scalar;
 pub mod shape;
+mod sparse;
 mod storage;
 mod strided_index;
 mod tensor;
@@ -71,17 +81,23 @@ pub mod test_utils;
 pub mod utils;
 mod variable;
 
-pub use cpu_backend::CpuStorage;
+pub use backprop::checkpoint;
+pub use cpu_backend::{CpuDeviceConfig, CpuStorage};
 pub use device::{Device, DeviceLocation};
-pub use dtype::{DType, FloatDType, IntDType, WithDType};
+pub use dtype::{DType, ElemType, FloatDType, IntDType, WithDType};
 pub use error::{Error, Result};
-pub use indexer::IndexOp;
+pub use grid_sample::{GridPaddingMode, GridSampleMode};
+pub use indexer::{Ellipsis, Idx, IndexOp, NewAxis, Slice};
 pub use layout::Layout;
+pub use num_complex::Complex;
 pub use op::{CustomOp1, CustomOp2, CustomOp3};
-pub use shape::{Shape, D};
+pub use resize::ResizeMode;
+pub use shape::{DimName, DimNames, Shape, D};
+pub use sparse::{SparseIndices, SparseTensor};
 pub use storage::Storage;
 pub use strided_index::{StridedBlocks, StridedIndex};
-pub use tensor::{Tensor, TensorId};
+pub use tensor::{PadMode, Tensor, TensorId};
+pub use utils::{is_deterministic, set_deterministic};
 pub use variable::Var;
 
 #[cfg(feature = "cuda")]
@@ -118,6 +134,22 @@ impl ToUsize2 for (usize, usize) {
     }
 }
 
+pub trait ToUsize3 {
+    fn to_usize3(self) -> (usize, usize, usize);
+}
+
+impl ToUsize3 for usize {
+    fn to_usize3(self) -> (usize, usize, usize) {
+        (self, self, self)
+    }
+}
+
+impl ToUsize3 for (usize, usize, usize) {
+    fn to_usize3(self) -> (usize, usize, usize) {
+        self
+    }
+}
+
 // A simple trait defining a module with forward method using a single argument.
 pub trait Module {
     fn forward(&self, xs: &Tensor) -> Result<Tensor>;
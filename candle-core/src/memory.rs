@@ -0,0 +1,721 @@
+//! Memory usage tracking.
+//!
+//! Every tensor storage allocated through the cpu, cuda or metal backends is accounted for in
+//! per-device statistics, see [`crate::Device::memory_stats`]. On cpu, the buffers allocated by
+//! the ops are accounted for from their allocation, including the intermediate buffers that never
+//! end up in a tensor. The statistics are maintained with
+//! atomic counters, no lock is taken when allocating or releasing a storage. A [`Profiler`] can
+//! also be used to attribute the allocations made while it is alive to the ops that triggered
+//! them.
+//!
+//! The buffers of cpu storages can optionally be recycled through a caching allocator, see
+//! [`set_cpu_caching`]. When enabled, the buffers of the dropped storages are kept in size
+//! buckets and reused by the following ops rather than being released. There is a single cache
+//! for the whole process, it is shared by all the cpu tensors and holds at most
+//! [`set_cpu_cache_limit`] bytes.
+//!
+//! ```rust
+//! use candle_core::{memory::Profiler, DType, Device, Tensor};
+//! let device = Device::Cpu;
+//! let profiler = Profiler::start();
+//! let xs = Tensor::zeros((16, 16), DType::F32, &device)?;
+//! let ys = xs.matmul(&xs)?.exp()?;
+//! let report = profiler.report();
+//! assert!(report.iter().any(|r| r.op == "matmul" && r.bytes == 16 * 16 * 4));
+//! assert!(device.memory_stats().live_bytes >= 2 * 16 * 16 * 4);
+//! # Ok::<(), candle_core::Error>(())
+//! ```
+use crate::backend::BackendStorage;
+use crate::{CpuStorage, DType, Storage};
+use half::{bf16, f16};
+use num_complex::Complex;
+use std::alloc::Layout;
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// Memory statistics for a single dtype.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DTypeMemoryStats {
+    /// The number of bytes currently allocated.
+    pub live_bytes: usize,
+    /// The total number of allocations.
+    pub allocations: usize,
+}
+
+/// Memory statistics for a device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The number of bytes currently allocated.
+    pub live_bytes: usize,
+    /// The maximum value reached by `live_bytes`.
+    pub peak_bytes: usize,
+    /// The number of allocations that have not been released yet.
+    pub live_allocations: usize,
+    /// The total number of allocations.
+    pub allocations: usize,
+    /// The statistics split by dtype.
+    pub by_dtype: HashMap<DType, DTypeMemoryStats>,
+}
+
+const DTYPES: [DType; 13] = [
+    DType::Bool,
+    DType::U8,
+    DType::U32,
+    DType::I8,
+    DType::I16,
+    DType::I32,
+    DType::I64,
+    DType::BF16,
+    DType::F16,
+    DType::F32,
+    DType::F64,
+    DType::C64,
+    DType::C128,
+];
+
+/// The allocation counters of a device, these are only updated with atomic operations so that
+/// allocating does not require any lock.
+#[derive(Debug)]
+pub(crate) struct MemoryCounters {
+    live_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    live_allocations: AtomicUsize,
+    allocations: AtomicUsize,
+    dtype_live_bytes: [AtomicUsize; DTYPES.len()],
+    dtype_allocations: [AtomicUsize; DTYPES.len()],
+}
+
+impl MemoryCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+            live_allocations: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+            dtype_live_bytes: [const { AtomicUsize::new(0) }; DTYPES.len()],
+            dtype_allocations: [const { AtomicUsize::new(0) }; DTYPES.len()],
+        }
+    }
+
+    fn alloc(&self, dtype: DType, bytes: usize) {
+        self.reuse(dtype, bytes);
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.dtype_allocations[dtype as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Accounts for a buffer that gets used again, e.g. after being taken from a cache, this does
+    // not count as a new allocation.
+    fn reuse(&self, dtype: DType, bytes: usize) {
+        let live_bytes = self.live_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_bytes.fetch_max(live_bytes, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.dtype_live_bytes[dtype as usize].fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn free(&self, dtype: DType, bytes: usize) {
+        self.live_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+        self.dtype_live_bytes[dtype as usize].fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// A snapshot of the counters, the values are read independently so they may be slightly
+    /// inconsistent when other threads allocate concurrently.
+    pub(crate) fn stats(&self) -> MemoryStats {
+        let by_dtype = DTYPES
+            .iter()
+            .filter_map(|&dtype| {
+                let allocations = self.dtype_allocations[dtype as usize].load(Ordering::Relaxed);
+                let live_bytes = self.dtype_live_bytes[dtype as usize].load(Ordering::Relaxed);
+                let stats = DTypeMemoryStats {
+                    live_bytes,
+                    allocations,
+                };
+                (allocations > 0).then_some((dtype, stats))
+            })
+            .collect();
+        MemoryStats {
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            by_dtype,
+        }
+    }
+}
+
+/// The counters shared by the cpu storages.
+pub(crate) fn cpu_counters() -> &'static Arc<MemoryCounters> {
+    static CPU_COUNTERS: OnceLock<Arc<MemoryCounters>> = OnceLock::new();
+    CPU_COUNTERS.get_or_init(|| Arc::new(MemoryCounters::new()))
+}
+
+/// A buffer allocation that is accounted for in the counters of its device until it gets
+/// dropped. Backends keep this next to the buffer that they allocate.
+#[derive(Debug)]
+pub(crate) struct Allocation {
+    counters: Arc<MemoryCounters>,
+    dtype: DType,
+    bytes: usize,
+}
+
+impl Allocation {
+    pub(crate) fn new(counters: &Arc<MemoryCounters>, dtype: DType, bytes: usize) -> Self {
+        counters.alloc(dtype, bytes);
+        Self {
+            counters: counters.clone(),
+            dtype,
+            bytes,
+        }
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.counters.free(self.dtype, self.bytes)
+    }
+}
+
+// The number of bytes held by the buffer of a cpu storage.
+fn cpu_buffer_bytes(storage: &CpuStorage) -> usize {
+    use std::mem::size_of;
+    match storage {
+        CpuStorage::Bool(vs) => vs.capacity() * size_of::<bool>(),
+        CpuStorage::U8(vs) => vs.capacity() * size_of::<u8>(),
+        CpuStorage::U32(vs) => vs.capacity() * size_of::<u32>(),
+        CpuStorage::I8(vs) => vs.capacity() * size_of::<i8>(),
+        CpuStorage::I16(vs) => vs.capacity() * size_of::<i16>(),
+        CpuStorage::I32(vs) => vs.capacity() * size_of::<i32>(),
+        CpuStorage::I64(vs) => vs.capacity() * size_of::<i64>(),
+        CpuStorage::BF16(vs) => vs.capacity() * size_of::<bf16>(),
+        CpuStorage::F16(vs) => vs.capacity() * size_of::<f16>(),
+        CpuStorage::F32(vs) => vs.capacity() * size_of::<f32>(),
+        CpuStorage::F64(vs) => vs.capacity() * size_of::<f64>(),
+        CpuStorage::C64(vs) => vs.capacity() * size_of::<Complex<f32>>(),
+        CpuStorage::C128(vs) => vs.capacity() * size_of::<Complex<f64>>(),
+    }
+}
+
+/// A tensor storage together with a version counter that gets incremented each time the storage
+/// is modified in place.
+///
+/// Cuda and metal storages account for their buffers themselves when allocating them, as do the
+/// cpu ops that allocate their buffers through [`alloc_vec`]. The other cpu buffers, e.g. the
+/// ones provided by the user, are accounted for here once a tensor takes ownership of them.
+pub(crate) struct TrackedStorage {
+    storage: RwLock<Storage>,
+    version: AtomicUsize,
+    _allocation: Option<Allocation>,
+}
+
+impl TrackedStorage {
+    pub(crate) fn new(storage: Storage, elem_count: usize, op: Option<&'static str>) -> Self {
+        let allocation = match &storage {
+            Storage::Cpu(s) | Storage::CpuPool(s, _) if !is_tracked(s) => Some(Allocation::new(
+                cpu_counters(),
+                s.dtype(),
+                cpu_buffer_bytes(s),
+            )),
+            Storage::Cpu(_) | Storage::CpuPool(..) | Storage::Cuda(_) | Storage::Metal(_) => None,
+        };
+        if is_profiling() {
+            record(
+                op.unwrap_or("other"),
+                elem_count * storage.dtype().size_in_bytes(),
+            )
+        }
+        Self {
+            storage: RwLock::new(storage),
+            version: AtomicUsize::new(0),
+            _allocation: allocation,
+        }
+    }
+
+    pub(crate) fn version(&self) -> usize {
+        self.version.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+impl std::ops::Deref for TrackedStorage {
+    type Target = RwLock<Storage>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+/// Statistics of the caching allocator used for cpu storages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of allocations served by a cached buffer.
+    pub hits: usize,
+    /// The number of allocations that required a new buffer while caching was enabled.
+    pub misses: usize,
+    /// The number of buffers released to stay within the cache limit.
+    pub evictions: usize,
+    /// The number of buffers currently held by the cache.
+    pub cached_buffers: usize,
+    /// The number of bytes currently held by the cache.
+    pub cached_bytes: usize,
+}
+
+impl CacheStats {
+    /// The fraction of the allocations that were served by a cached buffer.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+// Buffers are bucketed by size in bytes, the bucket sizes are powers of two so that a buffer can
+// be reused for any element type and any length that rounds up to its bucket. Smaller buffers
+// are cheap to allocate and are not cached.
+const MIN_BUCKET_BYTES: usize = 64;
+
+// The alignment of the buffers allocated while caching is enabled, this is the size of a cache
+// line and is enough for the widest simd loads.
+const BUFFER_ALIGN: usize = 64;
+
+// The maximum number of bytes held by the cache unless set with `set_cpu_cache_limit`.
+const DEFAULT_CACHE_LIMIT: usize = 1 << 30;
+
+struct Cache {
+    // The free blocks of each bucket as (release stamp, address), the most recently released
+    // blocks come last.
+    buffers: BTreeMap<usize, VecDeque<(u64, usize)>>,
+    stamp: u64,
+    limit: usize,
+    stats: CacheStats,
+}
+
+impl Cache {
+    // Releases the least recently cached blocks until `bytes` more bytes fit within the limit.
+    fn evict(&mut self, bytes: usize) {
+        while self.stats.cached_bytes + bytes > self.limit {
+            let oldest = self
+                .buffers
+                .iter()
+                .filter_map(|(&bucket, blocks)| Some((blocks.front()?.0, bucket)))
+                .min();
+            let Some((_, bucket)) = oldest else { break };
+            if let Some((_, addr)) = self.buffers.get_mut(&bucket).and_then(|b| b.pop_front()) {
+                dealloc_block(addr, bucket);
+                self.stats.cached_buffers -= 1;
+                self.stats.cached_bytes -= bucket;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+}
+
+static CACHING: AtomicBool = AtomicBool::new(false);
+static CACHE: Mutex<Cache> = Mutex::new(Cache {
+    buffers: BTreeMap::new(),
+    stamp: 0,
+    limit: DEFAULT_CACHE_LIMIT,
+    stats: CacheStats {
+        hits: 0,
+        misses: 0,
+        evictions: 0,
+        cached_buffers: 0,
+        cached_bytes: 0,
+    },
+});
+
+fn is_caching() -> bool {
+    CACHING.load(Ordering::Relaxed)
+}
+
+/// Enables or disables the caching allocator used for the cpu storages, disabling it releases
+/// the cached buffers. The setting applies to the whole process and the cache is only locked
+/// when it is enabled. The buffers allocated while caching is enabled are aligned on 64 bytes.
+///
+/// ```rust
+/// use candle_core::{memory, DType, Device, Tensor};
+/// memory::set_cpu_caching(true);
+/// for _ in 0..4 {
+///     let xs = Tensor::ones((32, 32), DType::F32, &Device::Cpu)?;
+///     let _ys = xs.exp()?;
+/// }
+/// assert!(memory::cpu_cache_stats().hits > 0);
+/// memory::set_cpu_caching(false);
+/// # Ok::<(), candle_core::Error>(())
+/// ```
+pub fn set_cpu_caching(enabled: bool) {
+    CACHING.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        empty_cpu_cache()
+    }
+}
+
+/// Sets the maximum number of bytes held by the cpu caching allocator, 1GB by default. When
+/// releasing a buffer would exceed this limit, the least recently cached buffers are released
+/// first.
+pub fn set_cpu_cache_limit(bytes: usize) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.limit = bytes;
+    cache.evict(0)
+}
+
+/// Releases the buffers held by the cpu caching allocator.
+pub fn empty_cpu_cache() {
+    let mut cache = CACHE.lock().unwrap();
+    for (bucket, blocks) in std::mem::take(&mut cache.buffers) {
+        for (_, addr) in blocks {
+            dealloc_block(addr, bucket)
+        }
+    }
+    cache.stats.cached_buffers = 0;
+    cache.stats.cached_bytes = 0;
+}
+
+/// Returns the statistics of the cpu caching allocator, the number of cache hits and misses are
+/// counted since the start of the process.
+pub fn cpu_cache_stats() -> CacheStats {
+    CACHE.lock().unwrap().stats.clone()
+}
+
+fn block_layout(bytes: usize) -> Layout {
+    Layout::from_size_align(bytes, BUFFER_ALIGN).expect("capacity overflow")
+}
+
+fn alloc_block(bytes: usize) -> usize {
+    let layout = block_layout(bytes);
+    // SAFETY: blocks are at least MIN_BUCKET_BYTES long so the layout is not zero sized.
+    let ptr = unsafe { std::alloc::alloc(layout) };
+    if ptr.is_null() {
+        std::alloc::handle_alloc_error(layout)
+    }
+    ptr as usize
+}
+
+fn dealloc_block(addr: usize, bytes: usize) {
+    // SAFETY: the block was allocated by `alloc_block` with the same size.
+    unsafe { std::alloc::dealloc(addr as *mut u8, block_layout(bytes)) }
+}
+
+// The buffers allocated by `alloc_vec` that are currently used by a vector, indexed by address.
+// These buffers are accounted for until the storage that holds them gives them back through
+// `release`. The blocks allocated while caching is enabled are never freed by `Vec` as their
+// alignment differs from the one of their elements. The registry is sharded to limit the
+// contention between threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    bytes: usize,
+    dtype: DType,
+    aligned: bool,
+}
+
+const NUM_SHARDS: usize = 16;
+static LIVE_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+static BLOCKS: [Mutex<BTreeMap<usize, Block>>; NUM_SHARDS] =
+    [const { Mutex::new(BTreeMap::new()) }; NUM_SHARDS];
+
+fn blocks(addr: usize) -> std::sync::MutexGuard<'static, BTreeMap<usize, Block>> {
+    BLOCKS[(addr / BUFFER_ALIGN) % NUM_SHARDS].lock().unwrap()
+}
+
+fn register<T>(vs: &[T], block: Block) {
+    let addr = vs.as_ptr() as usize;
+    if let Some(stale) = blocks(addr).insert(addr, block) {
+        // The vector that held this address was freed without being released.
+        cpu_counters().free(stale.dtype, stale.bytes);
+        LIVE_BLOCKS.fetch_sub(1, Ordering::Relaxed);
+    }
+    LIVE_BLOCKS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn lookup<T>(vs: &Vec<T>) -> Option<Block> {
+    let bytes = vs.capacity() * std::mem::size_of::<T>();
+    if LIVE_BLOCKS.load(Ordering::Relaxed) == 0 || bytes == 0 {
+        return None;
+    }
+    let addr = vs.as_ptr() as usize;
+    blocks(addr)
+        .get(&addr)
+        .copied()
+        .filter(|b| b.bytes == bytes)
+}
+
+// The dtype of the storages with `T` elements.
+fn dtype_of<T: 'static>() -> Option<DType> {
+    let type_id = TypeId::of::<T>();
+    let elem_type_id = |dtype: DType| match dtype {
+        DType::Bool => TypeId::of::<bool>(),
+        DType::U8 => TypeId::of::<u8>(),
+        DType::U32 => TypeId::of::<u32>(),
+        DType::I8 => TypeId::of::<i8>(),
+        DType::I16 => TypeId::of::<i16>(),
+        DType::I32 => TypeId::of::<i32>(),
+        DType::I64 => TypeId::of::<i64>(),
+        DType::BF16 => TypeId::of::<bf16>(),
+        DType::F16 => TypeId::of::<f16>(),
+        DType::F32 => TypeId::of::<f32>(),
+        DType::F64 => TypeId::of::<f64>(),
+        DType::C64 => TypeId::of::<Complex<f32>>(),
+        DType::C128 => TypeId::of::<Complex<f64>>(),
+    };
+    DTYPES
+        .into_iter()
+        .find(|&dtype| elem_type_id(dtype) == type_id)
+}
+
+// Gives back the buffer of a vector allocated by `alloc_vec`, it stops being accounted for and
+// aligned blocks go back to the cache when caching is enabled. Other vectors are left untouched.
+fn release_vec<T: Copy>(vs: &mut Vec<T>) {
+    let Some(block) = lookup(vs) else { return };
+    let addr = vs.as_ptr() as usize;
+    blocks(addr).remove(&addr);
+    LIVE_BLOCKS.fetch_sub(1, Ordering::Relaxed);
+    cpu_counters().free(block.dtype, block.bytes);
+    if !block.aligned {
+        return;
+    }
+    // The elements have no destructor, the block is released with the layout it was allocated
+    // with rather than by the vector.
+    std::mem::forget(std::mem::take(vs));
+    let bytes = block.bytes;
+    if !is_caching() {
+        return dealloc_block(addr, bytes);
+    }
+    let mut cache = CACHE.lock().unwrap();
+    if bytes > cache.limit {
+        return dealloc_block(addr, bytes);
+    }
+    cache.evict(bytes);
+    cache.stamp += 1;
+    let stamp = cache.stamp;
+    cache
+        .buffers
+        .entry(bytes)
+        .or_default()
+        .push_back((stamp, addr));
+    cache.stats.cached_buffers += 1;
+    cache.stats.cached_bytes += bytes;
+}
+
+/// Gives back the buffer of a cpu storage that is being dropped, see [`alloc_vec`].
+pub(crate) fn release(storage: &mut CpuStorage) {
+    match storage {
+        CpuStorage::Bool(vs) => release_vec(vs),
+        CpuStorage::U8(vs) => release_vec(vs),
+        CpuStorage::U32(vs) => release_vec(vs),
+        CpuStorage::I8(vs) => release_vec(vs),
+        CpuStorage::I16(vs) => release_vec(vs),
+        CpuStorage::I32(vs) => release_vec(vs),
+        CpuStorage::I64(vs) => release_vec(vs),
+        CpuStorage::BF16(vs) => release_vec(vs),
+        CpuStorage::F16(vs) => release_vec(vs),
+        CpuStorage::F32(vs) => release_vec(vs),
+        CpuStorage::F64(vs) => release_vec(vs),
+        CpuStorage::C64(vs) => release_vec(vs),
+        CpuStorage::C128(vs) => release_vec(vs),
+    }
+}
+
+// Returns true if the buffer of a cpu storage is already accounted for.
+fn is_tracked(storage: &CpuStorage) -> bool {
+    match storage {
+        CpuStorage::Bool(vs) => lookup(vs).is_some(),
+        CpuStorage::U8(vs) => lookup(vs).is_some(),
+        CpuStorage::U32(vs) => lookup(vs).is_some(),
+        CpuStorage::I8(vs) => lookup(vs).is_some(),
+        CpuStorage::I16(vs) => lookup(vs).is_some(),
+        CpuStorage::I32(vs) => lookup(vs).is_some(),
+        CpuStorage::I64(vs) => lookup(vs).is_some(),
+        CpuStorage::BF16(vs) => lookup(vs).is_some(),
+        CpuStorage::F16(vs) => lookup(vs).is_some(),
+        CpuStorage::F32(vs) => lookup(vs).is_some(),
+        CpuStorage::F64(vs) => lookup(vs).is_some(),
+        CpuStorage::C64(vs) => lookup(vs).is_some(),
+        CpuStorage::C128(vs) => lookup(vs).is_some(),
+    }
+}
+
+/// Moves the elements out of the buffer of a cpu storage, buffers that come from the cache are
+/// copied so that the returned vector can be freed as a regular vector.
+pub(crate) fn take_vec<T: Copy>(vs: &mut Vec<T>) -> Vec<T> {
+    match lookup(vs) {
+        Some(block) if block.aligned => vs.to_vec(),
+        _ => {
+            release_vec(vs);
+            std::mem::take(vs)
+        }
+    }
+}
+
+/// Returns an empty vector with a capacity of at least `len` elements, the buffer is accounted
+/// for in the cpu memory statistics until the [`CpuStorage`] that holds it gets dropped. When
+/// caching is enabled the buffer is a block taken from the cache or newly allocated, it is
+/// aligned on 64 bytes. In both cases the vector must end up in a storage and must not grow
+/// beyond its capacity.
+pub(crate) fn alloc_vec<T: Copy + 'static>(len: usize) -> Vec<T> {
+    let dtype = match dtype_of::<T>() {
+        Some(dtype) if len > 0 => dtype,
+        _ => return Vec::with_capacity(len),
+    };
+    if !is_caching() {
+        return track(Vec::with_capacity(len), dtype);
+    }
+    let elem_size = std::mem::size_of::<T>();
+    let bucket = len
+        .checked_mul(elem_size)
+        .expect("capacity overflow")
+        .max(MIN_BUCKET_BYTES)
+        .next_power_of_two();
+    let cached = {
+        let mut cache = CACHE.lock().unwrap();
+        match cache.buffers.get_mut(&bucket).and_then(|b| b.pop_back()) {
+            Some((_, addr)) => {
+                cache.stats.hits += 1;
+                cache.stats.cached_buffers -= 1;
+                cache.stats.cached_bytes -= bucket;
+                Some(addr)
+            }
+            None => {
+                cache.stats.misses += 1;
+                None
+            }
+        }
+    };
+    let addr = match cached {
+        Some(addr) => {
+            cpu_counters().reuse(dtype, bucket);
+            addr
+        }
+        None => {
+            cpu_counters().alloc(dtype, bucket);
+            alloc_block(bucket)
+        }
+    };
+    // SAFETY: the block holds `bucket` bytes, a multiple of the element size as the storage
+    // element sizes are powers of two of at most 16 bytes, and is aligned on BUFFER_ALIGN which
+    // is a multiple of the element alignment. The vector is empty so no element is read before
+    // being written.
+    let vs = unsafe { Vec::from_raw_parts(addr as *mut T, 0, bucket / elem_size) };
+    let block = Block {
+        bytes: bucket,
+        dtype,
+        aligned: true,
+    };
+    register(&vs, block);
+    vs
+}
+
+// Accounts for a newly allocated vector until it gets released.
+fn track<T>(vs: Vec<T>, dtype: DType) -> Vec<T> {
+    let bytes = vs.capacity() * std::mem::size_of::<T>();
+    cpu_counters().alloc(dtype, bytes);
+    let block = Block {
+        bytes,
+        dtype,
+        aligned: false,
+    };
+    register(&vs, block);
+    vs
+}
+
+/// Returns a vector of `len` elements all set to `v`.
+pub(crate) fn filled_vec<T: Copy + 'static>(len: usize, v: T) -> Vec<T> {
+    if !is_caching() {
+        return match dtype_of::<T>() {
+            Some(dtype) if len > 0 => track(vec![v; len], dtype),
+            _ => vec![v; len],
+        };
+    }
+    let mut vs = alloc_vec(len);
+    vs.resize(len, v);
+    vs
+}
+
+/// The allocations attributed to an op by a [`Profiler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpMemoryStats {
+    /// The name of the op, `other` is used for allocations that are not the result of an op,
+    /// e.g. when creating a new tensor.
+    pub op: &'static str,
+    /// The number of allocations.
+    pub allocations: usize,
+    /// The total number of bytes allocated.
+    pub bytes: usize,
+}
+
+type Records = Arc<Mutex<HashMap<&'static str, OpMemoryStats>>>;
+
+static NUM_PROFILERS: AtomicUsize = AtomicUsize::new(0);
+static PROFILERS: Mutex<Vec<(usize, Records)>> = Mutex::new(Vec::new());
+
+/// Returns true if some memory profiler is active.
+pub(crate) fn is_profiling() -> bool {
+    NUM_PROFILERS.load(Ordering::Relaxed) > 0
+}
+
+fn record(op: &'static str, bytes: usize) {
+    for (_, records) in PROFILERS.lock().unwrap().iter() {
+        let mut records = records.lock().unwrap();
+        let stats = records.entry(op).or_insert(OpMemoryStats {
+            op,
+            allocations: 0,
+            bytes: 0,
+        });
+        stats.allocations += 1;
+        stats.bytes += bytes;
+    }
+}
+
+/// Records the allocations made from its creation until it gets dropped, grouped by the op
+/// that triggered them. Allocations made from all threads are recorded.
+pub struct Profiler {
+    id: usize,
+    records: Records,
+}
+
+impl Profiler {
+    /// Starts recording allocations.
+    pub fn start() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let records = Records::default();
+        PROFILERS.lock().unwrap().push((id, records.clone()));
+        NUM_PROFILERS.fetch_add(1, Ordering::Relaxed);
+        Self { id, records }
+    }
+
+    /// Returns the allocations recorded so far per op, the ops that allocated the most bytes
+    /// come first.
+    pub fn report(&self) -> Vec<OpMemoryStats> {
+        let mut report: Vec<_> = self.records.lock().unwrap().values().cloned().collect();
+        report.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.op.cmp(b.op)));
+        report
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        PROFILERS.lock().unwrap().retain(|(id, _)| *id != self.id);
+        NUM_PROFILERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{:<24} {:>12} {:>16}", "op", "allocations", "bytes")?;
+        for stats in self.report() {
+            writeln!(
+                f,
+                "{:<24} {:>12} {:>16}",
+                stats.op, stats.allocations, stats.bytes
+            )?
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use crate::backend::{BackendDevice, BackendStorage};
-use crate::conv::{ParamsConv1D, ParamsConv2D, ParamsConvTranspose1D, ParamsConvTranspose2D};
-use crate::op::{BinaryOpT, CmpOp, ReduceOp, UnaryOpT};
+use crate::conv::{
+    ParamsConv1D, ParamsConv2D, ParamsConv3D, ParamsConvTranspose1D, ParamsConvTranspose2D,
+    ParamsConvTranspose3D,
+};
+use crate::op::{BinaryOpT, CmpOp, ReduceOp, ScanOp, UnaryOpT};
 use crate::{CpuStorage, DType, Layout, Result, Shape};
 use candle_metal_kernels;
 use candle_metal_kernels::Kernels;
@@ -106,6 +109,8 @@ pub struct MetalDevice {
     /// Whenever we actually allocate a new buffer, we make a full sweep to cleanup unused buffers
     /// (strong_count = 1).
     buffers: AllocatedBuffers,
+    /// The memory statistics of the storages allocated on this device.
+    memory: Arc<crate::memory::MemoryCounters>,
 }
 
 impl std::fmt::Debug for MetalDevice {
@@ -127,6 +132,10 @@ impl MetalDevice {
         self.registry_id()
     }
 
+    pub(crate) fn memory_stats(&self) -> crate::memory::MemoryStats {
+        self.memory.stats()
+    }
+
     pub fn metal_device(&self) -> &metal::Device {
         &self.device
     }
@@ -290,6 +299,9 @@ pub struct MetalStorage {
     device: MetalDevice,
     /// The dtype is kept since buffers are untyped.
     dtype: DType,
+    /// Accounts for the buffer in the memory statistics of the device until it gets released,
+    /// this is shared by the clones of the storage as they share the same buffer.
+    _allocation: Arc<crate::memory::Allocation>,
 }
 
 impl BackendStorage for MetalStorage {
@@ -330,13 +342,23 @@ impl BackendStorage for MetalStorage {
         self.device.wait_until_completed()?;
 
         match self.dtype {
+            DType::Bool => {
+                let data: Vec<u8> = read_to_vec(&buffer, length / size);
+                Ok(CpuStorage::Bool(data.into_iter().map(|v| v != 0).collect()))
+            }
             DType::U8 => Ok(CpuStorage::U8(read_to_vec(&buffer, length / size))),
             DType::U32 => Ok(CpuStorage::U32(read_to_vec(&buffer, length / size))),
+            DType::I8 => Ok(CpuStorage::I8(read_to_vec(&buffer, length / size))),
+            DType::I16 => Ok(CpuStorage::I16(read_to_vec(&buffer, length / size))),
+            DType::I32 => Ok(CpuStorage::I32(read_to_vec(&buffer, length / size))),
             DType::I64 => Ok(CpuStorage::I64(read_to_vec(&buffer, length / size))),
             DType::F16 => Ok(CpuStorage::F16(read_to_vec(&buffer, length / size))),
             DType::BF16 => Ok(CpuStorage::BF16(read_to_vec(&buffer, length / size))),
             DType::F32 => Ok(CpuStorage::F32(read_to_vec(&buffer, length / size))),
             DType::F64 => Ok(CpuStorage::F64(read_to_vec(&buffer, length / size))),
+            DType::C64 | DType::C128 => {
+                crate::bail!("Metal to_cpu_storage {:?} not implemented", self.dtype)
+            }
         }
     }
 
@@ -557,6 +579,32 @@ impl BackendStorage for MetalStorage {
         Ok(Self::new(buffer, device, dtype))
     }
 
+    fn scan(&self, layout: &Layout, op: ScanOp, dim: usize) -> Result<Self> {
+        // There is no scan kernel yet so the values are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.scan(layout, op, dim)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn linear_recurrence(&self, l: &Layout, b: &Self, b_l: &Layout, dim: usize) -> Result<Self> {
+        // There is no scan kernel yet so the values are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.linear_recurrence(l, &b.to_cpu_storage()?, b_l, dim)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn arg_sort_last_dim(&self, layout: &Layout, asc: bool) -> Result<Self> {
+        // There is no sorting kernel yet so the indexes are computed on the cpu.
+        let cpu_storage = self.to_cpu_storage()?;
+        let cpu_storage = cpu_storage.arg_sort_last_dim(layout, asc)?;
+        self.device().storage_from_cpu_storage(&cpu_storage)
+    }
+
+    fn fft_last_dim(&self, _: &Layout, _: bool) -> Result<Self> {
+        // Complex dtypes are only supported on the cpu for now.
+        Err(crate::Error::UnsupportedDTypeForOp(self.dtype(), "fft").bt())
+    }
+
     fn cmp(&self, op: CmpOp, rhs: &Self, lhs_l: &Layout, rhs_l: &Layout) -> Result<Self> {
         let name = match op {
             CmpOp::Eq => "eq",
@@ -570,6 +618,12 @@ impl BackendStorage for MetalStorage {
     }
 
     fn to_dtype(&self, layout: &Layout, dtype: DType) -> Result<Self> {
+        if dtype == DType::Bool {
+            // Non-zero values map to true, this is computed by comparing against zero.
+            let zero = self.device.zeros_impl(&Shape::from(()), self.dtype)?;
+            let zero_l = Layout::contiguous(()).broadcast_as(layout.shape())?;
+            return self.cmp(CmpOp::Ne, &zero, layout, &zero_l);
+        }
         let device = self.device();
         let shape = layout.shape();
         let el_count = shape.elem_count();
@@ -579,8 +633,8 @@ impl BackendStorage for MetalStorage {
             let kernel_name = match (self.dtype, dtype) {
                 (DType::U32, DType::F32) => "cast_u32_f32",
                 (DType::U32, DType::U8) => "cast_u32_u8",
-                (DType::U8, DType::U32) => "cast_u8_u32",
-                (DType::U8, DType::F32) => "cast_u8_f32",
+                (DType::Bool | DType::U8, DType::U32) => "cast_u8_u32",
+                (DType::Bool | DType::U8, DType::F32) => "cast_u8_f32",
                 (DType::F32, DType::F16) => "cast_f32_f16",
                 (DType::F16, DType::F32) => "cast_f16_f32",
                 (left, right) => crate::bail!("to dtype {left:?} - {right:?}"),
@@ -600,8 +654,8 @@ impl BackendStorage for MetalStorage {
             let kernel_name = match (self.dtype, dtype) {
                 (DType::U32, DType::F32) => "cast_u32_f32_strided",
                 (DType::U32, DType::U8) => "cast_u32_u8_strided",
-                (DType::U8, DType::U32) => "cast_u8_u32_strided",
-                (DType::U8, DType::F32) => "cast_u8_f32_strided",
+                (DType::Bool | DType::U8, DType::U32) => "cast_u8_u32_strided",
+                (DType::Bool | DType::U8, DType::F32) => "cast_u8_f32_strided",
                 (DType::F32, DType::F16) => "cast_f32_f16_strided",
                 (DType::F16, DType::F32) => "cast_f16_f32_strided",
                 (left, right) => crate::bail!("to dtype {left:?} - {right:?}"),
@@ -755,8 +809,8 @@ impl BackendStorage for MetalStorage {
             );
         }
         let name = match (self.dtype, t.dtype()) {
-            (DType::U8, DType::F32) => "where_u8_f32",
-            (DType::U8, DType::F16) => "where_u8_f16",
+            (DType::Bool | DType::U8, DType::F32) => "where_u8_f32",
+            (DType::Bool | DType::U8, DType::F16) => "where_u8_f16",
             (left, right) => crate::bail!("where {left:?} - {right:?} not implemented"),
         };
         candle_metal_kernels::call_where_cond_strided(
@@ -820,6 +874,26 @@ impl BackendStorage for MetalStorage {
         crate::bail!("conv_tranpose2d metal")
     }
 
+    fn conv3d(
+        &self,
+        _l: &Layout,
+        _kernel: &Self,
+        _kernel_l: &Layout,
+        _params: &ParamsConv3D,
+    ) -> Result<Self> {
+        crate::bail!("conv3d metal")
+    }
+
+    fn conv_transpose3d(
+        &self,
+        _l: &Layout,
+        _kernel: &Self,
+        _kernel_l: &Layout,
+        _params: &ParamsConvTranspose3D,
+    ) -> Result<Self> {
+        crate::bail!("conv_transpose3d metal")
+    }
+
     fn avg_pool2d(&self, _: &Layout, _: (usize, usize), _: (usize, usize)) -> Result<Self> {
         crate::bail!("avg_pool2d metal")
     }
@@ -828,6 +902,24 @@ impl BackendStorage for MetalStorage {
         crate::bail!("max_pool2d metal")
     }
 
+    fn avg_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self> {
+        crate::bail!("avg_pool3d metal")
+    }
+
+    fn max_pool3d(
+        &self,
+        _: &Layout,
+        _: (usize, usize, usize),
+        _: (usize, usize, usize),
+    ) -> Result<Self> {
+        crate::bail!("max_pool3d metal")
+    }
+
     fn upsample_nearest1d(&self, _: &Layout, _: usize) -> Result<Self> {
         crate::bail!("upsample_nearest1d metal")
     }
@@ -1057,7 +1149,7 @@ impl BackendStorage for MetalStorage {
                 DType::F16 => candle_metal_kernels::unary::strided::copy::HALF,
                 DType::BF16 => candle_metal_kernels::unary::strided::copy::BFLOAT,
                 DType::U32 => candle_metal_kernels::unary::strided::copy::U32,
-                DType::U8 => candle_metal_kernels::unary::strided::copy::U8,
+                DType::Bool | DType::U8 => candle_metal_kernels::unary::strided::copy::U8,
                 dtype => crate::bail!("copy_strided not implemented for {dtype:?}"),
             };
             candle_metal_kernels::call_unary_strided(
@@ -1081,10 +1173,13 @@ impl BackendStorage for MetalStorage {
 
 impl MetalStorage {
     pub fn new(buffer: Arc<Buffer>, device: MetalDevice, dtype: DType) -> Self {
+        let bytes = buffer.length() as usize;
+        let allocation = crate::memory::Allocation::new(&device.memory, dtype, bytes);
         Self {
             buffer,
             device,
             dtype,
+            _allocation: Arc::new(allocation),
         }
     }
 
@@ -1114,22 +1209,22 @@ impl MetalStorage {
                 ("sub", DType::F32) => (contiguous::sub::FLOAT, self.dtype),
                 ("mul", DType::F32) => (contiguous::mul::FLOAT, self.dtype),
                 ("div", DType::F32) => (contiguous::div::FLOAT, self.dtype),
-                ("eq", DType::F32) => (contiguous::eq::FLOAT, DType::U8),
-                ("ne", DType::F32) => (contiguous::ne::FLOAT, DType::U8),
-                ("le", DType::F32) => (contiguous::le::FLOAT, DType::U8),
-                ("lt", DType::F32) => (contiguous::lt::FLOAT, DType::U8),
-                ("ge", DType::F32) => (contiguous::ge::FLOAT, DType::U8),
-                ("gt", DType::F32) => (contiguous::gt::FLOAT, DType::U8),
+                ("eq", DType::F32) => (contiguous::eq::FLOAT, DType::Bool),
+                ("ne", DType::F32) => (contiguous::ne::FLOAT, DType::Bool),
+                ("le", DType::F32) => (contiguous::le::FLOAT, DType::Bool),
+                ("lt", DType::F32) => (contiguous::lt::FLOAT, DType::Bool),
+                ("ge", DType::F32) => (contiguous::ge::FLOAT, DType::Bool),
+                ("gt", DType::F32) => (contiguous::gt::FLOAT, DType::Bool),
                 ("add", DType::F16) => (contiguous::add::HALF, self.dtype),
                 ("sub", DType::F16) => (contiguous::sub::HALF, self.dtype),
                 ("mul", DType::F16) => (contiguous::mul::HALF, self.dtype),
                 ("div", DType::F16) => (contiguous::div::HALF, self.dtype),
-                ("eq", DType::F16) => (contiguous::eq::HALF, DType::U8),
-                ("ne", DType::F16) => (contiguous::ne::HALF, DType::U8),
-                ("le", DType::F16) => (contiguous::le::HALF, DType::U8),
-                ("lt", DType::F16) => (contiguous::lt::HALF, DType::U8),
-                ("ge", DType::F16) => (contiguous::ge::HALF, DType::U8),
-                ("gt", DType::F16) => (contiguous::gt::HALF, DType::U8),
+                ("eq", DType::F16) => (contiguous::eq::HALF, DType::Bool),
+                ("ne", DType::F16) => (contiguous::ne::HALF, DType::Bool),
+                ("le", DType::F16) => (contiguous::le::HALF, DType::Bool),
+                ("lt", DType::F16) => (contiguous::lt::HALF, DType::Bool),
+                ("ge", DType::F16) => (contiguous::ge::HALF, DType::Bool),
+                ("gt", DType::F16) => (contiguous::gt::HALF, DType::Bool),
                 (name, dtype) => crate::bail!("Binary {name} - {dtype:?} not implemented"),
             };
             let buffer = device.new_buffer(el_count, dtype, op)?;
@@ -1155,24 +1250,24 @@ impl MetalStorage {
                 ("bdiv", DType::F32) => (strided::div::FLOAT, self.dtype),
                 ("bminimum", DType::F32) => (strided::min::FLOAT, self.dtype),
                 ("bmaximum", DType::F32) => (strided::max::FLOAT, self.dtype),
-                ("eq", DType::F32) => (strided::eq::FLOAT, DType::U8),
-                ("ne", DType::F32) => (strided::ne::FLOAT, DType::U8),
-                ("le", DType::F32) => (strided::le::FLOAT, DType::U8),
-                ("lt", DType::F32) => (strided::lt::FLOAT, DType::U8),
-                ("ge", DType::F32) => (strided::ge::FLOAT, DType::U8),
-                ("gt", DType::F32) => (strided::gt::FLOAT, DType::U8),
+                ("eq", DType::F32) => (strided::eq::FLOAT, DType::Bool),
+                ("ne", DType::F32) => (strided::ne::FLOAT, DType::Bool),
+                ("le", DType::F32) => (strided::le::FLOAT, DType::Bool),
+                ("lt", DType::F32) => (strided::lt::FLOAT, DType::Bool),
+                ("ge", DType::F32) => (strided::ge::FLOAT, DType::Bool),
+                ("gt", DType::F32) => (strided::gt::FLOAT, DType::Bool),
                 ("badd", DType::F16) => (strided::add::HALF, self.dtype),
                 ("bsub", DType::F16) => (strided::sub::HALF, self.dtype),
                 ("bmul", DType::F16) => (strided::mul::HALF, self.dtype),
                 ("bdiv", DType::F16) => (strided::div::HALF, self.dtype),
                 ("bminimum", DType::F16) => (strided::min::HALF, self.dtype),
                 ("bmaximum", DType::F16) => (strided::max::HALF, self.dtype),
-                ("eq", DType::F16) => (strided::eq::HALF, DType::U8),
-                ("ne", DType::F16) => (strided::ne::HALF, DType::U8),
-                ("le", DType::F16) => (strided::le::HALF, DType::U8),
-                ("lt", DType::F16) => (strided::lt::HALF, DType::U8),
-                ("ge", DType::F16) => (strided::ge::HALF, DType::U8),
-                ("gt", DType::F16) => (strided::gt::HALF, DType::U8),
+                ("eq", DType::F16) => (strided::eq::HALF, DType::Bool),
+                ("ne", DType::F16) => (strided::ne::HALF, DType::Bool),
+                ("le", DType::F16) => (strided::le::HALF, DType::Bool),
+                ("lt", DType::F16) => (strided::lt::HALF, DType::Bool),
+                ("ge", DType::F16) => (strided::ge::HALF, DType::Bool),
+                ("gt", DType::F16) => (strided::gt::HALF, DType::Bool),
                 (name, dtype) => crate::bail!("Binary strided {name} - {dtype:?} not implemented"),
             };
             let buffer = device.new_buffer(el_count, dtype, op)?;
@@ -1224,6 +1319,7 @@ impl BackendDevice for MetalDevice {
             compute_per_buffer,
             buffers,
             kernels,
+            memory: Arc::new(crate::memory::MemoryCounters::new()),
         })
     }
 
@@ -1268,13 +1364,25 @@ impl BackendDevice for MetalDevice {
 
     fn storage_from_cpu_storage(&self, storage: &CpuStorage) -> Result<Self::Storage> {
         let buffer = match storage {
+            CpuStorage::Bool(storage) => {
+                let storage: Vec<u8> = storage.iter().map(|&v| u8::from(v)).collect();
+                self.new_buffer_with_data(&storage)
+            }
             CpuStorage::U8(storage) => self.new_buffer_with_data(storage),
             CpuStorage::U32(storage) => self.new_buffer_with_data(storage),
+            CpuStorage::I8(storage) => self.new_buffer_with_data(storage),
+            CpuStorage::I16(storage) => self.new_buffer_with_data(storage),
+            CpuStorage::I32(storage) => self.new_buffer_with_data(storage),
             CpuStorage::I64(storage) => self.new_buffer_with_data(storage),
             CpuStorage::BF16(storage) => self.new_buffer_with_data(storage),
             CpuStorage::F16(storage) => self.new_buffer_with_data(storage),
             CpuStorage::F32(storage) => self.new_buffer_with_data(storage),
             CpuStorage::F64(storage) => self.new_buffer_with_data(storage),
+            CpuStorage::C64(_) | CpuStorage::C128(_) => Err(crate::Error::UnsupportedDTypeForOp(
+                storage.dtype(),
+                "storage_from_cpu_storage",
+            )
+            .bt()),
         }?;
         Ok(Self::Storage::new(buffer, self.clone(), storage.dtype()))
     }
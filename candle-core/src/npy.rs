@@ -28,6 +28,7 @@
 use crate::{DType, Device, Error, Result, Shape, Tensor};
 use byteorder::{LittleEndian, ReadBytesExt};
 use half::{bf16, f16, slice::HalfFloatSliceExt};
+use num_complex::Complex;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
@@ -86,8 +87,14 @@ impl Header {
             DType::F32 => "f4",
             DType::F64 => "f8",
             DType::I64 => "i8",
+            DType::I32 => "i4",
+            DType::I16 => "i2",
+            DType::I8 => "i1",
             DType::U32 => "u4",
             DType::U8 => "u1",
+            DType::Bool => "b1",
+            DType::C64 => "c8",
+            DType::C128 => "c16",
         };
         if !shape.is_empty() {
             shape.push(',')
@@ -160,15 +167,15 @@ impl Header {
                     "e" | "f2" => DType::F16,
                     "f" | "f4" => DType::F32,
                     "d" | "f8" => DType::F64,
-                    // "i" | "i4" => DType::S32,
+                    "i" | "i4" => DType::I32,
                     "q" | "i8" => DType::I64,
-                    // "h" | "i2" => DType::S16,
-                    // "b" | "i1" => DType::S8,
+                    "h" | "i2" => DType::I16,
+                    "b" | "i1" => DType::I8,
                     "B" | "u1" => DType::U8,
                     "I" | "u4" => DType::U32,
-                    "?" | "b1" => DType::U8,
-                    // "F" | "F4" => DType::C64,
-                    // "D" | "F8" => DType::C128,
+                    "?" | "b1" => DType::Bool,
+                    "F" | "c8" => DType::C64,
+                    "D" | "c16" => DType::C128,
                     descr => return Err(Error::Npy(format!("unrecognized descr {descr}"))),
                 }
             }
@@ -239,6 +246,46 @@ impl Tensor {
                 reader.read_i64_into::<LittleEndian>(&mut data_t)?;
                 Tensor::from_vec(data_t, shape, &Device::Cpu)
             }
+            DType::I32 => {
+                let mut data_t = vec![0i32; elem_count];
+                reader.read_i32_into::<LittleEndian>(&mut data_t)?;
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
+            DType::I16 => {
+                let mut data_t = vec![0i16; elem_count];
+                reader.read_i16_into::<LittleEndian>(&mut data_t)?;
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
+            DType::I8 => {
+                let mut data_t = vec![0i8; elem_count];
+                reader.read_i8_into(&mut data_t)?;
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
+            DType::Bool => {
+                let mut data_t = vec![0u8; elem_count];
+                reader.read_exact(&mut data_t)?;
+                let data_t: Vec<bool> = data_t.into_iter().map(|v| v != 0).collect();
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
+            DType::C64 => {
+                // Complex values are stored as consecutive real and imaginary parts.
+                let mut data_t = vec![0f32; 2 * elem_count];
+                reader.read_f32_into::<LittleEndian>(&mut data_t)?;
+                let data_t: Vec<_> = data_t
+                    .chunks_exact(2)
+                    .map(|v| Complex::new(v[0], v[1]))
+                    .collect();
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
+            DType::C128 => {
+                let mut data_t = vec![0f64; 2 * elem_count];
+                reader.read_f64_into::<LittleEndian>(&mut data_t)?;
+                let data_t: Vec<_> = data_t
+                    .chunks_exact(2)
+                    .map(|v| Complex::new(v[0], v[1]))
+                    .collect();
+                Tensor::from_vec(data_t, shape, &Device::Cpu)
+            }
         }
     }
 
@@ -1,6 +1,7 @@
 #![allow(clippy::redundant_closure_call)]
 use crate::{CpuStorage, CudaStorage, Layout, MetalStorage, Result, Shape, Tensor};
 use half::{bf16, f16};
+use num_complex::Complex;
 use num_traits::float::Float;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -13,6 +14,19 @@ pub enum CmpOp {
     Gt,
 }
 
+impl CmpOp {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::Le => "le",
+            Self::Ge => "ge",
+            Self::Lt => "lt",
+            Self::Gt => "gt",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReduceOp {
     Sum,
@@ -34,6 +48,29 @@ impl ReduceOp {
     }
 }
 
+/// The combining operation of a cumulative scan, the value at index `i` of the output combines
+/// the input values at indexes `0..=i`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOp {
+    Sum,
+    Prod,
+    Max,
+    Min,
+    LogSumExp,
+}
+
+impl ScanOp {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Sum => "cumsum",
+            Self::Prod => "cumprod",
+            Self::Max => "cummax",
+            Self::Min => "cummin",
+            Self::LogSumExp => "logcumsumexp",
+        }
+    }
+}
+
 // These ops return the same type as their input type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOp {
@@ -74,6 +111,8 @@ pub enum Op {
     Cmp(Tensor, CmpOp),
     // The third argument is the reduced shape with `keepdim=true`.
     Reduce(Tensor, ReduceOp, Vec<usize>),
+    Scan(Tensor, ScanOp, usize),
+    LinearRecurrence(Tensor, Tensor, usize),
     Matmul(Tensor, Tensor),
     Gather(Tensor, Tensor, usize),
     ScatterAdd(Tensor, Tensor, Tensor, usize),
@@ -119,6 +158,24 @@ pub enum Op {
         dilation: usize,
     },
 
+    Conv3D {
+        arg: Tensor,
+        kernel: Tensor,
+        padding: usize,
+        stride: usize,
+        dilation: usize,
+    },
+
+    #[allow(dead_code)]
+    ConvTranspose3D {
+        arg: Tensor,
+        kernel: Tensor,
+        padding: usize,
+        output_padding: usize,
+        stride: usize,
+        dilation: usize,
+    },
+
     AvgPool2D {
         arg: Tensor,
         kernel_size: (usize, usize),
@@ -131,6 +188,18 @@ pub enum Op {
         stride: (usize, usize),
     },
 
+    AvgPool3D {
+        arg: Tensor,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    },
+
+    MaxPool3D {
+        arg: Tensor,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    },
+
     UpsampleNearest1D(Tensor),
     UpsampleNearest2D {
         arg: Tensor,
@@ -155,8 +224,13 @@ pub enum Op {
     ToDevice(Tensor),
     Transpose(Tensor, usize, usize),
     Permute(Tensor, Vec<usize>),
+    // A view of the argument, the layout applies to the elements of the argument in row-major
+    // order and the elements can be used multiple times.
+    AsStrided(Tensor, Layout),
     Elu(Tensor, f64),
     Powf(Tensor, f64),
+    // Discrete Fourier transform over the last dimension, the flag is set for the inverse transform.
+    Fft(Tensor, bool),
     CustomOp1(Tensor, std::sync::Arc<Box<dyn CustomOp1 + Send + Sync>>),
     CustomOp2(
         Tensor,
@@ -169,8 +243,173 @@ pub enum Op {
         Tensor,
         std::sync::Arc<Box<dyn CustomOp3 + Send + Sync>>,
     ),
+    // The result of a checkpointed closure, `deps` are the tensors created outside of the closure
+    // that this result depends on. The closure is run again on `inputs` in the backward pass.
+    Checkpoint {
+        inputs: Vec<Tensor>,
+        deps: Vec<Tensor>,
+        f: std::sync::Arc<CheckpointFn>,
+    },
+}
+
+impl UnaryOp {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Exp => Exp::NAME,
+            Self::Log => Log::NAME,
+            Self::Sin => Sin::NAME,
+            Self::Cos => Cos::NAME,
+            Self::Abs => Abs::NAME,
+            Self::Neg => Neg::NAME,
+            Self::Recip => Recip::NAME,
+            Self::Sqr => Sqr::NAME,
+            Self::Sqrt => Sqrt::NAME,
+            Self::Gelu => Gelu::NAME,
+            Self::GeluErf => GeluErf::NAME,
+            Self::Erf => Erf::NAME,
+            Self::Relu => Relu::NAME,
+            Self::Tanh => Tanh::NAME,
+            Self::Floor => Floor::NAME,
+            Self::Ceil => Ceil::NAME,
+            Self::Round => Round::NAME,
+        }
+    }
+}
+
+impl BinaryOp {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Add => Add::NAME,
+            Self::Mul => Mul::NAME,
+            Self::Sub => Sub::NAME,
+            Self::Div => Div::NAME,
+            Self::Maximum => Maximum::NAME,
+            Self::Minimum => Minimum::NAME,
+        }
+    }
 }
 
+impl Op {
+    /// The name of the op, this is used when reporting statistics.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Binary(_, _, op) => op.name(),
+            Self::Unary(_, op) => op.name(),
+            Self::Cmp(_, op) => op.name(),
+            Self::Reduce(_, op, _) => op.name(),
+            Self::Scan(_, op, _) => op.name(),
+            Self::LinearRecurrence(_, _, _) => "linear-recurrence",
+            Self::Matmul(_, _) => "matmul",
+            Self::Gather(_, _, _) => "gather",
+            Self::ScatterAdd(_, _, _, _) => "scatter-add",
+            Self::IndexSelect(_, _, _) => "index-select",
+            Self::IndexAdd(_, _, _, _) => "index-add",
+            Self::WhereCond(_, _, _) => "where-cond",
+            Self::Conv1D { .. } => "conv1d",
+            Self::ConvTranspose1D { .. } => "conv-transpose1d",
+            Self::Conv2D { .. } => "conv2d",
+            Self::ConvTranspose2D { .. } => "conv-transpose2d",
+            Self::Conv3D { .. } => "conv3d",
+            Self::ConvTranspose3D { .. } => "conv-transpose3d",
+            Self::AvgPool2D { .. } => "avg-pool2d",
+            Self::MaxPool2D { .. } => "max-pool2d",
+            Self::AvgPool3D { .. } => "avg-pool3d",
+            Self::MaxPool3D { .. } => "max-pool3d",
+            Self::UpsampleNearest1D(_) => "upsample-nearest1d",
+            Self::UpsampleNearest2D { .. } => "upsample-nearest2d",
+            Self::Cat(_, _) => "cat",
+            Self::Affine { .. } => "affine",
+            Self::ToDType(_) => "to-dtype",
+            Self::Copy(_) => "copy",
+            Self::Broadcast(_) => "broadcast",
+            Self::Narrow(_, _, _, _) => "narrow",
+            Self::SliceScatter0(_, _, _) => "slice-scatter",
+            Self::Reshape(_) => "reshape",
+            Self::ToDevice(_) => "to-device",
+            Self::Transpose(_, _, _) => "transpose",
+            Self::Permute(_, _) => "permute",
+            Self::AsStrided(_, _) => "as-strided",
+            Self::Elu(_, _) => "elu",
+            Self::Powf(_, _) => "powf",
+            Self::Fft(_, _) => "fft",
+            Self::CustomOp1(_, c) => c.name(),
+            Self::CustomOp2(_, _, c) => c.name(),
+            Self::CustomOp3(_, _, _, c) => c.name(),
+            Self::Checkpoint { .. } => "checkpoint",
+        }
+    }
+}
+
+// Helpers used by the backward pass to check the tensors it reads.
+impl Op {
+    // The arguments whose values are read when computing the gradients, the other arguments only
+    // contribute their shapes. The outputs that are read, e.g. for `exp`, are freshly allocated
+    // and cannot be modified in place as they are part of the graph.
+    fn backward_args(&self) -> Vec<&Tensor> {
+        match self {
+            Self::Binary(_, _, BinaryOp::Add | BinaryOp::Sub) => vec![],
+            Self::Binary(lhs, rhs, _) => vec![lhs, rhs],
+            Self::Unary(
+                _,
+                UnaryOp::Exp
+                | UnaryOp::Neg
+                | UnaryOp::Tanh
+                | UnaryOp::Sqrt
+                | UnaryOp::Floor
+                | UnaryOp::Ceil
+                | UnaryOp::Round,
+            ) => vec![],
+            Self::Unary(arg, _) => vec![arg],
+            Self::Reduce(arg, ReduceOp::Min | ReduceOp::Max, _) => vec![arg],
+            Self::Reduce(_, _, _) | Self::Cmp(_, _) => vec![],
+            Self::Scan(_, ScanOp::Sum, _) => vec![],
+            Self::Scan(arg, _, _) => vec![arg],
+            Self::LinearRecurrence(a, _, _) => vec![a],
+            Self::Matmul(lhs, rhs) => vec![lhs, rhs],
+            Self::Gather(_, indexes, _)
+            | Self::IndexSelect(_, indexes, _)
+            | Self::ScatterAdd(_, indexes, _, _)
+            | Self::IndexAdd(_, indexes, _, _) => vec![indexes],
+            Self::WhereCond(pred, _, _) => vec![pred],
+            Self::Conv1D { arg, kernel, .. }
+            | Self::ConvTranspose1D { arg, kernel, .. }
+            | Self::Conv2D { arg, kernel, .. }
+            | Self::ConvTranspose2D { arg, kernel, .. }
+            | Self::Conv3D { arg, kernel, .. }
+            | Self::ConvTranspose3D { arg, kernel, .. } => vec![arg, kernel],
+            Self::MaxPool2D { arg, .. } | Self::MaxPool3D { arg, .. } => vec![arg],
+            Self::Elu(arg, _) | Self::Powf(arg, _) => vec![arg],
+            Self::AvgPool2D { .. }
+            | Self::AvgPool3D { .. }
+            | Self::UpsampleNearest1D(_)
+            | Self::UpsampleNearest2D { .. }
+            | Self::Cat(_, _)
+            | Self::Affine { .. }
+            | Self::ToDType(_)
+            | Self::Copy(_)
+            | Self::Broadcast(_)
+            | Self::Narrow(_, _, _, _)
+            | Self::SliceScatter0(_, _, _)
+            | Self::Reshape(_)
+            | Self::ToDevice(_)
+            | Self::Transpose(_, _, _)
+            | Self::Permute(_, _)
+            | Self::AsStrided(_, _)
+            | Self::Fft(_, _) => vec![],
+            // Custom ops can read any of their arguments.
+            Self::CustomOp1(arg, _) => vec![arg],
+            Self::CustomOp2(arg1, arg2, _) => vec![arg1, arg2],
+            Self::CustomOp3(arg1, arg2, arg3, _) => vec![arg1, arg2, arg3],
+            // The closure is run again on the inputs and reads the dependencies.
+            Self::Checkpoint { inputs, deps, .. } => inputs.iter().chain(deps.iter()).collect(),
+        }
+    }
+}
+
+/// A closure whose intermediate values are recomputed during the backward pass, see
+/// [`crate::checkpoint`].
+pub type CheckpointFn = dyn Fn(&[Tensor]) -> Result<Tensor> + Send + Sync;
+
 /// Unary ops that can be defined in user-land.
 pub trait CustomOp1 {
     // Box<dyn> does not support const yet, so use a function to get the name.
@@ -329,8 +568,20 @@ pub trait UnaryOpT {
     fn f64(v1: f64) -> f64;
     fn u8(v1: u8) -> u8;
     fn u32(v1: u32) -> u32;
+    fn i8(v1: i8) -> i8;
+    fn i16(v1: i16) -> i16;
+    fn i32(v1: i32) -> i32;
     fn i64(v1: i64) -> i64;
 
+    // Most unary ops are not defined on complex values, the ones that are set this flag.
+    const COMPLEX: bool = false;
+    fn c64(_v1: Complex<f32>) -> Complex<f32> {
+        todo!("no complex function for {}", Self::NAME)
+    }
+    fn c128(_v1: Complex<f64>) -> Complex<f64> {
+        todo!("no complex function for {}", Self::NAME)
+    }
+
     // There is no very good way to represent optional function in traits so we go for an explicit
     // boolean flag to mark the function as existing.
     const BF16_VEC: bool = false;
@@ -353,8 +604,19 @@ pub trait BinaryOpT {
     fn f64(v1: f64, v2: f64) -> f64;
     fn u8(v1: u8, v2: u8) -> u8;
     fn u32(v1: u32, v2: u32) -> u32;
+    fn i8(v1: i8, v2: i8) -> i8;
+    fn i16(v1: i16, v2: i16) -> i16;
+    fn i32(v1: i32, v2: i32) -> i32;
     fn i64(v1: i64, v2: i64) -> i64;
 
+    const COMPLEX: bool = false;
+    fn c64(_v1: Complex<f32>, _v2: Complex<f32>) -> Complex<f32> {
+        todo!("no complex function for {}", Self::NAME)
+    }
+    fn c128(_v1: Complex<f64>, _v2: Complex<f64>) -> Complex<f64> {
+        todo!("no complex function for {}", Self::NAME)
+    }
+
     const BF16_VEC: bool = false;
     fn bf16_vec(_xs1: &[bf16], _xs2: &[bf16], _ys: &mut [bf16]) {}
     const F16_VEC: bool = false;
@@ -396,7 +658,23 @@ pub(crate) struct Ceil;
 pub(crate) struct Round;
 
 macro_rules! bin_op {
+    ($op:ident, $name: literal, $e: expr, $f32_vec: ident, $f64_vec: ident, complex) => {
+        bin_op!(@impl $op, $name, $e, $f32_vec, $f64_vec, {
+            const COMPLEX: bool = true;
+            #[inline(always)]
+            fn c64(v1: Complex<f32>, v2: Complex<f32>) -> Complex<f32> {
+                $e(v1, v2)
+            }
+            #[inline(always)]
+            fn c128(v1: Complex<f64>, v2: Complex<f64>) -> Complex<f64> {
+                $e(v1, v2)
+            }
+        });
+    };
     ($op:ident, $name: literal, $e: expr, $f32_vec: ident, $f64_vec: ident) => {
+        bin_op!(@impl $op, $name, $e, $f32_vec, $f64_vec, {});
+    };
+    (@impl $op:ident, $name: literal, $e: expr, $f32_vec: ident, $f64_vec: ident, { $($complex:tt)* }) => {
         impl BinaryOpT for $op {
             const NAME: &'static str = $name;
             const KERNEL: &'static str = concat!("b", $name);
@@ -426,9 +704,22 @@ macro_rules! bin_op {
                 $e(v1, v2)
             }
             #[inline(always)]
+            fn i8(v1: i8, v2: i8) -> i8 {
+                $e(v1, v2)
+            }
+            #[inline(always)]
+            fn i16(v1: i16, v2: i16) -> i16 {
+                $e(v1, v2)
+            }
+            #[inline(always)]
+            fn i32(v1: i32, v2: i32) -> i32 {
+                $e(v1, v2)
+            }
+            #[inline(always)]
             fn i64(v1: i64, v2: i64) -> i64 {
                 $e(v1, v2)
             }
+            $($complex)*
 
             #[cfg(feature = "mkl")]
             const F32_VEC: bool = true;
@@ -463,10 +754,10 @@ macro_rules! bin_op {
     };
 }
 
-bin_op!(Add, "add", |v1, v2| v1 + v2, vs_add, vd_add);
-bin_op!(Sub, "sub", |v1, v2| v1 - v2, vs_sub, vd_sub);
-bin_op!(Mul, "mul", |v1, v2| v1 * v2, vs_mul, vd_mul);
-bin_op!(Div, "div", |v1, v2| v1 / v2, vs_div, vd_div);
+bin_op!(Add, "add", |v1, v2| v1 + v2, vs_add, vd_add, complex);
+bin_op!(Sub, "sub", |v1, v2| v1 - v2, vs_sub, vd_sub, complex);
+bin_op!(Mul, "mul", |v1, v2| v1 * v2, vs_mul, vd_mul, complex);
+bin_op!(Div, "div", |v1, v2| v1 / v2, vs_div, vd_div, complex);
 bin_op!(
     Minimum,
     "minimum",
@@ -484,7 +775,23 @@ bin_op!(
 
 #[allow(clippy::redundant_closure_call)]
 macro_rules! unary_op {
+    ($op: ident, $name: literal, $a: ident, $e: expr, complex) => {
+        unary_op!(@impl $op, $name, $a, $e, {
+            const COMPLEX: bool = true;
+            #[inline(always)]
+            fn c64($a: Complex<f32>) -> Complex<f32> {
+                $e
+            }
+            #[inline(always)]
+            fn c128($a: Complex<f64>) -> Complex<f64> {
+                $e
+            }
+        });
+    };
     ($op: ident, $name: literal, $a: ident, $e: expr) => {
+        unary_op!(@impl $op, $name, $a, $e, {});
+    };
+    (@impl $op: ident, $name: literal, $a: ident, $e: expr, { $($complex:tt)* }) => {
         impl UnaryOpT for $op {
             const NAME: &'static str = $name;
             const KERNEL: &'static str = concat!("u", $name);
@@ -514,9 +821,22 @@ macro_rules! unary_op {
                 todo!("no unary function for u32")
             }
             #[inline(always)]
+            fn i8(_: i8) -> i8 {
+                todo!("no unary function for i8")
+            }
+            #[inline(always)]
+            fn i16(_: i16) -> i16 {
+                todo!("no unary function for i16")
+            }
+            #[inline(always)]
+            fn i32(_: i32) -> i32 {
+                todo!("no unary function for i32")
+            }
+            #[inline(always)]
             fn i64(_: i64) -> i64 {
                 todo!("no unary function for i64")
             }
+            $($complex)*
         }
     };
 
@@ -550,10 +870,32 @@ macro_rules! unary_op {
                 todo!("no unary function for u32")
             }
             #[inline(always)]
+            fn i8(_: i8) -> i8 {
+                todo!("no unary function for i8")
+            }
+            #[inline(always)]
+            fn i16(_: i16) -> i16 {
+                todo!("no unary function for i16")
+            }
+            #[inline(always)]
+            fn i32(_: i32) -> i32 {
+                todo!("no unary function for i32")
+            }
+            #[inline(always)]
             fn i64(_: i64) -> i64 {
                 todo!("no unary function for i64")
             }
 
+            const COMPLEX: bool = true;
+            #[inline(always)]
+            fn c64($a: Complex<f32>) -> Complex<f32> {
+                $e
+            }
+            #[inline(always)]
+            fn c128($a: Complex<f64>) -> Complex<f64> {
+                $e
+            }
+
             #[cfg(feature = "mkl")]
             const F32_VEC: bool = true;
             #[cfg(feature = "mkl")]
@@ -592,7 +934,7 @@ unary_op!(Log, "log", v, v.ln(), vs_ln, vd_ln);
 unary_op!(Sin, "sin", v, v.sin(), vs_sin, vd_sin);
 unary_op!(Cos, "cos", v, v.cos(), vs_cos, vd_cos);
 unary_op!(Tanh, "tanh", v, v.tanh(), vs_tanh, vd_tanh);
-unary_op!(Neg, "neg", v, -v);
+unary_op!(Neg, "neg", v, -v, complex);
 unary_op!(Recip, "recip", v, v.recip());
 unary_op!(Sqr, "sqr", v, v * v, vs_sqr, vd_sqr);
 unary_op!(Sqrt, "sqrt", v, v.sqrt(), vs_sqrt, vd_sqrt);
@@ -646,6 +988,18 @@ impl UnaryOpT for Gelu {
         0
     }
     #[inline(always)]
+    fn i8(_: i8) -> i8 {
+        0
+    }
+    #[inline(always)]
+    fn i16(_: i16) -> i16 {
+        0
+    }
+    #[inline(always)]
+    fn i32(_: i32) -> i32 {
+        0
+    }
+    #[inline(always)]
     fn i64(_: i64) -> i64 {
         0
     }
@@ -719,6 +1073,18 @@ impl UnaryOpT for Erf {
         0
     }
     #[inline(always)]
+    fn i8(_: i8) -> i8 {
+        0
+    }
+    #[inline(always)]
+    fn i16(_: i16) -> i16 {
+        0
+    }
+    #[inline(always)]
+    fn i32(_: i32) -> i32 {
+        0
+    }
+    #[inline(always)]
     fn i64(_: i64) -> i64 {
         0
     }
@@ -753,6 +1119,18 @@ impl UnaryOpT for Abs {
         v
     }
     #[inline(always)]
+    fn i8(v: i8) -> i8 {
+        v.abs()
+    }
+    #[inline(always)]
+    fn i16(v: i16) -> i16 {
+        v.abs()
+    }
+    #[inline(always)]
+    fn i32(v: i32) -> i32 {
+        v.abs()
+    }
+    #[inline(always)]
     fn i64(v: i64) -> i64 {
         v.abs()
     }
@@ -787,6 +1165,18 @@ impl UnaryOpT for Ceil {
         v
     }
     #[inline(always)]
+    fn i8(v: i8) -> i8 {
+        v
+    }
+    #[inline(always)]
+    fn i16(v: i16) -> i16 {
+        v
+    }
+    #[inline(always)]
+    fn i32(v: i32) -> i32 {
+        v
+    }
+    #[inline(always)]
     fn i64(v: i64) -> i64 {
         v
     }
@@ -821,6 +1211,18 @@ impl UnaryOpT for Floor {
         v
     }
     #[inline(always)]
+    fn i8(v: i8) -> i8 {
+        v
+    }
+    #[inline(always)]
+    fn i16(v: i16) -> i16 {
+        v
+    }
+    #[inline(always)]
+    fn i32(v: i32) -> i32 {
+        v
+    }
+    #[inline(always)]
     fn i64(v: i64) -> i64 {
         v
     }
@@ -855,6 +1257,18 @@ impl UnaryOpT for Round {
         v
     }
     #[inline(always)]
+    fn i8(v: i8) -> i8 {
+        v
+    }
+    #[inline(always)]
+    fn i16(v: i16) -> i16 {
+        v
+    }
+    #[inline(always)]
+    fn i32(v: i32) -> i32 {
+        v
+    }
+    #[inline(always)]
     fn i64(v: i64) -> i64 {
         v
     }
@@ -889,6 +1303,18 @@ impl UnaryOpT for GeluErf {
         0
     }
     #[inline(always)]
+    fn i8(_: i8) -> i8 {
+        0
+    }
+    #[inline(always)]
+    fn i16(_: i16) -> i16 {
+        0
+    }
+    #[inline(always)]
+    fn i32(_: i32) -> i32 {
+        0
+    }
+    #[inline(always)]
     fn i64(_: i64) -> i64 {
         0
     }
@@ -923,37 +1349,86 @@ impl UnaryOpT for Relu {
         v
     }
     #[inline(always)]
+    fn i8(v: i8) -> i8 {
+        v.max(0)
+    }
+    #[inline(always)]
+    fn i16(v: i16) -> i16 {
+        v.max(0)
+    }
+    #[inline(always)]
+    fn i32(v: i32) -> i32 {
+        v.max(0)
+    }
+    #[inline(always)]
     fn i64(v: i64) -> i64 {
-        v
+        v.max(0)
     }
 }
 
 /// `BackpropOp` is a wrapper around `Option<Op>`. The main goal is to ensure that dependencies are
 /// properly checked when creating a new value
 #[derive(Clone)]
-pub struct BackpropOp(Option<Op>);
+pub struct BackpropOp {
+    op: Option<Op>,
+    // The name of ops that are not tracked, this is only filled when the memory profiler is
+    // active so that allocations can be attributed to the op that triggered them.
+    name: Option<&'static str>,
+    // The arguments that the gradient computation reads together with their storage version at
+    // the time the op was created, these are checked during the backward pass to detect in-place
+    // modifications.
+    saved: Vec<(Tensor, usize)>,
+}
 
 impl BackpropOp {
     pub(crate) fn none() -> Self {
-        BackpropOp(None)
+        Self {
+            op: None,
+            name: None,
+            saved: vec![],
+        }
     }
 
-    pub(crate) fn new1(arg: &Tensor, f: impl Fn(Tensor) -> Op) -> Self {
-        let op = if arg.track_op() {
-            Some(f(arg.clone()))
+    fn tracked(op: Op) -> Self {
+        let saved = op
+            .backward_args()
+            .into_iter()
+            .map(|arg| (arg.clone(), arg.version()))
+            .collect();
+        Self {
+            op: Some(op),
+            name: None,
+            saved,
+        }
+    }
+
+    fn untracked(f: impl FnOnce() -> Op) -> Self {
+        let name = if crate::memory::is_profiling() {
+            Some(f().name())
         } else {
             None
         };
-        Self(op)
+        Self {
+            op: None,
+            name,
+            saved: vec![],
+        }
+    }
+
+    pub(crate) fn new1(arg: &Tensor, f: impl Fn(Tensor) -> Op) -> Self {
+        if arg.track_op() {
+            Self::tracked(f(arg.clone()))
+        } else {
+            Self::untracked(|| f(arg.clone()))
+        }
     }
 
     pub(crate) fn new2(arg1: &Tensor, arg2: &Tensor, f: impl Fn(Tensor, Tensor) -> Op) -> Self {
-        let op = if arg1.track_op() || arg2.track_op() {
-            Some(f(arg1.clone(), arg2.clone()))
+        if arg1.track_op() || arg2.track_op() {
+            Self::tracked(f(arg1.clone(), arg2.clone()))
         } else {
-            None
-        };
-        Self(op)
+            Self::untracked(|| f(arg1.clone(), arg2.clone()))
+        }
     }
 
     pub(crate) fn new3(
@@ -962,32 +1437,44 @@ impl BackpropOp {
         arg3: &Tensor,
         f: impl Fn(Tensor, Tensor, Tensor) -> Op,
     ) -> Self {
-        let op = if arg1.track_op() || arg2.track_op() || arg3.track_op() {
-            Some(f(arg1.clone(), arg2.clone(), arg3.clone()))
+        if arg1.track_op() || arg2.track_op() || arg3.track_op() {
+            Self::tracked(f(arg1.clone(), arg2.clone(), arg3.clone()))
         } else {
-            None
-        };
-        Self(op)
+            Self::untracked(|| f(arg1.clone(), arg2.clone(), arg3.clone()))
+        }
     }
 
     pub(crate) fn new<A: AsRef<Tensor>>(args: &[A], f: impl Fn(Vec<Tensor>) -> Op) -> Self {
-        let op = if args.iter().any(|arg| arg.as_ref().track_op()) {
-            let args: Vec<Tensor> = args.iter().map(|arg| arg.as_ref().clone()).collect();
-            Some(f(args))
+        let cloned_args = || args.iter().map(|arg| arg.as_ref().clone()).collect();
+        if args.iter().any(|arg| arg.as_ref().track_op()) {
+            Self::tracked(f(cloned_args()))
         } else {
-            None
-        };
-        Self(op)
+            Self::untracked(|| f(cloned_args()))
+        }
     }
 
     pub(crate) fn is_none(&self) -> bool {
-        self.0.is_none()
+        self.op.is_none()
+    }
+
+    /// The arguments read by the gradient computation together with their version when the op
+    /// was created.
+    pub(crate) fn saved_versions(&self) -> &[(Tensor, usize)] {
+        &self.saved
+    }
+
+    /// The name of the op that created the value, if known.
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        match &self.op {
+            Some(op) => Some(op.name()),
+            None => self.name,
+        }
     }
 }
 
 impl std::ops::Deref for BackpropOp {
     type Target = Option<Op>;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.op
     }
 }
@@ -609,7 +609,11 @@ fn rebuild_args(args: Object) -> Result<(Layout, DType, String, usize)> {
         "HalfStorage" => DType::F16,
         "BFloat16Storage" => DType::BF16,
         "ByteStorage" => DType::U8,
+        "CharStorage" => DType::I8,
+        "ShortStorage" => DType::I16,
+        "IntStorage" => DType::I32,
         "LongStorage" => DType::I64,
+        "BoolStorage" => DType::Bool,
         other => {
             crate::bail!("unsupported storage type {other}")
         }
@@ -0,0 +1,283 @@
+//! Op level profiling.
+//!
+//! A [`Profiler`] records an event for every op dispatched to a backend while it is alive, with
+//! the op name, the input shapes, dtype, device, duration and the number of bytes read. The
+//! events can be aggregated per op or exported as a trace in the Chrome trace event format
+//! which can be loaded in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+//!
+//! ```rust
+//! use candle_core::{profiler::Profiler, Device, Tensor};
+//! let profiler = Profiler::start();
+//! let xs = Tensor::ones((16, 16), candle_core::DType::F32, &Device::Cpu)?;
+//! let ys = xs.matmul(&xs)?.exp()?;
+//! let events = profiler.events();
+//! assert_eq!(events[0].op, "matmul");
+//! assert_eq!(events[0].shapes, [[16, 16], [16, 16]]);
+//! assert_eq!(events[1].op, "exp");
+//! let trace = profiler.chrome_trace();
+//! # Ok::<(), candle_core::Error>(())
+//! ```
+//!
+//! The cuda and metal backends run the kernels asynchronously, so the durations recorded on these
+//! devices only measure the time taken to launch the kernels unless the device gets synchronized.
+use crate::{DType, DeviceLocation, Layout, Result, Storage};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A single op dispatched to a backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpEvent {
+    /// The name of the op.
+    pub op: &'static str,
+    /// The shapes of the op inputs.
+    pub shapes: Vec<Vec<usize>>,
+    /// The dtype of the first input.
+    pub dtype: DType,
+    pub device: DeviceLocation,
+    /// The time at which the op started, relative to the creation of the profiler.
+    pub start: Duration,
+    pub duration: Duration,
+    /// The number of bytes in the op inputs.
+    pub bytes: usize,
+    /// An identifier for the thread that ran the op.
+    pub thread_id: usize,
+}
+
+/// The events recorded for an op, aggregated by [`Profiler::summary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpSummary {
+    pub op: &'static str,
+    pub count: usize,
+    pub duration: Duration,
+    pub bytes: usize,
+}
+
+struct RawEvent {
+    op: &'static str,
+    shapes: Vec<Vec<usize>>,
+    dtype: DType,
+    device: DeviceLocation,
+    start: Instant,
+    duration: Duration,
+    bytes: usize,
+    thread_id: usize,
+}
+
+type Events = Arc<Mutex<Vec<RawEvent>>>;
+
+static NUM_PROFILERS: AtomicUsize = AtomicUsize::new(0);
+static PROFILERS: Mutex<Vec<(usize, Events)>> = Mutex::new(Vec::new());
+
+fn is_profiling() -> bool {
+    NUM_PROFILERS.load(Ordering::Relaxed) > 0
+}
+
+fn thread_id() -> usize {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static THREAD_ID: usize = COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    THREAD_ID.with(|id| *id)
+}
+
+struct TimerState {
+    op: &'static str,
+    shapes: Vec<Vec<usize>>,
+    dtype: DType,
+    device: DeviceLocation,
+    bytes: usize,
+    start: Instant,
+}
+
+/// Times an op dispatch, the event is recorded when the timer is dropped. This does nothing
+/// when no profiler is active.
+pub(crate) struct OpTimer(Option<TimerState>);
+
+impl OpTimer {
+    pub(crate) fn new(op: &'static str, args: &[(&Storage, &Layout)]) -> Self {
+        if !is_profiling() {
+            return Self(None);
+        }
+        let (dtype, device) = match args.first() {
+            Some((storage, _)) => (storage.dtype(), storage.device().location()),
+            None => return Self(None),
+        };
+        let shapes = args.iter().map(|(_, l)| l.dims().to_vec()).collect();
+        let bytes = args
+            .iter()
+            .map(|(s, l)| l.shape().elem_count() * s.dtype().size_in_bytes())
+            .sum();
+        Self(Some(TimerState {
+            op,
+            shapes,
+            dtype,
+            device,
+            bytes,
+            start: Instant::now(),
+        }))
+    }
+}
+
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        let state = match self.0.take() {
+            None => return,
+            Some(state) => state,
+        };
+        let duration = state.start.elapsed();
+        let thread_id = thread_id();
+        for (_, events) in PROFILERS.lock().unwrap().iter() {
+            events.lock().unwrap().push(RawEvent {
+                op: state.op,
+                shapes: state.shapes.clone(),
+                dtype: state.dtype,
+                device: state.device,
+                start: state.start,
+                duration,
+                bytes: state.bytes,
+                thread_id,
+            })
+        }
+    }
+}
+
+/// Records the ops dispatched from all threads, from its creation until it gets dropped.
+pub struct Profiler {
+    id: usize,
+    start: Instant,
+    events: Events,
+}
+
+impl Profiler {
+    /// Starts recording ops.
+    pub fn start() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let events = Events::default();
+        PROFILERS.lock().unwrap().push((id, events.clone()));
+        NUM_PROFILERS.fetch_add(1, Ordering::Relaxed);
+        Self {
+            id,
+            start: Instant::now(),
+            events,
+        }
+    }
+
+    /// Returns the events recorded so far, in the order in which the ops completed.
+    pub fn events(&self) -> Vec<OpEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| OpEvent {
+                op: e.op,
+                shapes: e.shapes.clone(),
+                dtype: e.dtype,
+                device: e.device,
+                start: e.start.saturating_duration_since(self.start),
+                duration: e.duration,
+                bytes: e.bytes,
+                thread_id: e.thread_id,
+            })
+            .collect()
+    }
+
+    /// Aggregates the recorded events per op, the ops that took the most time come first.
+    pub fn summary(&self) -> Vec<OpSummary> {
+        let mut summary: Vec<OpSummary> = vec![];
+        for e in self.events.lock().unwrap().iter() {
+            match summary.iter_mut().find(|s| s.op == e.op) {
+                Some(s) => {
+                    s.count += 1;
+                    s.duration += e.duration;
+                    s.bytes += e.bytes;
+                }
+                None => summary.push(OpSummary {
+                    op: e.op,
+                    count: 1,
+                    duration: e.duration,
+                    bytes: e.bytes,
+                }),
+            }
+        }
+        summary.sort_by(|a, b| b.duration.cmp(&a.duration).then(a.op.cmp(b.op)));
+        summary
+    }
+
+    /// Returns the recorded events as a json string using the Chrome trace event format.
+    pub fn chrome_trace(&self) -> String {
+        let events: Vec<String> = self
+            .events()
+            .iter()
+            .map(|e| {
+                let device = match e.device {
+                    DeviceLocation::Cpu => "cpu".to_string(),
+                    DeviceLocation::Cuda { gpu_id } => format!("cuda:{gpu_id}"),
+                    DeviceLocation::Metal { gpu_id } => format!("metal:{gpu_id}"),
+                };
+                format!(
+                    "{{\"name\":\"{}\",\"cat\":\"op\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{},\"args\":{{\"shapes\":\"{:?}\",\"dtype\":\"{}\",\"device\":\"{device}\",\"bytes\":{}}}}}",
+                    escape_json(e.op),
+                    e.start.as_secs_f64() * 1e6,
+                    e.duration.as_secs_f64() * 1e6,
+                    e.thread_id,
+                    e.shapes,
+                    e.dtype.as_str(),
+                    e.bytes,
+                )
+            })
+            .collect();
+        format!(
+            "{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n",
+            events.join(",\n")
+        )
+    }
+
+    /// Writes the recorded events to a json file using the Chrome trace event format.
+    pub fn write_chrome_trace<P: AsRef<std::path::Path>>(&self, p: P) -> Result<()> {
+        std::fs::write(p, self.chrome_trace())?;
+        Ok(())
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        PROFILERS.lock().unwrap().retain(|(id, _)| *id != self.id);
+        NUM_PROFILERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>14} {:>16}",
+            "op", "count", "duration (ms)", "bytes"
+        )?;
+        for s in self.summary() {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>14.3} {:>16}",
+                s.op,
+                s.count,
+                s.duration.as_secs_f64() * 1e3,
+                s.bytes
+            )?
+        }
+        Ok(())
+    }
+}
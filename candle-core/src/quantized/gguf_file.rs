@@ -2,10 +2,12 @@
 //!
 //! Spec: https://github.com/philpax/ggml/blob/gguf-spec/docs/gguf.md
 
-use super::{GgmlDType, QTensor};
-use crate::Result;
+use super::{GgmlDType, GgmlType, QTensor};
+use crate::{Error, Result};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 pub const DEFAULT_ALIGNMENT: u64 = 32;
 
@@ -47,6 +49,20 @@ impl VersionedMagic {
     }
 }
 
+// Reads the blocks directly in the buffer used by the tensor to avoid an intermediary copy.
+fn read_blocks<T: GgmlType + Send + Sync + 'static, R: std::io::Read>(
+    reader: &mut R,
+    shape: crate::Shape,
+) -> Result<QTensor> {
+    let len = shape.elem_count() / T::BLCK_SIZE;
+    let mut data = vec![T::zeros(); len];
+    let size_in_bytes = len * std::mem::size_of::<T>();
+    let dst =
+        unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, size_in_bytes) };
+    reader.read_exact(dst)?;
+    QTensor::new(data, shape)
+}
+
 #[derive(Debug)]
 pub struct TensorInfo {
     pub ggml_dtype: GgmlDType,
@@ -67,11 +83,9 @@ impl TensorInfo {
             "the number of elements {tensor_elems} is not divisible by the block size {blck_size}"
         )
         }
-        let size_in_bytes = tensor_elems / blck_size * self.ggml_dtype.type_size();
-        let mut raw_data = vec![0u8; size_in_bytes];
         reader.seek(std::io::SeekFrom::Start(tensor_data_offset + self.offset))?;
-        reader.read_exact(&mut raw_data)?;
-        super::ggml_file::qtensor_from_ggml(self.ggml_dtype, &raw_data, self.shape.dims().to_vec())
+        let shape = self.shape.clone();
+        with_block_type!(self.ggml_dtype, T => read_blocks::<T, R>(reader, shape))
     }
 }
 
@@ -469,6 +483,65 @@ impl Content {
     }
 }
 
+/// A GGUF file mapped in memory. Only the header is read when opening the file, the tensors are
+/// created on demand and borrow their blocks from the mapped file rather than copying them, the
+/// file pages are only loaded when the tensor data is accessed.
+pub struct MmapedContent {
+    content: Content,
+    mmap: Arc<memmap2::Mmap>,
+}
+
+impl MmapedContent {
+    /// Creates a wrapper around a memory mapped file and reads the GGUF header.
+    ///
+    /// # Safety
+    ///
+    /// The unsafe is inherited from [`memmap2::MmapOptions`].
+    pub unsafe fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
+        let p = p.as_ref();
+        let file = std::fs::File::open(p).map_err(|e| Error::from(e).with_path(p))?;
+        let mmap = memmap2::MmapOptions::new()
+            .map(&file)
+            .map_err(|e| Error::from(e).with_path(p))?;
+        let mut cursor = std::io::Cursor::new(&mmap[..]);
+        let content = Content::read(&mut cursor).map_err(|e| e.with_path(p))?;
+        Ok(Self {
+            content,
+            mmap: Arc::new(mmap),
+        })
+    }
+
+    /// The metadata and tensor infos of the file.
+    pub fn content(&self) -> &Content {
+        &self.content
+    }
+
+    /// Returns the tensor named `name`, its blocks are not copied out of the mapped file.
+    pub fn tensor(&self, name: &str) -> Result<QTensor> {
+        let tensor_info = match self.content.tensor_infos.get(name) {
+            Some(tensor_info) => tensor_info,
+            None => crate::bail!("cannot find tensor info for {name}"),
+        };
+        let offset = self
+            .content
+            .tensor_data_offset
+            .checked_add(tensor_info.offset)
+            .and_then(|offset| usize::try_from(offset).ok());
+        let offset = match offset {
+            Some(offset) => offset,
+            None => crate::bail!(
+                "tensor data offset {}+{} for {name} overflows",
+                self.content.tensor_data_offset,
+                tensor_info.offset
+            ),
+        };
+        let shape = tensor_info.shape.clone();
+        with_block_type!(tensor_info.ggml_dtype, T => {
+            QTensor::from_mmap::<_, T>(&self.mmap, offset, shape)
+        })
+    }
+}
+
 fn write_string<W: std::io::Write>(w: &mut W, str: &str) -> Result<()> {
     let bytes = str.as_bytes();
     w.write_u64::<LittleEndian>(bytes.len() as u64)?;
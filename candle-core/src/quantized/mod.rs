@@ -1,5 +1,63 @@
 use crate::{Device, Result, Shape, Tensor};
 
+// Dispatches on the block type used by a ggml dtype, `$t` is bound to this type when evaluating
+// `$e`. This is defined before the submodules so that they can use it.
+macro_rules! with_block_type {
+    ($dtype:expr, $t:ident => $e:expr) => {
+        match $dtype {
+            $crate::quantized::GgmlDType::F32 => {
+                type $t = f32;
+                $e
+            }
+            $crate::quantized::GgmlDType::F16 => {
+                type $t = half::f16;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q4_0 => {
+                type $t = $crate::quantized::k_quants::BlockQ4_0;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q4_1 => {
+                type $t = $crate::quantized::k_quants::BlockQ4_1;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q5_0 => {
+                type $t = $crate::quantized::k_quants::BlockQ5_0;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q5_1 => {
+                type $t = $crate::quantized::k_quants::BlockQ5_1;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q8_0 => {
+                type $t = $crate::quantized::k_quants::BlockQ8_0;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q2K => {
+                type $t = $crate::quantized::k_quants::BlockQ2K;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q3K => {
+                type $t = $crate::quantized::k_quants::BlockQ3K;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q4K => {
+                type $t = $crate::quantized::k_quants::BlockQ4K;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q5K => {
+                type $t = $crate::quantized::k_quants::BlockQ5K;
+                $e
+            }
+            $crate::quantized::GgmlDType::Q6K => {
+                type $t = $crate::quantized::k_quants::BlockQ6K;
+                $e
+            }
+            dtype => $crate::bail!("quantized type {dtype:?} is not supported yet"),
+        }
+    };
+}
+
 #[cfg(target_feature = "avx")]
 pub mod avx;
 pub mod ggml_file;
@@ -146,6 +204,47 @@ impl<T: k_quants::GgmlType + Send + Sync> QuantizedType for Vec<T> {
     }
 }
 
+// Blocks borrowed from a memory mapped file rather than copied to a vector, the map is kept
+// alive by the tensors using it. See [`gguf_file::MmapedContent`].
+struct MmapedBlocks<T> {
+    mmap: std::sync::Arc<memmap2::Mmap>,
+    offset: usize,
+    len: usize,
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> MmapedBlocks<T> {
+    fn as_slice(&self) -> &[T] {
+        // The bounds and the alignment are checked in `QTensor::from_mmap`.
+        unsafe {
+            let ptr = self.mmap.as_ptr().add(self.offset) as *const T;
+            std::slice::from_raw_parts(ptr, self.len)
+        }
+    }
+}
+
+impl<T: k_quants::GgmlType + Send + Sync> QuantizedType for MmapedBlocks<T> {
+    fn matmul_t(&self, mkn: (usize, usize, usize), lhs: &[f32], dst: &mut [f32]) -> Result<()> {
+        k_quants::matmul(mkn, lhs, self.as_slice(), dst)
+    }
+
+    fn dtype(&self) -> GgmlDType {
+        T::DTYPE
+    }
+
+    fn to_float(&self, ys: &mut [f32]) -> Result<()> {
+        T::to_float(self.as_slice(), ys)
+    }
+
+    fn storage_size_in_bytes(&self) -> usize {
+        self.len * std::mem::size_of::<T>()
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        self.as_slice().as_ptr() as *const u8
+    }
+}
+
 impl std::fmt::Debug for QTensor {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "QTensor[{:?}; {:?}]", self.shape, self.dtype())
@@ -179,6 +278,48 @@ impl QTensor {
         })
     }
 
+    /// Creates a tensor using the blocks stored in `mmap` starting at byte `offset`, the data is
+    /// not copied. When the blocks are not suitably aligned, they get copied to a vector
+    /// instead.
+    pub(crate) fn from_mmap<S: Into<Shape>, T: k_quants::GgmlType + Send + Sync + 'static>(
+        mmap: &std::sync::Arc<memmap2::Mmap>,
+        offset: usize,
+        shape: S,
+    ) -> Result<Self> {
+        let shape = shape.into();
+        check_shape::<T>(&shape)?;
+        let len = shape.elem_count() / T::BLCK_SIZE;
+        let size_in_bytes = len * std::mem::size_of::<T>();
+        let bytes = match offset.checked_add(size_in_bytes) {
+            Some(end) => mmap.get(offset..end),
+            None => None,
+        };
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => crate::bail!(
+                "tensor data {offset}+{size_in_bytes} is out of the file bounds {}",
+                mmap.len()
+            ),
+        };
+        let data: Box<dyn QuantizedType> =
+            if bytes.as_ptr().align_offset(std::mem::align_of::<T>()) == 0 {
+                Box::new(MmapedBlocks::<T> {
+                    mmap: mmap.clone(),
+                    offset,
+                    len,
+                    phantom: std::marker::PhantomData,
+                })
+            } else {
+                let mut data = vec![T::zeros(); len];
+                let dst = unsafe {
+                    std::slice::from_raw_parts_mut(data.as_mut_ptr() as *mut u8, size_in_bytes)
+                };
+                dst.copy_from_slice(bytes);
+                Box::new(data)
+            };
+        Ok(Self { data, shape })
+    }
+
     pub fn quantize<T: k_quants::GgmlType + Send + Sync + 'static>(src: &Tensor) -> Result<Self> {
         let shape = src.shape();
         check_shape::<T>(shape)?;
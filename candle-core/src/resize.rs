@@ -0,0 +1,278 @@
+//! Resizing of images with various interpolation modes, following PyTorch's `F.interpolate`.
+use crate::{Result, Tensor};
+
+/// The interpolation mode used by [`Tensor::resize2d`].
+///
+/// When `align_corners` is set, the centers of the corner pixels of the input and output are
+/// aligned, otherwise the corners of these pixels are aligned. The `antialias` flag applies an
+/// antialiasing filter when downsampling, the filter support is scaled by the downsampling factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Take the value of the nearest element, this is the same as [`Tensor::interpolate2d`].
+    Nearest,
+    Bilinear {
+        align_corners: bool,
+        antialias: bool,
+    },
+    /// Cubic convolution with `a = -0.75`, or `a = -0.5` when antialiasing.
+    Bicubic {
+        align_corners: bool,
+        antialias: bool,
+    },
+    /// Average over the input area covered by each output element, this is the same as adaptive
+    /// average pooling.
+    Area,
+}
+
+// https://github.com/pytorch/pytorch/blob/main/aten/src/ATen/native/UpSample.h
+fn compute_scale(in_size: usize, out_size: usize, align_corners: bool) -> f64 {
+    if align_corners {
+        if out_size > 1 {
+            (in_size - 1) as f64 / (out_size - 1) as f64
+        } else {
+            0.
+        }
+    } else {
+        in_size as f64 / out_size as f64
+    }
+}
+
+fn source_index(scale: f64, dst_index: usize, align_corners: bool, cubic: bool) -> f64 {
+    if align_corners {
+        scale * dst_index as f64
+    } else {
+        let src = scale * (dst_index as f64 + 0.5) - 0.5;
+        if !cubic && src < 0. {
+            0.
+        } else {
+            src
+        }
+    }
+}
+
+fn cubic_convolution1(x: f64, a: f64) -> f64 {
+    ((a + 2.) * x - (a + 3.)) * x * x + 1.
+}
+
+fn cubic_convolution2(x: f64, a: f64) -> f64 {
+    ((a * x - 5. * a) * x + 8. * a) * x - 4. * a
+}
+
+fn bilinear_filter(x: f64) -> f64 {
+    let x = x.abs();
+    if x < 1. {
+        1. - x
+    } else {
+        0.
+    }
+}
+
+fn bicubic_filter(x: f64) -> f64 {
+    // The antialiased version uses a = -0.5 for compatibility with PIL.
+    let a = -0.5;
+    let x = x.abs();
+    if x < 1. {
+        cubic_convolution1(x, a)
+    } else if x < 2. {
+        cubic_convolution2(x, a)
+    } else {
+        0.
+    }
+}
+
+// The interpolation taps along a single dimension, output element `i` is the weighted sum of
+// the input elements `indices[i * size + k]` with weights `weights[i * size + k]` for `k` in
+// `0..size`. Outputs that use fewer taps are padded with zero weights.
+struct Taps {
+    indices: Vec<u32>,
+    weights: Vec<f64>,
+    size: usize,
+}
+
+impl Taps {
+    fn new(taps: Vec<Vec<(usize, f64)>>) -> Self {
+        let size = taps.iter().map(|t| t.len()).max().unwrap_or(0);
+        let mut indices = Vec::with_capacity(taps.len() * size);
+        let mut weights = Vec::with_capacity(taps.len() * size);
+        for t in taps.iter() {
+            for k in 0..size {
+                let (idx, w) = t.get(k).copied().unwrap_or((0, 0.));
+                indices.push(idx as u32);
+                weights.push(w)
+            }
+        }
+        Self {
+            indices,
+            weights,
+            size,
+        }
+    }
+}
+
+// Taps of the antialiased interpolation, the filter support gets scaled when downsampling.
+fn antialias_taps(
+    in_size: usize,
+    out_size: usize,
+    align_corners: bool,
+    interp_size: usize,
+    filter: fn(f64) -> f64,
+) -> Vec<Vec<(usize, f64)>> {
+    let scale = compute_scale(in_size, out_size, align_corners);
+    let (support, invscale) = if scale >= 1. {
+        (interp_size as f64 * 0.5 * scale, 1. / scale)
+    } else {
+        (interp_size as f64 * 0.5, 1.)
+    };
+    (0..out_size)
+        .map(|i| {
+            let center = scale * (i as f64 + 0.5);
+            // The casts truncate towards zero, as done in PyTorch.
+            let xmin = f64::max(center - support + 0.5, 0.) as usize;
+            let xmax = usize::min((center + support + 0.5) as usize, in_size);
+            let mut taps: Vec<_> = (xmin..xmax)
+                .map(|j| (j, filter((j as f64 - center + 0.5) * invscale)))
+                .collect();
+            let total: f64 = taps.iter().map(|(_, w)| w).sum();
+            if total != 0. {
+                taps.iter_mut().for_each(|(_, w)| *w /= total)
+            }
+            taps
+        })
+        .collect()
+}
+
+// The interpolation taps along a single dimension, the number of taps per output element only
+// depends on the kernel support so the cost is linear in the output size.
+fn interpolation_taps(in_size: usize, out_size: usize, mode: ResizeMode) -> Taps {
+    let taps = match mode {
+        ResizeMode::Nearest => {
+            let scale = in_size as f64 / out_size as f64;
+            (0..out_size)
+                .map(|i| vec![(usize::min((i as f64 * scale) as usize, in_size - 1), 1.)])
+                .collect()
+        }
+        ResizeMode::Area => (0..out_size)
+            .map(|i| {
+                let start = i * in_size / out_size;
+                let end = ((i + 1) * in_size).div_ceil(out_size);
+                let w = 1. / (end - start) as f64;
+                (start..end).map(|j| (j, w)).collect()
+            })
+            .collect(),
+        ResizeMode::Bilinear {
+            align_corners,
+            antialias: true,
+        } => antialias_taps(in_size, out_size, align_corners, 2, bilinear_filter),
+        ResizeMode::Bicubic {
+            align_corners,
+            antialias: true,
+        } => antialias_taps(in_size, out_size, align_corners, 4, bicubic_filter),
+        ResizeMode::Bilinear {
+            align_corners,
+            antialias: false,
+        } => {
+            let scale = compute_scale(in_size, out_size, align_corners);
+            (0..out_size)
+                .map(|i| {
+                    let src = source_index(scale, i, align_corners, false);
+                    let i0 = usize::min(src as usize, in_size - 1);
+                    let i1 = usize::min(i0 + 1, in_size - 1);
+                    let lambda = src - i0 as f64;
+                    vec![(i0, 1. - lambda), (i1, lambda)]
+                })
+                .collect()
+        }
+        ResizeMode::Bicubic {
+            align_corners,
+            antialias: false,
+        } => {
+            let a = -0.75;
+            let scale = compute_scale(in_size, out_size, align_corners);
+            (0..out_size)
+                .map(|i| {
+                    let src = source_index(scale, i, align_corners, true);
+                    let src_floor = src.floor();
+                    let t = src - src_floor;
+                    let coeffs = [
+                        cubic_convolution2(t + 1., a),
+                        cubic_convolution1(t, a),
+                        cubic_convolution1(1. - t, a),
+                        cubic_convolution2(2. - t, a),
+                    ];
+                    // Out of bounds accesses are clamped to the border.
+                    coeffs
+                        .iter()
+                        .enumerate()
+                        .map(|(k, &coeff)| {
+                            let idx =
+                                (src_floor as i64 - 1 + k as i64).clamp(0, in_size as i64 - 1);
+                            (idx as usize, coeff)
+                        })
+                        .collect()
+                })
+                .collect()
+        }
+    };
+    Taps::new(taps)
+}
+
+impl Tensor {
+    /// Resizes the input tensor to `(target_h, target_w)` using the given interpolation mode.
+    /// The results match PyTorch's `F.interpolate` with the same mode and options.
+    ///
+    /// The input tensor should have four dimensions, `(batch, channels, h, w)`, the returned
+    /// tensor also has four dimensions, `(batch, channels, target_h, target_w)`.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, ResizeMode};
+    /// let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], &Device::Cpu)?;
+    /// let mode = ResizeMode::Bilinear { align_corners: false, antialias: false };
+    /// let t = t.resize2d(3, 4, mode)?;
+    /// assert_eq!(
+    ///     t.squeeze(0)?.squeeze(0)?.to_vec2::<f32>()?,
+    ///     &[[1., 1.25, 1.75, 2.], [2., 2.25, 2.75, 3.], [3., 3.25, 3.75, 4.]]
+    /// );
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn resize2d(&self, target_h: usize, target_w: usize, mode: ResizeMode) -> Result<Self> {
+        let (_b, _c, h, w) = self.dims4()?;
+        if !self.dtype().is_float() {
+            Err(crate::Error::UnsupportedDTypeForOp(self.dtype(), "resize2d").bt())?
+        }
+        if mode == ResizeMode::Nearest {
+            return self.interpolate2d(target_h, target_w);
+        }
+        if h == 0 || w == 0 || target_h == 0 || target_w == 0 {
+            crate::bail!(
+                "resize2d: empty input {:?} or target {target_h}x{target_w}",
+                self.dims()
+            )
+        }
+        // The interpolation is separable, each dimension is resized by gathering the taps of
+        // the output elements and summing them with their weights, the gradients come for free.
+        let resize_dim = |xs: &Tensor, dim: usize, in_size: usize, out_size: usize| {
+            let taps = interpolation_taps(in_size, out_size, mode);
+            let indices = Tensor::from_vec(taps.indices, out_size * taps.size, self.device())?;
+            let mut weights_shape = vec![1; 5];
+            weights_shape[dim] = out_size;
+            weights_shape[dim + 1] = taps.size;
+            let weights = Tensor::from_vec(taps.weights, weights_shape, self.device())?
+                .to_dtype(self.dtype())?;
+            let mut dims = xs.dims().to_vec();
+            dims[dim] = out_size;
+            dims.insert(dim + 1, taps.size);
+            xs.index_select(&indices, dim)?
+                .reshape(dims)?
+                .broadcast_mul(&weights)?
+                .sum(dim + 1)
+        };
+        let mut xs = self.clone();
+        if w != target_w {
+            xs = resize_dim(&xs, 3, w, target_w)?;
+        }
+        if h != target_h {
+            xs = resize_dim(&xs, 2, h, target_h)?;
+        }
+        Ok(xs)
+    }
+}
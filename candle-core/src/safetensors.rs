@@ -1,20 +1,28 @@
-use crate::{DType, Device, Error, Result, Tensor, WithDType};
+use crate::{DType, Device, ElemType, Error, Result, Tensor, WithDType};
 use safetensors::tensor as st;
 use safetensors::tensor::SafeTensors;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 
-impl From<DType> for st::Dtype {
-    fn from(value: DType) -> Self {
+impl TryFrom<DType> for st::Dtype {
+    type Error = Error;
+    fn try_from(value: DType) -> Result<Self> {
         match value {
-            DType::U8 => st::Dtype::U8,
-            DType::U32 => st::Dtype::U32,
-            DType::I64 => st::Dtype::I64,
-            DType::BF16 => st::Dtype::BF16,
-            DType::F16 => st::Dtype::F16,
-            DType::F32 => st::Dtype::F32,
-            DType::F64 => st::Dtype::F64,
+            DType::Bool => Ok(st::Dtype::BOOL),
+            DType::U8 => Ok(st::Dtype::U8),
+            DType::U32 => Ok(st::Dtype::U32),
+            DType::I8 => Ok(st::Dtype::I8),
+            DType::I16 => Ok(st::Dtype::I16),
+            DType::I32 => Ok(st::Dtype::I32),
+            DType::I64 => Ok(st::Dtype::I64),
+            DType::BF16 => Ok(st::Dtype::BF16),
+            DType::F16 => Ok(st::Dtype::F16),
+            DType::F32 => Ok(st::Dtype::F32),
+            DType::F64 => Ok(st::Dtype::F64),
+            DType::C64 | DType::C128 => {
+                Err(Error::UnsupportedDTypeForOp(value, "safetensors").bt())
+            }
         }
     }
 }
@@ -23,8 +31,12 @@ impl TryFrom<st::Dtype> for DType {
     type Error = Error;
     fn try_from(value: st::Dtype) -> Result<Self> {
         match value {
+            st::Dtype::BOOL => Ok(DType::Bool),
             st::Dtype::U8 => Ok(DType::U8),
             st::Dtype::U32 => Ok(DType::U32),
+            st::Dtype::I8 => Ok(DType::I8),
+            st::Dtype::I16 => Ok(DType::I16),
+            st::Dtype::I32 => Ok(DType::I32),
             st::Dtype::I64 => Ok(DType::I64),
             st::Dtype::BF16 => Ok(DType::BF16),
             st::Dtype::F16 => Ok(DType::F16),
@@ -35,56 +47,51 @@ impl TryFrom<st::Dtype> for DType {
     }
 }
 
-impl st::View for Tensor {
-    fn dtype(&self) -> st::Dtype {
-        self.dtype().into()
-    }
-    fn shape(&self) -> &[usize] {
-        self.shape().dims()
-    }
-
-    fn data(&self) -> Cow<[u8]> {
-        // This copies data from GPU to CPU.
-        // TODO: Avoid the unwrap here.
-        Cow::Owned(convert_back(self).unwrap())
-    }
+/// A tensor converted to the safetensors format, this is what gets passed to the `safetensors`
+/// crate when serializing. Its `View` trait cannot report errors, so the dtype is checked and the
+/// data is copied to the cpu when building the view.
+pub struct SafetensorsView {
+    dtype: st::Dtype,
+    shape: Vec<usize>,
+    data: Vec<u8>,
+}
 
-    fn data_len(&self) -> usize {
-        let n: usize = self.shape().elem_count();
-        let bytes_per_element = self.dtype().size_in_bytes();
-        n * bytes_per_element
+impl TryFrom<&Tensor> for SafetensorsView {
+    type Error = Error;
+    fn try_from(tensor: &Tensor) -> Result<Self> {
+        Ok(Self {
+            dtype: st::Dtype::try_from(tensor.dtype())?,
+            shape: tensor.dims().to_vec(),
+            data: convert_back(tensor)?,
+        })
     }
 }
 
-impl st::View for &Tensor {
+impl st::View for SafetensorsView {
     fn dtype(&self) -> st::Dtype {
-        (*self).dtype().into()
+        self.dtype
     }
     fn shape(&self) -> &[usize] {
-        self.dims()
+        &self.shape
     }
 
-    fn data(&self) -> Cow<[u8]> {
-        // This copies data from GPU to CPU.
-        // TODO: Avoid the unwrap here.
-        Cow::Owned(convert_back(self).unwrap())
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.data)
     }
 
     fn data_len(&self) -> usize {
-        let n: usize = self.dims().iter().product();
-        let bytes_per_element = (*self).dtype().size_in_bytes();
-        n * bytes_per_element
+        self.data.len()
     }
 }
 
 impl Tensor {
     pub fn save_safetensors<P: AsRef<Path>>(&self, name: &str, filename: P) -> Result<()> {
-        let data = [(name, self.clone())];
+        let data = [(name, SafetensorsView::try_from(self)?)];
         Ok(st::serialize_to_file(data, &None, filename.as_ref())?)
     }
 }
 
-fn convert_slice<T: WithDType>(data: &[u8], shape: &[usize], device: &Device) -> Result<Tensor> {
+fn convert_slice<T: ElemType>(data: &[u8], shape: &[usize], device: &Device) -> Result<Tensor> {
     let size_in_bytes = T::DTYPE.size_in_bytes();
     let elem_count = data.len() / size_in_bytes;
     if (data.as_ptr() as usize) % size_in_bytes == 0 {
@@ -109,7 +116,7 @@ fn convert_slice<T: WithDType>(data: &[u8], shape: &[usize], device: &Device) ->
     }
 }
 
-fn convert_slice_with_cast<T: Sized + Copy, U: WithDType, F: Fn(T) -> Result<U>>(
+fn convert_slice_with_cast<T: Sized + Copy, U: ElemType, F: Fn(T) -> Result<U>>(
     data: &[u8],
     shape: &[usize],
     device: &Device,
@@ -141,7 +148,7 @@ fn convert_slice_with_cast<T: Sized + Copy, U: WithDType, F: Fn(T) -> Result<U>>
     }
 }
 
-fn convert_with_cast_<T: Sized + Copy, U: WithDType, F: Fn(T) -> Result<U>>(
+fn convert_with_cast_<T: Sized + Copy, U: ElemType, F: Fn(T) -> Result<U>>(
     view: &st::TensorView<'_>,
     device: &Device,
     conv: F,
@@ -153,7 +160,7 @@ fn convert_<T: WithDType>(view: &st::TensorView<'_>, device: &Device) -> Result<
     convert_slice::<T>(view.data(), view.shape(), device)
 }
 
-fn convert_back_<T: WithDType>(mut vs: Vec<T>) -> Vec<u8> {
+fn convert_back_<T: ElemType>(mut vs: Vec<T>) -> Vec<u8> {
     let size_in_bytes = T::DTYPE.size_in_bytes();
     let length = vs.len() * size_in_bytes;
     let capacity = vs.capacity() * size_in_bytes;
@@ -185,29 +192,38 @@ impl Tensor {
         device: &Device,
     ) -> Result<Self> {
         match dtype {
+            DType::Bool => {
+                // Booleans are read byte per byte so that only 0 and 1 values end up in the tensor.
+                convert_slice_with_cast::<u8, bool, _>(data, shape, device, |x| Ok(x != 0))
+            }
             DType::U8 => convert_slice::<u8>(data, shape, device),
             DType::U32 => convert_slice::<u32>(data, shape, device),
+            DType::I8 => convert_slice::<i8>(data, shape, device),
+            DType::I16 => convert_slice::<i16>(data, shape, device),
+            DType::I32 => convert_slice::<i32>(data, shape, device),
             DType::I64 => convert_slice::<i64>(data, shape, device),
             DType::BF16 => convert_slice::<half::bf16>(data, shape, device),
             DType::F16 => convert_slice::<half::f16>(data, shape, device),
             DType::F32 => convert_slice::<f32>(data, shape, device),
             DType::F64 => convert_slice::<f64>(data, shape, device),
+            DType::C64 => convert_slice::<num_complex::Complex<f32>>(data, shape, device),
+            DType::C128 => convert_slice::<num_complex::Complex<f64>>(data, shape, device),
         }
     }
 }
 
 fn convert(view: &st::TensorView<'_>, device: &Device) -> Result<Tensor> {
     match view.dtype() {
+        st::Dtype::BOOL => convert_with_cast_::<u8, bool, _>(view, device, |x| Ok(x != 0)),
         st::Dtype::U8 => convert_::<u8>(view, device),
         st::Dtype::U16 => {
             let conv = |x| Ok(u32::from(x));
             convert_with_cast_::<u16, u32, _>(view, device, conv)
         }
         st::Dtype::U32 => convert_::<u32>(view, device),
-        st::Dtype::I32 => {
-            let conv = |x| Ok(i64::from(x));
-            convert_with_cast_::<i32, i64, _>(view, device, conv)
-        }
+        st::Dtype::I8 => convert_::<i8>(view, device),
+        st::Dtype::I16 => convert_::<i16>(view, device),
+        st::Dtype::I32 => convert_::<i32>(view, device),
         st::Dtype::I64 => convert_::<i64>(view, device),
         st::Dtype::BF16 => convert_::<half::bf16>(view, device),
         st::Dtype::F16 => convert_::<half::f16>(view, device),
@@ -221,13 +237,20 @@ fn convert_back(tensor: &Tensor) -> Result<Vec<u8>> {
     // TODO: This makes an unnecessary copy when the tensor is on the cpu.
     let tensor = tensor.flatten_all()?;
     match tensor.dtype() {
+        DType::Bool => Ok(convert_back_::<bool>(tensor.to_vec1()?)),
         DType::U8 => Ok(convert_back_::<u8>(tensor.to_vec1()?)),
         DType::U32 => Ok(convert_back_::<u32>(tensor.to_vec1()?)),
+        DType::I8 => Ok(convert_back_::<i8>(tensor.to_vec1()?)),
+        DType::I16 => Ok(convert_back_::<i16>(tensor.to_vec1()?)),
+        DType::I32 => Ok(convert_back_::<i32>(tensor.to_vec1()?)),
         DType::I64 => Ok(convert_back_::<i64>(tensor.to_vec1()?)),
         DType::F16 => Ok(convert_back_::<half::f16>(tensor.to_vec1()?)),
         DType::BF16 => Ok(convert_back_::<half::bf16>(tensor.to_vec1()?)),
         DType::F32 => Ok(convert_back_::<f32>(tensor.to_vec1()?)),
         DType::F64 => Ok(convert_back_::<f64>(tensor.to_vec1()?)),
+        DType::C64 | DType::C128 => {
+            Err(Error::UnsupportedDTypeForOp(tensor.dtype(), "safetensors").bt())
+        }
     }
 }
 
@@ -248,6 +271,10 @@ pub fn save<K: AsRef<str> + Ord + std::fmt::Display, P: AsRef<Path>>(
     tensors: &HashMap<K, Tensor>,
     filename: P,
 ) -> Result<()> {
+    let tensors = tensors
+        .iter()
+        .map(|(name, tensor)| Ok((name, SafetensorsView::try_from(tensor)?)))
+        .collect::<Result<Vec<_>>>()?;
     Ok(st::serialize_to_file(tensors, &None, filename.as_ref())?)
 }
 
@@ -438,4 +465,32 @@ mod tests {
         assert_eq!(bytes, b"x\0\0\0\0\0\0\0{\"t\":{\"dtype\":\"F32\",\"shape\":[2,2],\"data_offsets\":[0,16]},\"u\":{\"dtype\":\"F32\",\"shape\":[1,2],\"data_offsets\":[16,24]}}      \0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0");
         std::fs::remove_file("multi.safetensors").unwrap();
     }
+
+    #[test]
+    fn save_load_int_and_bool_tensors() {
+        let mask = Tensor::new(&[true, false, true], &Device::Cpu).unwrap();
+        let ids = Tensor::new(&[-1i32, 0, 42], &Device::Cpu).unwrap();
+        let map: HashMap<_, _> = [("mask", mask), ("ids", ids)].into_iter().collect();
+        save(&map, "int_bool.safetensors").unwrap();
+
+        let weights = load("int_bool.safetensors", &Device::Cpu).unwrap();
+        let mask = weights.get("mask").unwrap();
+        assert_eq!(mask.dtype(), DType::Bool);
+        assert_eq!(mask.to_vec1::<bool>().unwrap(), [true, false, true]);
+        let ids = weights.get("ids").unwrap();
+        assert_eq!(ids.dtype(), DType::I32);
+        assert_eq!(ids.to_vec1::<i32>().unwrap(), [-1, 0, 42]);
+        std::fs::remove_file("int_bool.safetensors").unwrap();
+    }
+
+    #[test]
+    fn save_complex_tensor_errors() {
+        let re = Tensor::new(&[1f32, 2.], &Device::Cpu).unwrap();
+        let z = Tensor::complex(&re, &re).unwrap();
+        assert!(SafetensorsView::try_from(&z).is_err());
+        assert!(z.save_safetensors("z", "complex.safetensors").is_err());
+        let map: HashMap<_, _> = [("z", z)].into_iter().collect();
+        assert!(save(&map, "complex.safetensors").is_err());
+        assert!(!std::path::Path::new("complex.safetensors").exists());
+    }
 }
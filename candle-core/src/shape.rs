@@ -1,81 +1,118 @@
 //! The shape of a tensor is a tuple with the size of each of its dimensions.
+//!
+//! The dimensions can optionally be named, e.g. `("batch", "seq", "hidden")`, see
+//! [`Shape::with_names`] and [`crate::Tensor::rename`]. The names propagate through the tensor
+//! ops, can be used in place of a dimension index, and appear in the shape related errors. They
+//! are not taken into account when comparing shapes.
 #![allow(clippy::redundant_closure_call)]
 use crate::{Error, Result};
+use std::sync::Arc;
 
-#[derive(Clone, PartialEq, Eq)]
-pub struct Shape(Vec<usize>);
+#[derive(Clone)]
+pub struct Shape {
+    dims: Vec<usize>,
+    // `None` when no dimension is named, otherwise there is one entry per dimension. The names
+    // are stored out of line to keep the shapes, and so the errors that embed them, small.
+    names: Option<Arc<[Option<Arc<str>>]>>,
+}
+
+impl PartialEq for Shape {
+    fn eq(&self, other: &Self) -> bool {
+        self.dims == other.dims
+    }
+}
 
-pub const SCALAR: Shape = Shape(vec![]);
+impl Eq for Shape {}
+
+pub const SCALAR: Shape = Shape {
+    dims: vec![],
+    names: None,
+};
 
 impl std::fmt::Debug for Shape {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", &self.dims())
+        match &self.names {
+            None => write!(f, "{:?}", &self.dims()),
+            Some(names) => {
+                write!(f, "[")?;
+                for (i, (dim, name)) in self.dims.iter().zip(names.iter()).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match name {
+                        Some(name) => write!(f, "{name}: {dim}")?,
+                        None => write!(f, "{dim}")?,
+                    }
+                }
+                write!(f, "]")
+            }
+        }
     }
 }
 
 impl<const C: usize> From<&[usize; C]> for Shape {
     fn from(dims: &[usize; C]) -> Self {
-        Self(dims.to_vec())
+        Self::new(dims.to_vec())
     }
 }
 
 impl From<&[usize]> for Shape {
     fn from(dims: &[usize]) -> Self {
-        Self(dims.to_vec())
+        Self::new(dims.to_vec())
     }
 }
 
 impl From<&Shape> for Shape {
     fn from(shape: &Shape) -> Self {
-        Self(shape.0.to_vec())
+        shape.clone()
     }
 }
 
 impl From<()> for Shape {
     fn from(_: ()) -> Self {
-        Self(vec![])
+        Self::new(vec![])
     }
 }
 
 impl From<usize> for Shape {
     fn from(d1: usize) -> Self {
-        Self(vec![d1])
+        Self::new(vec![d1])
     }
 }
 
 impl From<(usize,)> for Shape {
     fn from(d1: (usize,)) -> Self {
-        Self(vec![d1.0])
+        Self::new(vec![d1.0])
     }
 }
 
 impl From<(usize, usize)> for Shape {
     fn from(d12: (usize, usize)) -> Self {
-        Self(vec![d12.0, d12.1])
+        Self::new(vec![d12.0, d12.1])
     }
 }
 
 impl From<(usize, usize, usize)> for Shape {
     fn from(d123: (usize, usize, usize)) -> Self {
-        Self(vec![d123.0, d123.1, d123.2])
+        Self::new(vec![d123.0, d123.1, d123.2])
     }
 }
 
 impl From<(usize, usize, usize, usize)> for Shape {
     fn from(d1234: (usize, usize, usize, usize)) -> Self {
-        Self(vec![d1234.0, d1234.1, d1234.2, d1234.3])
+        Self::new(vec![d1234.0, d1234.1, d1234.2, d1234.3])
     }
 }
 
 impl From<(usize, usize, usize, usize, usize)> for Shape {
     fn from(d12345: (usize, usize, usize, usize, usize)) -> Self {
-        Self(vec![d12345.0, d12345.1, d12345.2, d12345.3, d12345.4])
+        Self::new(vec![d12345.0, d12345.1, d12345.2, d12345.3, d12345.4])
     }
 }
 
 impl From<(usize, usize, usize, usize, usize, usize)> for Shape {
     fn from(d123456: (usize, usize, usize, usize, usize, usize)) -> Self {
-        Self(vec![
+        Self::new(vec![
             d123456.0, d123456.1, d123456.2, d123456.3, d123456.4, d123456.5,
         ])
     }
@@ -83,7 +120,7 @@ impl From<(usize, usize, usize, usize, usize, usize)> for Shape {
 
 impl From<Vec<usize>> for Shape {
     fn from(dims: Vec<usize>) -> Self {
-        Self(dims)
+        Self::new(dims)
     }
 }
 
@@ -104,7 +141,7 @@ macro_rules! extract_dims {
 
         impl Shape {
             pub fn $fn_name(&self) -> Result<$out_type> {
-                $fn_name(self.0.as_slice())
+                $fn_name(self.dims.as_slice())
             }
         }
 
@@ -124,34 +161,38 @@ macro_rules! extract_dims {
 }
 
 impl Shape {
+    fn new(dims: Vec<usize>) -> Self {
+        Self { dims, names: None }
+    }
+
     pub fn from_dims(dims: &[usize]) -> Self {
-        Self(dims.to_vec())
+        Self::new(dims.to_vec())
     }
 
     /// The rank is the number of dimensions, 0 for a scalar value, 1 for a vector, etc.
     pub fn rank(&self) -> usize {
-        self.0.len()
+        self.dims.len()
     }
 
     pub fn into_dims(self) -> Vec<usize> {
-        self.0
+        self.dims
     }
 
     /// The dimensions as a slice of `usize`.
     pub fn dims(&self) -> &[usize] {
-        &self.0
+        &self.dims
     }
 
     /// The total number of elements, this is the product of all dimension sizes.
     pub fn elem_count(&self) -> usize {
-        self.0.iter().product()
+        self.dims.iter().product()
     }
 
     /// The strides given in number of elements for a contiguous n-dimensional
     /// arrays using this shape.
     pub(crate) fn stride_contiguous(&self) -> Vec<usize> {
         let mut stride: Vec<_> = self
-            .0
+            .dims
             .iter()
             .rev()
             .scan(1, |prod, u| {
@@ -166,11 +207,11 @@ impl Shape {
 
     /// Returns true if the strides are C contiguous (aka row major).
     pub fn is_contiguous(&self, stride: &[usize]) -> bool {
-        if self.0.len() != stride.len() {
+        if self.dims.len() != stride.len() {
             return false;
         }
         let mut acc = 1;
-        for (&stride, &dim) in stride.iter().zip(self.0.iter()).rev() {
+        for (&stride, &dim) in stride.iter().zip(self.dims.iter()).rev() {
             if stride != acc {
                 return false;
             }
@@ -181,11 +222,11 @@ impl Shape {
 
     /// Returns true if the strides are Fortran contiguous (aka column major).
     pub fn is_fortran_contiguous(&self, stride: &[usize]) -> bool {
-        if self.0.len() != stride.len() {
+        if self.dims.len() != stride.len() {
             return false;
         }
         let mut acc = 1;
-        for (&stride, &dim) in stride.iter().zip(self.0.iter()) {
+        for (&stride, &dim) in stride.iter().zip(self.dims.iter()) {
             if stride != acc {
                 return false;
             }
@@ -197,10 +238,159 @@ impl Shape {
     /// Modifies the shape by adding a list of additional dimensions at the end of the existing
     /// dimensions.
     pub fn extend(mut self, additional_dims: &[usize]) -> Self {
-        self.0.extend(additional_dims);
+        self.dims.extend(additional_dims);
+        if let Some(names) = self.names.take() {
+            let mut names = names.to_vec();
+            names.resize(self.dims.len(), None);
+            self.names = Some(names.into())
+        }
+        self
+    }
+
+    /// Attaches names to the dimensions of the shape, there must be one name per dimension and
+    /// `None` can be used to leave a dimension unnamed.
+    ///
+    /// ```rust
+    /// use candle_core::Shape;
+    /// let shape = Shape::from((2, 5, 8)).with_names(("batch", "seq", "hidden"))?;
+    /// assert_eq!(shape.name(1), Some("seq"));
+    /// // The names can also be built at runtime.
+    /// let hidden = format!("hidden{}", 0);
+    /// let shape = shape.with_names((None, "seq", hidden))?;
+    /// assert_eq!(format!("{shape:?}"), "[2, seq: 5, hidden0: 8]");
+    /// // Names are ignored when comparing shapes.
+    /// assert_eq!(shape, Shape::from((2, 5, 8)));
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn with_names<N: DimNames>(self, names: N) -> Result<Self> {
+        let names = names.to_names();
+        if names.len() != self.rank() {
+            crate::bail!(
+                "with_names: got {} names {names:?} for shape {:?}",
+                names.len(),
+                self.dims
+            )
+        }
+        for (i, name) in names.iter().enumerate() {
+            if name.is_some() && names[..i].contains(name) {
+                crate::bail!("with_names: duplicate name {name:?} in {names:?}")
+            }
+        }
+        Ok(Self::new(self.dims).set_names(names))
+    }
+
+    /// Removes the names of all the dimensions.
+    pub fn without_names(mut self) -> Self {
+        self.names = None;
+        self
+    }
+
+    /// The names of the dimensions, `None` if no dimension is named.
+    pub fn names(&self) -> Option<&[Option<Arc<str>>]> {
+        self.names.as_deref()
+    }
+
+    /// The name of dimension `dim` if any.
+    pub fn name(&self, dim: usize) -> Option<&str> {
+        self.names
+            .as_ref()
+            .and_then(|names| names.get(dim)?.as_deref())
+    }
+
+    /// The index of the dimension named `name` if any.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names
+            .as_ref()
+            .and_then(|names| names.iter().position(|n| n.as_deref() == Some(name)))
+    }
+
+    // Returns a shared handle on the name of dimension `dim` if any.
+    fn name_arc(&self, dim: usize) -> Option<Arc<str>> {
+        self.names
+            .as_ref()
+            .and_then(|names| names.get(dim)?.clone())
+    }
+
+    fn set_names(mut self, names: Vec<Option<Arc<str>>>) -> Self {
+        self.names = if names.iter().all(|n| n.is_none()) {
+            None
+        } else {
+            Some(names.into())
+        };
         self
     }
 
+    /// Returns a shape using `dims` where dimension `i` gets the name of dimension `src[i]` of
+    /// this shape, if any.
+    pub(crate) fn rearranged<I>(&self, dims: Vec<usize>, src: I) -> Self
+    where
+        I: IntoIterator<Item = Option<usize>>,
+    {
+        let shape = Self::new(dims);
+        if self.names.is_none() {
+            return shape;
+        }
+        let names = src
+            .into_iter()
+            .map(|i| i.and_then(|i| self.name_arc(i)))
+            .collect();
+        shape.set_names(names)
+    }
+
+    /// Returns a shape using `dims` and the names of this shape, the names are dropped if the
+    /// number of dimensions changes.
+    pub(crate) fn resized(&self, dims: Vec<usize>) -> Self {
+        let names = if dims.len() == self.rank() {
+            self.names.clone()
+        } else {
+            None
+        };
+        Self { dims, names }
+    }
+
+    /// The names of the dimensions of the result of a binary op between `self` and `rhs`, the
+    /// dimensions are aligned on the right as for broadcasting. When the two sides use
+    /// different names for the same dimension, the resulting dimension is left unnamed.
+    fn merged_names(&self, rhs: &Self, rank: usize) -> Option<Vec<Option<Arc<str>>>> {
+        if self.names.is_none() && rhs.names.is_none() {
+            return None;
+        }
+        let name = |s: &Self, rev_idx: usize| {
+            if s.rank() < rev_idx {
+                None
+            } else {
+                s.name_arc(s.rank() - rev_idx)
+            }
+        };
+        let names = (0..rank)
+            .map(
+                |idx| match (name(self, rank - idx), name(rhs, rank - idx)) {
+                    (Some(l), Some(r)) if l != r => None,
+                    (l, r) => l.or(r),
+                },
+            )
+            .collect();
+        Some(names)
+    }
+
+    /// Returns `self` with the names merged with the names of `rhs` if both shapes have the
+    /// same dims, errors otherwise. This is to be used for binary pointwise ops that do not
+    /// broadcast.
+    pub(crate) fn same_shape_binary_op(&self, rhs: &Self, op: &'static str) -> Result<Shape> {
+        if self != rhs {
+            Err(Error::ShapeMismatchBinaryOp {
+                lhs: self.clone(),
+                rhs: rhs.clone(),
+                op,
+            }
+            .bt())?
+        }
+        match self.merged_names(rhs, self.rank()) {
+            None => Ok(self.clone()),
+            Some(names) => Ok(Self::new(self.dims.clone()).set_names(names)),
+        }
+    }
+
     /// Check whether the two shapes are compatible for broadcast, and if it is the case return the
     /// broadcasted shape. This is to be used for binary pointwise ops.
     pub fn broadcast_shape_binary_op(&self, rhs: &Self, op: &'static str) -> Result<Shape> {
@@ -238,7 +428,28 @@ impl Shape {
                 .bt())?
             }
         }
-        Ok(Shape::from(bcast_dims))
+        match lhs.merged_names(rhs, bcast_ndims) {
+            None => Ok(Shape::from(bcast_dims)),
+            Some(names) => Ok(Shape::new(bcast_dims).set_names(names)),
+        }
+    }
+
+    /// The shape of the result of a matmul between `self` and `rhs`, the two shapes must have
+    /// the same rank, at least 2. The names of the batch dimensions are merged, the names of the
+    /// two last dimensions come from the last dimension of each side.
+    pub(crate) fn matmul_shape(&self, rhs: &Self) -> Shape {
+        let rank = self.rank();
+        let mut dims = self.dims[..rank - 1].to_vec();
+        dims.push(rhs.dims[rank - 1]);
+        let shape = Self::new(dims);
+        match self.merged_names(rhs, rank) {
+            None => shape,
+            Some(mut names) => {
+                names[rank - 2] = self.name_arc(rank - 2);
+                names[rank - 1] = rhs.name_arc(rank - 1);
+                shape.set_names(names)
+            }
+        }
     }
 
     pub(crate) fn broadcast_shape_matmul(&self, rhs: &Self) -> Result<(Shape, Shape)> {
@@ -254,15 +465,55 @@ impl Shape {
             crate::bail!("different inner dimensions in broadcast matmul {lhs:?} {rhs:?}")
         }
 
-        let lhs_b = Self::from(&lhs_dims[..lhs_dims.len() - 2]);
-        let rhs_b = Self::from(&rhs_dims[..rhs_dims.len() - 2]);
+        let (l_rank, r_rank) = (lhs_dims.len(), rhs_dims.len());
+        let lhs_b = lhs.rearranged(lhs_dims[..l_rank - 2].to_vec(), (0..l_rank - 2).map(Some));
+        let rhs_b = rhs.rearranged(rhs_dims[..r_rank - 2].to_vec(), (0..r_rank - 2).map(Some));
         let bcast = lhs_b.broadcast_shape_binary_op(&rhs_b, "broadcast_matmul")?;
         let bcast_dims = bcast.dims();
+        let b_rank = bcast_dims.len();
 
-        let bcast_lhs = [bcast_dims, &[m, lhs_k]].concat();
-        let bcast_rhs = [bcast_dims, &[rhs_k, n]].concat();
-        Ok((Shape::from(bcast_lhs), Shape::from(bcast_rhs)))
-    }
+        let bcast_names = |s: &Self, rank: usize| {
+            (0..b_rank)
+                .map(|i| bcast.name_arc(i))
+                .chain([s.name_arc(rank - 2), s.name_arc(rank - 1)])
+                .collect::<Vec<_>>()
+        };
+        let bcast_lhs = Self::new([bcast_dims, &[m, lhs_k]].concat());
+        let bcast_rhs = Self::new([bcast_dims, &[rhs_k, n]].concat());
+        Ok((
+            bcast_lhs.set_names(bcast_names(lhs, l_rank)),
+            bcast_rhs.set_names(bcast_names(rhs, r_rank)),
+        ))
+    }
+}
+
+// Points at the first dimension that differs between two shapes when one of them has named
+// dimensions, the dimensions are aligned on the right as for broadcasting.
+pub(crate) fn mismatch_details(lhs: &Shape, rhs: &Shape) -> String {
+    if lhs.names.is_none() && rhs.names.is_none() {
+        return String::new();
+    }
+    let rank = usize::min(lhs.rank(), rhs.rank());
+    let (l_off, r_off) = (lhs.rank() - rank, rhs.rank() - rank);
+    let differs = |i: usize| lhs.dims[l_off + i] != rhs.dims[r_off + i];
+    let broadcastable = |i: usize| lhs.dims[l_off + i] == 1 || rhs.dims[r_off + i] == 1;
+    let i = (0..rank)
+        .find(|&i| differs(i) && !broadcastable(i))
+        .or_else(|| (0..rank).find(|&i| differs(i)));
+    let i = match i {
+        None => return String::new(),
+        Some(i) => i,
+    };
+    let (l_dim, r_dim) = (l_off + i, r_off + i);
+    let name = match (lhs.name(l_dim), rhs.name(r_dim)) {
+        (Some(l), Some(r)) if l != r => format!("{l}/{r}"),
+        (Some(n), _) | (_, Some(n)) => n.to_string(),
+        (None, None) => format!("{l_dim}"),
+    };
+    format!(
+        ", mismatch on dim {name}: {} vs {}",
+        lhs.dims[l_dim], rhs.dims[r_dim]
+    )
 }
 
 pub trait Dim {
@@ -300,6 +551,25 @@ impl Dim for usize {
     }
 }
 
+/// Dimensions can be referred to by name, see [`Shape::with_names`].
+impl Dim for &str {
+    fn to_index(&self, shape: &Shape, op: &'static str) -> Result<usize> {
+        match shape.index_of(self) {
+            Some(dim) => Ok(dim),
+            None => Err(Error::UnknownDimName {
+                shape: shape.clone(),
+                name: self.to_string(),
+                op,
+            }
+            .bt())?,
+        }
+    }
+
+    fn to_index_plus_one(&self, shape: &Shape, op: &'static str) -> Result<usize> {
+        self.to_index(shape, op)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum D {
     Minus1,
@@ -456,6 +726,91 @@ impl<D1: Dim, D2: Dim, D3: Dim, D4: Dim, D5: Dim, D6: Dim> Dims for (D1, D2, D3,
     }
 }
 
+/// A dimension name, either a string or `None` for an unnamed dimension. The names can be
+/// built at runtime, e.g. from a `String`.
+pub trait DimName {
+    fn to_name(self) -> Option<Arc<str>>;
+}
+
+impl DimName for &str {
+    fn to_name(self) -> Option<Arc<str>> {
+        Some(self.into())
+    }
+}
+
+impl DimName for String {
+    fn to_name(self) -> Option<Arc<str>> {
+        Some(self.into())
+    }
+}
+
+impl DimName for &String {
+    fn to_name(self) -> Option<Arc<str>> {
+        Some(self.as_str().into())
+    }
+}
+
+impl DimName for Arc<str> {
+    fn to_name(self) -> Option<Arc<str>> {
+        Some(self)
+    }
+}
+
+impl DimName for Option<&str> {
+    fn to_name(self) -> Option<Arc<str>> {
+        self.map(|n| n.into())
+    }
+}
+
+/// A list of dimension names, one per dimension and `None` for the unnamed ones, see
+/// [`Shape::with_names`].
+pub trait DimNames {
+    fn to_names(self) -> Vec<Option<Arc<str>>>;
+}
+
+impl DimNames for &str {
+    fn to_names(self) -> Vec<Option<Arc<str>>> {
+        vec![self.to_name()]
+    }
+}
+
+impl<N: DimName> DimNames for Vec<N> {
+    fn to_names(self) -> Vec<Option<Arc<str>>> {
+        self.into_iter().map(|n| n.to_name()).collect()
+    }
+}
+
+impl<N: DimName + Clone> DimNames for &[N] {
+    fn to_names(self) -> Vec<Option<Arc<str>>> {
+        self.iter().map(|n| n.clone().to_name()).collect()
+    }
+}
+
+impl<N: DimName, const C: usize> DimNames for [N; C] {
+    fn to_names(self) -> Vec<Option<Arc<str>>> {
+        self.into_iter().map(|n| n.to_name()).collect()
+    }
+}
+
+macro_rules! dim_names_tuple {
+    ($($n:ident),*) => {
+        impl<$($n: DimName),*> DimNames for ($($n,)*) {
+            #[allow(non_snake_case)]
+            fn to_names(self) -> Vec<Option<Arc<str>>> {
+                let ($($n,)*) = self;
+                vec![$($n.to_name()),*]
+            }
+        }
+    };
+}
+
+dim_names_tuple!(N1);
+dim_names_tuple!(N1, N2);
+dim_names_tuple!(N1, N2, N3);
+dim_names_tuple!(N1, N2, N3, N4);
+dim_names_tuple!(N1, N2, N3, N4, N5);
+dim_names_tuple!(N1, N2, N3, N4, N5, N6);
+
 extract_dims!(dims0, 0, |_: &[usize]| (), ());
 extract_dims!(dims1, 1, |d: &[usize]| d[0], usize);
 extract_dims!(dims2, 2, |d: &[usize]| (d[0], d[1]), (usize, usize));
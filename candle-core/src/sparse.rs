@@ -0,0 +1,361 @@
+//! Sparse tensors using the COO and CSR formats.
+//!
+//! A [`SparseTensor`] only stores its non-zero values together with their indexes, these are
+//! regular dense tensors so the sparse operations are differentiable with respect to the values
+//! and to the dense operands.
+use crate::shape::Dim;
+use crate::{bail, DType, Result, Shape, Tensor};
+
+/// The indexes of the non-zero values of a [`SparseTensor`], these are `u32` tensors.
+#[derive(Debug, Clone)]
+pub enum SparseIndices {
+    /// Coordinate format, a `(rank, nnz)` tensor where column `i` holds the coordinates of the
+    /// value `i`. The values are in no specific order and duplicate coordinates are summed.
+    Coo(Tensor),
+    /// Compressed sparse row format for matrices, the values of row `r` are at positions
+    /// `crow_indices[r]..crow_indices[r + 1]` and `col_indices` holds their columns.
+    Csr {
+        crow_indices: Tensor,
+        col_indices: Tensor,
+    },
+}
+
+/// A tensor where only the non-zero values are stored.
+#[derive(Debug, Clone)]
+pub struct SparseTensor {
+    indices: SparseIndices,
+    values: Tensor,
+    shape: Shape,
+}
+
+// Converts integer indexes to `u32`, the callers check that the indexes are in bounds as negative
+// values would wrap around.
+fn index_values(indices: &Tensor) -> Result<Tensor> {
+    match indices.dtype() {
+        dtype if dtype.is_int() => indices.to_dtype(DType::U32),
+        dtype => bail!("sparse indices should use an integer dtype, got {dtype:?}"),
+    }
+}
+
+impl SparseTensor {
+    /// Creates a sparse tensor in the coordinate format, `indices` has shape `(rank, nnz)` and
+    /// `values` has shape `(nnz,)`.
+    ///
+    /// ```rust
+    /// use candle_core::{SparseTensor, Tensor, Device};
+    /// let dev = &Device::Cpu;
+    /// let indices = Tensor::new(&[[0u32, 1, 1], [2, 0, 2]], dev)?;
+    /// let values = Tensor::new(&[3f32, 4., 5.], dev)?;
+    /// let sp = SparseTensor::coo(&indices, &values, (2, 3))?;
+    /// assert_eq!(sp.to_dense()?.to_vec2::<f32>()?, &[[0., 0., 3.], [4., 0., 5.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn coo<S: Into<Shape>>(indices: &Tensor, values: &Tensor, shape: S) -> Result<Self> {
+        let shape = shape.into();
+        let (rank, nnz) = indices.dims2()?;
+        if rank != shape.rank() || values.dims1()? != nnz {
+            bail!(
+                "sparse coo: unexpected indices {:?} or values {:?} for shape {shape:?}",
+                indices.shape(),
+                values.shape()
+            )
+        }
+        if shape.elem_count() > u32::MAX as usize {
+            bail!("sparse coo: shape {shape:?} has too many elements")
+        }
+        let coords = indices.to_dtype(DType::I64)?.to_vec2::<i64>()?;
+        for (coords, &size) in coords.iter().zip(shape.dims().iter()) {
+            if let Some(c) = coords.iter().find(|&&c| c < 0 || c >= size as i64) {
+                bail!("sparse coo: index {c} is out of bounds for shape {shape:?}")
+            }
+        }
+        Ok(Self {
+            indices: SparseIndices::Coo(index_values(indices)?),
+            values: values.clone(),
+            shape,
+        })
+    }
+
+    /// Creates a sparse matrix in the compressed sparse row format, `crow_indices` has
+    /// `rows + 1` elements while `col_indices` and `values` have `nnz` elements.
+    pub fn csr<S: Into<Shape>>(
+        crow_indices: &Tensor,
+        col_indices: &Tensor,
+        values: &Tensor,
+        shape: S,
+    ) -> Result<Self> {
+        let shape = shape.into();
+        let (rows, cols) = shape.dims2()?;
+        let nnz = values.dims1()?;
+        let crow = crow_indices.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        let valid_crow = crow.len() == rows + 1
+            && crow.first() == Some(&0)
+            && crow.last() == Some(&(nnz as i64))
+            && crow.windows(2).all(|w| w[0] <= w[1]);
+        if !valid_crow || col_indices.dims1()? != nnz {
+            bail!(
+                "sparse csr: invalid crow indices {:?} or col indices {:?} for {nnz} values",
+                crow_indices.shape(),
+                col_indices.shape(),
+            )
+        }
+        if shape.elem_count() > u32::MAX as usize {
+            bail!("sparse csr: shape {shape:?} has too many elements")
+        }
+        let col = col_indices.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        if let Some(c) = col.iter().find(|&&c| c < 0 || c >= cols as i64) {
+            bail!("sparse csr: column {c} is out of bounds for shape {shape:?}")
+        }
+        Ok(Self {
+            indices: SparseIndices::Csr {
+                crow_indices: index_values(crow_indices)?,
+                col_indices: index_values(col_indices)?,
+            },
+            values: values.clone(),
+            shape,
+        })
+    }
+
+    pub fn indices(&self) -> &SparseIndices {
+        &self.indices
+    }
+
+    pub fn values(&self) -> &Tensor {
+        &self.values
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    pub fn dims(&self) -> &[usize] {
+        self.shape.dims()
+    }
+
+    pub fn dtype(&self) -> DType {
+        self.values.dtype()
+    }
+
+    pub fn device(&self) -> &crate::Device {
+        self.values.device()
+    }
+
+    /// The number of stored values.
+    pub fn nnz(&self) -> usize {
+        self.values.elem_count()
+    }
+
+    // The row of each value of a csr matrix.
+    fn csr_rows(crow_indices: &Tensor) -> Result<Tensor> {
+        let crow = crow_indices.to_vec1::<u32>()?;
+        let rows = crow
+            .windows(2)
+            .enumerate()
+            .flat_map(|(r, w)| std::iter::repeat_n(r as u32, (w[1] - w[0]) as usize))
+            .collect::<Vec<_>>();
+        let len = rows.len();
+        Tensor::from_vec(rows, len, crow_indices.device())
+    }
+
+    /// The coordinates of the values as a `(rank, nnz)` tensor.
+    pub fn coo_indices(&self) -> Result<Tensor> {
+        match &self.indices {
+            SparseIndices::Coo(indices) => Ok(indices.clone()),
+            SparseIndices::Csr {
+                crow_indices,
+                col_indices,
+            } => Tensor::stack(&[&Self::csr_rows(crow_indices)?, col_indices], 0),
+        }
+    }
+
+    // The position of each value in the flattened dense tensor.
+    fn flat_positions(&self) -> Result<Tensor> {
+        let indices = self.coo_indices()?;
+        let strides = self
+            .shape
+            .stride_contiguous()
+            .iter()
+            .map(|&s| s as u32)
+            .collect::<Vec<_>>();
+        let strides = Tensor::from_vec(strides, (self.shape.rank(), 1), indices.device())?;
+        indices.broadcast_mul(&strides)?.sum(0)
+    }
+
+    /// Converts to the coordinate format.
+    pub fn to_coo(&self) -> Result<Self> {
+        Ok(Self {
+            indices: SparseIndices::Coo(self.coo_indices()?),
+            values: self.values.clone(),
+            shape: self.shape.clone(),
+        })
+    }
+
+    /// Converts a sparse matrix to the compressed sparse row format, the values get sorted by row
+    /// and column.
+    pub fn to_csr(&self) -> Result<Self> {
+        let (rows, _cols) = self.shape.dims2()?;
+        let indices = match &self.indices {
+            SparseIndices::Csr { .. } => return Ok(self.clone()),
+            SparseIndices::Coo(indices) => indices.to_vec2::<u32>()?,
+        };
+        let (row, col) = (&indices[0], &indices[1]);
+        let mut order = (0..self.nnz() as u32).collect::<Vec<_>>();
+        order.sort_by_key(|&i| (row[i as usize], col[i as usize]));
+        let mut crow = vec![0u32; rows + 1];
+        for &r in row.iter() {
+            crow[r as usize + 1] += 1
+        }
+        for r in 0..rows {
+            crow[r + 1] += crow[r]
+        }
+        let col = order.iter().map(|&i| col[i as usize]).collect::<Vec<_>>();
+        let dev = self.device();
+        let nnz = order.len();
+        let order = Tensor::from_vec(order, nnz, dev)?;
+        Ok(Self {
+            indices: SparseIndices::Csr {
+                crow_indices: Tensor::from_vec(crow, rows + 1, dev)?,
+                col_indices: Tensor::from_vec(col, nnz, dev)?,
+            },
+            values: self.values.index_select(&order, 0)?,
+            shape: self.shape.clone(),
+        })
+    }
+
+    /// Converts to a dense tensor, duplicate coordinates are summed.
+    pub fn to_dense(&self) -> Result<Tensor> {
+        let positions = self.flat_positions()?;
+        Tensor::zeros(self.shape.elem_count(), self.dtype(), self.device())?
+            .index_add(&positions, &self.values, 0)?
+            .reshape(&self.shape)
+    }
+
+    /// Sparse-dense matrix multiplication, `self` has shape `(m, k)` and `rhs` has shape
+    /// `(k, n)`, the result is a dense tensor of shape `(m, n)`.
+    ///
+    /// ```rust
+    /// use candle_core::{SparseTensor, Tensor, Device};
+    /// let dev = &Device::Cpu;
+    /// let indices = Tensor::new(&[[0u32, 1, 1], [2, 0, 2]], dev)?;
+    /// let values = Tensor::new(&[3f32, 4., 5.], dev)?;
+    /// let sp = SparseTensor::coo(&indices, &values, (2, 3))?;
+    /// let rhs = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], dev)?;
+    /// assert_eq!(sp.matmul(&rhs)?.to_vec2::<f32>()?, &[[15., 18.], [29., 38.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn matmul(&self, rhs: &Tensor) -> Result<Tensor> {
+        let (m, k) = self.shape.dims2()?;
+        let (k2, n) = rhs.dims2()?;
+        if k != k2 {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: self.shape.clone(),
+                rhs: rhs.shape().clone(),
+                op: "sparse-matmul",
+            }
+            .bt())?
+        }
+        let (rows, cols) = match &self.indices {
+            SparseIndices::Coo(indices) => (indices.get(0)?, indices.get(1)?),
+            SparseIndices::Csr {
+                crow_indices,
+                col_indices,
+            } => (Self::csr_rows(crow_indices)?, col_indices.clone()),
+        };
+        let products = rhs
+            .contiguous()?
+            .index_select(&cols, 0)?
+            .broadcast_mul(&self.values.unsqueeze(1)?)?;
+        Tensor::zeros((m, n), products.dtype(), products.device())?.index_add(&rows, &products, 0)
+    }
+}
+
+impl Tensor {
+    /// Converts a dense tensor to a sparse tensor in the coordinate format, only the non-zero
+    /// values get stored. The values are in row-major order.
+    pub fn to_sparse_coo(&self) -> Result<SparseTensor> {
+        if self.elem_count() > u32::MAX as usize {
+            bail!("to_sparse: shape {:?} has too many elements", self.shape())
+        }
+        let non_zero = self
+            .ne(0.)?
+            .to_dtype(DType::U8)?
+            .flatten_all()?
+            .to_vec1::<u8>()?;
+        let positions = non_zero
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &nz)| (nz != 0).then_some(i))
+            .collect::<Vec<_>>();
+        let nnz = positions.len();
+        let rank = self.rank();
+        let strides = self.shape().stride_contiguous();
+        let mut indices = vec![0u32; rank * nnz];
+        for (i, &p) in positions.iter().enumerate() {
+            for (d, &stride) in strides.iter().enumerate() {
+                indices[d * nnz + i] = ((p / stride) % self.dims()[d]) as u32
+            }
+        }
+        let positions = positions.iter().map(|&p| p as u32).collect::<Vec<_>>();
+        let positions = Tensor::from_vec(positions, nnz, self.device())?;
+        let values = self.flatten_all()?.index_select(&positions, 0)?;
+        Ok(SparseTensor {
+            indices: SparseIndices::Coo(Tensor::from_vec(indices, (rank, nnz), self.device())?),
+            values,
+            shape: self.shape().clone(),
+        })
+    }
+
+    /// Converts a dense matrix to a sparse matrix in the compressed sparse row format.
+    pub fn to_sparse_csr(&self) -> Result<SparseTensor> {
+        self.to_sparse_coo()?.to_csr()
+    }
+
+    /// Same as [`Tensor::index_add`] with a sparse source, only the stored values of `source`
+    /// get added to `self`.
+    pub fn index_add_sparse<D: Dim>(
+        &self,
+        indexes: &Self,
+        source: &SparseTensor,
+        dim: D,
+    ) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "index-add-sparse")?;
+        let n_indexes = indexes.dims1()?;
+        let valid = source.shape.rank() == self.rank()
+            && source.dims()[dim] == n_indexes
+            && (0..self.rank()).all(|d| d == dim || source.dims()[d] == self.dims()[d]);
+        if !valid {
+            Err(crate::Error::ShapeMismatchBinaryOp {
+                lhs: self.shape().clone(),
+                rhs: source.shape().clone(),
+                op: "index-add-sparse",
+            }
+            .bt())?
+        }
+        if self.elem_count() > u32::MAX as usize {
+            bail!(
+                "index-add-sparse: shape {:?} has too many elements",
+                self.shape()
+            )
+        }
+        let targets = index_values(indexes)?;
+        let size = self.dims()[dim];
+        let ids = indexes.to_dtype(DType::I64)?.to_vec1::<i64>()?;
+        if let Some(i) = ids.iter().find(|&&i| i < 0 || i >= size as i64) {
+            bail!("index-add-sparse: index {i} is out of bounds for dim {dim} of size {size}")
+        }
+        // Map the source coordinates along dim to their target indexes and compute the
+        // positions of the values in the flattened tensor.
+        let coords = source.coo_indices()?;
+        let targets = targets.index_select(&coords.get(dim)?, 0)?;
+        let strides = self.shape().stride_contiguous();
+        let mut positions = (targets * strides[dim] as f64)?;
+        for (d, &stride) in strides.iter().enumerate() {
+            if d != dim {
+                positions = (positions + (coords.get(d)? * stride as f64)?)?
+            }
+        }
+        self.flatten_all()?
+            .index_add(&positions, source.values(), 0)?
+            .reshape(self.shape())
+    }
+}
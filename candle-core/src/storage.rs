@@ -1,12 +1,18 @@
 use crate::backend::BackendStorage;
-use crate::op::{self, CmpOp, CustomOp1, CustomOp2, CustomOp3, ReduceOp};
-use crate::{CpuStorage, CudaStorage, DType, Device, Error, Layout, MetalStorage, Result, Shape};
+use crate::op::{self, CmpOp, CustomOp1, CustomOp2, CustomOp3, ReduceOp, ScanOp};
+use crate::profiler::OpTimer;
+use crate::{
+    CpuDeviceConfig, CpuStorage, CudaStorage, DType, Device, Error, Layout, MetalStorage, Result,
+    Shape,
+};
 
 // We do not want to implement Clone on Storage as cloning may fail because of
 // out of memory. Instead try_clone should be used.
 #[derive(Debug)]
 pub enum Storage {
     Cpu(CpuStorage),
+    /// A cpu storage whose ops run on the thread pool of the configuration.
+    CpuPool(CpuStorage, CpuDeviceConfig),
     Cuda(CudaStorage),
     Metal(MetalStorage),
 }
@@ -15,6 +21,7 @@ impl Storage {
     pub fn try_clone(&self, layout: &Layout) -> Result<Self> {
         match self {
             Self::Cpu(storage) => Ok(Self::Cpu(storage.clone())),
+            Self::CpuPool(storage, config) => Ok(Self::CpuPool(storage.clone(), config.clone())),
             Self::Cuda(storage) => {
                 let storage = storage.try_clone(layout)?;
                 Ok(Self::Cuda(storage))
@@ -29,6 +36,7 @@ impl Storage {
     pub fn device(&self) -> Device {
         match self {
             Self::Cpu(_) => Device::Cpu,
+            Self::CpuPool(_, config) => Device::CpuPool(config.clone()),
             Self::Cuda(storage) => Device::Cuda(storage.device().clone()),
             Self::Metal(storage) => Device::Metal(storage.device().clone()),
         }
@@ -36,19 +44,45 @@ impl Storage {
 
     pub fn dtype(&self) -> DType {
         match self {
-            Self::Cpu(storage) => storage.dtype(),
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => storage.dtype(),
             Self::Cuda(storage) => storage.dtype(),
             Self::Metal(storage) => storage.dtype(),
         }
     }
 
     pub(crate) fn same_device(&self, rhs: &Self, op: &'static str) -> Result<()> {
-        let lhs = self.device().location();
-        let rhs = rhs.device().location();
-        if lhs != rhs {
-            Err(Error::DeviceMismatchBinaryOp { lhs, rhs, op }.bt())
-        } else {
-            Ok(())
+        match (self, rhs) {
+            (Self::CpuPool(_, lhs), Self::CpuPool(_, rhs)) if lhs.same_pool(rhs) => Ok(()),
+            (Self::Cpu(_), Self::Cpu(_)) => Ok(()),
+            (Self::Cpu(_) | Self::CpuPool(..), Self::Cpu(_) | Self::CpuPool(..)) => {
+                Err(Error::CpuPoolMismatchBinaryOp { op }.bt())
+            }
+            _ => {
+                let lhs = self.device().location();
+                let rhs = rhs.device().location();
+                if lhs != rhs {
+                    Err(Error::DeviceMismatchBinaryOp { lhs, rhs, op }.bt())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Runs `f` on the thread pool of the storage for the cpu storages bound to a pool, on the
+    /// current thread otherwise.
+    fn install<R: Send, F: FnOnce() -> R + Send>(&self, f: F) -> R {
+        match self {
+            Self::CpuPool(_, config) => config.install(f),
+            Self::Cpu(_) | Self::Cuda(_) | Self::Metal(_) => f(),
+        }
+    }
+
+    // Wraps the result of a cpu op so that it stays bound to the thread pool of this storage.
+    fn wrap_cpu(&self, storage: CpuStorage) -> Self {
+        match self {
+            Self::CpuPool(_, config) => Self::CpuPool(storage, config.clone()),
+            Self::Cpu(_) | Self::Cuda(_) | Self::Metal(_) => Self::Cpu(storage),
         }
     }
 
@@ -63,10 +97,11 @@ impl Storage {
     }
 
     pub(crate) fn affine(&self, layout: &Layout, mul: f64, add: f64) -> Result<Self> {
+        let _timer = OpTimer::new("affine", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.affine(layout, mul, add)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.affine(layout, mul, add))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.affine(layout, mul, add)?;
@@ -80,10 +115,11 @@ impl Storage {
     }
 
     pub(crate) fn powf(&self, layout: &Layout, alpha: f64) -> Result<Self> {
+        let _timer = OpTimer::new("powf", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.powf(layout, alpha)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.powf(layout, alpha))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.powf(layout, alpha)?;
@@ -97,10 +133,11 @@ impl Storage {
     }
 
     pub(crate) fn elu(&self, layout: &Layout, alpha: f64) -> Result<Self> {
+        let _timer = OpTimer::new("elu", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.elu(layout, alpha)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.elu(layout, alpha))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.elu(layout, alpha)?;
@@ -120,12 +157,13 @@ impl Storage {
         lhs_layout: &Layout,
         rhs_layout: &Layout,
     ) -> Result<Self> {
+        let _timer = OpTimer::new(op.name(), &[(self, lhs_layout), (rhs, rhs_layout)]);
         self.same_device(rhs, "cmp")?;
         self.same_dtype(rhs, "cmp")?;
         match (self, rhs) {
-            (Storage::Cpu(lhs), Storage::Cpu(rhs)) => {
-                let storage = lhs.cmp(op, rhs, lhs_layout, rhs_layout)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(lhs), Self::Cpu(rhs)) | (Self::CpuPool(lhs, _), Self::CpuPool(rhs, _)) => {
+                let storage = self.install(|| lhs.cmp(op, rhs, lhs_layout, rhs_layout))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(lhs), Self::Cuda(rhs)) => {
                 let storage = lhs.cmp(op, rhs, lhs_layout, rhs_layout)?;
@@ -149,10 +187,11 @@ impl Storage {
     }
 
     pub(crate) fn reduce_op(&self, op: ReduceOp, layout: &Layout, s: &[usize]) -> Result<Self> {
+        let _timer = OpTimer::new(op.name(), &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.reduce_op(op, layout, s)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.reduce_op(op, layout, s))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.reduce_op(op, layout, s)?;
@@ -165,11 +204,98 @@ impl Storage {
         }
     }
 
+    pub(crate) fn scan(&self, layout: &Layout, op: ScanOp, dim: usize) -> Result<Self> {
+        let _timer = OpTimer::new(op.name(), &[(self, layout)]);
+        match self {
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.scan(layout, op, dim))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            Self::Cuda(storage) => {
+                let storage = storage.scan(layout, op, dim)?;
+                Ok(Self::Cuda(storage))
+            }
+            Self::Metal(storage) => {
+                let storage = storage.scan(layout, op, dim)?;
+                Ok(Self::Metal(storage))
+            }
+        }
+    }
+
+    pub(crate) fn linear_recurrence(
+        &self,
+        layout: &Layout,
+        b: &Self,
+        b_layout: &Layout,
+        dim: usize,
+    ) -> Result<Self> {
+        let _timer = OpTimer::new("linear-recurrence", &[(self, layout), (b, b_layout)]);
+        self.same_device(b, "linear-recurrence")?;
+        self.same_dtype(b, "linear-recurrence")?;
+        match (self, b) {
+            (Self::Cpu(a), Self::Cpu(b)) | (Self::CpuPool(a, _), Self::CpuPool(b, _)) => {
+                let storage = self.install(|| a.linear_recurrence(layout, b, b_layout, dim))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            (Self::Cuda(a), Self::Cuda(b)) => {
+                let storage = a.linear_recurrence(layout, b, b_layout, dim)?;
+                Ok(Self::Cuda(storage))
+            }
+            (Self::Metal(a), Self::Metal(b)) => {
+                let storage = a.linear_recurrence(layout, b, b_layout, dim)?;
+                Ok(Self::Metal(storage))
+            }
+            (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
+                lhs: lhs.device().location(),
+                rhs: rhs.device().location(),
+                op: "linear-recurrence",
+            }
+            .bt()),
+        }
+    }
+
+    pub(crate) fn arg_sort_last_dim(&self, layout: &Layout, asc: bool) -> Result<Self> {
+        let _timer = OpTimer::new("arg-sort", &[(self, layout)]);
+        match self {
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.arg_sort_last_dim(layout, asc))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            Self::Cuda(storage) => {
+                let storage = storage.arg_sort_last_dim(layout, asc)?;
+                Ok(Self::Cuda(storage))
+            }
+            Self::Metal(storage) => {
+                let storage = storage.arg_sort_last_dim(layout, asc)?;
+                Ok(Self::Metal(storage))
+            }
+        }
+    }
+
+    pub(crate) fn fft_last_dim(&self, layout: &Layout, inverse: bool) -> Result<Self> {
+        let _timer = OpTimer::new("fft", &[(self, layout)]);
+        match self {
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.fft_last_dim(layout, inverse))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            Self::Cuda(storage) => {
+                let storage = storage.fft_last_dim(layout, inverse)?;
+                Ok(Self::Cuda(storage))
+            }
+            Self::Metal(storage) => {
+                let storage = storage.fft_last_dim(layout, inverse)?;
+                Ok(Self::Metal(storage))
+            }
+        }
+    }
+
     pub(crate) fn to_dtype(&self, layout: &Layout, dtype: DType) -> Result<Self> {
+        let _timer = OpTimer::new("to-dtype", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.to_dtype(layout, dtype)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.to_dtype(layout, dtype))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.to_dtype(layout, dtype)?;
@@ -182,11 +308,16 @@ impl Storage {
         }
     }
 
-    pub(crate) fn apply_op1(&self, l: &Layout, c: &dyn CustomOp1) -> Result<(Self, Shape)> {
+    pub(crate) fn apply_op1(
+        &self,
+        l: &Layout,
+        c: &(dyn CustomOp1 + Sync),
+    ) -> Result<(Self, Shape)> {
+        let _timer = OpTimer::new(c.name(), &[(self, l)]);
         match self {
-            Self::Cpu(storage) => {
-                let (storage, shape) = c.cpu_fwd(storage, l)?;
-                Ok((Self::Cpu(storage), shape))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let (storage, shape) = self.install(|| c.cpu_fwd(storage, l))?;
+                Ok((self.wrap_cpu(storage), shape))
             }
             Self::Cuda(storage) => {
                 let (storage, shape) = c.cuda_fwd(storage, l)?;
@@ -204,13 +335,14 @@ impl Storage {
         l1: &Layout,
         t2: &Self,
         l2: &Layout,
-        c: &dyn CustomOp2,
+        c: &(dyn CustomOp2 + Sync),
     ) -> Result<(Self, Shape)> {
+        let _timer = OpTimer::new(c.name(), &[(self, l1), (t2, l2)]);
         self.same_device(t2, c.name())?;
         match (self, t2) {
-            (Self::Cpu(s1), Self::Cpu(s2)) => {
-                let (s, shape) = c.cpu_fwd(s1, l1, s2, l2)?;
-                Ok((Self::Cpu(s), shape))
+            (Self::Cpu(s1), Self::Cpu(s2)) | (Self::CpuPool(s1, _), Self::CpuPool(s2, _)) => {
+                let (s, shape) = self.install(|| c.cpu_fwd(s1, l1, s2, l2))?;
+                Ok((self.wrap_cpu(s), shape))
             }
             (Self::Cuda(s1), Self::Cuda(s2)) => {
                 let (s, shape) = c.cuda_fwd(s1, l1, s2, l2)?;
@@ -231,14 +363,16 @@ impl Storage {
         l2: &Layout,
         t3: &Self,
         l3: &Layout,
-        c: &dyn CustomOp3,
+        c: &(dyn CustomOp3 + Sync),
     ) -> Result<(Self, Shape)> {
+        let _timer = OpTimer::new(c.name(), &[(self, l1), (t2, l2), (t3, l3)]);
         self.same_device(t2, c.name())?;
         self.same_device(t3, c.name())?;
         match (self, t2, t3) {
-            (Self::Cpu(s1), Self::Cpu(s2), Self::Cpu(s3)) => {
-                let (s, shape) = c.cpu_fwd(s1, l1, s2, l2, s3, l3)?;
-                Ok((Self::Cpu(s), shape))
+            (Self::Cpu(s1), Self::Cpu(s2), Self::Cpu(s3))
+            | (Self::CpuPool(s1, _), Self::CpuPool(s2, _), Self::CpuPool(s3, _)) => {
+                let (s, shape) = self.install(|| c.cpu_fwd(s1, l1, s2, l2, s3, l3))?;
+                Ok((self.wrap_cpu(s), shape))
             }
             (Self::Cuda(s1), Self::Cuda(s2), Self::Cuda(s3)) => {
                 let (s, shape) = c.cuda_fwd(s1, l1, s2, l2, s3, l3)?;
@@ -253,10 +387,11 @@ impl Storage {
     }
 
     pub(crate) fn unary_impl<B: op::UnaryOpT>(&self, layout: &Layout) -> Result<Self> {
+        let _timer = OpTimer::new(B::NAME, &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.unary_impl::<B>(layout)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.unary_impl::<B>(layout))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.unary_impl::<B>(layout)?;
@@ -275,12 +410,13 @@ impl Storage {
         lhs_layout: &Layout,
         rhs_layout: &Layout,
     ) -> Result<Self> {
+        let _timer = OpTimer::new(B::NAME, &[(self, lhs_layout), (rhs, rhs_layout)]);
         self.same_device(rhs, B::NAME)?;
         self.same_dtype(rhs, B::NAME)?;
         match (self, rhs) {
-            (Storage::Cpu(lhs), Storage::Cpu(rhs)) => {
-                let storage = lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(lhs), Self::Cpu(rhs)) | (Self::CpuPool(lhs, _), Self::CpuPool(rhs, _)) => {
+                let storage = self.install(|| lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(lhs), Self::Cuda(rhs)) => {
                 let storage = lhs.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
@@ -303,6 +439,55 @@ impl Storage {
         }
     }
 
+    // The in-place ops below mutate the cpu buffers directly, the other backends compute the
+    // result out of place and copy it back.
+    pub(crate) fn binary_impl_inplace<B: op::BinaryOpT>(
+        &mut self,
+        rhs: &Self,
+        lhs_layout: &Layout,
+        rhs_layout: &Layout,
+    ) -> Result<()> {
+        let _timer = OpTimer::new(B::NAME, &[(self, lhs_layout), (rhs, rhs_layout)]);
+        self.same_device(rhs, B::NAME)?;
+        self.same_dtype(rhs, B::NAME)?;
+        let device = self.device();
+        match (&mut *self, rhs) {
+            (Self::Cpu(lhs), Self::Cpu(rhs)) | (Self::CpuPool(lhs, _), Self::CpuPool(rhs, _)) => {
+                return device
+                    .install(|| lhs.binary_impl_inplace::<B>(rhs, lhs_layout, rhs_layout));
+            }
+            _ => {}
+        }
+        let storage = self.binary_impl::<B>(rhs, lhs_layout, rhs_layout)?;
+        let layout = Layout::contiguous(lhs_layout.shape());
+        storage.copy_strided_src(self, lhs_layout.start_offset(), &layout)
+    }
+
+    pub(crate) fn affine_inplace(&mut self, layout: &Layout, mul: f64, add: f64) -> Result<()> {
+        let _timer = OpTimer::new("affine", &[(self, layout)]);
+        let device = self.device();
+        if let Self::Cpu(storage) | Self::CpuPool(storage, _) = self {
+            return device.install(|| storage.affine_inplace(layout, mul, add));
+        }
+        let storage = self.affine(layout, mul, add)?;
+        let contiguous = Layout::contiguous(layout.shape());
+        storage.copy_strided_src(self, layout.start_offset(), &contiguous)
+    }
+
+    pub(crate) fn fill_inplace(&mut self, layout: &Layout, value: f64) -> Result<()> {
+        let _timer = OpTimer::new("fill", &[(self, layout)]);
+        let device = self.device();
+        if let Self::Cpu(storage) | Self::CpuPool(storage, _) = self {
+            return device.install(|| storage.fill_inplace(layout, value));
+        }
+        let contiguous = Layout::contiguous(layout.shape());
+        let storage =
+            self.device()
+                .zeros(layout.shape(), self.dtype())?
+                .affine(&contiguous, 0., value)?;
+        storage.copy_strided_src(self, layout.start_offset(), &contiguous)
+    }
+
     pub(crate) fn conv1d(
         &self,
         l: &Layout,
@@ -310,12 +495,14 @@ impl Storage {
         kernel_l: &Layout,
         params: &crate::conv::ParamsConv1D,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("conv1d", &[(self, l), (kernel, kernel_l)]);
         self.same_device(kernel, "conv1d")?;
         self.same_dtype(kernel, "conv1d")?;
         match (self, &kernel) {
-            (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
-                let s = inp.conv1d(l, kernel, kernel_l, params)?;
-                Ok(Self::Cpu(s))
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv1d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
             }
             (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
                 let s = inp.conv1d(l, kernel, kernel_l, params)?;
@@ -341,12 +528,14 @@ impl Storage {
         kernel_l: &Layout,
         params: &crate::conv::ParamsConvTranspose1D,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("conv-transpose1d", &[(self, l), (kernel, kernel_l)]);
         self.same_device(kernel, "conv-transpose1d")?;
         self.same_dtype(kernel, "conv-transpose1d")?;
         match (self, &kernel) {
-            (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
-                let s = inp.conv_transpose1d(l, kernel, kernel_l, params)?;
-                Ok(Self::Cpu(s))
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv_transpose1d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
             }
             (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
                 let s = inp.conv_transpose1d(l, kernel, kernel_l, params)?;
@@ -368,12 +557,14 @@ impl Storage {
         kernel_l: &Layout,
         params: &crate::conv::ParamsConv2D,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("conv2d", &[(self, l), (kernel, kernel_l)]);
         self.same_device(kernel, "conv2d")?;
         self.same_dtype(kernel, "conv2d")?;
         match (self, &kernel) {
-            (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
-                let s = inp.conv2d(l, kernel, kernel_l, params)?;
-                Ok(Self::Cpu(s))
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv2d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
             }
             (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
                 let s = inp.conv2d(l, kernel, kernel_l, params)?;
@@ -399,12 +590,14 @@ impl Storage {
         kernel_l: &Layout,
         params: &crate::conv::ParamsConvTranspose2D,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("conv-transpose2d", &[(self, l), (kernel, kernel_l)]);
         self.same_device(kernel, "conv_transpose2d")?;
         self.same_dtype(kernel, "conv_transpose2d")?;
         match (self, &kernel) {
-            (Storage::Cpu(inp), Storage::Cpu(kernel)) => {
-                let s = inp.conv_transpose2d(l, kernel, kernel_l, params)?;
-                Ok(Self::Cpu(s))
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv_transpose2d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
             }
             (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
                 let s = inp.conv_transpose2d(l, kernel, kernel_l, params)?;
@@ -423,16 +616,83 @@ impl Storage {
         }
     }
 
+    pub(crate) fn conv3d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConv3D,
+    ) -> Result<Self> {
+        let _timer = OpTimer::new("conv3d", &[(self, l), (kernel, kernel_l)]);
+        self.same_device(kernel, "conv3d")?;
+        self.same_dtype(kernel, "conv3d")?;
+        match (self, &kernel) {
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv3d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
+            }
+            (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
+                let s = inp.conv3d(l, kernel, kernel_l, params)?;
+                Ok(Self::Cuda(s))
+            }
+            (Storage::Metal(inp), Storage::Metal(kernel)) => {
+                let s = inp.conv3d(l, kernel, kernel_l, params)?;
+                Ok(Self::Metal(s))
+            }
+            (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
+                lhs: lhs.device().location(),
+                rhs: rhs.device().location(),
+                op: "conv3d",
+            }
+            .bt()),
+        }
+    }
+
+    pub(crate) fn conv_transpose3d(
+        &self,
+        l: &Layout,
+        kernel: &Self,
+        kernel_l: &Layout,
+        params: &crate::conv::ParamsConvTranspose3D,
+    ) -> Result<Self> {
+        let _timer = OpTimer::new("conv-transpose3d", &[(self, l), (kernel, kernel_l)]);
+        self.same_device(kernel, "conv_transpose3d")?;
+        self.same_dtype(kernel, "conv_transpose3d")?;
+        match (self, &kernel) {
+            (Self::Cpu(inp), Self::Cpu(kernel))
+            | (Self::CpuPool(inp, _), Self::CpuPool(kernel, _)) => {
+                let s = self.install(|| inp.conv_transpose3d(l, kernel, kernel_l, params))?;
+                Ok(self.wrap_cpu(s))
+            }
+            (Storage::Cuda(inp), Storage::Cuda(kernel)) => {
+                let s = inp.conv_transpose3d(l, kernel, kernel_l, params)?;
+                Ok(Self::Cuda(s))
+            }
+            (Storage::Metal(inp), Storage::Metal(kernel)) => {
+                let s = inp.conv_transpose3d(l, kernel, kernel_l, params)?;
+                Ok(Self::Metal(s))
+            }
+            (lhs, rhs) => Err(Error::DeviceMismatchBinaryOp {
+                lhs: lhs.device().location(),
+                rhs: rhs.device().location(),
+                op: "conv_transpose3d",
+            }
+            .bt()),
+        }
+    }
+
     pub(crate) fn avg_pool2d(
         &self,
         layout: &Layout,
         kernel_size: (usize, usize),
         stride: (usize, usize),
     ) -> Result<Self> {
+        let _timer = OpTimer::new("avg-pool2d", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.avg_pool2d(layout, kernel_size, stride)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.avg_pool2d(layout, kernel_size, stride))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.avg_pool2d(layout, kernel_size, stride)?;
@@ -451,10 +711,11 @@ impl Storage {
         kernel_size: (usize, usize),
         stride: (usize, usize),
     ) -> Result<Self> {
+        let _timer = OpTimer::new("max-pool2d", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.max_pool2d(layout, kernel_size, stride)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.max_pool2d(layout, kernel_size, stride))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.max_pool2d(layout, kernel_size, stride)?;
@@ -467,11 +728,58 @@ impl Storage {
         }
     }
 
+    pub(crate) fn avg_pool3d(
+        &self,
+        layout: &Layout,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    ) -> Result<Self> {
+        let _timer = OpTimer::new("avg-pool3d", &[(self, layout)]);
+        match self {
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.avg_pool3d(layout, kernel_size, stride))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            Self::Cuda(storage) => {
+                let storage = storage.avg_pool3d(layout, kernel_size, stride)?;
+                Ok(Self::Cuda(storage))
+            }
+            Self::Metal(storage) => {
+                let storage = storage.avg_pool3d(layout, kernel_size, stride)?;
+                Ok(Self::Metal(storage))
+            }
+        }
+    }
+
+    pub(crate) fn max_pool3d(
+        &self,
+        layout: &Layout,
+        kernel_size: (usize, usize, usize),
+        stride: (usize, usize, usize),
+    ) -> Result<Self> {
+        let _timer = OpTimer::new("max-pool3d", &[(self, layout)]);
+        match self {
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.max_pool3d(layout, kernel_size, stride))?;
+                Ok(self.wrap_cpu(storage))
+            }
+            Self::Cuda(storage) => {
+                let storage = storage.max_pool3d(layout, kernel_size, stride)?;
+                Ok(Self::Cuda(storage))
+            }
+            Self::Metal(storage) => {
+                let storage = storage.max_pool3d(layout, kernel_size, stride)?;
+                Ok(Self::Metal(storage))
+            }
+        }
+    }
+
     pub(crate) fn upsample_nearest1d(&self, layout: &Layout, sz: usize) -> Result<Self> {
+        let _timer = OpTimer::new("upsample-nearest1d", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.upsample_nearest1d(layout, sz)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.upsample_nearest1d(layout, sz))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.upsample_nearest1d(layout, sz)?;
@@ -485,10 +793,11 @@ impl Storage {
     }
 
     pub(crate) fn upsample_nearest2d(&self, layout: &Layout, h: usize, w: usize) -> Result<Self> {
+        let _timer = OpTimer::new("upsample-nearest2d", &[(self, layout)]);
         match self {
-            Storage::Cpu(storage) => {
-                let storage = storage.upsample_nearest2d(layout, h, w)?;
-                Ok(Self::Cpu(storage))
+            Self::Cpu(storage) | Self::CpuPool(storage, _) => {
+                let storage = self.install(|| storage.upsample_nearest2d(layout, h, w))?;
+                Ok(self.wrap_cpu(storage))
             }
             Self::Cuda(storage) => {
                 let storage = storage.upsample_nearest2d(layout, h, w)?;
@@ -509,13 +818,18 @@ impl Storage {
         f: &Self,
         layout_f: &Layout,
     ) -> Result<Self> {
+        let _timer = OpTimer::new(
+            "where-cond",
+            &[(self, layout), (t, layout_t), (f, layout_f)],
+        );
         self.same_device(t, "where")?;
         self.same_device(f, "where")?;
         t.same_dtype(f, "where")?;
         match (self, t, f) {
-            (Storage::Cpu(cond), Storage::Cpu(t), Storage::Cpu(f)) => {
-                let storage = cond.where_cond(layout, t, layout_t, f, layout_f)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(cond), Self::Cpu(t), Self::Cpu(f))
+            | (Self::CpuPool(cond, _), Self::CpuPool(t, _), Self::CpuPool(f, _)) => {
+                let storage = self.install(|| cond.where_cond(layout, t, layout_t, f, layout_f))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(cond), Self::Cuda(t), Self::Cuda(f)) => {
                 let storage = cond.where_cond(layout, t, layout_t, f, layout_f)?;
@@ -541,11 +855,13 @@ impl Storage {
         indexes_l: &Layout,
         d: usize,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("gather", &[(self, l), (indexes, indexes_l)]);
         self.same_device(indexes, "index-add")?;
         match (self, indexes) {
-            (Self::Cpu(s), Self::Cpu(indexes)) => {
-                let storage = s.gather(l, indexes, indexes_l, d)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(s), Self::Cpu(indexes))
+            | (Self::CpuPool(s, _), Self::CpuPool(indexes, _)) => {
+                let storage = self.install(|| s.gather(l, indexes, indexes_l, d))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(s), Self::Cuda(indexes)) => {
                 let storage = s.gather(l, indexes, indexes_l, d)?;
@@ -568,12 +884,18 @@ impl Storage {
         source_l: &Layout,
         d: usize,
     ) -> Result<Self> {
+        let _timer = OpTimer::new(
+            "scatter-add",
+            &[(self, l), (indexes, indexes_l), (source, source_l)],
+        );
         self.same_device(indexes, "scatter-add")?;
         self.same_device(source, "scatter-add")?;
         match (self, indexes, source) {
-            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
-                let storage = s.scatter_add(l, indexes, indexes_l, source, source_l, d)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source))
+            | (Self::CpuPool(s, _), Self::CpuPool(indexes, _), Self::CpuPool(source, _)) => {
+                let storage =
+                    self.install(|| s.scatter_add(l, indexes, indexes_l, source, source_l, d))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(s), Self::Cuda(indexes), Self::Cuda(source)) => {
                 let storage = s.scatter_add(l, indexes, indexes_l, source, source_l, d)?;
@@ -596,12 +918,18 @@ impl Storage {
         source_l: &Layout,
         d: usize,
     ) -> Result<Self> {
+        let _timer = OpTimer::new(
+            "index-add",
+            &[(self, l), (indexes, indexes_l), (source, source_l)],
+        );
         self.same_device(indexes, "index-add")?;
         self.same_device(source, "index-add")?;
         match (self, indexes, source) {
-            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source)) => {
-                let storage = s.index_add(l, indexes, indexes_l, source, source_l, d)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(s), Self::Cpu(indexes), Self::Cpu(source))
+            | (Self::CpuPool(s, _), Self::CpuPool(indexes, _), Self::CpuPool(source, _)) => {
+                let storage =
+                    self.install(|| s.index_add(l, indexes, indexes_l, source, source_l, d))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(s), Self::Cuda(indexes), Self::Cuda(source)) => {
                 let storage = s.index_add(l, indexes, indexes_l, source, source_l, d)?;
@@ -622,11 +950,12 @@ impl Storage {
         rhs_l: &Layout,
         d: usize,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("index-select", &[(self, lhs_l), (rhs, rhs_l)]);
         self.same_device(rhs, "index-select")?;
         match (self, rhs) {
-            (Self::Cpu(lhs), Self::Cpu(rhs)) => {
-                let storage = lhs.index_select(rhs, lhs_l, rhs_l, d)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(lhs), Self::Cpu(rhs)) | (Self::CpuPool(lhs, _), Self::CpuPool(rhs, _)) => {
+                let storage = self.install(|| lhs.index_select(rhs, lhs_l, rhs_l, d))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(lhs), Self::Cuda(rhs)) => {
                 let storage = lhs.index_select(rhs, lhs_l, rhs_l, d)?;
@@ -652,12 +981,13 @@ impl Storage {
         lhs_layout: &Layout,
         rhs_layout: &Layout,
     ) -> Result<Self> {
+        let _timer = OpTimer::new("matmul", &[(self, lhs_layout), (rhs, rhs_layout)]);
         self.same_device(rhs, "matmul")?;
         self.same_dtype(rhs, "matmul")?;
         match (self, rhs) {
-            (Self::Cpu(lhs), Self::Cpu(rhs)) => {
-                let storage = lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout)?;
-                Ok(Self::Cpu(storage))
+            (Self::Cpu(lhs), Self::Cpu(rhs)) | (Self::CpuPool(lhs, _), Self::CpuPool(rhs, _)) => {
+                let storage = self.install(|| lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout))?;
+                Ok(self.wrap_cpu(storage))
             }
             (Self::Cuda(lhs), Self::Cuda(rhs)) => {
                 let storage = lhs.matmul(rhs, bmnk, lhs_layout, rhs_layout)?;
@@ -683,8 +1013,14 @@ impl Storage {
         dst_offset: usize,
         src_l: &Layout,
     ) -> Result<()> {
+        let _timer = OpTimer::new("copy-strided", &[(self, src_l)]);
         match (self, dst) {
             (Self::Cpu(src), Self::Cpu(dst)) => src.copy_strided_src(dst, dst_offset, src_l),
+            (Self::CpuPool(src, config), Self::CpuPool(dst, dst_config))
+                if config.same_pool(dst_config) =>
+            {
+                config.install(|| src.copy_strided_src(dst, dst_offset, src_l))
+            }
             (Self::Cuda(src), Self::Cuda(dst)) => Ok(src.copy_strided_src(dst, dst_offset, src_l)?),
             (Self::Metal(src), Self::Metal(dst)) => {
                 Ok(src.copy_strided_src(dst, dst_offset, src_l)?)
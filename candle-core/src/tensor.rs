@@ -1,8 +1,9 @@
 //! Tensors are N-dimensional matrixes of elements using a single data type.
 #![allow(clippy::redundant_closure_call)]
 use crate::backend::{BackendDevice, BackendStorage};
+use crate::memory::TrackedStorage;
 use crate::op::{
-    BackpropOp, BinaryOp, CmpOp, CustomOp1, CustomOp2, CustomOp3, Op, ReduceOp, UnaryOp,
+    BackpropOp, BinaryOp, CmpOp, CustomOp1, CustomOp2, CustomOp3, Op, ReduceOp, ScanOp, UnaryOp,
 };
 use crate::scalar::TensorOrScalar;
 use crate::shape::{Dim, Dims};
@@ -10,11 +11,11 @@ use crate::{bail, storage::Storage, DType, Device, Error, Layout, Result, Shape}
 use std::sync::{Arc, RwLock};
 
 /// Unique identifier for tensors.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TensorId(usize);
 
 impl TensorId {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         // https://users.rust-lang.org/t/idiomatic-rust-way-to-generate-unique-id/33805
         use std::sync::atomic;
         static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(1);
@@ -36,12 +37,17 @@ pub struct Tensor_ {
     // Ideally, we would use Arc<Storage> for tensors on which we don't plan on modifying the data
     // and Arc<Mutex<Storage>> for tensors where the data could be modified, e.g. variables but
     // that's tricky to encode in the current setup.
-    storage: Arc<RwLock<Storage>>,
+    // The storage lock is wrapped in a `TrackedStorage` so that the allocation gets accounted for
+    // in the memory statistics of the device.
+    storage: Arc<TrackedStorage>,
     layout: Layout,
     op: BackpropOp,
     is_variable: bool,
     dtype: DType,
     device: Device,
+    // Callbacks applied to the gradient of this tensor during the backward pass, see
+    // [`Tensor::register_hook`].
+    hooks: RwLock<Vec<crate::backprop::GradHook>>,
 }
 
 impl AsRef<Tensor> for Tensor {
@@ -50,6 +56,21 @@ impl AsRef<Tensor> for Tensor {
     }
 }
 
+/// The padding mode used by [`Tensor::pad`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PadMode {
+    /// Pad with a constant value.
+    Constant(f64),
+    /// Pad with the reflection of the values, the edge values are not repeated, e.g. padding
+    /// `[1, 2, 3]` with two elements on each side gives `[3, 2, 1, 2, 3, 2, 1]`.
+    Reflect,
+    /// Pad by repeating the edge values.
+    Replicate,
+    /// Pad by wrapping the values around, e.g. padding `[1, 2, 3]` with two elements on each side
+    /// gives `[2, 3, 1, 2, 3, 1, 2]`.
+    Circular,
+}
+
 // Tensors are refcounted so that cloning is cheap when building the op graph.
 // Storages are also refcounted independently so that its possible to avoid
 // copying the storage for operations that only modify the shape or stride.
@@ -100,7 +121,7 @@ macro_rules! binary_op {
                 rhs.layout(),
             )?;
             let op = BackpropOp::new2(self, rhs, |t1, t2| Op::Binary(t1, t2, BinaryOp::$op_name));
-            Ok(from_storage(storage, shape.clone(), op, false))
+            Ok(from_storage(storage, shape, op, false))
         }
     };
 }
@@ -122,7 +143,7 @@ macro_rules! binary_op_scalar {
                 rhs.layout(),
             )?;
             let op = BackpropOp::new2(self, &rhs, |t1, t2| Op::Binary(t1, t2, BinaryOp::$op_name));
-            Ok(from_storage(storage, shape.clone(), op, false))
+            Ok(from_storage(storage, shape, op, false))
         }
     };
 }
@@ -157,14 +178,17 @@ pub(crate) fn from_storage<S: Into<Shape>>(
 ) -> Tensor {
     let dtype = storage.dtype();
     let device = storage.device();
+    let layout = Layout::contiguous(shape);
+    let storage = TrackedStorage::new(storage, layout.shape().elem_count(), op.name());
     let tensor_ = Tensor_ {
         id: TensorId::new(),
-        storage: Arc::new(RwLock::new(storage)),
-        layout: Layout::contiguous(shape),
+        storage: Arc::new(storage),
+        layout,
         op,
         is_variable,
         dtype,
         device,
+        hooks: RwLock::new(vec![]),
     };
     Tensor(Arc::new(tensor_))
 }
@@ -415,7 +439,7 @@ impl Tensor {
         Self::from_vec_impl(data, len, device, false)
     }
 
-    pub(crate) fn from_vec_impl<S: Into<Shape>, D: crate::WithDType>(
+    pub(crate) fn from_vec_impl<S: Into<Shape>, D: crate::ElemType>(
         data: Vec<D>,
         shape: S,
         device: &Device,
@@ -434,7 +458,7 @@ impl Tensor {
     /// Creates a new tensor initialized with values from the input vector. The number of elements
     /// in this vector must be the same as the number of elements defined by the shape.
     /// If the device is cpu, no data copy is made.
-    pub fn from_vec<S: Into<Shape>, D: crate::WithDType>(
+    pub fn from_vec<S: Into<Shape>, D: crate::ElemType>(
         data: Vec<D>,
         shape: S,
         device: &Device,
@@ -444,7 +468,7 @@ impl Tensor {
 
     /// Creates a new tensor initialized with values from the input slice. The number of elements
     /// in this vector must be the same as the number of elements defined by the shape.
-    pub fn from_slice<S: Into<Shape>, D: crate::WithDType>(
+    pub fn from_slice<S: Into<Shape>, D: crate::ElemType>(
         array: &[D],
         shape: S,
         device: &Device,
@@ -452,19 +476,8 @@ impl Tensor {
         Self::new_impl(array, shape.into(), device, false)
     }
 
-    pub(crate) fn same_shape_binary_op(&self, rhs: &Self, op: &'static str) -> Result<&Shape> {
-        let lhs = self.shape();
-        let rhs = rhs.shape();
-        if lhs != rhs {
-            Err(Error::ShapeMismatchBinaryOp {
-                lhs: lhs.clone(),
-                rhs: rhs.clone(),
-                op,
-            }
-            .bt())
-        } else {
-            Ok(lhs)
-        }
+    pub(crate) fn same_shape_binary_op(&self, rhs: &Self, op: &'static str) -> Result<Shape> {
+        self.shape().same_shape_binary_op(rhs.shape(), op)
     }
 
     /// Returns true if the computation graph should track this op, that is if it is
@@ -523,7 +536,7 @@ impl Tensor {
 
     /// Retrieves the single scalar value hold in the tensor. If the tensor contains multiple
     /// dimensions, an error is returned instead.
-    pub fn to_scalar<S: crate::WithDType>(&self) -> Result<S> {
+    pub fn to_scalar<S: crate::ElemType>(&self) -> Result<S> {
         if self.rank() != 0 {
             Err(Error::UnexpectedNumberOfDims {
                 expected: 0,
@@ -537,14 +550,16 @@ impl Tensor {
             Ok::<_, Error>(data[self.layout().start_offset()])
         };
         match &*self.storage() {
-            Storage::Cpu(cpu_storage) => from_cpu_storage(cpu_storage),
+            Storage::Cpu(cpu_storage) | Storage::CpuPool(cpu_storage, _) => {
+                from_cpu_storage(cpu_storage)
+            }
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
     /// An alias for `to_scalar`.
-    pub fn to_vec0<S: crate::WithDType>(&self) -> Result<S> {
+    pub fn to_vec0<S: crate::ElemType>(&self) -> Result<S> {
         self.to_scalar::<S>()
     }
 
@@ -740,6 +755,7 @@ impl Tensor {
                 is_variable: false,
                 dtype: self.dtype,
                 device: self.device.clone(),
+                hooks: RwLock::new(vec![]),
             };
             Ok(Tensor(Arc::new(tensor_)))
         }
@@ -750,19 +766,11 @@ impl Tensor {
             [] => Ok(self),
             [i] => self.squeeze(*i),
             dims => {
-                let dims = self
-                    .dims()
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(dim_idx, &v)| {
-                        if dims.contains(&dim_idx) {
-                            None
-                        } else {
-                            Some(v)
-                        }
-                    })
+                let kept = (0..self.rank())
+                    .filter(|dim_idx| !dims.contains(dim_idx))
                     .collect::<Vec<_>>();
-                self.reshape(dims)
+                let dims = kept.iter().map(|&i| self.dims()[i]).collect();
+                self.reshape(self.shape().rearranged(dims, kept.into_iter().map(Some)))
             }
         }
     }
@@ -778,7 +786,7 @@ impl Tensor {
             }
             ReduceOp::ArgMin | ReduceOp::ArgMax => BackpropOp::none(),
         };
-        let res = from_storage(storage, dims, op, false);
+        let res = from_storage(storage, self.shape().resized(dims), op, false);
         if keepdim {
             Ok(res)
         } else {
@@ -796,7 +804,7 @@ impl Tensor {
             dims[sum_dim] = 1
         }
         let op = BackpropOp::new1(self, |a| Op::Reduce(a, ReduceOp::Sum, dims.to_vec()));
-        let sum = from_storage(storage, dims, op, false);
+        let sum = from_storage(storage, self.shape().resized(dims), op, false);
         if keepdim {
             Ok(sum)
         } else {
@@ -920,10 +928,60 @@ impl Tensor {
         self.reduce_impl(dim, false, ReduceOp::ArgMin)
     }
 
+    /// Returns the indices that sort the tensor along the last dimension.
+    ///
+    /// If `asc` is `true`, sorting is in ascending order. Otherwise sorting is performed in
+    /// descending order. The sort is stable and the returned tensor uses `u32` elements.
+    pub fn arg_sort_last_dim(&self, asc: bool) -> Result<Tensor> {
+        if self.rank() == 0 {
+            bail!("arg_sort_last_dim requires a tensor with at least one dimension")
+        }
+        let t = self.contiguous()?;
+        let storage = t.storage().arg_sort_last_dim(t.layout(), asc)?;
+        Ok(from_storage(storage, t.shape(), BackpropOp::none(), false))
+    }
+
+    /// Sorts the tensor along the last dimension, returns the sorted tensor together with the
+    /// sorted indexes.
+    ///
+    /// If `asc` is `true`, sorting is in ascending order. Otherwise sorting is performed in
+    /// descending order. The gradient flows back to the original tensor through the sorted
+    /// values.
+    pub fn sort_last_dim(&self, asc: bool) -> Result<(Tensor, Tensor)> {
+        let t = self.contiguous()?;
+        let asort = t.arg_sort_last_dim(asc)?;
+        let sorted = t.gather(&asort, crate::D::Minus1)?;
+        Ok((sorted, asort))
+    }
+
+    /// Returns the `k` largest elements along the given dimension, sorted in descending order,
+    /// together with their indexes along this dimension.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[3f32, 1., 4., 1.], [5., 9., 2., 6.]], &Device::Cpu)?;
+    /// let (values, indexes) = t.topk(2, 1)?;
+    /// assert_eq!(values.to_vec2::<f32>()?, &[[4., 3.], [9., 6.]]);
+    /// assert_eq!(indexes.to_vec2::<u32>()?, &[[2, 0], [1, 3]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn topk<D: Dim>(&self, k: usize, dim: D) -> Result<(Tensor, Tensor)> {
+        let dim = dim.to_index(self.shape(), "topk")?;
+        let size = self.dim(dim)?;
+        if k > size {
+            bail!("topk: k ({k}) is larger than the size of dim {dim} ({size})")
+        }
+        let last_dim = self.rank() - 1;
+        let (values, indexes) = self.transpose(dim, last_dim)?.sort_last_dim(false)?;
+        let values = values.narrow(last_dim, 0, k)?.transpose(dim, last_dim)?;
+        let indexes = indexes.narrow(last_dim, 0, k)?.transpose(dim, last_dim)?;
+        Ok((values, indexes))
+    }
+
     /// Element-wise comparison between two tensors, e.g. equality, greater than, ... The actual
     /// comparison operation is specified by the `op` argument.
     ///
-    /// The returned tensor has the same shape as the original tensors and uses `u8` elements.
+    /// The returned tensor has the same shape as the original tensors and uses `bool` elements.
     pub fn cmp<T: TensorOrScalar>(&self, rhs: T, op: CmpOp) -> Result<Self> {
         let rhs = match rhs.to_tensor_scalar()? {
             crate::scalar::TensorScalar::Tensor(rhs) => rhs,
@@ -937,7 +995,7 @@ impl Tensor {
             .storage()
             .cmp(op, &rhs.storage(), self.layout(), rhs.layout())?;
         let op = BackpropOp::new1(self, |a| Op::Cmp(a, op));
-        Ok(from_storage(storage, shape.dims(), op, false))
+        Ok(from_storage(storage, shape, op, false))
     }
 
     /// Element-wise equality.
@@ -1096,6 +1154,94 @@ impl Tensor {
         Ok(from_storage(storage, (n, c, h_out, w_out), op, false))
     }
 
+    /// 3D average pooling over an input tensor with multiple channels.
+    ///
+    /// The input tensor should have five dimensions, `(batch, channels, d, h, w)`, the returned
+    /// tensor also has five dimensions, `(batch, channels, d', h', w')`. The pooling is performed
+    /// on the three last dimensions using a kernel of size `sz`. The returned element is the
+    /// average value over the kernel window.
+    pub fn avg_pool3d<T: crate::ToUsize3>(&self, sz: T) -> Result<Self> {
+        let sz = sz.to_usize3();
+        self.avg_pool3d_with_stride(sz, sz)
+    }
+
+    /// Same as `avg_pool3d` but with a `stride` that can be set to a value different from the
+    /// kernel size.
+    pub fn avg_pool3d_with_stride<T: crate::ToUsize3>(
+        &self,
+        kernel_size: T,
+        stride: T,
+    ) -> Result<Self> {
+        let kernel_size = kernel_size.to_usize3();
+        let stride = stride.to_usize3();
+        let (n, c, d, h, w) = self.dims5()?;
+        if d < kernel_size.0 || h < kernel_size.1 || w < kernel_size.2 {
+            bail!("kernel-size {kernel_size:?} is larger than the input size {d},{h},{w}")
+        }
+        // https://pytorch.org/docs/stable/generated/torch.nn.AvgPool3d.html
+        let d_out = (d - kernel_size.0) / stride.0 + 1;
+        let h_out = (h - kernel_size.1) / stride.1 + 1;
+        let w_out = (w - kernel_size.2) / stride.2 + 1;
+        let op = BackpropOp::new1(self, |arg| Op::AvgPool3D {
+            arg,
+            kernel_size,
+            stride,
+        });
+        let storage = self
+            .storage()
+            .avg_pool3d(self.layout(), kernel_size, stride)?;
+        Ok(from_storage(
+            storage,
+            (n, c, d_out, h_out, w_out),
+            op,
+            false,
+        ))
+    }
+
+    /// 3D max pooling over an input tensor with multiple channels.
+    ///
+    /// The input tensor should have five dimensions, `(batch, channels, d, h, w)`, the returned
+    /// tensor also has five dimensions, `(batch, channels, d', h', w')`. The pooling is performed
+    /// on the three last dimensions using a kernel of size `sz`, the returned element is the
+    /// maximum value over the kernel window.
+    pub fn max_pool3d<T: crate::ToUsize3>(&self, sz: T) -> Result<Self> {
+        let sz = sz.to_usize3();
+        self.max_pool3d_with_stride(sz, sz)
+    }
+
+    /// Same as `max_pool3d` but with a `stride` that can be set to a value different from the
+    /// kernel size.
+    pub fn max_pool3d_with_stride<T: crate::ToUsize3>(
+        &self,
+        kernel_size: T,
+        stride: T,
+    ) -> Result<Self> {
+        let kernel_size = kernel_size.to_usize3();
+        let stride = stride.to_usize3();
+        let (n, c, d, h, w) = self.dims5()?;
+        if d < kernel_size.0 || h < kernel_size.1 || w < kernel_size.2 {
+            bail!("kernel-size {kernel_size:?} is larger than the input size {d},{h},{w}")
+        }
+        // https://pytorch.org/docs/stable/generated/torch.nn.MaxPool3d.html
+        let d_out = (d - kernel_size.0) / stride.0 + 1;
+        let h_out = (h - kernel_size.1) / stride.1 + 1;
+        let w_out = (w - kernel_size.2) / stride.2 + 1;
+        let op = BackpropOp::new1(self, |arg| Op::MaxPool3D {
+            arg,
+            kernel_size,
+            stride,
+        });
+        let storage = self
+            .storage()
+            .max_pool3d(self.layout(), kernel_size, stride)?;
+        Ok(from_storage(
+            storage,
+            (n, c, d_out, h_out, w_out),
+            op,
+            false,
+        ))
+    }
+
     /// Returns the matrix-multiplication of the input tensor with the other provided tensor.
     ///
     /// # Arguments
@@ -1124,7 +1270,7 @@ impl Tensor {
         let k2 = b_dims[dim - 2];
         let n = b_dims[dim - 1];
 
-        let c_shape = Shape::from(&a_dims[..dim - 2]).extend(&[m, n]);
+        let c_shape = self.shape().matmul_shape(rhs.shape());
         let batching: usize = a_dims[..dim - 2].iter().product();
         let batching_b: usize = b_dims[..dim - 2].iter().product();
         if k != k2 || batching != batching_b {
@@ -1169,11 +1315,12 @@ impl Tensor {
     }
 
     /// Returns a tensor with the same shape as the input tensor, the values are taken from
-    /// `on_true` if the input tensor value is not zero, and `on_false` at the positions where the
-    /// input tensor is equal to zero.
+    /// `on_true` if the input tensor value is true (or not zero for integer inputs), and
+    /// `on_false` at the other positions.
     pub fn where_cond(&self, on_true: &Self, on_false: &Self) -> Result<Self> {
-        let _shap = self.same_shape_binary_op(on_true, "where_cond")?;
-        let shape = self.same_shape_binary_op(on_false, "where_cond")?;
+        let shape = self
+            .same_shape_binary_op(on_true, "where_cond")?
+            .same_shape_binary_op(on_false.shape(), "where_cond")?;
         let storage = self.storage().where_cond(
             self.layout(),
             &on_true.storage(),
@@ -1469,7 +1616,7 @@ impl Tensor {
     }
 
     /// Returns the data contained in a 1D tensor as a vector of scalar values.
-    pub fn to_vec1<S: crate::WithDType>(&self) -> Result<Vec<S>> {
+    pub fn to_vec1<S: crate::ElemType>(&self) -> Result<Vec<S>> {
         if self.rank() != 1 {
             Err(Error::UnexpectedNumberOfDims {
                 expected: 1,
@@ -1487,14 +1634,14 @@ impl Tensor {
             Ok::<Vec<_>, Error>(data)
         };
         match &*self.storage() {
-            Storage::Cpu(storage) => from_cpu_storage(storage),
+            Storage::Cpu(storage) | Storage::CpuPool(storage, _) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
     /// Returns the data contained in a 2D tensor as a vector of vector of scalar values.
-    pub fn to_vec2<S: crate::WithDType>(&self) -> Result<Vec<Vec<S>>> {
+    pub fn to_vec2<S: crate::ElemType>(&self) -> Result<Vec<Vec<S>>> {
         let (dim1, dim2) = self.dims2()?;
         let from_cpu_storage = |cpu_storage: &crate::CpuStorage| {
             let data = S::cpu_storage_as_slice(cpu_storage)?;
@@ -1518,14 +1665,14 @@ impl Tensor {
             Ok(rows)
         };
         match &*self.storage() {
-            Storage::Cpu(storage) => from_cpu_storage(storage),
+            Storage::Cpu(storage) | Storage::CpuPool(storage, _) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
     }
 
     /// Returns the data contained in a 3D tensor.
-    pub fn to_vec3<S: crate::WithDType>(&self) -> Result<Vec<Vec<Vec<S>>>> {
+    pub fn to_vec3<S: crate::ElemType>(&self) -> Result<Vec<Vec<Vec<S>>>> {
         let (dim1, dim2, dim3) = self.dims3()?;
         let from_cpu_storage = |cpu_storage: &crate::CpuStorage| {
             let data = S::cpu_storage_as_slice(cpu_storage)?;
@@ -1559,7 +1706,7 @@ impl Tensor {
             Ok(top_rows)
         };
         match &*self.storage() {
-            Storage::Cpu(storage) => from_cpu_storage(storage),
+            Storage::Cpu(storage) | Storage::CpuPool(storage, _) => from_cpu_storage(storage),
             Storage::Cuda(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
             Storage::Metal(storage) => from_cpu_storage(&storage.to_cpu_storage()?),
         }
@@ -1591,6 +1738,41 @@ impl Tensor {
         Ok(self.dims()[dim])
     }
 
+    /// The names of the dimensions of this tensor, `None` if no dimension is named.
+    pub fn names(&self) -> Option<&[Option<Arc<str>>]> {
+        self.shape().names()
+    }
+
+    /// Returns a view of the tensor where the dimensions have been given the specified names,
+    /// `None` can be used to leave a dimension unnamed. The names propagate through the tensor
+    /// ops and can be used wherever a dimension index is expected.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, DType, Device};
+    /// let t = Tensor::zeros((2, 5, 8), DType::F32, &Device::Cpu)?;
+    /// let t = t.rename(("batch", "seq", "hidden"))?;
+    /// assert_eq!(t.dim("seq")?, 5);
+    /// let s = t.sum_keepdim("hidden")?.transpose("batch", "seq")?;
+    /// assert_eq!(format!("{:?}", s.shape()), "[seq: 5, batch: 2, hidden: 1]");
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn rename<N: crate::DimNames>(&self, names: N) -> Result<Tensor> {
+        let shape = self.shape().clone().with_names(names)?;
+        let layout = Layout::new(shape, self.stride().to_vec(), self.layout.start_offset());
+        let op = BackpropOp::new1(self, Op::Reshape);
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: self.storage.clone(),
+            layout,
+            op,
+            is_variable: false,
+            dtype: self.dtype,
+            device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
+        };
+        Ok(Tensor(Arc::new(tensor_)))
+    }
+
     /// The layout of the input tensor, this stores both the shape of the tensor as well as the
     /// strides and the start offset to apply to the underlying storage.
     pub fn layout(&self) -> &Layout {
@@ -1626,6 +1808,21 @@ impl Tensor {
         &self.op
     }
 
+    pub(crate) fn saved_versions(&self) -> &[(Tensor, usize)] {
+        self.op.saved_versions()
+    }
+
+    /// The version of the storage of this tensor, this starts at zero and gets incremented each
+    /// time the storage is modified in place, e.g. by [`Tensor::add_`] or [`crate::Var::set`].
+    /// Tensors that share their storage, like views, also share their version.
+    pub fn version(&self) -> usize {
+        self.storage.version()
+    }
+
+    pub(crate) fn bump_version(&self) {
+        self.storage.bump_version()
+    }
+
     /// Computes the sum of all the elements in this tensor and returns a tensor holding this
     /// scalar with zero dimensions.
     ///
@@ -1783,6 +1980,7 @@ impl Tensor {
             is_variable: false,
             dtype: self.dtype,
             device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
         };
         Ok(Tensor(Arc::new(tensor_)))
     }
@@ -1819,10 +2017,164 @@ impl Tensor {
             is_variable: false,
             dtype: self.dtype,
             device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
         };
         Ok(Tensor(Arc::new(tensor_)))
     }
 
+    // Returns a view of `self` using `layout` on the same storage, `logical` is the same view
+    // expressed on the elements of `self` in row-major order and is used for backprop.
+    fn strided_view(&self, layout: Layout, logical: Layout) -> Tensor {
+        let op = BackpropOp::new1(self, |t| Op::AsStrided(t, logical.clone()));
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: self.storage.clone(),
+            layout,
+            op,
+            is_variable: false,
+            dtype: self.dtype,
+            device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
+        };
+        Tensor(Arc::new(tensor_))
+    }
+
+    /// Returns a view of the tensor with the given shape and strides, `offset` is the position of
+    /// the first element of the view. The strides and offset refer to the elements of `self` in
+    /// row-major order and an element can appear multiple times in the view. No copy is made
+    /// if `self` is contiguous.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 6, &Device::Cpu)?;
+    /// let t = t.as_strided((3, 3), &[1, 1], 1)?;
+    /// assert_eq!(t.to_vec2::<u32>()?, &[[1, 2, 3], [2, 3, 4], [3, 4, 5]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn as_strided<S: Into<Shape>>(
+        &self,
+        shape: S,
+        stride: &[usize],
+        offset: usize,
+    ) -> Result<Tensor> {
+        let shape = shape.into();
+        if stride.len() != shape.rank() {
+            bail!(
+                "as_strided: stride {stride:?} does not match the rank of {:?}",
+                shape
+            )
+        }
+        let last = if shape.elem_count() == 0 {
+            offset
+        } else {
+            offset
+                + shape
+                    .dims()
+                    .iter()
+                    .zip(stride.iter())
+                    .map(|(d, s)| (d - 1) * s)
+                    .sum::<usize>()
+        };
+        if shape.elem_count() > 0 && last >= self.elem_count() {
+            bail!(
+                "as_strided: view {shape:?} with stride {stride:?} and offset {offset} is out of bounds for {:?}",
+                self.shape()
+            )
+        }
+        let logical = Layout::new(shape.clone(), stride.to_vec(), offset);
+        let xs = self.contiguous()?;
+        let layout = Layout::new(shape, stride.to_vec(), xs.layout.start_offset() + offset);
+        Ok(xs.strided_view(layout, logical))
+    }
+
+    /// Returns a view of the sliding windows of length `size` along dimension `dim`, the
+    /// windows start every `step` elements. The dimension `dim` gets replaced by the number of
+    /// windows and a new dimension of size `size` is appended. The data is not copied.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 7, &Device::Cpu)?;
+    /// let t = t.unfold(0, 3, 2)?;
+    /// assert_eq!(t.to_vec2::<u32>()?, &[[0, 1, 2], [2, 3, 4], [4, 5, 6]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn unfold<D: Dim>(&self, dim: D, size: usize, step: usize) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "unfold")?;
+        let layout = self.layout.unfold(dim, size, step)?;
+        let logical = Layout::contiguous(self.shape()).unfold(dim, size, step)?;
+        Ok(self.strided_view(layout, logical))
+    }
+
+    /// Returns a view of the diagonal formed by dimensions `dim1` and `dim2`, these dimensions
+    /// are removed and a dimension for the diagonal is appended. A positive `offset` selects a
+    /// diagonal above the main one, a negative one a diagonal below it. The data is not copied.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 12, &Device::Cpu)?.reshape((3, 4))?;
+    /// assert_eq!(t.diagonal(0, 0, 1)?.to_vec1::<u32>()?, &[0, 5, 10]);
+    /// assert_eq!(t.diagonal(2, 0, 1)?.to_vec1::<u32>()?, &[2, 7]);
+    /// assert_eq!(t.diagonal(-1, 0, 1)?.to_vec1::<u32>()?, &[4, 9]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn diagonal<D1: Dim, D2: Dim>(&self, offset: isize, dim1: D1, dim2: D2) -> Result<Tensor> {
+        let dim1 = dim1.to_index(self.shape(), "diagonal")?;
+        let dim2 = dim2.to_index(self.shape(), "diagonal")?;
+        let layout = self.layout.diagonal(offset, dim1, dim2)?;
+        let logical = Layout::contiguous(self.shape()).diagonal(offset, dim1, dim2)?;
+        Ok(self.strided_view(layout, logical))
+    }
+
+    /// Reverses the order of the elements along the given dimensions. Negative strides are not
+    /// supported so this makes a copy.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 6, &Device::Cpu)?.reshape((2, 3))?;
+    /// assert_eq!(t.flip(1)?.to_vec2::<u32>()?, &[[2, 1, 0], [5, 4, 3]]);
+    /// assert_eq!(t.flip((0, 1))?.to_vec2::<u32>()?, &[[5, 4, 3], [2, 1, 0]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn flip<D: Dims>(&self, dims: D) -> Result<Tensor> {
+        let dims = dims.to_indexes(self.shape(), "flip")?;
+        let mut xs = self.clone();
+        for dim in dims {
+            let size = self.dim(dim)?;
+            if size <= 1 {
+                continue;
+            }
+            let indexes: Vec<u32> = (0..size as u32).rev().collect();
+            let indexes = Tensor::from_vec(indexes, size, self.device())?;
+            xs = xs.contiguous()?.index_select(&indexes, dim)?;
+        }
+        Ok(xs)
+    }
+
+    /// Rolls the elements along dimension `dim` by `shift` positions, the elements that go past
+    /// the last position are moved back to the first ones.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(0u32, 5, &Device::Cpu)?;
+    /// assert_eq!(t.roll(2, 0)?.to_vec1::<u32>()?, &[3, 4, 0, 1, 2]);
+    /// assert_eq!(t.roll(-1, 0)?.to_vec1::<u32>()?, &[1, 2, 3, 4, 0]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn roll<D: Dim>(&self, shift: isize, dim: D) -> Result<Tensor> {
+        let dim = dim.to_index(self.shape(), "roll")?;
+        let size = self.dim(dim)?;
+        if size == 0 {
+            return Ok(self.clone());
+        }
+        let shift = shift.rem_euclid(size as isize) as usize;
+        if shift == 0 {
+            return Ok(self.clone());
+        }
+        let head = self.narrow(dim, size - shift, shift)?;
+        let tail = self.narrow(dim, 0, size - shift)?;
+        Tensor::cat(&[&head, &tail], dim)
+    }
+
     /// Returns true if the data is stored in a C contiguous (aka row major) way.
     pub fn is_contiguous(&self) -> bool {
         self.layout.is_contiguous()
@@ -1837,14 +2189,17 @@ impl Tensor {
     /// memory.
     pub fn copy(&self) -> Result<Tensor> {
         let op = BackpropOp::new1(self, Op::Copy);
+        let storage = self.storage().try_clone(self.layout())?;
+        let storage = TrackedStorage::new(storage, self.elem_count(), op.name());
         let tensor_ = Tensor_ {
             id: TensorId::new(),
-            storage: Arc::new(RwLock::new(self.storage().try_clone(self.layout())?)),
+            storage: Arc::new(storage),
             layout: self.layout.clone(),
             op,
             is_variable: false,
             dtype: self.dtype,
             device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
         };
         Ok(Tensor(Arc::new(tensor_)))
     }
@@ -1865,45 +2220,100 @@ impl Tensor {
                 is_variable: false,
                 dtype: self.dtype,
                 device: self.device.clone(),
+                hooks: RwLock::new(vec![]),
             };
             Ok(Tensor(Arc::new(tensor_)))
         }
     }
 
+    /// Registers a callback that is called with the gradient of this tensor during the backward
+    /// pass. The callback can inspect the gradient and return `None` to keep it unchanged, or
+    /// return `Some(grad)` to replace it before it gets propagated to the arguments of this
+    /// tensor. Hooks are run in the order in which they have been registered.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Var, Device};
+    /// let x = Var::new(&[1f32, 2., 3.], &Device::Cpu)?;
+    /// x.register_hook(|grad| Ok(Some(grad.affine(2., 0.)?)));
+    /// let grads = x.sqr()?.sum_all()?.backward()?;
+    /// let grad = grads.get(&x).unwrap();
+    /// assert_eq!(grad.to_vec1::<f32>()?, &[4., 8., 12.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn register_hook<F>(&self, f: F)
+    where
+        F: Fn(&Tensor) -> Result<Option<Tensor>> + Send + Sync + 'static,
+    {
+        self.hooks.write().unwrap().push(Arc::new(f))
+    }
+
+    /// Removes all the gradient hooks registered on this tensor.
+    pub fn clear_hooks(&self) {
+        self.hooks.write().unwrap().clear()
+    }
+
+    pub(crate) fn hooks(&self) -> Vec<crate::backprop::GradHook> {
+        self.hooks.read().unwrap().clone()
+    }
+
+    // Returns a new tensor sharing the storage of this one but with a different backprop op.
+    pub(crate) fn with_op(&self, op: BackpropOp) -> Tensor {
+        let tensor_ = Tensor_ {
+            id: TensorId::new(),
+            storage: self.storage.clone(),
+            layout: self.layout.clone(),
+            op,
+            is_variable: false,
+            dtype: self.dtype,
+            device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
+        };
+        Tensor(Arc::new(tensor_))
+    }
+
     /// If the target device is the same as the tensor device, only a shallow copy is performed.
     pub fn to_device(&self, device: &Device) -> Result<Tensor> {
         if self.device().same_device(device) {
             Ok(self.clone())
         } else {
             let storage = match (&*self.storage(), device) {
-                (Storage::Cpu(storage), Device::Cuda(cuda)) => {
+                (Storage::Cpu(storage) | Storage::CpuPool(storage, _), Device::Cuda(cuda)) => {
                     Storage::Cuda(cuda.storage_from_cpu_storage(storage)?)
                 }
-                (Storage::Cpu(storage), Device::Metal(metal)) => {
+                (Storage::Cpu(storage) | Storage::CpuPool(storage, _), Device::Metal(metal)) => {
                     Storage::Metal(metal.storage_from_cpu_storage(storage)?)
                 }
-                (Storage::Cuda(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
-                (Storage::Metal(storage), Device::Cpu) => Storage::Cpu(storage.to_cpu_storage()?),
+                (Storage::Cuda(storage), Device::Cpu | Device::CpuPool(_)) => {
+                    device.cpu_storage(storage.to_cpu_storage()?)
+                }
+                (Storage::Metal(storage), Device::Cpu | Device::CpuPool(_)) => {
+                    device.cpu_storage(storage.to_cpu_storage()?)
+                }
                 (Storage::Cuda(storage), Device::Cuda(cuda)) => {
                     // TODO: Avoid passing through the cpu storage here, especially if the gpu ids
                     // are the same.
                     let cpu_storage = storage.to_cpu_storage()?;
                     Storage::Cuda(cuda.storage_from_cpu_storage(&cpu_storage)?)
                 }
-                (Storage::Cpu(storage), Device::Cpu) => Storage::Cpu(storage.clone()),
+                (
+                    Storage::Cpu(storage) | Storage::CpuPool(storage, _),
+                    Device::Cpu | Device::CpuPool(_),
+                ) => device.cpu_storage(storage.clone()),
                 _ => {
                     bail!("not implemented yet")
                 }
             };
             let op = BackpropOp::new1(self, Op::ToDevice);
+            let storage = TrackedStorage::new(storage, self.elem_count(), op.name());
             let tensor_ = Tensor_ {
                 id: TensorId::new(),
-                storage: Arc::new(RwLock::new(storage)),
+                storage: Arc::new(storage),
                 layout: self.layout.clone(),
                 op,
                 is_variable: false,
                 dtype: self.dtype,
                 device: device.clone(),
+                hooks: RwLock::new(vec![]),
             };
             Ok(Tensor(Arc::new(tensor_)))
         }
@@ -1934,6 +2344,7 @@ impl Tensor {
             is_variable: false,
             dtype: self.dtype,
             device: self.device.clone(),
+            hooks: RwLock::new(vec![]),
         };
         Ok(Tensor(Arc::new(tensor_)))
     }
@@ -2033,6 +2444,7 @@ impl Tensor {
                 is_variable: false,
                 dtype: self.dtype,
                 device: self.device.clone(),
+                hooks: RwLock::new(vec![]),
             };
             Ok(Tensor(Arc::new(tensor_)))
         } else {
@@ -2064,7 +2476,8 @@ impl Tensor {
         if dims[dim] == 1 {
             let mut dims = dims.to_vec();
             dims.remove(dim);
-            self.reshape(dims)
+            let src = (0..self.rank()).filter(|&i| i != dim).map(Some);
+            self.reshape(self.shape().rearranged(dims, src))
         } else {
             Ok(self.clone())
         }
@@ -2088,7 +2501,9 @@ impl Tensor {
         let dim = dim.to_index_plus_one(self.shape(), "unsqueeze")?;
         // Cannot panic because to_index_plus_one already checks dimensions
         dims.insert(dim, 1);
-        self.reshape(dims)
+        let mut src = (0..self.rank()).map(Some).collect::<Vec<_>>();
+        src.insert(dim, None);
+        self.reshape(self.shape().rearranged(dims, src))
     }
 
     /// Stacks two or more tensors along a particular dimension.
@@ -2254,7 +2669,7 @@ impl Tensor {
             let next_offset = offsets.last().unwrap() + arg.elem_count();
             offsets.push(next_offset);
         }
-        let shape = Shape::from(cat_dims);
+        let shape = arg0.shape().resized(cat_dims);
         let op = BackpropOp::new(args, |args| Op::Cat(args, 0));
         let mut storage = device.zeros(&shape, dtype)?;
         for (arg, &offset) in args.iter().zip(offsets.iter()) {
@@ -2265,6 +2680,65 @@ impl Tensor {
         Ok(from_storage(storage, shape, op, false))
     }
 
+    /// Pads the trailing dimensions of the tensor, the last pair of `pad` applies to the last
+    /// dimension, the previous pair to the previous dimension and so on. Each pair gives the
+    /// number of elements added before and after the values of that dimension.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device, PadMode};
+    /// let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &Device::Cpu)?;
+    /// let p = t.pad(&[(2, 1)], PadMode::Reflect)?;
+    /// assert_eq!(p.to_vec2::<f32>()?, &[[3., 2., 1., 2., 3., 2.], [6., 5., 4., 5., 6., 5.]]);
+    /// let p = t.pad(&[(1, 0), (0, 1)], PadMode::Constant(-1.))?;
+    /// assert_eq!(
+    ///     p.to_vec2::<f32>()?,
+    ///     &[[-1., -1., -1., -1.], [1., 2., 3., -1.], [4., 5., 6., -1.]]
+    /// );
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn pad(&self, pad: &[(usize, usize)], mode: PadMode) -> Result<Self> {
+        let rank = self.rank();
+        if pad.len() > rank {
+            bail!(
+                "pad: {} padding pairs provided for a tensor of rank {rank}",
+                pad.len()
+            )
+        }
+        let mut xs = self.clone();
+        for (dim, &(before, after)) in (rank - pad.len()..rank).zip(pad.iter()) {
+            if before == 0 && after == 0 {
+                continue;
+            }
+            xs = match mode {
+                PadMode::Constant(v) => {
+                    let mut dims = xs.dims().to_vec();
+                    let mut pad_with = |len| {
+                        dims[dim] = len;
+                        Tensor::new(v, xs.device())?
+                            .to_dtype(xs.dtype())?
+                            .broadcast_as(dims.as_slice())
+                    };
+                    let before = pad_with(before)?;
+                    let after = pad_with(after)?;
+                    Tensor::cat(&[&before, &xs, &after], dim)?
+                }
+                PadMode::Reflect | PadMode::Replicate | PadMode::Circular => {
+                    let size = xs.dim(dim)?;
+                    if size == 0 {
+                        bail!("pad: cannot use {mode:?} padding on an empty dimension")
+                    }
+                    let indexes: Vec<u32> = (-(before as isize)..(size + after) as isize)
+                        .map(|i| pad_index(i, size, mode) as u32)
+                        .collect();
+                    let len = indexes.len();
+                    let indexes = Tensor::from_vec(indexes, len, xs.device())?;
+                    xs.contiguous()?.index_select(&indexes, dim)?
+                }
+            }
+        }
+        Ok(xs)
+    }
+
     /// Pad the input tensor using 0s along dimension `dim`. This adds `left` elements before the
     /// input tensor values and `right` elements after.
     pub fn pad_with_zeros<D: Dim>(&self, dim: D, left: usize, right: usize) -> Result<Self> {
@@ -2362,6 +2836,13 @@ impl Tensor {
         (storage, &self.layout)
     }
 
+    // Whether the storage is also used by other tensors, e.g. views created with `reshape` or
+    // `narrow`. The clones of a tensor are the same tensor and do not count. Views can be created
+    // concurrently so this is only meaningful while holding the write lock on the storage.
+    pub(crate) fn storage_is_shared(&self) -> bool {
+        Arc::strong_count(&self.storage) > 1
+    }
+
     pub(crate) fn same_storage(&self, rhs: &Self) -> bool {
         let lhs: &RwLock<Storage> = self.storage.as_ref();
         let rhs: &RwLock<Storage> = rhs.storage.as_ref();
@@ -2369,13 +2850,13 @@ impl Tensor {
     }
 
     /// Applies a unary custom op without backward support
-    pub fn apply_op1_no_bwd<C: CustomOp1>(&self, c: &C) -> Result<Self> {
+    pub fn apply_op1_no_bwd<C: CustomOp1 + Sync>(&self, c: &C) -> Result<Self> {
         let (storage, shape) = self.storage().apply_op1(self.layout(), c)?;
         Ok(from_storage(storage, shape, BackpropOp::none(), false))
     }
 
     /// Applies a binary custom op without backward support
-    pub fn apply_op2_no_bwd<C: CustomOp2>(&self, rhs: &Self, c: &C) -> Result<Self> {
+    pub fn apply_op2_no_bwd<C: CustomOp2 + Sync>(&self, rhs: &Self, c: &C) -> Result<Self> {
         let (storage, shape) =
             self.storage()
                 .apply_op2(self.layout(), &rhs.storage(), rhs.layout(), c)?;
@@ -2383,7 +2864,12 @@ impl Tensor {
     }
 
     /// Applies a ternary custom op without backward support
-    pub fn apply_op3_no_bwd<C: CustomOp3>(&self, t2: &Self, t3: &Self, c: &C) -> Result<Self> {
+    pub fn apply_op3_no_bwd<C: CustomOp3 + Sync>(
+        &self,
+        t2: &Self,
+        t3: &Self,
+        c: &C,
+    ) -> Result<Self> {
         let (storage, shape) = self.storage().apply_op3(
             self.layout(),
             &t2.storage(),
@@ -2491,6 +2977,45 @@ impl Tensor {
         t1.ge(&t2)?.to_dtype(dtype)
     }
 
+    /// Returns a copy of the tensor where the elements above the `diagonal`-th diagonal of the
+    /// last two dimensions are set to zero. A positive `diagonal` refers to a diagonal above the
+    /// main one, a negative one to a diagonal below it.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::arange(1u32, 7, &Device::Cpu)?.reshape((2, 3))?;
+    /// assert_eq!(t.tril(0)?.to_vec2::<u32>()?, &[[1, 0, 0], [4, 5, 0]]);
+    /// assert_eq!(t.triu(1)?.to_vec2::<u32>()?, &[[0, 2, 3], [0, 0, 6]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn tril(&self, diagonal: isize) -> Result<Self> {
+        self.triangular(diagonal, true)
+    }
+
+    /// Returns a copy of the tensor where the elements below the `diagonal`-th diagonal of the
+    /// last two dimensions are set to zero, see [`Tensor::tril`].
+    pub fn triu(&self, diagonal: isize) -> Result<Self> {
+        self.triangular(diagonal, false)
+    }
+
+    fn triangular(&self, diagonal: isize, lower: bool) -> Result<Self> {
+        let (h, w) = match self.dims() {
+            [.., h, w] => (*h, *w),
+            _ => bail!(
+                "tril/triu expect at least two dimensions, got {:?}",
+                self.shape()
+            ),
+        };
+        let mask: Vec<u8> = (0..h * w)
+            .map(|i| {
+                let d = (i % w) as isize - (i / w) as isize;
+                (if lower { d <= diagonal } else { d >= diagonal }) as u8
+            })
+            .collect();
+        let mask = Tensor::from_vec(mask, (h, w), self.device())?.broadcast_as(self.shape())?;
+        mask.where_cond(self, &self.zeros_like()?)
+    }
+
     /// Returns a matrix with a diagonal of ones of size n by n.
     pub fn eye(n: usize, dtype: DType, device: &Device) -> Result<Self> {
         let t = Tensor::arange(0u32, n as u32, device)?;
@@ -2499,26 +3024,158 @@ impl Tensor {
         t1.eq(&t2)?.to_dtype(dtype)
     }
 
+    fn scan_impl<D: Dim>(&self, dim: D, op: ScanOp) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), op.name())?;
+        let storage = self.storage().scan(self.layout(), op, dim)?;
+        let op = BackpropOp::new1(self, |arg| Op::Scan(arg, op, dim));
+        Ok(from_storage(storage, self.shape(), op, false))
+    }
+
     /// Returns the cumulative sum of elements of the input tensor summed over the specified
     /// dimension.
     ///
-    /// This operation is most efficient when dim is the last dimension of the tensor.
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &Device::Cpu)?;
+    /// assert_eq!(t.cumsum(1)?.to_vec2::<f32>()?, &[[1., 3., 6.], [4., 9., 15.]]);
+    /// assert_eq!(t.cumsum(0)?.to_vec2::<f32>()?, &[[1., 2., 3.], [5., 7., 9.]]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
     pub fn cumsum<D: Dim>(&self, dim: D) -> Result<Self> {
-        let dim = dim.to_index(self.shape(), "cumsum")?;
-        let rank = self.rank();
-        if rank == 0 {
-            return Ok(self.clone());
+        self.scan_impl(dim, ScanOp::Sum)
+    }
+
+    /// Returns the cumulative product of elements of the input tensor over the specified
+    /// dimension.
+    pub fn cumprod<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.scan_impl(dim, ScanOp::Prod)
+    }
+
+    /// Returns the running maximum of the input tensor over the specified dimension. Once a nan
+    /// value has been encountered, all the following values are nan.
+    pub fn cummax<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.scan_impl(dim, ScanOp::Max)
+    }
+
+    /// Returns the running minimum of the input tensor over the specified dimension. Once a nan
+    /// value has been encountered, all the following values are nan.
+    pub fn cummin<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.scan_impl(dim, ScanOp::Min)
+    }
+
+    /// Returns the logarithm of the cumulative sum of the exponentials of the input tensor over
+    /// the specified dimension. This is computed in a numerically stable way and only supports
+    /// floating point dtypes.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let t = Tensor::new(&[0f64, 0., 1000.], &Device::Cpu)?;
+    /// let t = t.logcumsumexp(0)?.to_vec1::<f64>()?;
+    /// assert_eq!(t, &[0., std::f64::consts::LN_2, 1000.]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn logcumsumexp<D: Dim>(&self, dim: D) -> Result<Self> {
+        self.scan_impl(dim, ScanOp::LogSumExp)
+    }
+
+    /// Computes the linear recurrence `h_i = a_i * h_{i-1} + b_i` along dimension `dim`, starting
+    /// from `h_{-1} = 0`, where `self` holds the `a` values. The two tensors must have the same
+    /// shape, the recurrence is computed sequentially along `dim` in a single pass.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2., 0.5, 3.], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 1., 2., 0.], &Device::Cpu)?;
+    /// let hs = a.linear_recurrence(&b, 0)?;
+    /// assert_eq!(hs.to_vec1::<f32>()?, &[1., 3., 3.5, 10.5]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn linear_recurrence<D: Dim>(&self, b: &Self, dim: D) -> Result<Self> {
+        let dim = dim.to_index(self.shape(), "linear-recurrence")?;
+        let shape = self.same_shape_binary_op(b, "linear-recurrence")?;
+        let storage =
+            self.storage()
+                .linear_recurrence(self.layout(), &b.storage(), b.layout(), dim)?;
+        let op = BackpropOp::new2(self, b, |a, b| Op::LinearRecurrence(a, b, dim));
+        Ok(from_storage(storage, shape, op, false))
+    }
+
+    /// Inclusive scan of the tensors `xs` along dimension `dim` using an associative operation.
+    ///
+    /// The scanned values are tuples of tensors, element `i` along `dim` of each of the returned
+    /// tensors is `f(... f(f(x_0, x_1), x_2) ..., x_i)` where `x_i` is the tuple of elements `i`
+    /// of the tensors in `xs`. The combining function `f(earlier, later)` gets called on slices
+    /// of the tensors along `dim` and should return as many tensors as there are in `xs`.
+    ///
+    /// The scan is computed with `log2(n)` calls to `f` on the whole tensors, so it is
+    /// differentiable as long as `f` is, and `f` has to be associative but not necessarily
+    /// commutative. Each step also concatenates the tensors back together so the scan does
+    /// `O(n log n)` work and allocates `log2(n)` copies of the inputs, this is mostly useful for
+    /// operations that have no native kernel. For example the linear recurrence
+    /// `h_i = a_i * h_{i-1} + b_i` can be computed as a scan on the `(a, b)` pairs, although
+    /// [`Tensor::linear_recurrence`] computes it in a single pass.
+    ///
+    /// ```rust
+    /// use candle_core::{Tensor, Device};
+    /// let a = Tensor::new(&[1f32, 2., 0.5, 3.], &Device::Cpu)?;
+    /// let b = Tensor::new(&[1f32, 1., 2., 0.], &Device::Cpu)?;
+    /// let hs = Tensor::associative_scan(&[a, b], 0, |l, r| {
+    ///     let a = (&l[0] * &r[0])?;
+    ///     let b = ((&r[0] * &l[1])? + &r[1])?;
+    ///     Ok(vec![a, b])
+    /// })?;
+    /// assert_eq!(hs[1].to_vec1::<f32>()?, &[1., 3., 3.5, 10.5]);
+    /// # Ok::<(), candle_core::Error>(())
+    /// ```
+    pub fn associative_scan<D: Dim, F>(xs: &[Self], dim: D, f: F) -> Result<Vec<Self>>
+    where
+        F: Fn(&[Self], &[Self]) -> Result<Vec<Self>>,
+    {
+        let first = match xs.first() {
+            Some(first) => first,
+            None => bail!("associative_scan expects at least one tensor"),
+        };
+        let dim = dim.to_index(first.shape(), "associative_scan")?;
+        let size = first.dim(dim)?;
+        for x in xs.iter() {
+            if x.rank() != first.rank() || x.dim(dim)? != size {
+                bail!(
+                    "associative_scan: shape mismatch between {:?} and {:?} on dim {dim}",
+                    first.shape(),
+                    x.shape()
+                )
+            }
         }
-        let n_axis = self.dim(dim)?;
-        let triu = Tensor::triu2(n_axis, self.dtype(), self.device())?;
-        if rank == 1 {
-            self.unsqueeze(0)?.matmul(&triu)?.squeeze(0)
-        } else {
-            let last = rank - 1;
-            let t = self.transpose(dim, last)?;
-            let t = t.broadcast_matmul(&triu)?;
-            t.transpose(dim, last)
+        // Hillis-Steele scan, after the step with offset `o` each element combines itself with
+        // the `2 * o - 1` elements preceding it.
+        let mut xs = xs.to_vec();
+        let mut offset = 1;
+        while offset < size {
+            let len = size - offset;
+            let earlier = xs
+                .iter()
+                .map(|x| x.narrow(dim, 0, len))
+                .collect::<Result<Vec<_>>>()?;
+            let later = xs
+                .iter()
+                .map(|x| x.narrow(dim, offset, len))
+                .collect::<Result<Vec<_>>>()?;
+            let combined = f(&earlier, &later)?;
+            if combined.len() != xs.len() {
+                bail!(
+                    "associative_scan: the combining function returned {} tensors, expected {}",
+                    combined.len(),
+                    xs.len()
+                )
+            }
+            xs = xs
+                .iter()
+                .zip(combined.iter())
+                .map(|(x, c)| Tensor::cat(&[&x.narrow(dim, 0, offset)?, c], dim))
+                .collect::<Result<Vec<_>>>()?;
+            offset *= 2;
         }
+        Ok(xs)
     }
 
     /// Returns a copy of `self` where the values within `ranges` have been replaced with the
@@ -2723,3 +3380,27 @@ impl std::ops::Div<&Tensor> for f64 {
         rhs.recip()? * self
     }
 }
+
+// Maps an index of the padded dimension, which can be out of `0..size`, to an index of the
+// original dimension.
+fn pad_index(i: isize, size: usize, mode: PadMode) -> usize {
+    let size = size as isize;
+    let i = match mode {
+        PadMode::Constant(_) | PadMode::Replicate => i.clamp(0, size - 1),
+        PadMode::Circular => i.rem_euclid(size),
+        PadMode::Reflect => {
+            if size == 1 {
+                0
+            } else {
+                let period = 2 * (size - 1);
+                let i = i.rem_euclid(period);
+                if i < size {
+                    i
+                } else {
+                    period - i
+                }
+            }
+        }
+    };
+    i as usize
+}
@@ -60,3 +60,28 @@ pub fn to_vec3_round(t: &Tensor, digits: i32) -> Result<Vec<Vec<Vec<f32>>>> {
         .collect();
     Ok(t)
 }
+
+/// Compares the gradient of the scalar valued function `f` at `x` with central finite
+/// differences. The tensor `x` should use `f64` elements to get accurate differences.
+pub fn check_grad<F: Fn(&Tensor) -> Result<Tensor>>(f: F, x: &Tensor) -> Result<()> {
+    let var = crate::Var::from_tensor(x)?;
+    let grads = f(&var)?.backward()?;
+    let grad = match grads.get(&var) {
+        Some(grad) => grad.flatten_all()?.to_vec1::<f64>()?,
+        None => crate::bail!("no gradient for the input"),
+    };
+    let xs = x.flatten_all()?.to_vec1::<f64>()?;
+    let eps = 1e-6;
+    for (i, g) in grad.iter().enumerate() {
+        let mut xs = xs.clone();
+        xs[i] += eps;
+        let plus = f(&Tensor::from_vec(xs.clone(), x.shape(), x.device())?)?;
+        xs[i] -= 2. * eps;
+        let minus = f(&Tensor::from_vec(xs, x.shape(), x.device())?)?;
+        let fd = (plus.to_scalar::<f64>()? - minus.to_scalar::<f64>()?) / (2. * eps);
+        if (fd - g).abs() > 1e-5 * (1. + g.abs()) {
+            crate::bail!("gradient mismatch at index {i}, finite difference {fd}, backprop {g}")
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,30 @@
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the deterministic mode. When enabled, the results of the cpu ops do not
+/// depend on the number of threads used to compute them, and the random tensors created on cpu
+/// are generated from a separate stream per op derived from the seed set via
+/// [`crate::Device::set_seed`], or from a seed of zero if no seed has been set.
+///
+/// Running the same program with the same seed then gives bit-identical results whatever the
+/// size of the thread pool, at the cost of some performance for large matmuls.
+pub fn set_deterministic(deterministic: bool) {
+    DETERMINISTIC.store(deterministic, Ordering::Relaxed)
+}
+
+/// Returns true if the deterministic mode is enabled, see [`set_deterministic`].
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/// The number of threads used by the cpu ops. Within a thread pool, e.g. when running the ops of
+/// a tensor on [`crate::Device::CpuPool`], this is the size of the pool.
 pub fn get_num_threads() -> usize {
+    if rayon::current_thread_index().is_some() {
+        return rayon::current_num_threads();
+    }
     // Respond to the same environment variable as rayon.
     match std::env::var("RAYON_NUM_THREADS")
         .ok()
@@ -138,6 +138,7 @@ impl Var {
             .bt())?
         }
         src.copy_strided_src(&mut dst, layout.start_offset(), src_l)?;
+        self.bump_version();
         Ok(())
     }
 }
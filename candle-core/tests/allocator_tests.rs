@@ -0,0 +1,79 @@
+use candle_core::{memory, DType, Device, Result, Storage, Tensor};
+
+// The address of the first element of the buffer of a cpu tensor.
+fn buffer_addr(t: &Tensor) -> Result<usize> {
+    let (storage, _) = t.storage_and_layout();
+    match &*storage {
+        Storage::Cpu(storage) | Storage::CpuPool(storage, _) => {
+            Ok(storage.as_slice::<f32>()?.as_ptr() as usize)
+        }
+        _ => candle_core::bail!("not a cpu tensor"),
+    }
+}
+
+// The caching allocator is process wide so everything is checked in a single test.
+#[test]
+fn caching_allocator() -> Result<()> {
+    let device = Device::Cpu;
+    let xs = Tensor::arange(0f32, 64., &device)?.reshape((8, 8))?;
+    let expected = xs.exp()?.matmul(&xs.t()?)?.sum_keepdim(1)?;
+    let expected = expected.flatten_all()?.to_vec1::<f32>()?;
+
+    device.set_caching_allocator(true)?;
+    let before = device.caching_allocator_stats();
+    for _ in 0..8 {
+        let ys = xs.exp()?.matmul(&xs.t()?)?.sum_keepdim(1)?;
+        assert_eq!(ys.flatten_all()?.to_vec1::<f32>()?, expected);
+        // Buffers that are reused must be fully overwritten.
+        let zs = Tensor::zeros((8, 8), DType::F32, &device)?;
+        assert_eq!(zs.sum_all()?.to_vec0::<f32>()?, 0.);
+        let cs = Tensor::cat(&[&xs, &xs], 0)?.affine(2., 1.)?;
+        assert_eq!(
+            cs.narrow(0, 8, 8)?.to_vec2::<f32>()?,
+            ((&xs * 2.)? + 1.)?.to_vec2::<f32>()?
+        );
+    }
+    let stats = device.caching_allocator_stats();
+    assert!(stats.hits > before.hits);
+    assert!(stats.hit_rate() > 0.);
+    assert!(stats.cached_buffers > 0);
+    assert!(stats.cached_bytes >= 64 * 4);
+
+    // Recycled buffers keep their alignment, including when reused for another element type.
+    for len in [3, 16, 100, 1000] {
+        let hits = device.caching_allocator_stats().hits;
+        drop(Tensor::ones(len, DType::F64, &device)?);
+        let ys = Tensor::ones(2 * len, DType::F32, &device)?;
+        assert!(device.caching_allocator_stats().hits > hits);
+        assert_eq!(buffer_addr(&ys)? % 64, 0);
+        assert_eq!(ys.sum_all()?.to_vec0::<f32>()?, (2 * len) as f32);
+        assert_eq!(buffer_addr(&ys.exp()?)? % 64, 0);
+    }
+
+    device.empty_cache();
+    let stats = device.caching_allocator_stats();
+    assert_eq!((stats.cached_buffers, stats.cached_bytes), (0, 0));
+
+    // Beyond the limit, the least recently cached buffers are evicted.
+    memory::set_cpu_cache_limit(4096);
+    let small = Tensor::ones(64, DType::F32, &device)?;
+    let large = Tensor::ones(1024, DType::F32, &device)?;
+    drop(small);
+    drop(large);
+    let stats = device.caching_allocator_stats();
+    assert_eq!((stats.cached_buffers, stats.cached_bytes), (1, 4096));
+    assert!(stats.evictions > 0);
+    drop(Tensor::ones(4096, DType::F32, &device)?);
+    assert_eq!(device.caching_allocator_stats().cached_bytes, 4096);
+    memory::set_cpu_cache_limit(1024);
+    assert_eq!(device.caching_allocator_stats().cached_bytes, 0);
+    memory::set_cpu_cache_limit(1 << 30);
+
+    device.set_caching_allocator(false)?;
+    let ys = xs.exp()?;
+    drop(ys);
+    let after = device.caching_allocator_stats();
+    assert_eq!(after.hits, stats.hits);
+    assert_eq!(after.cached_bytes, 0);
+    Ok(())
+}
@@ -563,6 +563,153 @@ fn conv2d_grad(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn conv3d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t = Tensor::randn(0f32, 1., (2, 4, 5, 6, 7), dev)?;
+    let w = Tensor::randn(0f32, 1., (3, 4, 2, 3, 3), dev)?;
+    let res = t.conv3d(&w, 0, 1, 1, 1)?;
+    assert_eq!(res.dims(), [2, 3, 4, 4, 5]);
+    // Each output depth slice sums the 2d convolutions over the kernel depth.
+    for z in 0..4 {
+        let mut expected = t.i((.., .., z))?.conv2d(&w.i((.., .., 0))?, 0, 1, 1, 1)?;
+        expected = (expected
+            + t.i((.., .., z + 1))?
+                .conv2d(&w.i((.., .., 1))?, 0, 1, 1, 1)?)?;
+        let diff = (res.i((.., .., z))? - expected)?
+            .abs()?
+            .flatten_all()?
+            .max(0)?;
+        assert!(diff.to_scalar::<f32>()? < 1e-4);
+    }
+
+    // Padding is the same as zero-padding all the spatial dimensions.
+    let res = t.conv3d(&w, 1, 2, 1, 1)?;
+    let padded = t
+        .pad_with_zeros(2, 1, 1)?
+        .pad_with_zeros(3, 1, 1)?
+        .pad_with_zeros(4, 1, 1)?;
+    let expected = padded.conv3d(&w, 0, 2, 1, 1)?;
+    assert_eq!(res.dims(), [2, 3, 3, 3, 4]);
+    let diff = (res - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+
+    // Dilation, the kernel is spread with zeros.
+    let res = t.conv3d(&w, 0, 1, 2, 1)?;
+    let w_dilated = dilate2(&w)?;
+    let expected = t.conv3d(&w_dilated, 0, 1, 1, 1)?;
+    assert_eq!(res.dims(), [2, 3, 3, 2, 3]);
+    let diff = (res - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+
+    // Groups.
+    let w = Tensor::randn(0f32, 1., (6, 2, 1, 2, 2), dev)?;
+    let res = t.conv3d(&w, 0, 1, 1, 2)?;
+    let r0 = t.narrow(1, 0, 2)?.conv3d(&w.narrow(0, 0, 3)?, 0, 1, 1, 1)?;
+    let r1 = t.narrow(1, 2, 2)?.conv3d(&w.narrow(0, 3, 3)?, 0, 1, 1, 1)?;
+    let diff = (res - Tensor::cat(&[r0, r1], 1)?)?
+        .abs()?
+        .flatten_all()?
+        .max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+    assert!(t.conv3d(&w, 0, 1, 1, 1).is_err());
+    Ok(())
+}
+
+// Inserts zeros between the kernel elements, this is the same as using a dilation of two.
+fn dilate2(w: &Tensor) -> candle_core::Result<Tensor> {
+    let (c_out, c_in, k_d, k_h, k_w) = w.dims5()?;
+    let mut out = Tensor::zeros(
+        (c_out, c_in, 2 * k_d - 1, 2 * k_h - 1, 2 * k_w - 1),
+        w.dtype(),
+        w.device(),
+    )?;
+    for z in 0..k_d {
+        for y in 0..k_h {
+            for x in 0..k_w {
+                let v = w.i((.., .., z..z + 1, y..y + 1, x..x + 1))?;
+                let dst = [
+                    0..c_out,
+                    0..c_in,
+                    2 * z..2 * z + 1,
+                    2 * y..2 * y + 1,
+                    2 * x..2 * x + 1,
+                ];
+                out = out.slice_assign(&dst, &v)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[test]
+fn conv_transpose3d() -> Result<()> {
+    let dev = &Device::Cpu;
+    // With a depth of one, this matches the 2d version.
+    let t = Tensor::randn(0f32, 1., (2, 3, 1, 4, 5), dev)?;
+    let w = Tensor::randn(0f32, 1., (3, 2, 1, 3, 3), dev)?;
+    let res = t.conv_transpose3d(&w, 0, 0, 2, 1)?;
+    assert_eq!(res.dims(), [2, 2, 1, 9, 11]);
+    let expected = t.squeeze(2)?.conv_transpose2d(&w.squeeze(2)?, 0, 0, 2, 1)?;
+    let diff = (res.squeeze(2)? - expected)?.abs()?.flatten_all()?.max(0)?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+
+    // The transposed convolution is the adjoint of the convolution.
+    let x = Tensor::randn(0f64, 1., (2, 3, 7, 6, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (4, 3, 3, 2, 3), dev)?;
+    for (padding, stride, dilation) in [(0, 1, 1), (1, 2, 1), (2, 1, 2), (1, 3, 2)] {
+        let y = x.conv3d(&w, padding, stride, dilation, 1)?;
+        let g = y.randn_like(0., 1.)?;
+        // Use an output padding large enough to cover all the input positions.
+        let x_t = g
+            .conv_transpose3d(&w, padding, stride, stride, dilation)?
+            .narrow(2, 0, 7)?
+            .narrow(3, 0, 6)?
+            .narrow(4, 0, 5)?;
+        let lhs = (y * &g)?.sum_all()?.to_scalar::<f64>()?;
+        let rhs = (&x * x_t)?.sum_all()?.to_scalar::<f64>()?;
+        assert!((lhs - rhs).abs() < 1e-8 * (1. + lhs.abs()));
+    }
+    Ok(())
+}
+
+#[test]
+fn conv3d_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 2, 4, 5, 4), dev)?;
+    let w = Tensor::randn(0f64, 1., (3, 2, 2, 3, 2), dev)?;
+    for (padding, stride, dilation) in [(0, 1, 1), (1, 2, 1), (1, 1, 2)] {
+        test_utils::check_grad(
+            |x| x.conv3d(&w, padding, stride, dilation, 1)?.sqr()?.sum_all(),
+            &x,
+        )?;
+        test_utils::check_grad(
+            |w| x.conv3d(w, padding, stride, dilation, 1)?.sqr()?.sum_all(),
+            &w,
+        )?;
+    }
+    let w = Tensor::randn(0f64, 1., (2, 3, 2, 3, 2), dev)?;
+    for (padding, out_padding, stride, dilation) in [(0, 0, 1, 1), (1, 1, 2, 1), (1, 0, 1, 2)] {
+        test_utils::check_grad(
+            |x| {
+                x.conv_transpose3d(&w, padding, out_padding, stride, dilation)?
+                    .sqr()?
+                    .sum_all()
+            },
+            &x,
+        )?;
+        test_utils::check_grad(
+            |w| {
+                x.conv_transpose3d(w, padding, out_padding, stride, dilation)?
+                    .sqr()?
+                    .sum_all()
+            },
+            &w,
+        )?;
+    }
+    Ok(())
+}
+
 test_device!(conv1d, conv1d_cpu, conv1d_gpu, conv1d_metal);
 test_device!(
     conv1d_small,
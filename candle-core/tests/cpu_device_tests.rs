@@ -0,0 +1,99 @@
+use candle_core::{
+    utils, CpuDeviceConfig, CpuStorage, CustomOp1, DType, Device, Layout, Result, Shape, Tensor,
+};
+
+// Returns the number of threads of the rayon pool the op runs on as a scalar.
+struct NumThreads;
+
+impl CustomOp1 for NumThreads {
+    fn name(&self) -> &'static str {
+        "num-threads"
+    }
+
+    fn cpu_fwd(&self, _: &CpuStorage, _: &Layout) -> Result<(CpuStorage, Shape)> {
+        let num_threads = rayon::current_num_threads() as u32;
+        Ok((CpuStorage::U32(vec![num_threads]), Shape::from(())))
+    }
+}
+
+fn max_diff(lhs: &Tensor, rhs: &Tensor) -> Result<f32> {
+    (lhs.to_device(rhs.device())? - rhs)?
+        .abs()?
+        .flatten_all()?
+        .max(0)?
+        .to_vec0::<f32>()
+}
+
+#[test]
+fn cpu_device_config() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::randn(0f32, 1., (3, 32, 48), dev)?;
+    let ws = Tensor::randn(0f32, 1., (48, 16), dev)?;
+    let expected = xs.broadcast_matmul(&ws)?.cumsum(1)?;
+
+    let config = CpuDeviceConfig::new(3)?;
+    assert_eq!(config.num_threads(), 3);
+    assert_eq!(config.affinity(), None);
+    let pool = &Device::CpuPool(config.clone());
+    assert!(pool.is_cpu());
+    assert!(pool.same_device(&Device::CpuPool(config.clone())));
+    assert!(!pool.same_device(dev));
+
+    // The tensors on the device run their ops on the pool, the results stay on the device.
+    let xs_pool = xs.to_device(pool)?;
+    let ys = xs_pool.broadcast_matmul(&ws.to_device(pool)?)?.cumsum(1)?;
+    assert!(ys.device().same_device(pool));
+    assert!(max_diff(&ys, &expected)? < 1e-6);
+    let num_threads = xs_pool.apply_op1_no_bwd(&NumThreads)?;
+    assert_eq!(num_threads.to_vec0::<u32>()?, 3);
+    let ones = Tensor::ones((2, 2), DType::F32, pool)?;
+    assert_eq!(ones.apply_op1_no_bwd(&NumThreads)?.to_vec0::<u32>()?, 3);
+
+    // The device round-trips through the ops that are not matmuls, so do the threads they use.
+    let ys = [
+        xs_pool.exp()?,
+        xs_pool.sum_keepdim(2)?,
+        (&xs_pool + &xs_pool)?,
+        xs_pool.to_dtype(DType::F64)?,
+        Tensor::cat(&[&xs_pool, &xs_pool], 1)?,
+        xs_pool.narrow(2, 1, 8)?.contiguous()?,
+        xs_pool.lazy().relu()?.eval()?,
+    ];
+    for ys in ys.iter() {
+        assert!(matches!(ys.device(), Device::CpuPool(c) if c.same_pool(&config)));
+        assert_eq!(ys.apply_op1_no_bwd(&NumThreads)?.to_vec0::<u32>()?, 3);
+    }
+    // Tensors bound to different pools, or to no pool, cannot be mixed.
+    assert!((&xs_pool + &xs).is_err());
+    assert!(xs.broadcast_matmul(&ws.to_device(pool)?).is_err());
+
+    // Separate configurations use separate pools and can run concurrently.
+    let other = &Device::CpuPool(CpuDeviceConfig::new(1)?);
+    assert!(!pool.same_device(other));
+    let xs_other = xs.to_device(other)?;
+    let num_threads = std::thread::scope(|s| {
+        let a = s.spawn(|| xs_pool.apply_op1_no_bwd(&NumThreads)?.to_vec0::<u32>());
+        let b = s.spawn(|| xs_other.apply_op1_no_bwd(&NumThreads)?.to_vec0::<u32>());
+        Ok::<_, candle_core::Error>((a.join().unwrap()?, b.join().unwrap()?))
+    })?;
+    assert_eq!(num_threads, (3, 1));
+    assert_eq!(config.install(utils::get_num_threads), 3);
+
+    assert!(CpuDeviceConfig::new(0).is_err());
+    if cfg!(target_os = "linux") {
+        // The first cores are not always available, e.g. when running under taskset.
+        let config = (0..1024)
+            .find_map(|core| CpuDeviceConfig::with_affinity(2, &[core]).ok())
+            .expect("no cpu core available");
+        assert_eq!(config.affinity().map(|cores| cores.len()), Some(1));
+        let pinned = &Device::CpuPool(config);
+        let ys = xs.to_device(pinned)?;
+        let ys = ys.broadcast_matmul(&ws.to_device(pinned)?)?.cumsum(1)?;
+        assert!(max_diff(&ys, &expected)? < 1e-6);
+        assert!(CpuDeviceConfig::with_affinity(2, &[usize::MAX]).is_err());
+        assert!(CpuDeviceConfig::with_affinity(2, &[]).is_err());
+    } else {
+        assert!(CpuDeviceConfig::with_affinity(2, &[0]).is_err());
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use candle_core::{test_utils, Complex, DType, Device, Result, Tensor, D};
+
+// Naive O(n^2) discrete Fourier transform used as a reference.
+fn naive_dft(xs: &[f64]) -> Vec<Complex<f64>> {
+    let n = xs.len();
+    (0..n)
+        .map(|k| {
+            xs.iter()
+                .enumerate()
+                .map(|(j, &x)| {
+                    let angle = -2. * std::f64::consts::PI * (j * k) as f64 / n as f64;
+                    Complex::from_polar(x, angle)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+fn assert_close(lhs: &[Complex<f64>], rhs: &[Complex<f64>]) {
+    assert_eq!(lhs.len(), rhs.len());
+    for (l, r) in lhs.iter().zip(rhs.iter()) {
+        assert!((l - r).norm() < 1e-9, "{lhs:?} {rhs:?}")
+    }
+}
+
+#[test]
+fn complex_dtypes() -> Result<()> {
+    let dev = &Device::Cpu;
+    let re = Tensor::new(&[1f32, -2., 0.5], dev)?;
+    let im = Tensor::new(&[0f32, 3., -1.], dev)?;
+    let z = Tensor::complex(&re, &im)?;
+    assert_eq!(z.dtype(), DType::C64);
+    assert_eq!(
+        z.to_vec1::<Complex<f32>>()?,
+        [
+            Complex::new(1., 0.),
+            Complex::new(-2., 3.),
+            Complex::new(0.5, -1.)
+        ]
+    );
+    assert_eq!(z.real()?.to_vec1::<f32>()?, [1., -2., 0.5]);
+    assert_eq!(z.imag()?.to_vec1::<f32>()?, [0., 3., -1.]);
+    assert_eq!(z.conj()?.imag()?.to_vec1::<f32>()?, [0., -3., 1.]);
+    let zz = (&z * &z.conj()?)?;
+    assert_eq!(zz.real()?.to_vec1::<f32>()?, [1., 13., 1.25]);
+    assert_eq!(zz.imag()?.to_vec1::<f32>()?, [0., 0., 0.]);
+    assert_eq!(
+        z.sum_all()?.to_scalar::<Complex<f32>>()?,
+        Complex::new(-0.5, 2.)
+    );
+    let z = z.to_dtype(DType::C128)?;
+    assert_eq!(z.dtype(), DType::C128);
+    assert_eq!(z.to_dtype(DType::F64)?.to_vec1::<f64>()?, [1., -2., 0.5]);
+    assert_eq!(
+        z.to_dtype(DType::Bool)?.to_vec1::<bool>()?,
+        [true, true, true]
+    );
+    assert!(z.exp().is_ok());
+    assert!(z.abs().is_err());
+    assert!(z.max(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn fft_ifft() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t = Tensor::new(&[1f32, 2., 3., 4.], dev)?;
+    let f = t.fft(0)?;
+    assert_eq!(f.dtype(), DType::C64);
+    assert_eq!(
+        test_utils::to_vec1_round(&f.real()?, 4)?,
+        [10., -2., -2., -2.]
+    );
+    assert_eq!(test_utils::to_vec1_round(&f.imag()?, 4)?, [0., 2., 0., -2.]);
+    let t = f.ifft(0)?.real()?;
+    assert_eq!(test_utils::to_vec1_round(&t, 4)?, [1., 2., 3., 4.]);
+
+    // Power of two and other sizes, including prime ones.
+    for n in [1usize, 2, 5, 6, 7, 8, 12, 17] {
+        let xs: Vec<f64> = (0..n).map(|i| ((i * i) as f64 * 0.37).sin()).collect();
+        let t = Tensor::new(xs.as_slice(), dev)?;
+        let f = t.fft(0)?;
+        assert_close(&f.to_vec1::<Complex<f64>>()?, &naive_dft(&xs));
+        let back = f.ifft(0)?.real()?.to_vec1::<f64>()?;
+        for (b, x) in back.iter().zip(xs.iter()) {
+            assert!((b - x).abs() < 1e-9)
+        }
+    }
+
+    // Transform along a dimension that is not the last one.
+    let t = Tensor::arange(0f64, 6., dev)?.reshape((3, 2))?;
+    let f = t.fft(0)?;
+    assert_eq!(f.dims(), [3, 2]);
+    let col = f.transpose(0, 1)?.get(1)?.to_vec1::<Complex<f64>>()?;
+    assert_close(&col, &naive_dft(&[1., 3., 5.]));
+    Ok(())
+}
+
+#[test]
+fn rfft_irfft() -> Result<()> {
+    let dev = &Device::Cpu;
+    for n in [6usize, 7, 8] {
+        let xs: Vec<f32> = (0..2 * n).map(|i| (i as f32 * 0.7).cos()).collect();
+        let t = Tensor::from_vec(xs.clone(), (2, n), dev)?;
+        let f = t.rfft(1)?;
+        assert_eq!(f.dims(), [2, n / 2 + 1]);
+        let full = t.fft(1)?.narrow(1, 0, n / 2 + 1)?;
+        let diff = (f - full)?.real()?.abs()?.sum_all()?.to_scalar::<f32>()?;
+        assert!(diff < 1e-5);
+        let back = t.rfft(1)?.irfft(n, 1)?;
+        assert_eq!(back.dtype(), DType::F32);
+        assert_eq!(
+            test_utils::to_vec2_round(&back, 4)?,
+            test_utils::to_vec2_round(&t, 4)?
+        );
+    }
+    assert!(Tensor::new(&[1f32, 2.], dev)?.fft(0)?.rfft(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn stft_istft() -> Result<()> {
+    let dev = &Device::Cpu;
+    let n_fft = 8;
+    let hop_length = 2;
+    let window: Vec<f32> = (0..n_fft)
+        .map(|i| 0.5 - 0.5 * (2. * std::f32::consts::PI * i as f32 / n_fft as f32).cos())
+        .collect();
+    let window = Tensor::new(window.as_slice(), dev)?;
+    let xs: Vec<f32> = (0..40).map(|i| (i as f32 * 0.3).sin()).collect();
+    let t = Tensor::from_vec(xs, (1, 40), dev)?;
+
+    let spec = t.stft(n_fft, hop_length, Some(&window), true)?;
+    assert_eq!(spec.dims(), [1, n_fft / 2 + 1, 21]);
+    // The frame centered on sample 8 is the windowed signal starting at sample 4.
+    let frame = t.narrow(1, 4, n_fft)?.broadcast_mul(&window)?.rfft(1)?;
+    let diff = (spec.narrow(2, 4, 1)?.squeeze(2)? - frame)?;
+    let diff = diff.real()?.abs()?.sum_all()?.to_scalar::<f32>()?;
+    assert!(diff < 1e-5);
+
+    let back = spec.istft(n_fft, hop_length, Some(&window), true, Some(40))?;
+    assert_eq!(back.dims(), [1, 40]);
+    assert_eq!(
+        test_utils::to_vec2_round(&back, 4)?,
+        test_utils::to_vec2_round(&t, 4)?
+    );
+
+    let spec = t.stft(n_fft, hop_length, None, false)?;
+    assert_eq!(spec.dims(), [1, n_fft / 2 + 1, 17]);
+    let back = spec.istft(n_fft, hop_length, None, false, None)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&back, 4)?,
+        test_utils::to_vec2_round(&t, 4)?
+    );
+    Ok(())
+}
+
+#[test]
+fn fft_npy() -> Result<()> {
+    let z = Tensor::new(&[1f32, 2., 3.], &Device::Cpu)?.fft(D::Minus1)?;
+    let tmp_dir = std::env::temp_dir();
+    let path = tmp_dir.join("candle_fft_test.npy");
+    z.write_npy(&path)?;
+    let z2 = Tensor::read_npy(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(z2.dtype(), DType::C64);
+    assert_eq!(z.to_vec1::<Complex<f32>>()?, z2.to_vec1::<Complex<f32>>()?);
+    Ok(())
+}
@@ -475,6 +475,224 @@ fn binary_grad(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn sort_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[3f32, 1., 4., 1.5], [5., 9., 2., 6.]], device)?;
+    let (sorted, _) = x.sort_last_dim(true)?;
+    let w = Tensor::new(&[1f32, 2., 3., 4.], device)?;
+    let y = sorted.broadcast_mul(&w)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        sorted.to_vec2::<f32>()?,
+        [[1., 1.5, 3., 4.], [2., 5., 6., 9.]]
+    );
+    assert_eq!(
+        grad_x.to_vec2::<f32>()?,
+        [[3., 1., 4., 2.], [2., 4., 1., 3.]]
+    );
+
+    let (values, indexes) = x.topk(1, 0)?;
+    assert_eq!(values.to_vec2::<f32>()?, [[5., 9., 4., 6.]]);
+    assert_eq!(indexes.to_vec2::<u32>()?, [[1, 1, 0, 1]]);
+    let y = values.sqr()?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.to_vec2::<f32>()?,
+        [[0., 0., 8., 0.], [10., 18., 0., 12.]]
+    );
+    Ok(())
+}
+
+fn einsum_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    let w = Var::new(&[[1f32, -1.], [2., 0.], [0.5, 3.]], device)?;
+    let y = Tensor::einsum("ij,jk->", &[&x, &w])?;
+    assert_eq!(y.to_scalar::<f32>()?, 45.5);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    let grad_w = grads.get(&w).context("no grad for w")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[0., 2., 3.5], [0., 2., 3.5]]);
+    assert_eq!(grad_w.to_vec2::<f32>()?, [[5., 5.], [7., 7.], [9., 9.]]);
+
+    // The gradient of the trace is the identity matrix.
+    let x = Var::new(&[[1f32, 2.], [3., 4.]], device)?;
+    let y = Tensor::einsum("ii", &[&x])?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec2::<f32>()?, [[1., 0.], [0., 1.]]);
+    Ok(())
+}
+
+fn higher_order_grad(device: &Device) -> Result<()> {
+    let x = Var::new(&[1f32, 2., 3.], device)?;
+    let y = x.sqr()?.mul(&x)?.sum_all()?;
+    let grads = y.backward_with_graph()?;
+    let dy = grads.get(&x).context("no grad for x")?;
+    assert_eq!(dy.to_vec1::<f32>()?, [3., 12., 27.]);
+    let grads = dy.sum_all()?.backward()?;
+    let d2y = grads.get(&x).context("no grad for x")?;
+    assert_eq!(d2y.to_vec1::<f32>()?, [6., 12., 18.]);
+
+    // Hessian-vector product for f(w) = w^T A w / 2, the hessian being A.
+    let a = Tensor::new(&[[2f32, 1.], [1., 3.]], device)?;
+    let v = Tensor::new(&[[1f32], [-1.]], device)?;
+    let w = Var::new(&[[0.5f32], [2.]], device)?;
+    let f = (w.t()?.matmul(&a.matmul(&w)?)? * 0.5)?.sum_all()?;
+    let grads = f.backward_with_graph()?;
+    let grad_w = grads.get(&w).context("no grad for w")?;
+    assert_eq!(grad_w.to_vec2::<f32>()?, [[3.], [6.5]]);
+    let grads = grad_w.mul(&v)?.sum_all()?.backward()?;
+    let hvp = grads.get(&w).context("no grad for w")?;
+    assert_eq!(hvp.to_vec2::<f32>()?, [[1.], [-2.]]);
+
+    // Gradient penalty, the gradient of sum(cos(x)^2) is -sin(2x).
+    let x = Var::new(&[0.5f32, 1., -2.], device)?;
+    let grads = x.sin()?.sum_all()?.backward_with_graph()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    let penalty = grad_x.sqr()?.sum_all()?;
+    let grads = penalty.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec1_round(grad_x, 4)?,
+        [-0.8415, -0.9093, -0.7568]
+    );
+    Ok(())
+}
+
+fn grad_hooks(device: &Device) -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    // A hook modifying the gradient of an intermediate value.
+    let x = Var::new(&[1f32, 2., 3.], device)?;
+    let y = x.sqr()?;
+    y.register_hook(|grad| Ok(Some(grad.affine(2., 0.)?)));
+    let grads = y.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [4., 8., 12.]);
+
+    // Hooks inspecting the gradients, including the one of a variable.
+    let seen = Arc::new(Mutex::new(vec![]));
+    let y = x.exp()?;
+    let seen_y = seen.clone();
+    y.register_hook(move |grad| {
+        seen_y.lock().unwrap().push(grad.to_vec1::<f32>()?);
+        Ok(None)
+    });
+    let seen_x = seen.clone();
+    x.register_hook(move |grad| {
+        seen_x.lock().unwrap().push(grad.to_vec1::<f32>()?);
+        Ok(None)
+    });
+    let grads = (y * 3.)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0], [3., 3., 3.]);
+    assert_eq!(seen[1], grad_x.to_vec1::<f32>()?);
+    x.clear_hooks();
+
+    // Hooks cannot change the shape of the gradient.
+    let y = x.sqr()?;
+    y.register_hook(|grad| Ok(Some(grad.sum_all()?)));
+    assert!(y.sum_all()?.backward().is_err());
+    Ok(())
+}
+
+fn autograd_transforms(device: &Device) -> Result<()> {
+    use candle_core::autograd;
+
+    let w = Tensor::new(&[[1f32, 2., 3.], [-1., 0., 2.]], device)?;
+    let x = Tensor::new(&[[0.5f32], [1.], [-2.]], device)?;
+    let f = |xs: &[Tensor]| xs[0].matmul(&xs[1]);
+
+    let v = Tensor::new(&[[1f32], [2.]], device)?;
+    let (out, vjps) = autograd::vjp(f, &[w.clone(), x.clone()], &v)?;
+    assert_eq!(out.to_vec2::<f32>()?, [[-3.5], [-4.5]]);
+    assert_eq!(vjps[0].to_vec2::<f32>()?, [[0.5, 1., -2.], [1., 2., -4.]]);
+    assert_eq!(vjps[1].to_vec2::<f32>()?, [[-1.], [2.], [7.]]);
+
+    let dw = Tensor::new(&[[1f32, 0., 0.], [0., 0., 1.]], device)?;
+    let dx = Tensor::new(&[[0f32], [1.], [1.]], device)?;
+    let (out, jvp) = autograd::jvp(f, &[w.clone(), x.clone()], &[dw, dx])?;
+    assert_eq!(out.to_vec2::<f32>()?, [[-3.5], [-4.5]]);
+    // w.dx + dw.x
+    assert_eq!(jvp.to_vec2::<f32>()?, [[5.5], [0.]]);
+
+    let jac = autograd::jacobian(f, &[w.clone(), x.clone()])?;
+    assert_eq!(jac[0].dims(), [2, 1, 2, 3]);
+    assert_eq!(jac[1].dims(), [2, 1, 3, 1]);
+    assert_eq!(
+        jac[1].reshape((2, 3))?.to_vec2::<f32>()?,
+        w.to_vec2::<f32>()?
+    );
+    assert_eq!(
+        jac[0].reshape((2, 6))?.to_vec2::<f32>()?,
+        [[0.5, 1., -2., 0., 0., 0.], [0., 0., 0., 0.5, 1., -2.]]
+    );
+
+    // f(x, y) = sum(x^2 * y)
+    let x = Tensor::new(&[1f32, -2.], device)?;
+    let y = Tensor::new(&[3f32, 0.5], device)?;
+    let f = |xs: &[Tensor]| xs[0].sqr()?.mul(&xs[1])?.sum_all();
+    let hess = autograd::hessian(f, &[x, y])?;
+    assert_eq!(hess[0][0].to_vec2::<f32>()?, [[6., 0.], [0., 1.]]);
+    assert_eq!(hess[0][1].to_vec2::<f32>()?, [[2., 0.], [0., -4.]]);
+    assert_eq!(hess[1][0].to_vec2::<f32>()?, [[2., 0.], [0., -4.]]);
+    assert_eq!(hess[1][1].to_vec2::<f32>()?, [[0., 0.], [0., 0.]]);
+    Ok(())
+}
+
+fn checkpoint_grad(device: &Device) -> Result<()> {
+    let w = Var::new(&[[1f32, -0.5], [2., 0.25]], device)?;
+    let b = Var::new(&[0.1f32, -0.2], device)?;
+    let x = Var::new(&[[0.5f32, 1.], [-1., 2.], [0.3, 0.7]], device)?;
+    let block = {
+        let w = w.as_tensor().clone();
+        let b = b.as_tensor().clone();
+        move |xs: &[Tensor]| xs[0].matmul(&w)?.broadcast_add(&b)?.tanh()?.sqr()
+    };
+    let loss = |ys: Tensor| ys.sum_keepdim(1)?.exp()?.sum_all();
+
+    // The input of the checkpointed block is not a leaf of the graph.
+    let xs = (x.as_tensor() * 2.)?.sin()?;
+    let expected = loss(block(std::slice::from_ref(&xs))?)?.backward()?;
+    let ys = candle_core::checkpoint(block.clone(), &[xs])?;
+    assert!(ys.track_op());
+    let grads = loss(ys)?.backward()?;
+    for v in [&w, &b, &x] {
+        let expected = expected.get(v).context("no grad")?;
+        let grad = grads.get(v).context("no grad")?;
+        let diff = (expected - grad)?.abs()?.flatten_all()?.max(0)?;
+        assert!(diff.to_scalar::<f32>()? < 1e-6);
+    }
+
+    // Nested checkpoints and second order derivatives.
+    let inner = block.clone();
+    let xs = x.as_tensor().clone();
+    let ys = candle_core::checkpoint(
+        move |xs: &[Tensor]| candle_core::checkpoint(inner.clone(), xs)?.affine(3., 0.),
+        std::slice::from_ref(&xs),
+    )?;
+    let grads = ys.sum_all()?.backward_with_graph()?;
+    let grad_x = grads.get(&x).context("no grad for x")?.sum_all()?;
+    let expected = (block(&[xs])? * 3.)?.sum_all()?.backward_with_graph()?;
+    let expected_x = expected.get(&x).context("no grad for x")?.sum_all()?;
+    assert_eq!(
+        test_utils::to_vec0_round(&grad_x, 4)?,
+        test_utils::to_vec0_round(&expected_x, 4)?
+    );
+    let grad_w = grad_x.backward()?;
+    let grad_w = grad_w.get(&w).context("no grad for w")?;
+    let expected_w = expected_x.backward()?;
+    let expected_w = expected_w.get(&w).context("no grad for w")?;
+    assert_eq!(
+        test_utils::to_vec2_round(grad_w, 4)?,
+        test_utils::to_vec2_round(expected_w, 4)?
+    );
+    Ok(())
+}
+
 test_device!(
     simple_grad,
     simple_grad_cpu,
@@ -501,3 +719,312 @@ test_device!(
     binary_grad_gpu,
     binary_grad_metal
 );
+test_device!(sort_grad, sort_grad_cpu, sort_grad_gpu, sort_grad_metal);
+test_device!(
+    einsum_grad,
+    einsum_grad_cpu,
+    einsum_grad_gpu,
+    einsum_grad_metal
+);
+test_device!(
+    higher_order_grad,
+    higher_order_grad_cpu,
+    higher_order_grad_gpu,
+    higher_order_grad_metal
+);
+test_device!(grad_hooks, grad_hooks_cpu, grad_hooks_gpu, grad_hooks_metal);
+
+#[test]
+fn fft_grad() -> Result<()> {
+    let device = &Device::Cpu;
+    let x = Var::new(&[1f32, 2., 3., 4.], device)?;
+    // By Parseval's theorem, the sum of the squared magnitudes is n times the sum of squares.
+    let f = x.fft(0)?;
+    let y = (f.real()?.sqr()? + f.imag()?.sqr()?)?.sum_all()?;
+    assert_eq!(y.to_scalar::<f32>()?, 120.);
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [8., 16., 24., 32.]);
+
+    let f = x.ifft(0)?;
+    let y = (f.real()?.sqr()? + f.imag()?.sqr()?)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [0.5, 1., 1.5, 2.]);
+
+    // irfft(rfft(x)) is the identity so the gradient is the weight.
+    let x = Var::new(&[1f32, -2., 3., 0.5, 7.], device)?;
+    let w = Tensor::new(&[1f32, 2., 3., 4., 5.], device)?;
+    let y = x.rfft(0)?.irfft(5, 0)?.mul(&w)?.sum_all()?;
+    let grads = y.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(test_utils::to_vec1_round(grad_x, 4)?, [1., 2., 3., 4., 5.]);
+    Ok(())
+}
+
+#[test]
+fn conv_transpose_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 3, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (3, 2, 3), dev)?;
+    for (padding, out_padding, stride, dilation) in [(0, 0, 1, 1), (1, 1, 2, 1), (2, 0, 1, 2)] {
+        let f = |x: &Tensor, w: &Tensor| {
+            x.conv_transpose1d(w, padding, out_padding, stride, dilation)?
+                .sqr()?
+                .sum_all()
+        };
+        test_utils::check_grad(|x| f(x, &w), &x)?;
+        test_utils::check_grad(|w| f(&x, w), &w)?;
+    }
+
+    let x = Tensor::randn(0f64, 1., (2, 3, 4, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (3, 2, 3, 2), dev)?;
+    for (padding, out_padding, stride, dilation) in [(0, 0, 1, 1), (1, 1, 2, 1), (1, 0, 1, 2)] {
+        let f = |x: &Tensor, w: &Tensor| {
+            x.conv_transpose2d(w, padding, out_padding, stride, dilation)?
+                .sqr()?
+                .sum_all()
+        };
+        test_utils::check_grad(|x| f(x, &w), &x)?;
+        test_utils::check_grad(|w| f(&x, w), &w)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn pool_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 2, 7, 6), dev)?;
+    // Overlapping windows, windows with gaps and uncovered elements.
+    for (kernel_size, stride) in [
+        ((2, 2), (2, 2)),
+        ((3, 3), (2, 2)),
+        ((2, 3), (1, 2)),
+        ((2, 2), (3, 3)),
+    ] {
+        let w = x
+            .avg_pool2d_with_stride(kernel_size, stride)?
+            .randn_like(0., 1.)?;
+        test_utils::check_grad(
+            |x| {
+                x.avg_pool2d_with_stride(kernel_size, stride)?
+                    .mul(&w)?
+                    .sum_all()
+            },
+            &x,
+        )?;
+        test_utils::check_grad(
+            |x| {
+                x.max_pool2d_with_stride(kernel_size, stride)?
+                    .mul(&w)?
+                    .sum_all()
+            },
+            &x,
+        )?;
+    }
+
+    let x = Tensor::randn(0f64, 1., (1, 2, 4, 5, 4), dev)?;
+    for (kernel_size, stride) in [((2, 2, 2), (2, 2, 2)), ((2, 3, 2), (1, 2, 1))] {
+        let w = x
+            .avg_pool3d_with_stride(kernel_size, stride)?
+            .randn_like(0., 1.)?;
+        test_utils::check_grad(
+            |x| {
+                x.avg_pool3d_with_stride(kernel_size, stride)?
+                    .mul(&w)?
+                    .sum_all()
+            },
+            &x,
+        )?;
+        test_utils::check_grad(
+            |x| {
+                x.max_pool3d_with_stride(kernel_size, stride)?
+                    .mul(&w)?
+                    .sum_all()
+            },
+            &x,
+        )?;
+    }
+
+    // Ties split the gradient evenly between the maximum values.
+    let x = Var::new(&[[[[1f32, 1.], [0., 1.]]]], dev)?;
+    let grads = x.max_pool2d(2)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        test_utils::to_vec2_round(&grad_x.squeeze(0)?.squeeze(0)?, 4)?,
+        [[0.3333, 0.3333], [0., 0.3333]]
+    );
+    Ok(())
+}
+
+#[test]
+fn upsample_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 3, 4), dev)?;
+    for target in [8, 6, 3] {
+        let w = Tensor::randn(0f64, 1., (2, 3, target), dev)?;
+        test_utils::check_grad(|x| x.upsample_nearest1d(target)?.mul(&w)?.sum_all(), &x)?;
+    }
+
+    let x = Tensor::randn(0f64, 1., (2, 3, 4, 5), dev)?;
+    for (target_h, target_w) in [(8, 10), (8, 15), (6, 7), (3, 2)] {
+        let w = Tensor::randn(0f64, 1., (2, 3, target_h, target_w), dev)?;
+        test_utils::check_grad(
+            |x| x.upsample_nearest2d(target_h, target_w)?.mul(&w)?.sum_all(),
+            &x,
+        )?;
+    }
+
+    // Each input element receives the sum of the gradients of its copies.
+    let x = Var::new(&[[[1f32, 2., 3.]]], dev)?;
+    let w = Tensor::new(&[[[1f32, 2., 3., 4., 5., 6., 7.]]], dev)?;
+    let grads = x.upsample_nearest1d(7)?.mul(&w)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec3::<f32>()?, [[[6., 9., 13.]]]);
+    Ok(())
+}
+
+#[test]
+fn strided_view_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (3, 4, 5), dev)?;
+    let grad = |f: &dyn Fn(&Tensor) -> candle_core::Result<Tensor>| -> Result<()> {
+        let y = f(&x)?;
+        let w = Tensor::randn(0f64, 1., y.shape(), dev)?;
+        test_utils::check_grad(|x| f(x)?.mul(&w)?.sum_all(), &x)?;
+        Ok(())
+    };
+    grad(&|x| x.unfold(2, 3, 1))?;
+    grad(&|x| x.transpose(0, 2)?.unfold(1, 2, 2))?;
+    grad(&|x| x.diagonal(1, 1, 2))?;
+    grad(&|x| x.diagonal(-1, 2, 0))?;
+    grad(&|x| x.transpose(1, 2)?.as_strided((4, 6), &[3, 2], 5))?;
+    grad(&|x| x.flip((0, 2)))?;
+    grad(&|x| x.roll(-3, 1))?;
+    grad(&|x| x.tril(1))?;
+    grad(&|x| x.triu(-1))?;
+
+    // Elements that appear in multiple windows accumulate the gradients.
+    let x = Var::new(&[1f32, 2., 3., 4., 5.], dev)?;
+    let grads = x.unfold(0, 3, 1)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [1., 2., 3., 2., 1.]);
+    Ok(())
+}
+
+#[test]
+fn resize2d_grad() -> Result<()> {
+    use candle_core::ResizeMode;
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 3, 4, 5), dev)?;
+    let modes = [
+        ResizeMode::Bilinear {
+            align_corners: false,
+            antialias: false,
+        },
+        ResizeMode::Bilinear {
+            align_corners: true,
+            antialias: true,
+        },
+        ResizeMode::Bicubic {
+            align_corners: false,
+            antialias: true,
+        },
+        ResizeMode::Bicubic {
+            align_corners: true,
+            antialias: false,
+        },
+        ResizeMode::Area,
+    ];
+    for mode in modes {
+        for (target_h, target_w) in [(8, 10), (3, 2), (4, 7)] {
+            let w = Tensor::randn(0f64, 1., (2, 3, target_h, target_w), dev)?;
+            test_utils::check_grad(
+                |x| x.resize2d(target_h, target_w, mode)?.mul(&w)?.sum_all(),
+                &x,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn pad_grad() -> Result<()> {
+    use candle_core::PadMode;
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (2, 3, 4), dev)?;
+    let w = Tensor::randn(0f64, 1., (2, 8, 6), dev)?;
+    for mode in [
+        PadMode::Constant(0.5),
+        PadMode::Reflect,
+        PadMode::Replicate,
+        PadMode::Circular,
+    ] {
+        test_utils::check_grad(|x| x.pad(&[(4, 1), (0, 2)], mode)?.mul(&w)?.sum_all(), &x)?;
+    }
+    Ok(())
+}
+#[test]
+fn scan_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Tensor::randn(0f64, 1., (3, 5, 4), dev)?;
+    let w = Tensor::randn(0f64, 1., (3, 5, 4), dev)?;
+    for dim in 0..3 {
+        test_utils::check_grad(|x| x.cumsum(dim)?.mul(&w)?.sum_all(), &x)?;
+        test_utils::check_grad(|x| x.cumprod(dim)?.mul(&w)?.sum_all(), &x)?;
+        test_utils::check_grad(|x| x.cummax(dim)?.mul(&w)?.sum_all(), &x)?;
+        test_utils::check_grad(|x| x.cummin(dim)?.mul(&w)?.sum_all(), &x)?;
+        test_utils::check_grad(|x| x.logcumsumexp(dim)?.mul(&w)?.sum_all(), &x)?;
+    }
+    let f = |x: &Tensor| x.t()?.cumprod(0)?.mul(&w.get(0)?.t()?)?.sum_all();
+    test_utils::check_grad(f, &x.get(0)?)?;
+
+    // The cumulative product of inputs with zeros, only the first zero of each row gets a
+    // non-zero gradient among the zeros and the elements that follow.
+    let x = Var::new(&[[2f64, 0., 3., 0., 4.], [1., 2., 3., 4., 0.]], dev)?;
+    let grads = x.cumprod(1)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(
+        grad_x.to_vec2::<f64>()?,
+        [[1., 8., 0., 0., 0.], [33., 16., 10., 6., 24.]]
+    );
+
+    // The gradient of the running maximum flows to the last element reaching the maximum.
+    let x = Var::new(&[1f32, 3., 2., 3., 5.], dev)?;
+    let grads = x.cummax(0)?.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).context("no grad for x")?;
+    assert_eq!(grad_x.to_vec1::<f32>()?, [1., 2., 0., 1., 1.]);
+
+    // Scans built with the associative scan primitive get their gradients from the combining
+    // function.
+    let a = Tensor::randn(0f64, 1., (2, 7), dev)?;
+    let b = Tensor::randn(0f64, 1., (2, 7), dev)?;
+    let w = Tensor::randn(0f64, 1., (2, 7), dev)?;
+    let f = |a: &Tensor, b: &Tensor| {
+        let hs = Tensor::associative_scan(&[a.clone(), b.clone()], 1, |l, r| {
+            Ok(vec![(&l[0] * &r[0])?, ((&r[0] * &l[1])? + &r[1])?])
+        })?;
+        hs[1].mul(&w)?.sum_all()
+    };
+    test_utils::check_grad(|a| f(a, &b), &a)?;
+    test_utils::check_grad(|b| f(&a, b), &b)?;
+    for dim in 0..2 {
+        let f = |a: &Tensor, b: &Tensor| a.linear_recurrence(b, dim)?.mul(&w)?.sum_all();
+        test_utils::check_grad(|a| f(a, &b), &a)?;
+        test_utils::check_grad(|b| f(&a, b), &b)?;
+    }
+    Ok(())
+}
+
+test_device!(
+    autograd_transforms,
+    autograd_transforms_cpu,
+    autograd_transforms_gpu,
+    autograd_transforms_metal
+);
+test_device!(
+    checkpoint_grad,
+    checkpoint_grad_cpu,
+    checkpoint_grad_gpu,
+    checkpoint_grad_metal
+);
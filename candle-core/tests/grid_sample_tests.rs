@@ -0,0 +1,222 @@
+use candle_core::{
+    test_device, test_utils, Device, GridPaddingMode, GridSampleMode, IndexOp, ResizeMode, Result,
+    Tensor,
+};
+
+fn grid_sample(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], dev)?;
+    let grid = Tensor::new(&[[[[0f32, 0.], [-1., -1.], [1., 1.], [0.5, -0.5]]]], dev)?;
+    let sample = |padding| -> Result<Vec<f32>> {
+        let ys = t.grid_sample(&grid, GridSampleMode::Bilinear, padding, false)?;
+        assert_eq!(ys.dims(), [1, 1, 1, 4]);
+        ys.flatten_all()?.to_vec1::<f32>()
+    };
+    assert_eq!(sample(GridPaddingMode::Zeros)?, [2.5, 0.25, 1., 2.]);
+    assert_eq!(sample(GridPaddingMode::Border)?, [2.5, 1., 4., 2.]);
+    let ys = t.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridPaddingMode::Zeros,
+        true,
+    )?;
+    assert_eq!(ys.flatten_all()?.to_vec1::<f32>()?, [2.5, 1., 4., 2.25]);
+
+    // Reflection mirrors the coordinates around the borders of the input.
+    let t = Tensor::arange(0f32, 4., dev)?.reshape((1, 1, 1, 4))?;
+    let grid = Tensor::new(&[[[[-1.5f32, 0.], [1.5, 0.], [-1.25, 0.]]]], dev)?;
+    let sample = |padding| -> Result<Vec<f32>> {
+        let ys = t.grid_sample(&grid, GridSampleMode::Bilinear, padding, false)?;
+        ys.flatten_all()?.to_vec1::<f32>()
+    };
+    assert_eq!(sample(GridPaddingMode::Reflection)?, [0.5, 2.5, 0.]);
+    assert_eq!(sample(GridPaddingMode::Zeros)?, [0., 0., 0.]);
+    assert_eq!(sample(GridPaddingMode::Border)?, [0., 3., 0.]);
+
+    // Halfway coordinates are rounded to the nearest even index.
+    let grid = Tensor::new(&[[[[-0.5f32, 0.], [0., 0.], [0.5, 0.], [0.9, 0.]]]], dev)?;
+    let ys = t.grid_sample(
+        &grid,
+        GridSampleMode::Nearest,
+        GridPaddingMode::Zeros,
+        false,
+    )?;
+    assert_eq!(ys.flatten_all()?.to_vec1::<f32>()?, [0., 2., 2., 3.]);
+
+    // Sampling on the identity grid is the same as resizing.
+    let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], dev)?;
+    let theta = Tensor::new(&[[[1f32, 0., 0.], [0., 1., 0.]]], dev)?;
+    let grid = theta.affine_grid((1, 1, 4, 4), false)?;
+    for (mode, resize_mode) in [
+        (
+            GridSampleMode::Bilinear,
+            ResizeMode::Bilinear {
+                align_corners: false,
+                antialias: false,
+            },
+        ),
+        (
+            GridSampleMode::Bicubic,
+            ResizeMode::Bicubic {
+                align_corners: false,
+                antialias: false,
+            },
+        ),
+    ] {
+        let ys = t.grid_sample(&grid, mode, GridPaddingMode::Border, false)?;
+        let expected = t.resize2d(4, 4, resize_mode)?;
+        assert_eq!(
+            test_utils::to_vec2_round(&ys.i(0)?.i(0)?, 4)?,
+            test_utils::to_vec2_round(&expected.i(0)?.i(0)?, 4)?,
+        );
+    }
+
+    // Trilinear sampling of volumetric inputs.
+    let t = Tensor::arange(0f32, 8., dev)?.reshape((1, 1, 2, 2, 2))?;
+    let grid = Tensor::new(&[0f32, 0., 0., 1., -1., 1.], dev)?.reshape((1, 1, 1, 2, 3))?;
+    let ys = t.grid_sample(
+        &grid,
+        GridSampleMode::Bilinear,
+        GridPaddingMode::Zeros,
+        true,
+    )?;
+    assert_eq!(ys.dims(), [1, 1, 1, 1, 2]);
+    assert_eq!(ys.flatten_all()?.to_vec1::<f32>()?, [3.5, 5.]);
+    Ok(())
+}
+
+fn affine_grid(dev: &Device) -> Result<()> {
+    let theta = Tensor::new(&[[[1f32, 0., 0.], [0., 1., 0.]]], dev)?;
+    let grid = theta.affine_grid((1, 1, 2, 3), true)?;
+    assert_eq!(
+        grid.i(0)?.to_vec3::<f32>()?,
+        [
+            [[-1., -1.], [0., -1.], [1., -1.]],
+            [[-1., 1.], [0., 1.], [1., 1.]]
+        ]
+    );
+    let grid = theta.affine_grid((1, 1, 2, 2), false)?;
+    assert_eq!(
+        grid.i(0)?.to_vec3::<f32>()?,
+        [[[-0.5, -0.5], [0.5, -0.5]], [[-0.5, 0.5], [0.5, 0.5]]]
+    );
+
+    // Scaling and translation, with a rotation for the second batch element.
+    let theta = Tensor::new(
+        &[
+            [[2f32, 0., 0.5], [0., 1., -1.]],
+            [[0., -1., 0.], [1., 0., 0.]],
+        ],
+        dev,
+    )?;
+    let grid = theta.affine_grid((2, 3, 2, 3), true)?;
+    assert_eq!(grid.dims(), [2, 2, 3, 2]);
+    assert_eq!(
+        grid.i(0)?.to_vec3::<f32>()?,
+        [
+            [[-1.5, -2.], [0.5, -2.], [2.5, -2.]],
+            [[-1.5, 0.], [0.5, 0.], [2.5, 0.]]
+        ]
+    );
+    assert_eq!(
+        grid.i(1)?.to_vec3::<f32>()?,
+        [
+            [[1., -1.], [1., 0.], [1., 1.]],
+            [[-1., -1.], [-1., 0.], [-1., 1.]]
+        ]
+    );
+
+    let theta = Tensor::new(
+        &[[[1f32, 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.]]],
+        dev,
+    )?;
+    let grid = theta.affine_grid((1, 1, 2, 1, 2), true)?;
+    assert_eq!(grid.dims(), [1, 2, 1, 2, 3]);
+    assert_eq!(
+        grid.flatten_all()?.to_vec1::<f32>()?,
+        [-1., 0., -1., 1., 0., -1., -1., 0., 1., 1., 0., 1.]
+    );
+    Ok(())
+}
+
+test_device!(
+    grid_sample,
+    grid_sample_cpu,
+    grid_sample_gpu,
+    grid_sample_metal
+);
+test_device!(
+    affine_grid,
+    affine_grid_cpu,
+    affine_grid_gpu,
+    affine_grid_metal
+);
+
+#[test]
+fn grid_sample_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    // Use a deterministic grid that covers the out of bounds regions while staying away from
+    // the points where the interpolation is not differentiable.
+    let grid: Vec<f64> = (0..24)
+        .map(|i| 1.3 * (i as f64 * 1.7 + 0.3).sin())
+        .collect();
+    let grid = Tensor::from_vec(grid, (2, 2, 3, 2), dev)?;
+    let xs = Tensor::randn(0f64, 1., (2, 3, 4, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (2, 3, 2, 3), dev)?;
+    for padding in [
+        GridPaddingMode::Zeros,
+        GridPaddingMode::Border,
+        GridPaddingMode::Reflection,
+    ] {
+        for align_corners in [false, true] {
+            for mode in [
+                GridSampleMode::Bilinear,
+                GridSampleMode::Bicubic,
+                GridSampleMode::Nearest,
+            ] {
+                let f = |xs: &Tensor, grid: &Tensor| {
+                    xs.grid_sample(grid, mode, padding, align_corners)?
+                        .mul(&w)?
+                        .sum_all()
+                };
+                test_utils::check_grad(|xs| f(xs, &grid), &xs)?;
+                if mode != GridSampleMode::Nearest {
+                    test_utils::check_grad(|grid| f(&xs, grid), &grid)?;
+                }
+            }
+        }
+    }
+
+    let grid: Vec<f64> = (0..18).map(|i| (i as f64 * 0.9 + 0.2).cos()).collect();
+    let grid = Tensor::from_vec(grid, (1, 2, 1, 3, 3), dev)?;
+    let xs = Tensor::randn(0f64, 1., (1, 2, 3, 2, 4), dev)?;
+    let w = Tensor::randn(0f64, 1., (1, 2, 2, 1, 3), dev)?;
+    let f = |xs: &Tensor, grid: &Tensor| {
+        xs.grid_sample(
+            grid,
+            GridSampleMode::Bilinear,
+            GridPaddingMode::Reflection,
+            false,
+        )?
+        .mul(&w)?
+        .sum_all()
+    };
+    test_utils::check_grad(|xs| f(xs, &grid), &xs)?;
+    test_utils::check_grad(|grid| f(&xs, grid), &grid)?;
+
+    let xs = Tensor::randn(0f64, 1., (2, 3, 4, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (2, 3, 2, 3), dev)?;
+    let theta = Tensor::randn(0f64, 1., (2, 2, 3), dev)?;
+    let f = |theta: &Tensor| {
+        let grid = theta.affine_grid((2, 3, 2, 3), false)?;
+        xs.grid_sample(
+            &grid,
+            GridSampleMode::Bilinear,
+            GridPaddingMode::Zeros,
+            false,
+        )?
+        .mul(&w)?
+        .sum_all()
+    };
+    test_utils::check_grad(f, &theta)?;
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use candle_core::{Device, IndexOp, Tensor};
+use candle_core::{DType, Device, Ellipsis, Idx, IndexOp, NewAxis, Slice, Tensor, Var};
 
 #[test]
 fn integer_index() -> Result<()> {
@@ -120,3 +120,221 @@ fn slice_assign() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn advanced_index() -> Result<()> {
+    let dev = Device::Cpu;
+    let tensor = Tensor::arange(0u32, 12, &dev)?.reshape((3, 4))?;
+
+    // Multi-dimensional integer indexes.
+    let ids = Tensor::new(&[[0u32, 2], [1, 1]], &dev)?;
+    assert_eq!(
+        tensor.i(&ids)?.to_vec3::<u32>()?,
+        &[[[0, 1, 2, 3], [8, 9, 10, 11]], [[4, 5, 6, 7], [4, 5, 6, 7]]]
+    );
+    assert_eq!(
+        tensor.i((.., &ids))?.to_vec3::<u32>()?,
+        &[[[0, 2], [1, 1]], [[4, 6], [5, 5]], [[8, 10], [9, 9]]]
+    );
+
+    // Index tensors are broadcasted together, negative values count from the end.
+    let rows = Tensor::new(&[[0u32], [2]], &dev)?;
+    let cols = Tensor::new(&[1i64, -1], &dev)?;
+    assert_eq!(
+        tensor.i((&rows, &cols))?.to_vec2::<u32>()?,
+        &[[1, 3], [9, 11]]
+    );
+    let scalar = Tensor::new(-2i64, &dev)?;
+    assert_eq!(tensor.i((.., &scalar))?.to_vec1::<u32>()?, &[2, 6, 10]);
+
+    // The indexed dimensions go first when they are not adjacent.
+    let tensor = Tensor::arange(0u32, 24, &dev)?.reshape((2, 3, 4))?;
+    let ids0 = Tensor::new(&[0u32, 1], &dev)?;
+    let ids1 = Tensor::new(&[0u32, 2], &dev)?;
+    let ids2 = Tensor::new(&[1u32, 2], &dev)?;
+    assert_eq!(
+        tensor.i((&ids0, .., &ids2))?.to_vec2::<u32>()?,
+        &[[1, 5, 9], [14, 18, 22]]
+    );
+    assert_eq!(
+        tensor.i((.., &ids1, &ids2))?.to_vec2::<u32>()?,
+        &[[1, 10], [13, 22]]
+    );
+
+    assert_eq!(
+        tensor.i((&ids0, 1..3, &ids2))?.to_vec2::<u32>()?,
+        &[[5, 9], [18, 22]]
+    );
+    assert_eq!(
+        tensor.i((.., 1..3, &ids2))?.to_vec3::<u32>()?,
+        &[[[5, 6], [9, 10]], [[17, 18], [21, 22]]]
+    );
+
+    // Boolean masks cover as many dimensions as they have.
+    let mask = Tensor::new(&[[true, false, true], [false, false, true]], &dev)?;
+    assert_eq!(
+        tensor.i(&mask)?.to_vec2::<u32>()?,
+        &[[0, 1, 2, 3], [8, 9, 10, 11], [20, 21, 22, 23]]
+    );
+    let mask = Tensor::new(&[false, true, true, false], &dev)?;
+    assert_eq!(
+        tensor.i((1, .., &mask))?.to_vec2::<u32>()?,
+        &[[13, 14], [17, 18], [21, 22]]
+    );
+    let mask = tensor.ge(20u32)?.to_dtype(DType::Bool)?;
+    assert_eq!(tensor.i(&mask)?.to_vec1::<u32>()?, &[20, 21, 22, 23]);
+
+    assert!(tensor.i(&Tensor::new(&[2i64], &dev)?).is_err());
+    assert!(tensor.i((&ids0, &Tensor::new(&[-4i64], &dev)?)).is_err());
+    assert!(tensor.i(&Tensor::new(&[true, false, true], &dev)?).is_err());
+    assert!(tensor.i(&Tensor::new(&[0f32], &dev)?).is_err());
+    Ok(())
+}
+
+#[test]
+fn index_ellipsis_new_axis_and_slices() -> Result<()> {
+    let dev = Device::Cpu;
+    let tensor = Tensor::arange(0u32, 24, &dev)?.reshape((2, 3, 4))?;
+    assert_eq!(tensor.i((Ellipsis, 1))?.dims(), &[2, 3]);
+    assert_eq!(
+        tensor.i((Ellipsis, 1))?.to_vec2::<u32>()?,
+        tensor.i((.., .., 1))?.to_vec2::<u32>()?
+    );
+    assert_eq!(tensor.i((1, Ellipsis, 2))?.to_vec1::<u32>()?, &[14, 18, 22]);
+    assert_eq!(tensor.i((0, 0, 0, Ellipsis))?.to_scalar::<u32>()?, 0);
+    assert_eq!(tensor.i((None, 0, NewAxis))?.dims(), &[1, 1, 3, 4]);
+    assert_eq!(tensor.i((Ellipsis, None))?.dims(), &[2, 3, 4, 1]);
+    assert!(tensor.i((Ellipsis, 0, Ellipsis)).is_err());
+    assert!(tensor.i((0, 0, 0, 0)).is_err());
+
+    assert_eq!(
+        tensor.i((Idx(-1), Idx(-3), Idx(0)))?.to_scalar::<u32>()?,
+        12
+    );
+    assert!(tensor.i(Idx(-3)).is_err());
+    assert!(tensor.i(Idx(2)).is_err());
+
+    let tensor = Tensor::arange(0u32, 10, &dev)?;
+    let slice = |start, stop, step| -> Result<Vec<u32>> {
+        Ok(tensor.i(Slice::new(start, stop, step))?.to_vec1::<u32>()?)
+    };
+    assert_eq!(slice(Some(1), Some(8), 3)?, &[1, 4, 7]);
+    assert_eq!(slice(Some(-2), None, 1)?, &[8, 9]);
+    assert_eq!(slice(None, Some(-7), 2)?, &[0, 2]);
+    assert_eq!(slice(None, None, -1)?, &[9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+    assert_eq!(slice(Some(8), Some(1), -3)?, &[8, 5, 2]);
+    assert_eq!(slice(Some(-3), Some(-20), -4)?, &[7, 3]);
+    assert_eq!(slice(Some(5), Some(2), 1)?, &[] as &[u32]);
+    assert_eq!(slice(Some(20), None, 1)?, &[] as &[u32]);
+    assert!(slice(None, None, 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn index_put() -> Result<()> {
+    let dev = Device::Cpu;
+    let tensor = Tensor::zeros((3, 4), DType::F32, &dev)?;
+    let rows = Tensor::new(&[0u32, 2, 0], &dev)?;
+    let cols = Tensor::new(&[1i64, -1, 1], &dev)?;
+    let values = Tensor::new(&[1f32, 2., 3.], &dev)?;
+    assert_eq!(
+        tensor
+            .index_put((&rows, &cols), &values, true)?
+            .to_vec2::<f32>()?,
+        &[[0., 4., 0., 0.], [0., 0., 0., 0.], [0., 0., 0., 2.]]
+    );
+    assert_eq!(
+        tensor
+            .index_put((&rows, &cols), &values, false)?
+            .to_vec2::<f32>()?,
+        &[[0., 3., 0., 0.], [0., 0., 0., 0.], [0., 0., 0., 2.]]
+    );
+
+    // The values are broadcasted to the shape of the selection.
+    let values = Tensor::new(&[5f32, 6.], &dev)?;
+    assert_eq!(
+        tensor
+            .index_put((Slice::new(None, None, 2), 1..3), &values, false)?
+            .to_vec2::<f32>()?,
+        &[[0., 5., 6., 0.], [0., 0., 0., 0.], [0., 5., 6., 0.]]
+    );
+    let tensor = Tensor::arange(0i64, 6, &dev)?.reshape((2, 3))?;
+    let mask = tensor.ge(4i64)?.to_dtype(DType::Bool)?;
+    assert_eq!(
+        tensor
+            .index_put(&mask, &Tensor::new(-1i64, &dev)?, false)?
+            .to_vec2::<i64>()?,
+        &[[0, 1, 2], [3, -1, -1]]
+    );
+    assert!(tensor
+        .index_put(&mask, &Tensor::new(1f32, &dev)?, false)
+        .is_err());
+
+    // Reading back the written selection returns the values, whatever the kind of indexers.
+    let tensor = Tensor::zeros((2, 3, 4), DType::F32, &dev)?;
+    let ids = Tensor::new(&[[0i64], [-1]], &dev)?;
+    let values = Tensor::arange(1f32, 7., &dev)?.reshape((2, 1, 3))?;
+    let ys = tensor.index_put((&ids, .., Idx(-2)), &values, false)?;
+    assert_eq!(
+        ys.i((.., .., 2))?.to_vec2::<f32>()?,
+        &[[1., 2., 3.], [4., 5., 6.]]
+    );
+    let rows = Tensor::new(&[1u32, 0], &dev)?;
+    let cols = Tensor::new(&[0u32, 3], &dev)?;
+    let values = Tensor::arange(1f32, 7., &dev)?.reshape((2, 3))?;
+    let ys = tensor.index_put((&rows, .., &cols), &values, false)?;
+    assert_eq!(
+        ys.i((&rows, .., &cols))?.to_vec2::<f32>()?,
+        values.to_vec2::<f32>()?
+    );
+    let index = (NewAxis, Ellipsis, Slice::new(None, None, -2));
+    let values = Tensor::arange(1f32, 13., &dev)?.reshape((1, 2, 3, 2))?;
+    let ys = tensor.index_put(index, &values, false)?;
+    assert_eq!(
+        ys.i(index)?.squeeze(0)?.to_vec3::<f32>()?,
+        values.squeeze(0)?.to_vec3::<f32>()?
+    );
+    assert_eq!(ys.i((0, 0))?.to_vec1::<f32>()?, &[0., 2., 0., 1.]);
+    assert!(tensor.index_put((0, 0, 4), &values, false).is_err());
+
+    // Gradients flow to both the tensor and the values.
+    let xs = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], &dev)?;
+    let values = Var::new(&[7f32, 8.], &dev)?;
+    let w = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &dev)?;
+    for accumulate in [false, true] {
+        let ys = xs.index_put((.., 1), &values, accumulate)?;
+        let grads = ys.mul(&w)?.sum_all()?.backward()?;
+        let grad_xs = grads.get(&xs).unwrap().to_vec2::<f32>()?;
+        let expected = if accumulate { 2. } else { 0. };
+        assert_eq!(grad_xs, &[[1., expected, 3.], [4., 5. * expected / 2., 6.]]);
+        let grad_values = grads.get(&values).unwrap().to_vec1::<f32>()?;
+        assert_eq!(grad_values, &[2., 5.]);
+    }
+    Ok(())
+}
+
+#[test]
+fn masked_fill_and_select() -> Result<()> {
+    let dev = Device::Cpu;
+    let tensor = Tensor::arange(0f32, 6., &dev)?.reshape((2, 3))?;
+    let mask = Tensor::new(&[1u8, 0, 1], &dev)?;
+    assert_eq!(
+        tensor.masked_fill(&mask, -1f32)?.to_vec2::<f32>()?,
+        &[[-1., 1., -1.], [-1., 4., -1.]]
+    );
+    let mask = tensor.gt(2f32)?;
+    assert_eq!(
+        tensor.masked_fill(&mask, 0f64)?.to_vec2::<f32>()?,
+        &[[0., 1., 2.], [0., 0., 0.]]
+    );
+    assert_eq!(
+        tensor.masked_select(&mask)?.to_vec1::<f32>()?,
+        &[3., 4., 5.]
+    );
+    let mask = Tensor::new(&[[true], [false]], &dev)?;
+    assert_eq!(
+        tensor.masked_select(&mask)?.to_vec1::<f32>()?,
+        &[0., 1., 2.]
+    );
+    Ok(())
+}
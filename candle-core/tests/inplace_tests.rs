@@ -0,0 +1,105 @@
+use candle_core::{test_device, DType, Device, IndexOp, Result, Tensor, Var};
+
+fn inplace_ops(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], dev)?;
+    assert_eq!(t.version(), 0);
+    t.add_(&Tensor::new(&[10f32, 20., 30.], dev)?)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[11., 22., 33.], [14., 25., 36.]]);
+    t.sub_(&Tensor::new(&[[1f32], [4.]], dev)?)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[10., 21., 32.], [10., 21., 32.]]);
+    let row = t.i(0)?.copy()?;
+    t.mul_(&row)?;
+    assert_eq!(
+        t.to_vec2::<f32>()?,
+        [[100., 441., 1024.], [100., 441., 1024.]]
+    );
+    t.div_(&Tensor::new(4f32, dev)?)?;
+    t.affine_(2., -0.5)?;
+    assert_eq!(
+        t.to_vec2::<f32>()?,
+        [[49.5, 220., 511.5], [49.5, 220., 511.5]]
+    );
+    assert_eq!(t.version(), 5);
+
+    // Tensors that share their storage with views cannot be modified in place, the clones of a
+    // tensor are the same tensor and see the updates.
+    let view = t.i(1)?;
+    assert!(view.fill_(7.).is_err());
+    assert!(t.fill_(7.).is_err());
+    assert!(t.mul_(&t.i(0)?).is_err());
+    let col = t.t()?.i(0)?.reshape((2, 1))?.copy()?;
+    assert!(t.copy_from(&col).is_err());
+    drop(view);
+    let t2 = t.clone();
+    t.copy_from(&col)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[49.5, 49.5, 49.5]; 2]);
+    assert_eq!(t2.to_vec2::<f32>()?, [[49.5, 49.5, 49.5]; 2]);
+    t.copy_from(&Tensor::new(&[1u32, 2, 3], dev)?)?;
+    assert_eq!(t.to_vec2::<f32>()?, [[1., 2., 3.], [1., 2., 3.]]);
+    assert_eq!(t2.version(), 7);
+
+    let t = Tensor::arange(0i64, 4, dev)?;
+    t.add_(&t)?;
+    assert_eq!(t.to_vec1::<i64>()?, [0, 2, 4, 6]);
+
+    // Non-contiguous tensors and mismatched dtypes are rejected.
+    let t = Tensor::zeros((2, 3), DType::F32, dev)?;
+    assert!(t.t()?.fill_(1.).is_err());
+    assert!(t.add_(&Tensor::zeros((2, 3), DType::F64, dev)?).is_err());
+    assert!(t.add_(&Tensor::zeros(4, DType::F32, dev)?).is_err());
+    assert_eq!(t.version(), 0);
+    Ok(())
+}
+
+test_device!(
+    inplace_ops,
+    inplace_ops_cpu,
+    inplace_ops_gpu,
+    inplace_ops_metal
+);
+
+#[test]
+fn inplace_version_check() -> Result<()> {
+    let dev = &Device::Cpu;
+    let w = Var::new(&[1f32, 2., 3.], dev)?;
+    let x = Tensor::new(&[4f32, 5., 6.], dev)?;
+
+    // Modifying a tensor saved for the backward pass makes it fail.
+    let loss = w.mul(&x)?.sum_all()?;
+    x.add_(&Tensor::ones(3, DType::F32, dev)?)?;
+    let err = loss.backward().unwrap_err().to_string();
+    assert!(
+        err.starts_with("mul: a tensor needed for the gradient computation has been modified"),
+        "{err}"
+    );
+    assert!(err.contains("expected version 0 but got 1"), "{err}");
+
+    // Only the tensors read by the gradient computation are checked.
+    let ys = (w.as_tensor() + 1.)?.reshape((3, 1))?;
+    let loss = ys.sum_all()?;
+    w.set(&Tensor::new(&[2f32, 3., 4.], dev)?)?;
+    let grads = loss.backward()?;
+    assert_eq!(grads.get(&w).unwrap().to_vec1::<f32>()?, [1., 1., 1.]);
+
+    // The output of an op cannot be modified while it is part of a graph.
+    let ys = w.exp()?;
+    assert!(ys.detach()?.fill_(0.).is_err());
+
+    // Graphs that are created after the modification are fine, and setting a variable is
+    // tracked in the same way.
+    let loss = w.mul(&x)?.sum_all()?;
+    let grads = loss.backward()?;
+    assert_eq!(grads.get(&w).unwrap().to_vec1::<f32>()?, [5., 6., 7.]);
+    w.set(&Tensor::zeros(3, DType::F32, dev)?)?;
+    assert!(loss.backward().is_err());
+
+    // Tensors that are the result of a tracked op cannot be modified in place, a detached
+    // tensor can once the original is dropped.
+    let ys = w.mul(&x)?;
+    assert!(ys.add_(&x).is_err());
+    let zs = ys.detach()?;
+    assert!(zs.add_(&x).is_err());
+    drop(ys);
+    zs.add_(&x)?;
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use candle_core::{test_device, DType, Device, Result, Tensor, Var, D};
+
+fn max_diff(a: &Tensor, b: &Tensor) -> Result<f32> {
+    let diff = (a.to_dtype(DType::F32)? - b.to_dtype(DType::F32)?)?;
+    diff.abs()?.flatten_all()?.max(0)?.to_scalar::<f32>()
+}
+
+fn silu(device: &Device) -> Result<()> {
+    // More elements than a single block of the fused kernel.
+    let xs = Tensor::randn(0f32, 2., (3, 700), device)?;
+    for dtype in [DType::F32, DType::F64, DType::BF16, DType::F16] {
+        let xs = xs.to_dtype(dtype)?;
+        let expected = (&xs / (xs.neg()?.exp()? + 1.)?)?;
+        let lazy = xs.lazy();
+        let ys = lazy.div(&lazy.neg()?.exp()?.affine(1., 1.)?)?.eval()?;
+        assert_eq!(ys.dims(), [3, 700]);
+        assert_eq!(ys.dtype(), dtype);
+        assert_eq!(max_diff(&ys, &expected)?, 0.);
+    }
+    Ok(())
+}
+
+fn rms_norm(device: &Device) -> Result<()> {
+    // A non-contiguous input and a broadcasted weight.
+    let xs = Tensor::randn(0f32, 1., (2, 8, 5), device)?.transpose(1, 2)?;
+    let w = Tensor::randn(0f32, 1., 8, device)?;
+    let eps = 1e-5;
+    let expected = {
+        let norm = (xs.sqr()?.mean_keepdim(D::Minus1)? + eps)?.sqrt()?;
+        xs.broadcast_div(&norm)?.broadcast_mul(&w)?
+    };
+    let lazy = xs.lazy();
+    let norm = lazy
+        .sqr()?
+        .mean_keepdim(D::Minus1)?
+        .affine(1., eps)?
+        .sqrt()?;
+    let ys = lazy
+        .broadcast_div(&norm)?
+        .broadcast_mul(&w.lazy())?
+        .eval()?;
+    assert_eq!(ys.dims(), [2, 5, 8]);
+    assert!(max_diff(&ys, &expected)? < 1e-6);
+
+    let lhs = Tensor::new(&[[1f32], [2.]], device)?.lazy();
+    let rhs = Tensor::new(&[1f32, 2., 3.], device)?.lazy();
+    assert!(lhs.add(&rhs).is_err());
+    let ys = lhs.broadcast_maximum(&rhs)?.broadcast_sub(&rhs)?.eval()?;
+    assert_eq!(ys.to_vec2::<f32>()?, [[0., 0., 0.], [1., 0., 0.]]);
+    Ok(())
+}
+
+fn lazy_grad(device: &Device) -> Result<()> {
+    // Inputs that track gradients are evaluated eagerly so that backprop still works.
+    let x = Var::new(&[1f32, -2., 3.], device)?;
+    let ys = x.lazy().sqr()?.affine(2., 1.)?.eval()?;
+    assert_eq!(ys.to_vec1::<f32>()?, [3., 9., 19.]);
+    let grads = ys.sum_all()?.backward()?;
+    let grad_x = grads.get(&x).expect("no grad for x");
+    assert_eq!(grad_x.to_vec1::<f32>()?, [4., -8., 12.]);
+    Ok(())
+}
+
+test_device!(silu, silu_cpu, silu_gpu, silu_metal);
+test_device!(rms_norm, rms_norm_cpu, rms_norm_gpu, rms_norm_metal);
+test_device!(lazy_grad, lazy_grad_cpu, lazy_grad_gpu, lazy_grad_metal);
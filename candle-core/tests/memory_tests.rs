@@ -0,0 +1,83 @@
+use candle_core::{memory, memory::Profiler, DType, Device, Result, Tensor};
+
+// The memory statistics are global so all the checks happen in a single test.
+#[test]
+fn memory_stats() -> Result<()> {
+    let device = Device::Cpu;
+    let before = device.memory_stats();
+    let xs = Tensor::zeros((256, 128), DType::F32, &device)?;
+    let ys = Tensor::ones(1024, DType::F64, &device)?;
+    let stats = device.memory_stats();
+    assert_eq!(
+        stats.live_bytes,
+        before.live_bytes + 256 * 128 * 4 + 1024 * 8
+    );
+    assert_eq!(stats.live_allocations, before.live_allocations + 2);
+    assert_eq!(stats.allocations, before.allocations + 2);
+    assert!(stats.peak_bytes >= stats.live_bytes);
+    let f32_stats = &stats.by_dtype[&DType::F32];
+    assert!(f32_stats.live_bytes >= 256 * 128 * 4);
+
+    // Views share the storage of the tensor they are created from.
+    let zs = xs.narrow(0, 0, 16)?.transpose(0, 1)?;
+    assert_eq!(device.memory_stats().live_bytes, stats.live_bytes);
+    drop(xs);
+    assert_eq!(device.memory_stats().live_bytes, stats.live_bytes);
+    drop(zs);
+    drop(ys);
+    let stats = device.memory_stats();
+    assert_eq!(stats.live_bytes, before.live_bytes);
+    assert_eq!(stats.live_allocations, before.live_allocations);
+    assert!(stats.peak_bytes >= before.live_bytes + 256 * 128 * 4 + 1024 * 8);
+
+    // The intermediate buffers of the ops count towards the peak, here the im2col buffer of the
+    // convolution is larger than its output.
+    let xs = Tensor::ones((1, 4, 64, 64), DType::F32, &device)?;
+    let ws = Tensor::ones((8, 4, 3, 3), DType::F32, &device)?;
+    let before = device.memory_stats();
+    let ys = xs.conv2d(&ws, 0, 1, 1, 1)?;
+    let stats = device.memory_stats();
+    let (col_bytes, out_bytes) = (62 * 62 * 4 * 9 * 4, 8 * 62 * 62 * 4);
+    assert!(stats.peak_bytes >= before.live_bytes + col_bytes + out_bytes);
+    assert_eq!(stats.live_bytes, before.live_bytes + out_bytes);
+    drop((xs, ws, ys));
+
+    // Buffers reused from the cache are live again but are not new allocations.
+    memory::set_cpu_caching(true);
+    drop(Tensor::zeros(1024, DType::F32, &device)?);
+    let before = device.memory_stats();
+    let xs = Tensor::zeros(1024, DType::F32, &device)?;
+    let stats = device.memory_stats();
+    assert_eq!(stats.allocations, before.allocations);
+    assert_eq!(stats.live_bytes, before.live_bytes + 1024 * 4);
+    drop(xs);
+    assert_eq!(device.memory_stats().live_bytes, before.live_bytes);
+    memory::set_cpu_caching(false);
+
+    let xs = Tensor::arange(0f32, 64., &device)?.reshape((8, 8))?;
+    let profiler = Profiler::start();
+    let ys = xs.matmul(&xs)?.exp()?;
+    let zs = (ys.exp()? + &xs)?.sum_keepdim(1)?;
+    let report = profiler.report();
+    drop(profiler);
+    let bytes = |op: &str| {
+        report
+            .iter()
+            .find(|r| r.op == op)
+            .map(|r| (r.allocations, r.bytes))
+    };
+    assert_eq!(bytes("matmul"), Some((1, 8 * 8 * 4)));
+    assert_eq!(bytes("exp"), Some((2, 2 * 8 * 8 * 4)));
+    assert_eq!(bytes("add"), Some((1, 8 * 8 * 4)));
+    assert_eq!(bytes("sum"), Some((1, 8 * 4)));
+    assert_eq!(report[0].op, "exp");
+
+    // A new profiler only records the allocations made after its creation.
+    let profiler = Profiler::start();
+    drop(zs.exp()?);
+    let report = profiler.report();
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].op, "exp");
+    assert!(profiler.to_string().contains("exp"));
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use candle_core::{test_device, DType, Device, IndexOp, Result, Shape, Tensor, Var, D};
+
+fn names(t: &Tensor) -> Option<Vec<Option<&str>>> {
+    t.names().map(|n| n.iter().map(|n| n.as_deref()).collect())
+}
+
+fn named_dims(dev: &Device) -> Result<()> {
+    let xs = Tensor::arange(0f32, 24., dev)?.reshape((2, 3, 4))?;
+    assert_eq!(xs.names(), None);
+    let xs = xs.rename(("batch", "seq", "hidden"))?;
+    assert_eq!(
+        names(&xs),
+        Some(vec![Some("batch"), Some("seq"), Some("hidden")])
+    );
+    assert_eq!(xs.dim("seq")?, 3);
+    assert_eq!(format!("{:?}", xs.shape()), "[batch: 2, seq: 3, hidden: 4]");
+
+    // Names can be used wherever a dimension index is expected.
+    let s = xs.sum_keepdim("hidden")?;
+    assert_eq!(s.dims(), [2, 3, 1]);
+    assert_eq!(s.to_vec3::<f32>()?, xs.sum_keepdim(2)?.to_vec3::<f32>()?);
+    assert_eq!(
+        names(&s),
+        Some(vec![Some("batch"), Some("seq"), Some("hidden")])
+    );
+    let s = xs.sum(("batch", "hidden"))?;
+    assert_eq!(s.to_vec1::<f32>()?, [60., 92., 124.]);
+    assert_eq!(names(&s), Some(vec![Some("seq")]));
+    let m = xs.max("seq")?;
+    assert_eq!(names(&m), Some(vec![Some("batch"), Some("hidden")]));
+    let t = xs.transpose("seq", "hidden")?;
+    assert_eq!(t.dims(), [2, 4, 3]);
+    assert_eq!(
+        names(&t),
+        Some(vec![Some("batch"), Some("hidden"), Some("seq")])
+    );
+    let t = xs.transpose("seq", D::Minus1)?;
+    assert_eq!((t.dim("hidden")?, t.dim(1)?), (4, 4));
+    let p = xs.permute(("hidden", "batch", "seq"))?;
+    assert_eq!(p.dims(), [4, 2, 3]);
+    assert_eq!(p.i((.., 1, 2))?.to_vec1::<f32>()?, [20., 21., 22., 23.]);
+    assert_eq!(
+        names(&p.i((.., 1))?),
+        Some(vec![Some("hidden"), Some("seq")])
+    );
+
+    // Names go through unary, binary and broadcasted ops.
+    let ys = (xs.exp()? + xs.affine(2., 1.)?)?;
+    assert_eq!(ys.shape().names(), xs.shape().names());
+    let bias = Tensor::ones(4, DType::F32, dev)?;
+    let ys = xs.broadcast_add(&bias)?.narrow("seq", 1, 2)?.contiguous()?;
+    assert_eq!(ys.dims(), [2, 2, 4]);
+    assert_eq!(ys.shape().names(), xs.shape().names());
+    let u = xs.unsqueeze("seq")?;
+    assert_eq!(
+        names(&u),
+        Some(vec![Some("batch"), None, Some("seq"), Some("hidden")])
+    );
+    assert_eq!(u.squeeze(1)?.shape().names(), xs.shape().names());
+    let c = Tensor::cat(&[&xs, &xs], "seq")?;
+    assert_eq!(c.dims(), [2, 6, 4]);
+    assert_eq!(c.shape().names(), xs.shape().names());
+
+    // When both sides disagree on a name, the resulting dimension is left unnamed.
+    let zs = xs.rename(("batch", "tokens", None))?;
+    let ws = (&xs * &zs)?;
+    assert_eq!(names(&ws), Some(vec![Some("batch"), None, Some("hidden")]));
+
+    // Matmul keeps the batch dimensions and the outer dimensions of both sides.
+    let w = Tensor::ones((2, 4, 5), DType::F32, dev)?.rename((None, "in", "out"))?;
+    let mm = xs.matmul(&w)?;
+    assert_eq!(
+        names(&mm),
+        Some(vec![Some("batch"), Some("seq"), Some("out")])
+    );
+    let w = Tensor::ones((4, 5), DType::F32, dev)?.rename(("in", "out"))?;
+    let mm = xs.broadcast_matmul(&w)?;
+    assert_eq!(
+        names(&mm),
+        Some(vec![Some("batch"), Some("seq"), Some("out")])
+    );
+
+    // Names can be built at runtime.
+    let hidden = format!("hidden{}", 0);
+    let zs = xs.rename((String::from("batch"), None, hidden.clone()))?;
+    assert_eq!(zs.dim(hidden.as_str())?, 4);
+    assert_eq!(format!("{:?}", zs.shape()), "[batch: 2, 3, hidden0: 4]");
+
+    // Reshaping drops the names unless the target shape is named.
+    assert_eq!(xs.reshape((6, 4))?.names(), None);
+    let shape = Shape::from((6, 4)).with_names(("rows", "hidden"))?;
+    assert_eq!(
+        names(&xs.reshape(shape)?),
+        Some(vec![Some("rows"), Some("hidden")])
+    );
+    Ok(())
+}
+
+fn named_dims_errors(dev: &Device) -> Result<()> {
+    let xs = Tensor::zeros((2, 5, 8), DType::F32, dev)?.rename(("batch", "seq", "hidden"))?;
+    let ys = Tensor::zeros((2, 7, 8), DType::F32, dev)?.rename(("batch", "seq", "hidden"))?;
+    let err = xs.add(&ys).unwrap_err().to_string();
+    assert!(
+        err.starts_with(
+            "shape mismatch in add, lhs: [batch: 2, seq: 5, hidden: 8], \
+             rhs: [batch: 2, seq: 7, hidden: 8], mismatch on dim seq: 5 vs 7"
+        ),
+        "{err}"
+    );
+    let err = xs.broadcast_mul(&ys).unwrap_err().to_string();
+    assert!(err.contains("mismatch on dim seq: 5 vs 7"), "{err}");
+
+    // Unnamed shapes keep the previous error messages.
+    let err = xs
+        .reshape((2, 5, 8))?
+        .add(&ys.reshape((2, 7, 8))?)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.starts_with("shape mismatch in add, lhs: [2, 5, 8], rhs: [2, 7, 8]"),
+        "{err}"
+    );
+    assert!(!err.contains("mismatch on dim"), "{err}");
+
+    let err = xs.sum_keepdim("time").unwrap_err().to_string();
+    assert!(
+        err.starts_with("sum: no dimension named time in shape [batch: 2, seq: 5, hidden: 8]"),
+        "{err}"
+    );
+    assert!(xs.rename(("batch", "seq")).is_err());
+    assert!(xs.rename(("batch", "seq", "batch")).is_err());
+    Ok(())
+}
+
+test_device!(named_dims, named_dims_cpu, named_dims_gpu, named_dims_metal);
+test_device!(
+    named_dims_errors,
+    named_dims_errors_cpu,
+    named_dims_errors_gpu,
+    named_dims_errors_metal
+);
+
+#[test]
+fn named_dims_backprop() -> Result<()> {
+    let dev = &Device::Cpu;
+    let x = Var::new(&[[1f32, 2., 3.], [4., 5., 6.]], dev)?;
+    let y = Tensor::new(&[[1f32, 1., 1.], [2., 2., 2.]], dev)?;
+    // The names do not change the gradients, even when the graph uses conflicting names.
+    let xa = x.rename(("batch", "hidden"))?;
+    let ya = y.rename(("rows", "cols"))?;
+    let loss = ((&xa * &xa)? + (&xa * &ya)?)?.sum("hidden")?.sum_all()?;
+    let grads = loss.backward()?;
+    let grad = grads.get(&x).unwrap();
+    assert_eq!(grad.dims(), [2, 3]);
+    assert_eq!(grad.to_vec2::<f32>()?, [[3., 5., 7.], [10., 12., 14.]]);
+    Ok(())
+}
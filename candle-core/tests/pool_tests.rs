@@ -1,4 +1,4 @@
-use candle_core::{test_device, test_utils, Device, IndexOp, Result, Tensor};
+use candle_core::{test_device, test_utils, Device, IndexOp, ResizeMode, Result, Tensor};
 
 // https://github.com/huggingface/candle/issues/364
 fn avg_pool2d(dev: &Device) -> Result<()> {
@@ -98,7 +98,72 @@ fn upsample_nearest2d(dev: &Device) -> Result<()> {
     Ok(())
 }
 
+fn resize2d(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], dev)?;
+    let resize = |mode| -> Result<Vec<Vec<f32>>> {
+        test_utils::to_vec2_round(&t.resize2d(4, 4, mode)?.i(0)?.i(0)?, 4)
+    };
+    assert_eq!(
+        resize(ResizeMode::Bilinear {
+            align_corners: false,
+            antialias: false
+        })?,
+        [
+            [1.0, 1.25, 1.75, 2.0],
+            [1.5, 1.75, 2.25, 2.5],
+            [2.5, 2.75, 3.25, 3.5],
+            [3.0, 3.25, 3.75, 4.0]
+        ]
+    );
+    assert_eq!(
+        resize(ResizeMode::Bilinear {
+            align_corners: true,
+            antialias: false
+        })?,
+        [
+            [1.0, 1.3333, 1.6667, 2.0],
+            [1.6667, 2.0, 2.3333, 2.6667],
+            [2.3333, 2.6667, 3.0, 3.3333],
+            [3.0, 3.3333, 3.6667, 4.0]
+        ]
+    );
+    assert_eq!(
+        resize(ResizeMode::Bicubic {
+            align_corners: false,
+            antialias: false
+        })?,
+        [
+            [0.6836, 1.0156, 1.5625, 1.8945],
+            [1.3477, 1.6797, 2.2266, 2.5586],
+            [2.4414, 2.7734, 3.3203, 3.6523],
+            [3.1055, 3.4375, 3.9844, 4.3164]
+        ]
+    );
+    assert_eq!(
+        resize(ResizeMode::Nearest)?,
+        t.upsample_nearest2d(4, 4)?.i(0)?.i(0)?.to_vec2::<f32>()?
+    );
+
+    let t = Tensor::arange(0f32, 6f32, dev)?.reshape((1, 1, 2, 3))?;
+    let resized = t.resize2d(1, 2, ResizeMode::Area)?;
+    assert_eq!(resized.i(0)?.i(0)?.to_vec2::<f32>()?, [[2.0, 3.0]]);
+
+    // Downsampling with antialiasing averages over the scaled filter support.
+    let t = Tensor::arange(0f32, 8f32, dev)?.reshape((1, 1, 1, 8))?;
+    let mode = ResizeMode::Bilinear {
+        align_corners: false,
+        antialias: true,
+    };
+    let resized = t.resize2d(1, 4, mode)?.i(0)?.i(0)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&resized, 4)?,
+        [[0.7143, 2.5, 4.5, 6.2857]]
+    );
+    Ok(())
+}
+
 test_device!(avg_pool2d, avg_pool2d_cpu, avg_pool2d_gpu, avg_pool2d_metal);
+test_device!(resize2d, resize2d_cpu, resize2d_gpu, resize2d_metal);
 test_device!(
     avg_pool2d_pytorch,
     avg_pool2d_pytorch_cpu,
@@ -112,3 +177,71 @@ test_device!(
     upsample_nearest2d_gpu,
     upsample_nearest2d_metal
 );
+
+#[test]
+fn avg_pool3d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t = Tensor::arange(0f32, 32., dev)?.reshape((1, 1, 2, 4, 4))?;
+    let pool = t.avg_pool3d(2)?;
+    assert_eq!(pool.dims(), [1, 1, 1, 2, 2]);
+    assert_eq!(
+        pool.flatten_all()?.to_vec1::<f32>()?,
+        [10.5, 12.5, 18.5, 20.5]
+    );
+    // With a depth of one, this matches the 2d version.
+    let t = Tensor::randn(0f32, 1., (2, 3, 1, 7, 6), dev)?;
+    let pool = t.avg_pool3d_with_stride((1, 3, 2), (1, 2, 1))?.squeeze(2)?;
+    let expected = t.squeeze(2)?.avg_pool2d_with_stride((3, 2), (2, 1))?;
+    let diff = (pool - expected)?.abs()?.sum_all()?.to_scalar::<f32>()?;
+    assert!(diff < 1e-4);
+    assert!(t.avg_pool3d(2).is_err());
+    Ok(())
+}
+
+#[test]
+fn max_pool3d() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t = Tensor::arange(0f32, 32., dev)?.reshape((1, 1, 2, 4, 4))?;
+    let pool = t.max_pool3d(2)?;
+    assert_eq!(pool.flatten_all()?.to_vec1::<f32>()?, [21., 23., 29., 31.]);
+    let pool = t.max_pool3d_with_stride((1, 3, 3), (1, 1, 1))?;
+    assert_eq!(pool.dims(), [1, 1, 2, 2, 2]);
+    assert_eq!(
+        pool.flatten_all()?.to_vec1::<f32>()?,
+        [10., 11., 14., 15., 26., 27., 30., 31.]
+    );
+    Ok(())
+}
+
+#[test]
+fn pool3d_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let t =
+        candle_core::Var::from_tensor(&Tensor::arange(0f32, 27., dev)?.reshape((1, 1, 3, 3, 3))?)?;
+    // The last plane along each dimension is not covered by the windows.
+    let grads = t.avg_pool3d(2)?.sum_all()?.backward()?;
+    let grad = grads.get(&t).unwrap();
+    let expected = Tensor::ones((1, 1, 2, 2, 2), candle_core::DType::F32, dev)?
+        .affine(0.125, 0.)?
+        .pad_with_zeros(2, 0, 1)?
+        .pad_with_zeros(3, 0, 1)?
+        .pad_with_zeros(4, 0, 1)?;
+    assert_eq!(
+        grad.flatten_all()?.to_vec1::<f32>()?,
+        expected.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    let grads = t.max_pool3d(2)?.sum_all()?.backward()?;
+    let grad = grads.get(&t).unwrap();
+    let mut expected = vec![0f32; 27];
+    // The maximum of the single window is at position (1, 1, 1).
+    expected[13] = 1.;
+    assert_eq!(grad.flatten_all()?.to_vec1::<f32>()?, expected);
+
+    // Ties split the gradient evenly.
+    let t = candle_core::Var::ones((1, 1, 2, 2, 2), candle_core::DType::F32, dev)?;
+    let grads = t.max_pool3d(2)?.sum_all()?.backward()?;
+    let grad = grads.get(&t).unwrap();
+    assert_eq!(grad.flatten_all()?.to_vec1::<f32>()?, [0.125; 8]);
+    Ok(())
+}
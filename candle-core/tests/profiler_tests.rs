@@ -0,0 +1,49 @@
+use candle_core::{profiler::Profiler, DType, Device, DeviceLocation, Result, Tensor};
+
+// Profilers record the ops from all the threads so all the checks happen in a single test.
+#[test]
+fn op_profiler() -> Result<()> {
+    let xs = Tensor::arange(0f32, 12., &Device::Cpu)?.reshape((3, 4))?;
+    let ws = Tensor::ones((4, 2), DType::F32, &Device::Cpu)?;
+    let profiler = Profiler::start();
+    let ys = xs.matmul(&ws)?;
+    let ys = (ys.exp()? + 1.)?.sum_keepdim(1)?;
+    let events = profiler.events();
+    let ops: Vec<_> = events.iter().map(|e| e.op).collect();
+    assert_eq!(ops, ["matmul", "exp", "affine", "sum"]);
+    assert_eq!(events[0].shapes, [[3, 4], [4, 2]]);
+    assert_eq!(events[0].dtype, DType::F32);
+    assert_eq!(events[0].device, DeviceLocation::Cpu);
+    assert_eq!(events[0].bytes, (12 + 8) * 4);
+    assert_eq!(events[3].shapes, [[3, 2]]);
+    assert!(events.windows(2).all(|e| e[0].start <= e[1].start));
+
+    let _zs = ys.exp()?.exp()?;
+    let summary = profiler.summary();
+    let exp = summary
+        .iter()
+        .find(|s| s.op == "exp")
+        .expect("no exp summary");
+    assert_eq!(exp.count, 3);
+    assert_eq!(exp.bytes, (6 + 3 + 3) * 4);
+    assert_eq!(summary.iter().map(|s| s.count).sum::<usize>(), 6);
+    assert!(profiler.to_string().contains("matmul"));
+
+    let trace = profiler.chrome_trace();
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert_eq!(trace.matches("\"ph\":\"X\"").count(), 6);
+    assert!(trace.contains("\"name\":\"matmul\""));
+    assert!(trace.contains("\"shapes\":\"[[3, 4], [4, 2]]\""));
+    let tmp_dir = std::env::temp_dir();
+    let path = tmp_dir.join("candle_profiler_trace.json");
+    profiler.write_chrome_trace(&path)?;
+    assert_eq!(std::fs::read_to_string(&path)?, trace);
+    std::fs::remove_file(&path)?;
+
+    // Ops dispatched after the profiler has been dropped are not recorded by a new profiler.
+    drop(profiler);
+    let _zs = xs.exp()?;
+    let profiler = Profiler::start();
+    assert!(profiler.events().is_empty());
+    Ok(())
+}
@@ -720,3 +720,66 @@ fn quantized_matmul_q8k() -> Result<()> {
     ggml_matmul_error_test::<BlockQ8K>()?;
     Ok(())
 }
+
+#[test]
+fn gguf_mmaped() -> Result<()> {
+    use quantized::gguf_file;
+
+    let cpu = &Device::Cpu;
+    let xs = Tensor::randn(0f32, 1., (8, 256), cpu)?;
+    let q4k = quantized::QTensor::quantize::<k_quants::BlockQ4K>(&xs)?;
+    let q8_0 = quantized::QTensor::quantize::<k_quants::BlockQ8_0>(&xs.narrow(0, 0, 3)?)?;
+    let f32 = quantized::QTensor::quantize::<f32>(&xs.t()?.contiguous()?)?;
+    let tensors = [("q4k", &q4k), ("q8_0", &q8_0), ("f32", &f32)];
+    let arch = gguf_file::Value::String("test".to_string());
+
+    let path = std::env::temp_dir().join(format!("candle_gguf_mmap_{}.gguf", std::process::id()));
+    let mut file = std::fs::File::create(&path)?;
+    gguf_file::write(&mut file, &[("general.architecture", &arch)], &tensors)?;
+    drop(file);
+
+    let mmaped = unsafe { gguf_file::MmapedContent::new(&path)? };
+    let mut file = std::fs::File::open(&path)?;
+    let content = gguf_file::Content::read(&mut file)?;
+    assert_eq!(mmaped.content().tensor_infos.len(), 3);
+    for (name, tensor) in tensors {
+        let from_mmap = mmaped.tensor(name)?;
+        let from_file = content.tensor(&mut file, name)?;
+        assert_eq!(from_mmap.shape(), tensor.shape());
+        assert_eq!(from_mmap.dtype(), tensor.dtype());
+        let expected = tensor.dequantize(cpu)?.flatten_all()?.to_vec1::<f32>()?;
+        for t in [&from_mmap, &from_file] {
+            let dequantized = t.dequantize(cpu)?.flatten_all()?.to_vec1::<f32>()?;
+            assert_eq!(dequantized, expected);
+        }
+        // The blocks are borrowed from the mapped file so the same data is used each time.
+        assert_eq!(from_mmap.as_ptr(), mmaped.tensor(name)?.as_ptr());
+        assert_ne!(
+            from_file.as_ptr(),
+            content.tensor(&mut file, name)?.as_ptr()
+        );
+    }
+
+    // The tensors keep the file mapped after the content has been dropped.
+    let q4k_mmap = mmaped.tensor("q4k")?;
+    drop(mmaped);
+    let lhs = Tensor::randn(0f32, 1., (2, 256), cpu)?;
+    let mm = quantized::QMatMul::from_qtensor(q4k_mmap)?.forward(&lhs)?;
+    let expected = quantized::QMatMul::from_qtensor(q4k)?.forward(&lhs)?;
+    assert_eq!(mm.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    assert!(unsafe { gguf_file::MmapedContent::new(&path)? }
+        .tensor("missing")
+        .is_err());
+
+    // Offsets that overflow are reported as errors. The tensor info for q4k is its name followed
+    // by the number of dims, the dims, the dtype and the offset.
+    let mut bytes = std::fs::read(&path)?;
+    let pos = bytes.windows(3).position(|w| w == b"q4k").unwrap() + 3 + 4 + 2 * 8 + 4;
+    bytes[pos..pos + 8].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+    std::fs::write(&path, bytes)?;
+    let mmaped = unsafe { gguf_file::MmapedContent::new(&path)? };
+    assert!(mmaped.tensor("q4k").is_err());
+    assert!(mmaped.tensor("q8_0").is_ok());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
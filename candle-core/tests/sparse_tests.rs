@@ -0,0 +1,192 @@
+use candle_core::{
+    test_device, test_utils, DType, Device, Result, SparseIndices, SparseTensor, Tensor, Var,
+};
+
+fn sparse_conversions(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[0f32, 0., 3.], [4., 0., 5.]], dev)?;
+    let coo = t.to_sparse_coo()?;
+    assert_eq!(coo.nnz(), 3);
+    match coo.indices() {
+        SparseIndices::Coo(indices) => {
+            assert_eq!(indices.to_vec2::<u32>()?, [[0, 1, 1], [2, 0, 2]])
+        }
+        SparseIndices::Csr { .. } => panic!("unexpected csr indices"),
+    }
+    assert_eq!(coo.values().to_vec1::<f32>()?, [3., 4., 5.]);
+    assert_eq!(coo.to_dense()?.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+
+    let csr = t.to_sparse_csr()?;
+    match csr.indices() {
+        SparseIndices::Csr {
+            crow_indices,
+            col_indices,
+        } => {
+            assert_eq!(crow_indices.to_vec1::<u32>()?, [0, 1, 3]);
+            assert_eq!(col_indices.to_vec1::<u32>()?, [2, 0, 2]);
+        }
+        SparseIndices::Coo(_) => panic!("unexpected coo indices"),
+    }
+    assert_eq!(csr.to_dense()?.to_vec2::<f32>()?, t.to_vec2::<f32>()?);
+    assert_eq!(
+        csr.to_coo()?.coo_indices()?.to_vec2::<u32>()?,
+        [[0, 1, 1], [2, 0, 2]]
+    );
+
+    // Unsorted coordinates with duplicates, these get summed.
+    let indices = Tensor::new(&[[1i64, 0, 1], [2, 2, 2]], dev)?;
+    let values = Tensor::new(&[1f32, 3., 4.], dev)?;
+    let coo = SparseTensor::coo(&indices, &values, (2, 3))?;
+    let expected = [[0., 0., 3.], [0., 0., 5.]];
+    assert_eq!(coo.to_dense()?.to_vec2::<f32>()?, expected);
+    let csr = coo.to_csr()?;
+    assert_eq!(csr.values().to_vec1::<f32>()?, [3., 1., 4.]);
+    assert_eq!(csr.to_dense()?.to_vec2::<f32>()?, expected);
+
+    // Higher rank and non-contiguous tensors.
+    let t = Tensor::arange(0f32, 12., dev)?
+        .reshape((2, 2, 3))?
+        .transpose(0, 2)?;
+    let coo = t.to_sparse_coo()?;
+    assert_eq!(coo.nnz(), 11);
+    assert_eq!(coo.dims(), [3, 2, 2]);
+    assert_eq!(
+        coo.to_dense()?.flatten_all()?.to_vec1::<f32>()?,
+        t.flatten_all()?.to_vec1::<f32>()?
+    );
+
+    assert!(SparseTensor::coo(&indices, &values, (2, 2)).is_err());
+    assert!(SparseTensor::coo(&indices, &values, (2, 3, 1)).is_err());
+    let crow = Tensor::new(&[0u32, 2, 1], dev)?;
+    let col = Tensor::new(&[0u32], dev)?;
+    assert!(SparseTensor::csr(&crow, &col, &values.narrow(0, 0, 1)?, (2, 3)).is_err());
+    Ok(())
+}
+
+fn sparse_matmul(dev: &Device) -> Result<()> {
+    let t = Tensor::new(&[[0f32, 0., 3.], [4., 0., 5.], [0., 0., 0.]], dev)?;
+    let rhs = Tensor::new(&[[1f32, 2.], [3., 4.], [5., 6.]], dev)?;
+    let expected = t.matmul(&rhs)?.to_vec2::<f32>()?;
+    assert_eq!(expected, [[15., 18.], [29., 38.], [0., 0.]]);
+    assert_eq!(t.to_sparse_coo()?.matmul(&rhs)?.to_vec2::<f32>()?, expected);
+    assert_eq!(t.to_sparse_csr()?.matmul(&rhs)?.to_vec2::<f32>()?, expected);
+    let rhs_t = rhs.t()?.contiguous()?.t()?;
+    assert_eq!(
+        t.to_sparse_csr()?.matmul(&rhs_t)?.to_vec2::<f32>()?,
+        expected
+    );
+    assert!(t.to_sparse_coo()?.matmul(&rhs.t()?).is_err());
+    Ok(())
+}
+
+fn sparse_index_add(dev: &Device) -> Result<()> {
+    let t = Tensor::ones((3, 3), DType::F32, dev)?;
+    let source = Tensor::new(&[[1f32, 0.], [0., 2.], [3., 0.]], dev)?;
+    let indexes = Tensor::new(&[2u32, 0], dev)?;
+    let expected = t.index_add(&indexes, &source, 1)?;
+    assert_eq!(
+        expected.to_vec2::<f32>()?,
+        [[1., 1., 2.], [3., 1., 1.], [1., 1., 4.]]
+    );
+    for source in [source.to_sparse_coo()?, source.to_sparse_csr()?] {
+        let ys = t.index_add_sparse(&indexes, &source, 1)?;
+        assert_eq!(ys.to_vec2::<f32>()?, expected.to_vec2::<f32>()?);
+    }
+    let source = Tensor::new(&[[0f32, 1., 0.], [2., 0., 0.]], dev)?;
+    let ys = t.index_add_sparse(&indexes, &source.to_sparse_coo()?, 0)?;
+    assert_eq!(
+        ys.to_vec2::<f32>()?,
+        t.index_add(&indexes, &source, 0)?.to_vec2::<f32>()?
+    );
+    assert!(t
+        .index_add_sparse(&indexes, &source.to_sparse_coo()?, 1)
+        .is_err());
+
+    // Any integer dtype can be used for the indexes, these are checked to be in bounds.
+    let expected = t.index_add(&indexes, &source, 0)?.to_vec2::<f32>()?;
+    let source = source.to_sparse_coo()?;
+    for indexes in [
+        Tensor::new(&[2i8, 0], dev)?,
+        Tensor::new(&[2i16, 0], dev)?,
+        Tensor::new(&[2i32, 0], dev)?,
+    ] {
+        let ys = t.index_add_sparse(&indexes, &source, 0)?;
+        assert_eq!(ys.to_vec2::<f32>()?, expected);
+    }
+    assert!(t
+        .index_add_sparse(&Tensor::new(&[-1i64, 0], dev)?, &source, 0)
+        .is_err());
+    assert!(t
+        .index_add_sparse(&Tensor::new(&[3i32, 0], dev)?, &source, 0)
+        .is_err());
+    let coo = SparseTensor::coo(
+        &Tensor::new(&[[1i32], [2]], dev)?,
+        &Tensor::new(&[1f32], dev)?,
+        (2, 3),
+    )?;
+    assert_eq!(
+        coo.to_dense()?.to_vec2::<f32>()?,
+        [[0., 0., 0.], [0., 0., 1.]]
+    );
+    assert!(SparseTensor::coo(
+        &Tensor::new(&[[-1i8], [2]], dev)?,
+        &Tensor::new(&[1f32], dev)?,
+        (2, 3)
+    )
+    .is_err());
+    Ok(())
+}
+
+test_device!(
+    sparse_conversions,
+    sparse_conversions_cpu,
+    sparse_conversions_gpu,
+    sparse_conversions_metal
+);
+test_device!(
+    sparse_matmul,
+    sparse_matmul_cpu,
+    sparse_matmul_gpu,
+    sparse_matmul_metal
+);
+test_device!(
+    sparse_index_add,
+    sparse_index_add_cpu,
+    sparse_index_add_gpu,
+    sparse_index_add_metal
+);
+
+#[test]
+fn sparse_grad() -> Result<()> {
+    let dev = &Device::Cpu;
+    let indices = Tensor::new(&[[0u32, 2, 1, 2], [1, 0, 3, 3]], dev)?;
+    let values = Tensor::randn(0f64, 1., 4, dev)?;
+    let rhs = Tensor::randn(0f64, 1., (4, 5), dev)?;
+    let w = Tensor::randn(0f64, 1., (3, 5), dev)?;
+    let f = |values: &Tensor, rhs: &Tensor| {
+        SparseTensor::coo(&indices, values, (3, 4))?
+            .to_csr()?
+            .matmul(rhs)?
+            .mul(&w)?
+            .sum_all()
+    };
+    test_utils::check_grad(|values| f(values, &rhs), &values)?;
+    test_utils::check_grad(|rhs| f(&values, rhs), &rhs)?;
+
+    let w = Tensor::randn(0f64, 1., (3, 4), dev)?;
+    let f = |values: &Tensor| {
+        SparseTensor::coo(&indices, values, (3, 4))?
+            .to_dense()?
+            .mul(&w)?
+            .sum_all()
+    };
+    test_utils::check_grad(f, &values)?;
+
+    // The gradient flows from the sparse values back to the dense tensor they come from, the
+    // elements that are not stored get a zero gradient.
+    let xs = Var::new(&[[0f64, 1.5, -2.], [0.5, 0., 3.]], dev)?;
+    let rhs = Tensor::ones((3, 2), DType::F64, dev)?;
+    let grads = xs.to_sparse_csr()?.matmul(&rhs)?.sum_all()?.backward()?;
+    let grad_xs = grads.get(&xs).expect("no grad for xs");
+    assert_eq!(grad_xs.to_vec2::<f64>()?, [[0., 2., 2.], [2., 0., 2.]]);
+    Ok(())
+}
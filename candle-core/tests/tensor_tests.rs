@@ -1,4 +1,4 @@
-use candle_core::{test_device, test_utils, DType, Device, IndexOp, Result, Tensor, D};
+use candle_core::{test_device, test_utils, DType, Device, IndexOp, PadMode, Result, Tensor, D};
 
 fn zeros(device: &Device) -> Result<()> {
     let tensor = Tensor::zeros((5, 2), DType::F32, device)?;
@@ -573,6 +573,66 @@ fn argmax(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn asort(device: &Device) -> Result<()> {
+    let data = &[[3f32, 1., 4., 1.1, 5.], [2.1, 1., 7., 8., 2.]];
+    let tensor = Tensor::new(data, device)?;
+    let indexes = tensor.arg_sort_last_dim(true)?;
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[1, 3, 0, 2, 4], [1, 4, 0, 2, 3]],
+    );
+    let indexes = tensor.arg_sort_last_dim(false)?;
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[4, 2, 0, 3, 1], [3, 2, 0, 4, 1]],
+    );
+    let (sorted, indexes) = tensor.sort_last_dim(true)?;
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[1, 3, 0, 2, 4], [1, 4, 0, 2, 3]]
+    );
+    assert_eq!(
+        sorted.to_vec2::<f32>()?,
+        [[1.0, 1.1, 3.0, 4.0, 5.0], [1.0, 2.0, 2.1, 7.0, 8.0]]
+    );
+    // Sorting a non-contiguous tensor.
+    let (sorted, indexes) = tensor.t()?.sort_last_dim(false)?;
+    assert_eq!(
+        indexes.to_vec2::<u32>()?,
+        [[0, 1], [0, 1], [1, 0], [1, 0], [0, 1]]
+    );
+    assert_eq!(
+        sorted.to_vec2::<f32>()?,
+        [[3.0, 2.1], [1.0, 1.0], [7.0, 4.0], [8.0, 1.1], [5.0, 2.0]]
+    );
+    // Equal values keep their original order.
+    let tensor = Tensor::new(&[2u32, 1, 2, 1, 0], device)?;
+    let indexes = tensor.arg_sort_last_dim(true)?;
+    assert_eq!(indexes.to_vec1::<u32>()?, [4, 1, 3, 0, 2]);
+    // Nans are sorted last in both directions.
+    let tensor = Tensor::new(&[1f32, f32::NAN, 3., f32::NAN, 2.], device)?;
+    let indexes = tensor.arg_sort_last_dim(true)?;
+    assert_eq!(indexes.to_vec1::<u32>()?, [0, 4, 2, 1, 3]);
+    let indexes = tensor.arg_sort_last_dim(false)?;
+    assert_eq!(indexes.to_vec1::<u32>()?, [2, 4, 0, 1, 3]);
+    Ok(())
+}
+
+fn topk(device: &Device) -> Result<()> {
+    let data = &[[3f32, 1., 4., 1.1, 5.], [2.1, 1., 7., 8., 2.]];
+    let tensor = Tensor::new(data, device)?;
+    let (values, indexes) = tensor.topk(2, D::Minus1)?;
+    assert_eq!(values.to_vec2::<f32>()?, [[5.0, 4.0], [8.0, 7.0]]);
+    assert_eq!(indexes.to_vec2::<u32>()?, [[4, 2], [3, 2]]);
+    let (values, indexes) = tensor.topk(1, 0)?;
+    assert_eq!(values.to_vec2::<f32>()?, [[3.0, 1.0, 7.0, 8.0, 5.0]]);
+    assert_eq!(indexes.to_vec2::<u32>()?, [[0, 0, 1, 1, 0]]);
+    let (values, _indexes) = tensor.topk(0, 1)?;
+    assert_eq!(values.dims(), [2, 0]);
+    assert!(tensor.topk(6, 1).is_err());
+    Ok(())
+}
+
 fn narrow(device: &Device) -> Result<()> {
     let data = &[[[3f32, 1., 4.], [1., 5., 9.]], [[2., 1., 7.], [8., 2., 8.]]];
     let tensor = Tensor::new(data, device)?;
@@ -681,12 +741,42 @@ fn embeddings(device: &Device) -> Result<()> {
 fn cmp(device: &Device) -> Result<()> {
     let t1 = Tensor::new(&[[0f32, 1f32], [2f32, 3f32], [4f32, 5f32]], device)?;
     let t2 = Tensor::new(&[[1f32, 0f32], [3f32, 3f32], [4f32, 7f32]], device)?;
-    assert_eq!(t1.eq(&t2)?.to_vec2::<u8>()?, &[[0, 0], [0, 1], [1, 0]]);
-    assert_eq!(t1.ne(&t2)?.to_vec2::<u8>()?, &[[1, 1], [1, 0], [0, 1]]);
-    assert_eq!(t1.le(&t2)?.to_vec2::<u8>()?, &[[1, 0], [1, 1], [1, 1]]);
-    assert_eq!(t1.lt(&t2)?.to_vec2::<u8>()?, &[[1, 0], [1, 0], [0, 1]]);
-    assert_eq!(t1.gt(&t2)?.to_vec2::<u8>()?, &[[0, 1], [0, 0], [0, 0]]);
-    assert_eq!(t1.ge(&t2)?.to_vec2::<u8>()?, &[[0, 1], [0, 1], [1, 0]]);
+    assert_eq!(
+        t1.eq(&t2)?.to_vec2::<bool>()?,
+        &[[false, false], [false, true], [true, false]]
+    );
+    assert_eq!(
+        t1.ne(&t2)?.to_vec2::<bool>()?,
+        &[[true, true], [true, false], [false, true]]
+    );
+    assert_eq!(
+        t1.le(&t2)?.to_vec2::<bool>()?,
+        &[[true, false], [true, true], [true, true]]
+    );
+    assert_eq!(
+        t1.lt(&t2)?.to_vec2::<bool>()?,
+        &[[true, false], [true, false], [false, true]]
+    );
+    assert_eq!(
+        t1.gt(&t2)?.to_vec2::<bool>()?,
+        &[[false, true], [false, false], [false, false]]
+    );
+    assert_eq!(
+        t1.ge(&t2)?.to_vec2::<bool>()?,
+        &[[false, true], [false, true], [true, false]]
+    );
+
+    // Comparison results are typed as booleans and can be used as masks.
+    let mask = t1.lt(&t2)?;
+    assert_eq!(mask.dtype(), DType::Bool);
+    assert_eq!(
+        mask.where_cond(&t1, &t2)?.to_vec2::<f32>()?,
+        &[[0., 0.], [2., 3.], [4., 5.]]
+    );
+    assert_eq!(
+        mask.to_dtype(DType::F32)?.to_vec2::<f32>()?,
+        &[[1., 0.], [1., 0.], [0., 1.]]
+    );
     Ok(())
 }
 
@@ -969,6 +1059,62 @@ fn broadcast_matmul(device: &Device) -> Result<()> {
     Ok(())
 }
 
+fn einsum(device: &Device) -> Result<()> {
+    let a = Tensor::arange(0f32, 6., device)?.reshape((2, 3))?;
+    let b = Tensor::arange(0f32, 12., device)?.reshape((3, 4))?;
+    let c = Tensor::einsum("ij,jk->ik", &[&a, &b])?;
+    assert_eq!(c.to_vec2::<f32>()?, a.matmul(&b)?.to_vec2::<f32>()?);
+    // Implicit output, the labels appearing once are sorted.
+    let c = Tensor::einsum("ij,jk", &[&a, &b])?;
+    assert_eq!(c.to_vec2::<f32>()?, a.matmul(&b)?.to_vec2::<f32>()?);
+    let c = Tensor::einsum("ij->ji", &[&a])?;
+    assert_eq!(c.to_vec2::<f32>()?, a.t()?.to_vec2::<f32>()?);
+    let c = Tensor::einsum("ij->", &[&a])?;
+    assert_eq!(c.to_scalar::<f32>()?, 15.);
+    let c = Tensor::einsum("ij->j", &[&a])?;
+    assert_eq!(c.to_vec1::<f32>()?, [3., 5., 7.]);
+
+    // Trace, diagonal and outer product.
+    let sq = Tensor::arange(0f32, 9., device)?.reshape((3, 3))?;
+    assert_eq!(Tensor::einsum("ii", &[&sq])?.to_scalar::<f32>()?, 12.);
+    assert_eq!(
+        Tensor::einsum("ii->i", &[&sq])?.to_vec1::<f32>()?,
+        [0., 4., 8.]
+    );
+    let u = Tensor::new(&[1f32, 2.], device)?;
+    let v = Tensor::new(&[3f32, 4., 5.], device)?;
+    assert_eq!(
+        Tensor::einsum("i,j->ij", &[&u, &v])?.to_vec2::<f32>()?,
+        [[3., 4., 5.], [6., 8., 10.]]
+    );
+
+    // Batched matmul with an ellipsis and broadcasting.
+    let x = Tensor::randn(0f32, 1., (2, 3, 4, 5), device)?;
+    let y = Tensor::randn(0f32, 1., (3, 5, 6), device)?;
+    let z = Tensor::einsum("...ij,...jk->...ik", &[&x, &y])?;
+    assert_eq!(z.dims(), [2, 3, 4, 6]);
+    let diff = (z - x.broadcast_matmul(&y)?)?.abs()?.sum_all()?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+    let y = Tensor::randn(0f32, 1., (1, 5, 6), device)?;
+    let z = Tensor::einsum("bhij,hjk->bhik", &[&x, &y])?;
+    let diff = (z - x.broadcast_matmul(&y)?)?.abs()?.sum_all()?;
+    assert!(diff.to_scalar::<f32>()? < 1e-4);
+
+    // Three operands, the contraction order should not matter.
+    let w = Tensor::randn(0f32, 1., (4, 2), device)?;
+    let r = Tensor::einsum("ij,jk,kl->il", &[&a, &b, &w])?;
+    let expected = a.matmul(&b)?.matmul(&w)?;
+    let diff = (r - expected)?.abs()?.sum_all()?;
+    assert!(diff.to_scalar::<f32>()? < 1e-3);
+
+    assert!(Tensor::einsum("ij,jk->ik", &[&a]).is_err());
+    assert!(Tensor::einsum("ij,jk->ik", &[&a, &a]).is_err());
+    assert!(Tensor::einsum("ijk->ik", &[&a]).is_err());
+    assert!(Tensor::einsum("ij->iz", &[&a]).is_err());
+    assert!(Tensor::einsum("i1->i", &[&a]).is_err());
+    Ok(())
+}
+
 fn broadcasting(device: &Device) -> Result<()> {
     let t1 = Tensor::arange(0f32, 24f32, device)?.reshape((4, 2, 3))?;
     let t2 = Tensor::new(&[100f32, 200f32], device)?;
@@ -1092,12 +1238,15 @@ test_device!(min, min_cpu, min_gpu, min_metal);
 test_device!(max, max_cpu, max_gpu, max_metal);
 test_device!(argmax, argmax_cpu, argmax_gpu, argmax_metal);
 test_device!(argmin, argmin_cpu, argmin_gpu, argmin_metal);
+test_device!(asort, asort_cpu, asort_gpu, asort_metal);
+test_device!(topk, topk_cpu, topk_gpu, topk_metal);
 test_device!(transpose, transpose_cpu, transpose_gpu, transpose_metal);
 test_device!(unary_op, unary_op_cpu, unary_op_gpu, unary_op_metal);
 test_device!(binary_op, binary_op_cpu, binary_op_gpu, binary_op_metal);
 test_device!(embeddings, embeddings_cpu, embeddings_gpu, embeddings_metal);
 test_device!(cmp, cmp_cpu, cmp_gpu, cmp_metal);
 test_device!(matmul, matmul_cpu, matmul_gpu, matmul_metal);
+test_device!(einsum, einsum_cpu, einsum_gpu, einsum_metal);
 test_device!(
     broadcast_matmul,
     broadcast_matmul_cpu,
@@ -1169,6 +1318,44 @@ fn i64_abs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn small_int_and_bool_dtypes() -> Result<()> {
+    let t = Tensor::new(&[-3i8, 0, 5], &Device::Cpu)?;
+    assert_eq!(t.dtype(), DType::I8);
+    assert_eq!(t.abs()?.to_vec1::<i8>()?, [3, 0, 5]);
+    assert_eq!(t.to_dtype(DType::I16)?.to_vec1::<i16>()?, [-3, 0, 5]);
+    assert_eq!(t.to_dtype(DType::I32)?.to_vec1::<i32>()?, [-3, 0, 5]);
+    assert_eq!(t.to_dtype(DType::F32)?.to_vec1::<f32>()?, [-3., 0., 5.]);
+    assert_eq!(
+        t.to_dtype(DType::Bool)?.to_vec1::<bool>()?,
+        [true, false, true]
+    );
+
+    let t = Tensor::new(&[1i32, 2, 3], &Device::Cpu)?;
+    assert_eq!(((&t * 2.)? + 1.)?.to_vec1::<i32>()?, [3, 5, 7]);
+    assert_eq!(t.sum_all()?.to_vec0::<i32>()?, 6);
+
+    let mask = Tensor::new(&[true, false, true], &Device::Cpu)?;
+    assert_eq!(mask.dtype(), DType::Bool);
+    assert_eq!(mask.to_dtype(DType::U8)?.to_vec1::<u8>()?, [1, 0, 1]);
+    let on_false = Tensor::zeros(3, DType::I32, &Device::Cpu)?;
+    assert_eq!(mask.where_cond(&t, &on_false)?.to_vec1::<i32>()?, [1, 0, 3]);
+    let cat = Tensor::cat(
+        &[&mask, &mask.to_dtype(DType::U8)?.to_dtype(DType::Bool)?],
+        0,
+    )?;
+    assert_eq!(
+        cat.to_vec1::<bool>()?,
+        [true, false, true, true, false, true]
+    );
+    assert!(mask.affine(2., 1.).is_err());
+    assert_eq!(
+        format!("{mask}"),
+        "[true , false, true ]\nTensor[[3], bool]"
+    );
+    Ok(())
+}
+
 #[test]
 fn tril_triu_eye() -> Result<()> {
     let t = Tensor::tril2(4, DType::F32, &Device::Cpu)?;
@@ -1253,3 +1440,249 @@ fn logsumexp() -> Result<()> {
     assert_close(&output, &expected, 0.00001)?;
     Ok(())
 }
+
+fn strided_views(device: &Device) -> Result<()> {
+    let t = Tensor::arange(0u32, 12, device)?.reshape((3, 4))?;
+    // Overlapping windows, on a contiguous and on a transposed tensor.
+    let windows = t.unfold(1, 2, 1)?;
+    assert_eq!(windows.dims(), &[3, 3, 2]);
+    assert_eq!(windows.i(1)?.to_vec2::<u32>()?, &[[4, 5], [5, 6], [6, 7]]);
+    let windows = t.t()?.unfold(1, 2, 1)?;
+    assert_eq!(windows.dims(), &[4, 2, 2]);
+    assert_eq!(windows.i(3)?.to_vec2::<u32>()?, &[[3, 7], [7, 11]]);
+    let windows = Tensor::arange(0u32, 10, device)?.unfold(0, 4, 3)?;
+    assert_eq!(
+        windows.to_vec2::<u32>()?,
+        &[[0, 1, 2, 3], [3, 4, 5, 6], [6, 7, 8, 9]]
+    );
+    assert!(t.unfold(0, 4, 1).is_err());
+    assert!(t.unfold(0, 2, 0).is_err());
+
+    assert_eq!(t.diagonal(1, 0, 1)?.to_vec1::<u32>()?, &[1, 6, 11]);
+    assert_eq!(t.diagonal(0, 1, 0)?.to_vec1::<u32>()?, &[0, 5, 10]);
+    assert_eq!(t.t()?.diagonal(-1, 0, 1)?.to_vec1::<u32>()?, &[1, 6, 11]);
+    assert_eq!(t.diagonal(-3, 0, 1)?.dims(), &[0]);
+    assert_eq!(t.diagonal(5, 0, 1)?.dims(), &[0]);
+    let batched = Tensor::arange(0u32, 18, device)?.reshape((3, 2, 3))?;
+    assert_eq!(
+        batched.diagonal(0, 0, 2)?.to_vec2::<u32>()?,
+        &[[0, 7, 14], [3, 10, 17]]
+    );
+
+    let strided = t.t()?.as_strided((2, 3), &[1, 2], 1)?;
+    assert_eq!(strided.to_vec2::<u32>()?, &[[4, 1, 9], [8, 5, 2]]);
+    assert!(t.as_strided((2, 3), &[6, 1], 4).is_err());
+    assert!(t.as_strided((2, 3), &[1], 0).is_err());
+
+    assert_eq!(
+        t.flip((0, 1))?.to_vec2::<u32>()?,
+        &[[11, 10, 9, 8], [7, 6, 5, 4], [3, 2, 1, 0]]
+    );
+    assert_eq!(
+        t.t()?.flip(0)?.to_vec2::<u32>()?,
+        &[[3, 7, 11], [2, 6, 10], [1, 5, 9], [0, 4, 8]]
+    );
+    assert_eq!(
+        t.roll(5, 1)?.to_vec2::<u32>()?,
+        &[[3, 0, 1, 2], [7, 4, 5, 6], [11, 8, 9, 10]]
+    );
+    assert_eq!(t.roll(-4, 1)?.to_vec2::<u32>()?, t.to_vec2::<u32>()?);
+    assert_eq!(
+        t.roll(1, 0)?.to_vec2::<u32>()?,
+        &[[8, 9, 10, 11], [0, 1, 2, 3], [4, 5, 6, 7]]
+    );
+
+    let t = Tensor::ones((2, 3, 3), DType::F32, device)?;
+    assert_eq!(
+        t.tril(0)?.i(1)?.to_vec2::<f32>()?,
+        &[[1., 0., 0.], [1., 1., 0.], [1., 1., 1.]]
+    );
+    assert_eq!(
+        t.tril(-1)?.i(0)?.to_vec2::<f32>()?,
+        &[[0., 0., 0.], [1., 0., 0.], [1., 1., 0.]]
+    );
+    assert_eq!(
+        t.triu(-1)?.i(0)?.to_vec2::<f32>()?,
+        &[[1., 1., 1.], [1., 1., 1.], [0., 1., 1.]]
+    );
+    assert_eq!(
+        t.triu(0)?.sum_all()?.to_vec0::<f32>()?,
+        Tensor::triu2(3, DType::F32, device)?
+            .sum_all()?
+            .to_vec0::<f32>()?
+            * 2.
+    );
+    assert!(Tensor::ones(3, DType::F32, device)?.tril(0).is_err());
+    Ok(())
+}
+
+test_device!(
+    strided_views,
+    strided_views_cpu,
+    strided_views_gpu,
+    strided_views_metal
+);
+
+fn pad(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], device)?;
+    assert_eq!(
+        t.pad(&[(1, 1), (1, 1)], PadMode::Reflect)?
+            .to_vec2::<f32>()?,
+        &[
+            [5., 4., 5., 6., 5.],
+            [2., 1., 2., 3., 2.],
+            [5., 4., 5., 6., 5.],
+            [2., 1., 2., 3., 2.]
+        ]
+    );
+    // Reflecting more elements than the dimension size keeps bouncing on the edges.
+    let p = t.i(0)?.pad(&[(4, 0)], PadMode::Reflect)?;
+    assert_eq!(p.to_vec1::<f32>()?, &[1., 2., 3., 2., 1., 2., 3.]);
+    assert_eq!(
+        t.pad(&[(0, 2)], PadMode::Replicate)?.to_vec2::<f32>()?,
+        &[[1., 2., 3., 3., 3.], [4., 5., 6., 6., 6.]]
+    );
+    assert_eq!(
+        t.pad(&[(1, 0), (1, 1)], PadMode::Replicate)?
+            .to_vec2::<f32>()?,
+        &[
+            [1., 1., 2., 3., 3.],
+            [1., 1., 2., 3., 3.],
+            [4., 4., 5., 6., 6.]
+        ]
+    );
+    assert_eq!(
+        t.pad(&[(0, 0), (2, 3)], PadMode::Circular)?
+            .to_vec2::<f32>()?,
+        &[
+            [2., 3., 1., 2., 3., 1., 2., 3.],
+            [5., 6., 4., 5., 6., 4., 5., 6.]
+        ]
+    );
+    assert_eq!(
+        t.t()?.pad(&[(1, 0)], PadMode::Circular)?.to_vec2::<f32>()?,
+        &[[4., 1., 4.], [5., 2., 5.], [6., 3., 6.]]
+    );
+    let t = Tensor::new(&[[1u32, 2], [3, 4]], device)?;
+    assert_eq!(
+        t.pad(&[(0, 1), (1, 0)], PadMode::Constant(7.))?
+            .to_vec2::<u32>()?,
+        &[[7, 1, 2], [7, 3, 4], [7, 7, 7]]
+    );
+    assert!(t
+        .pad(&[(1, 1), (1, 1), (1, 1)], PadMode::Replicate)
+        .is_err());
+
+    let t = Tensor::zeros((2, 0), DType::F32, device)?;
+    assert_eq!(t.pad(&[(1, 1)], PadMode::Constant(0.))?.dims(), &[2, 2]);
+    assert!(t.pad(&[(1, 1)], PadMode::Reflect).is_err());
+    Ok(())
+}
+
+test_device!(pad, pad_cpu, pad_gpu, pad_metal);
+
+fn scan(device: &Device) -> Result<()> {
+    let t = Tensor::new(&[[3f32, 1., 4., 1., 5.], [2., 1., 7., 8., 2.]], device)?;
+    assert_eq!(
+        t.cumprod(1)?.to_vec2::<f32>()?,
+        [[3., 3., 12., 12., 60.], [2., 2., 14., 112., 224.]]
+    );
+    assert_eq!(
+        t.cummax(1)?.to_vec2::<f32>()?,
+        [[3., 3., 4., 4., 5.], [2., 2., 7., 8., 8.]]
+    );
+    assert_eq!(
+        t.cummin(1)?.to_vec2::<f32>()?,
+        [[3., 1., 1., 1., 1.], [2., 1., 1., 1., 1.]]
+    );
+    assert_eq!(
+        t.cummax(0)?.to_vec2::<f32>()?,
+        [[3., 1., 4., 1., 5.], [3., 1., 7., 8., 5.]]
+    );
+    assert_eq!(
+        t.t()?.cumsum(0)?.t()?.to_vec2::<f32>()?,
+        t.cumsum(1)?.to_vec2::<f32>()?
+    );
+    let t = Tensor::zeros((4, 1), DType::F32, device)?;
+    assert_eq!(
+        test_utils::to_vec2_round(&t.logcumsumexp(0)?.exp()?, 4)?,
+        [[1.], [2.], [3.], [4.]]
+    );
+    let t = Tensor::new(
+        &[f32::NEG_INFINITY, f32::NEG_INFINITY, 0., f32::INFINITY],
+        device,
+    )?;
+    assert_eq!(
+        t.logcumsumexp(0)?.to_vec1::<f32>()?,
+        [f32::NEG_INFINITY, f32::NEG_INFINITY, 0., f32::INFINITY]
+    );
+
+    // Nans are propagated by the running extremums.
+    let t = Tensor::new(&[1f32, f32::NAN, 3., 0.], device)?;
+    let nans = |t: Tensor| -> Result<Vec<bool>> {
+        Ok(t.to_vec1::<f32>()?.iter().map(|v| v.is_nan()).collect())
+    };
+    assert_eq!(nans(t.cummax(0)?)?, [false, true, true, true]);
+    assert_eq!(nans(t.cummin(0)?)?, [false, true, true, true]);
+
+    let t = Tensor::new(&[3u32, 1, 4], device)?;
+    assert_eq!(t.cummax(0)?.to_vec1::<u32>()?, [3, 3, 4]);
+    assert_eq!(t.cumsum(0)?.to_vec1::<u32>()?, [3, 4, 8]);
+    assert!(t.logcumsumexp(0).is_err());
+    let t = Tensor::new(&[-2i64, 3, -1], device)?;
+    assert_eq!(t.cumprod(0)?.to_vec1::<i64>()?, [-2, -6, 6]);
+    let t = Tensor::zeros((2, 0), DType::F32, device)?;
+    assert_eq!(t.cumprod(1)?.dims(), [2, 0]);
+    Ok(())
+}
+
+fn associative_scan(device: &Device) -> Result<()> {
+    let a = Tensor::new(&[[0.5f32, 2., 1., 0.25, 3.], [1., 1., -1., 2., 0.]], device)?;
+    let b = Tensor::new(&[[1f32, 2., 3., 4., 5.], [-1., 0., 1., 2., 3.]], device)?;
+    let hs = Tensor::associative_scan(&[a.clone(), b.clone()], 1, |l, r| {
+        let a = (&l[0] * &r[0])?;
+        let b = ((&r[0] * &l[1])? + &r[1])?;
+        Ok(vec![a, b])
+    })?;
+    // The same linear recurrence computed sequentially.
+    let (a, b) = (a.to_vec2::<f32>()?, b.to_vec2::<f32>()?);
+    let expected: Vec<Vec<f32>> = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| {
+            let mut h = 0.;
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| {
+                    h = a * h + b;
+                    h
+                })
+                .collect()
+        })
+        .collect();
+    assert_eq!(hs[1].to_vec2::<f32>()?, expected);
+    let a = Tensor::new(a, device)?;
+    let b = Tensor::new(b, device)?;
+    assert_eq!(a.linear_recurrence(&b, 1)?.to_vec2::<f32>()?, expected);
+    // Strided inputs along a leading dimension.
+    let hs = a.t()?.linear_recurrence(&b.t()?, 0)?;
+    assert_eq!(hs.t()?.to_vec2::<f32>()?, expected);
+    assert!(a.linear_recurrence(&b.narrow(1, 0, 3)?, 1).is_err());
+
+    let t = Tensor::arange(0f32, 7., device)?;
+    let sums = Tensor::associative_scan(std::slice::from_ref(&t), 0, |l, r| {
+        Ok(vec![(&l[0] + &r[0])?])
+    })?;
+    assert_eq!(sums[0].to_vec1::<f32>()?, t.cumsum(0)?.to_vec1::<f32>()?);
+    let res = Tensor::associative_scan(&[t.clone(), t.narrow(0, 0, 3)?], 0, |l, _| Ok(l.to_vec()));
+    assert!(res.is_err());
+    Ok(())
+}
+
+test_device!(scan, scan_cpu, scan_gpu, scan_metal);
+test_device!(
+    associative_scan,
+    associative_scan_cpu,
+    associative_scan_gpu,
+    associative_scan_metal
+);
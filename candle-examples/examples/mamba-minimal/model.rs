@@ -1,7 +1,7 @@
 /// This follows the lines of:
 /// https://github.com/johnma2006/mamba-minimal/blob/master/model.py
 /// Simple, minimal implementation of Mamba in one file of PyTorch.
-use candle::{IndexOp, Module, Result, Tensor, D};
+use candle::{Module, Result, Tensor, D};
 use candle_nn::{RmsNorm, VarBuilder};
 
 use candle_transformers::models::with_tracing::{linear, linear_no_bias, Linear};
@@ -112,14 +112,13 @@ fn selective_scan(
     let delta_b_u = delta
         .broadcast_mul(&b.reshape((b_sz, 1, l, n))?)?
         .broadcast_mul(&u.t()?.reshape((b_sz, d_in, l, 1))?)?;
-    let mut xs = Tensor::zeros((b_sz, d_in, n), delta_a.dtype(), delta_a.device())?;
-    let mut ys = Vec::with_capacity(l);
-    for i in 0..l {
-        xs = ((delta_a.i((.., .., i))? * xs)? + delta_b_u.i((.., .., i))?)?;
-        let y = xs.matmul(&c.i((.., i, ..))?.unsqueeze(2)?)?.squeeze(2)?;
-        ys.push(y)
-    }
-    let ys = Tensor::stack(ys.as_slice(), 1)?;
+    // The recurrence h_i = delta_a_i * h_{i-1} + delta_b_u_i over the sequence dimension.
+    let hs = delta_a.linear_recurrence(&delta_b_u, 2)?;
+    // b d_in l n, b l n -> b l d_in
+    let ys = hs
+        .broadcast_mul(&c.unsqueeze(1)?)?
+        .sum(D::Minus1)?
+        .transpose(1, 2)?;
     ys + u.broadcast_mul(d)
 }
 
@@ -291,6 +291,7 @@ impl EncodecConv1d {
                     stride,
                     groups: 1,
                     dilation: 1,
+                    ..Default::default()
                 },
                 vb.pp("conv"),
             )?,
@@ -303,6 +304,7 @@ impl EncodecConv1d {
                     stride,
                     groups: 1,
                     dilation: 1,
+                    ..Default::default()
                 },
                 vb.pp("conv"),
             )?,
@@ -1,6 +1,34 @@
 //! Convolution Layers.
 use crate::BatchNorm;
-use candle::{Result, Tensor};
+use candle::{PadMode, Result, Tensor};
+
+/// How the input of a convolution layer gets padded, this matches the `padding_mode` argument
+/// of the PyTorch convolution layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingMode {
+    #[default]
+    Zeros,
+    Reflect,
+    Replicate,
+    Circular,
+}
+
+impl PaddingMode {
+    // Pads the trailing `n` dimensions of `xs` and returns the padding that remains to be
+    // applied by the convolution itself.
+    fn pad(&self, xs: &Tensor, padding: usize, n: usize) -> Result<(Tensor, usize)> {
+        let mode = match self {
+            Self::Zeros => return Ok((xs.clone(), padding)),
+            Self::Reflect => PadMode::Reflect,
+            Self::Replicate => PadMode::Replicate,
+            Self::Circular => PadMode::Circular,
+        };
+        if padding == 0 {
+            return Ok((xs.clone(), 0));
+        }
+        Ok((xs.pad(&vec![(padding, padding); n], mode)?, 0))
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Conv1dConfig {
@@ -8,6 +36,7 @@ pub struct Conv1dConfig {
     pub stride: usize,
     pub dilation: usize,
     pub groups: usize,
+    pub padding_mode: PaddingMode,
 }
 
 impl Default for Conv1dConfig {
@@ -17,6 +46,7 @@ impl Default for Conv1dConfig {
             stride: 1,
             dilation: 1,
             groups: 1,
+            padding_mode: PaddingMode::Zeros,
         }
     }
 }
@@ -52,9 +82,10 @@ impl Conv1d {
 
 impl crate::Module for Conv1d {
     fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        let (x, padding) = self.config.padding_mode.pad(x, self.config.padding, 1)?;
         let x = x.conv1d(
             &self.weight,
-            self.config.padding,
+            padding,
             self.config.stride,
             self.config.dilation,
             self.config.groups,
@@ -137,6 +168,7 @@ pub struct Conv2dConfig {
     pub stride: usize,
     pub dilation: usize,
     pub groups: usize,
+    pub padding_mode: PaddingMode,
 }
 
 impl Default for Conv2dConfig {
@@ -146,6 +178,7 @@ impl Default for Conv2dConfig {
             stride: 1,
             dilation: 1,
             groups: 1,
+            padding_mode: PaddingMode::Zeros,
         }
     }
 }
@@ -201,9 +234,10 @@ impl Conv2d {
 
 impl crate::Module for Conv2d {
     fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        let (x, padding) = self.config.padding_mode.pad(x, self.config.padding, 2)?;
         let x = x.conv2d(
             &self.weight,
-            self.config.padding,
+            padding,
             self.config.stride,
             self.config.dilation,
             self.config.groups,
@@ -280,6 +314,138 @@ impl crate::Module for ConvTranspose2d {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conv3dConfig {
+    pub padding: usize,
+    pub stride: usize,
+    pub dilation: usize,
+    pub groups: usize,
+    pub padding_mode: PaddingMode,
+}
+
+impl Default for Conv3dConfig {
+    fn default() -> Self {
+        Self {
+            padding: 0,
+            stride: 1,
+            dilation: 1,
+            groups: 1,
+            padding_mode: PaddingMode::Zeros,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Conv3d {
+    weight: Tensor,
+    bias: Option<Tensor>,
+    config: Conv3dConfig,
+}
+
+impl Conv3d {
+    pub fn new(weight: Tensor, bias: Option<Tensor>, config: Conv3dConfig) -> Self {
+        Self {
+            weight,
+            bias,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Conv3dConfig {
+        &self.config
+    }
+
+    pub fn weight(&self) -> &Tensor {
+        &self.weight
+    }
+
+    pub fn bias(&self) -> Option<&Tensor> {
+        self.bias.as_ref()
+    }
+}
+
+impl crate::Module for Conv3d {
+    fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        let (x, padding) = self.config.padding_mode.pad(x, self.config.padding, 3)?;
+        let x = x.conv3d(
+            &self.weight,
+            padding,
+            self.config.stride,
+            self.config.dilation,
+            self.config.groups,
+        )?;
+        match &self.bias {
+            None => Ok(x),
+            Some(bias) => {
+                let b = bias.dims1()?;
+                let bias = bias.reshape((1, b, 1, 1, 1))?;
+                Ok(x.broadcast_add(&bias)?)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvTranspose3dConfig {
+    pub padding: usize,
+    pub output_padding: usize,
+    pub stride: usize,
+    pub dilation: usize,
+    // TODO: support groups.
+}
+
+impl Default for ConvTranspose3dConfig {
+    fn default() -> Self {
+        Self {
+            padding: 0,
+            output_padding: 0,
+            stride: 1,
+            dilation: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConvTranspose3d {
+    weight: Tensor,
+    bias: Option<Tensor>,
+    config: ConvTranspose3dConfig,
+}
+
+impl ConvTranspose3d {
+    pub fn new(weight: Tensor, bias: Option<Tensor>, config: ConvTranspose3dConfig) -> Self {
+        Self {
+            weight,
+            bias,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ConvTranspose3dConfig {
+        &self.config
+    }
+}
+
+impl crate::Module for ConvTranspose3d {
+    fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        let x = x.conv_transpose3d(
+            &self.weight,
+            self.config.padding,
+            self.config.output_padding,
+            self.config.stride,
+            self.config.dilation,
+        )?;
+        match &self.bias {
+            None => Ok(x),
+            Some(bias) => {
+                let b = bias.dims1()?;
+                let bias = bias.reshape((1, b, 1, 1, 1))?;
+                Ok(x.broadcast_add(&bias)?)
+            }
+        }
+    }
+}
+
 pub fn conv1d(
     in_channels: usize,
     out_channels: usize,
@@ -423,3 +589,108 @@ pub fn conv_transpose2d_no_bias(
     )?;
     Ok(ConvTranspose2d::new(ws, None, cfg))
 }
+
+pub fn conv3d(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: Conv3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<Conv3d> {
+    let init_ws = crate::init::DEFAULT_KAIMING_NORMAL;
+    let ws = vb.get_with_hints(
+        (
+            out_channels,
+            in_channels / cfg.groups,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init_ws,
+    )?;
+    let bound = 1. / (in_channels as f64).sqrt();
+    let init_bs = crate::Init::Uniform {
+        lo: -bound,
+        up: bound,
+    };
+    let bs = vb.get_with_hints(out_channels, "bias", init_bs)?;
+    Ok(Conv3d::new(ws, Some(bs), cfg))
+}
+
+pub fn conv3d_no_bias(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: Conv3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<Conv3d> {
+    let init_ws = crate::init::DEFAULT_KAIMING_NORMAL;
+    let ws = vb.get_with_hints(
+        (
+            out_channels,
+            in_channels / cfg.groups,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init_ws,
+    )?;
+    Ok(Conv3d::new(ws, None, cfg))
+}
+
+pub fn conv_transpose3d(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: ConvTranspose3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<ConvTranspose3d> {
+    let bound =
+        1. / (out_channels as f64 * (kernel_size * kernel_size * kernel_size) as f64).sqrt();
+    let init = crate::Init::Uniform {
+        lo: -bound,
+        up: bound,
+    };
+    let ws = vb.get_with_hints(
+        (
+            in_channels,
+            out_channels,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init,
+    )?;
+    let bs = vb.get_with_hints(out_channels, "bias", init)?;
+    Ok(ConvTranspose3d::new(ws, Some(bs), cfg))
+}
+
+pub fn conv_transpose3d_no_bias(
+    in_channels: usize,
+    out_channels: usize,
+    kernel_size: usize,
+    cfg: ConvTranspose3dConfig,
+    vb: crate::VarBuilder,
+) -> Result<ConvTranspose3d> {
+    let bound =
+        1. / (out_channels as f64 * (kernel_size * kernel_size * kernel_size) as f64).sqrt();
+    let init = crate::Init::Uniform {
+        lo: -bound,
+        up: bound,
+    };
+    let ws = vb.get_with_hints(
+        (
+            in_channels,
+            out_channels,
+            kernel_size,
+            kernel_size,
+            kernel_size,
+        ),
+        "weight",
+        init,
+    )?;
+    Ok(ConvTranspose3d::new(ws, None, cfg))
+}
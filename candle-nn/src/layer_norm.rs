@@ -66,6 +66,7 @@ pub struct LayerNorm {
     bias: Option<Tensor>,
     remove_mean: bool,
     eps: f64,
+    fused: bool,
 }
 
 impl LayerNorm {
@@ -75,6 +76,7 @@ impl LayerNorm {
             bias: Some(bias),
             remove_mean: true,
             eps,
+            fused: false,
         }
     }
 
@@ -84,6 +86,7 @@ impl LayerNorm {
             bias: None,
             remove_mean: true,
             eps,
+            fused: false,
         }
     }
 
@@ -93,9 +96,18 @@ impl LayerNorm {
             bias: None,
             remove_mean: false,
             eps,
+            fused: false,
         }
     }
 
+    /// When set, the elementwise ops of the normalization are fused in a single kernel on cpu,
+    /// see `candle::lazy`. Half precision inputs are then scaled and shifted in f32 before
+    /// getting converted back.
+    pub fn with_fused(mut self, fused: bool) -> Self {
+        self.fused = fused;
+        self
+    }
+
     pub fn weight(&self) -> &Tensor {
         &self.weight
     }
@@ -103,10 +115,43 @@ impl LayerNorm {
     pub fn bias(&self) -> Option<&Tensor> {
         self.bias.as_ref()
     }
+
+    fn forward_fused(&self, x: &Tensor) -> Result<Tensor> {
+        let x_dtype = x.dtype();
+        let internal_dtype = match x_dtype {
+            DType::F16 | DType::BF16 => DType::F32,
+            d => d,
+        };
+        let hidden_size = x.dim(D::Minus1)?;
+        let x = x.to_dtype(internal_dtype)?;
+        // The centered values are computed once, they are read by both the reduction and the
+        // fused kernel.
+        let x = if self.remove_mean {
+            let mean_x = (x.sum_keepdim(D::Minus1)? / hidden_size as f64)?;
+            x.broadcast_sub(&mean_x)?
+        } else {
+            x
+        }
+        .lazy();
+        let norm_x = x
+            .sqr()?
+            .sum_keepdim(D::Minus1)?
+            .affine(1. / hidden_size as f64, self.eps)?;
+        let weight = self.weight.to_dtype(internal_dtype)?.lazy();
+        let x = x.broadcast_div(&norm_x.sqrt()?)?.broadcast_mul(&weight)?;
+        let x = match &self.bias {
+            None => x,
+            Some(bias) => x.broadcast_add(&bias.to_dtype(internal_dtype)?.lazy())?,
+        };
+        x.eval()?.to_dtype(x_dtype)
+    }
 }
 
 impl crate::Module for LayerNorm {
     fn forward(&self, x: &Tensor) -> Result<Tensor> {
+        if self.fused {
+            return self.forward_fused(x);
+        }
         let x_dtype = x.dtype();
         let internal_dtype = match x_dtype {
             DType::F16 | DType::BF16 => DType::F32,
@@ -147,6 +192,7 @@ pub fn layer_norm<C: Into<LayerNormConfig>>(
         bias,
         remove_mean: config.remove_mean,
         eps: config.eps,
+        fused: false,
     })
 }
 
@@ -159,6 +205,11 @@ impl RmsNorm {
         Self(LayerNorm::rms_norm(weight, eps))
     }
 
+    /// Fuses the elementwise ops in a single kernel on cpu, see [`LayerNorm::with_fused`].
+    pub fn with_fused(self, fused: bool) -> Self {
+        Self(self.0.with_fused(fused))
+    }
+
     pub fn into_inner(self) -> LayerNorm {
         self.0
     }
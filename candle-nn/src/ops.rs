@@ -39,6 +39,13 @@ pub fn silu(xs: &Tensor) -> Result<Tensor> {
     xs / (xs.neg()?.exp()? + 1.0)?
 }
 
+/// Same as [`silu`] but the elementwise ops are fused in a single kernel on cpu, see
+/// `candle::lazy`.
+pub fn silu_fused(xs: &Tensor) -> Result<Tensor> {
+    let xs = xs.lazy();
+    xs.div(&xs.neg()?.exp()?.affine(1.0, 1.0)?)?.eval()
+}
+
 pub fn swiglu(xs: &Tensor) -> Result<Tensor> {
     let xs = xs.chunk(2, candle::D::Minus1)?;
     crate::ops::silu(&xs[0])? * &xs[1]
@@ -71,7 +78,7 @@ pub fn dropout(xs: &Tensor, drop_p: f32) -> Result<Tensor> {
     let rand = Tensor::rand(0f32, 1f32, xs.shape(), xs.device())?;
     let scale = 1.0 / (1.0 - drop_p as f64);
     let drop_p = Tensor::new(drop_p, xs.device())?.broadcast_as(xs.shape())?;
-    let mask = (rand.ge(&drop_p)? * scale)?.to_dtype(xs.dtype())?;
+    let mask = (rand.ge(&drop_p)?.to_dtype(xs.dtype())? * scale)?;
     xs * mask
 }
 
@@ -134,7 +141,7 @@ impl candle::CustomOp1 for SoftmaxLastDim {
                         *d /= sum_exp
                     }
                 });
-            let storage = candle::WithDType::to_cpu_storage_owned(dst);
+            let storage = candle::ElemType::to_cpu_storage_owned(dst);
             Ok((storage, Shape::from_dims(dims)))
         }
 
@@ -161,6 +168,8 @@ impl candle::CustomOp1 for SoftmaxLastDim {
 
         struct S;
         impl Map1 for S {
+            const OP: &'static str = "softmax";
+
             fn f<T: DeviceRepr + WithDType>(
                 &self,
                 src: &CudaSlice<T>,
@@ -195,10 +204,7 @@ impl candle::CustomOp1 for SoftmaxLastDim {
         use candle::backend::BackendStorage;
         let dev = storage.device();
         let slice = S.map(&storage.slice, dev, layout)?;
-        let dst = candle::cuda_backend::CudaStorage {
-            slice,
-            device: dev.clone(),
-        };
+        let dst = candle::cuda_backend::CudaStorage::new(slice, dev.clone());
         Ok((dst, layout.shape().clone()))
     }
 
@@ -274,15 +280,5 @@ pub fn pixel_unshuffle(xs: &Tensor, downscale_factor: usize) -> Result<Tensor> {
 
 // https://pytorch.org/docs/stable/generated/torch.nn.ReplicationPad2d.html
 pub fn replication_pad2d(xs: &Tensor, pad: usize) -> Result<Tensor> {
-    match pad {
-        0 => Ok(xs.clone()),
-        1 => {
-            let (_b_size, _c, h, w) = xs.dims4()?;
-            let (first, last) = (xs.narrow(3, 0, 1)?, xs.narrow(3, w - 1, 1)?);
-            let xs = Tensor::cat(&[&first, xs, &last], 3)?;
-            let (first, last) = (xs.narrow(2, 0, 1)?, xs.narrow(2, h - 1, 1)?);
-            Tensor::cat(&[&first, &xs, &last], 2)
-        }
-        n => candle::bail!("replication-pad with a size of {n} is not supported"),
-    }
+    xs.pad(&[(pad, pad), (pad, pad)], candle::PadMode::Replicate)
 }
@@ -29,7 +29,13 @@ impl VarMap {
     /// Save the map in the safetensors format.
     pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
         let tensor_data = self.data.lock().unwrap();
-        let data = tensor_data.iter().map(|(k, v)| (k, v.as_tensor()));
+        let data = tensor_data
+            .iter()
+            .map(|(k, v)| {
+                let view = candle::safetensors::SafetensorsView::try_from(v.as_tensor())?;
+                Ok((k, view))
+            })
+            .collect::<Result<Vec<_>>>()?;
         safetensors::tensor::serialize_to_file(data, &None, path.as_ref())?;
         Ok(())
     }
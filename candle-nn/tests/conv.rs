@@ -0,0 +1,52 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{Device, Tensor};
+use candle_nn::{Conv1d, Conv1dConfig, Conv2d, Conv2dConfig, Module, PaddingMode};
+
+#[test]
+fn conv_padding_mode() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = Tensor::new(&[[[1f32, 2., 3., 4.]]], device)?;
+    let w = Tensor::ones((1, 1, 3), candle::DType::F32, device)?;
+    let conv = |padding_mode| {
+        let cfg = Conv1dConfig {
+            padding: 1,
+            padding_mode,
+            ..Default::default()
+        };
+        Conv1d::new(w.clone(), None, cfg).forward(&xs)
+    };
+    assert_eq!(
+        conv(PaddingMode::Zeros)?.to_vec3::<f32>()?,
+        [[[3., 6., 9., 7.]]]
+    );
+    assert_eq!(
+        conv(PaddingMode::Reflect)?.to_vec3::<f32>()?,
+        [[[5., 6., 9., 10.]]]
+    );
+    assert_eq!(
+        conv(PaddingMode::Replicate)?.to_vec3::<f32>()?,
+        [[[4., 6., 9., 11.]]]
+    );
+    assert_eq!(
+        conv(PaddingMode::Circular)?.to_vec3::<f32>()?,
+        [[[7., 6., 9., 8.]]]
+    );
+
+    // The padding applies to both spatial dimensions of 2d convolutions.
+    let xs = Tensor::new(&[[[[1f32, 2.], [3., 4.]]]], device)?;
+    let w = Tensor::new(&[[[[1f32, 0., 0.], [0., 0., 0.], [0., 0., 0.]]]], device)?;
+    let cfg = Conv2dConfig {
+        padding: 1,
+        padding_mode: PaddingMode::Circular,
+        ..Default::default()
+    };
+    let ys = Conv2d::new(w, None, cfg).forward(&xs)?;
+    assert_eq!(ys.squeeze(0)?.to_vec3::<f32>()?, [[[4., 3.], [2., 1.]]]);
+    Ok(())
+}
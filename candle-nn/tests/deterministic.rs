@@ -0,0 +1,102 @@
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use anyhow::Result;
+use candle::{CpuDeviceConfig, DType, Device, Module, ModuleT, Tensor, D};
+use candle_nn::{loss, ops, Optimizer, VarBuilder, VarMap};
+
+struct Mlp {
+    conv: candle_nn::Conv2d,
+    fc1: candle_nn::Linear,
+    fc2: candle_nn::Linear,
+    dropout: candle_nn::Dropout,
+}
+
+impl Mlp {
+    fn new(vs: VarBuilder) -> Result<Self> {
+        let conv = candle_nn::conv2d(1, 4, 3, Default::default(), vs.pp("conv"))?;
+        let fc1 = candle_nn::linear(4 * 26 * 26, 64, vs.pp("fc1"))?;
+        let fc2 = candle_nn::linear(64, 10, vs.pp("fc2"))?;
+        let dropout = candle_nn::Dropout::new(0.5);
+        Ok(Self {
+            conv,
+            fc1,
+            fc2,
+            dropout,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor, train: bool) -> Result<Tensor> {
+        let xs = self.conv.forward(xs)?.relu()?.flatten_from(1)?;
+        let xs = self.fc1.forward(&xs)?.relu()?;
+        let xs = self.dropout.forward_t(&xs, train)?;
+        Ok(self.fc2.forward(&xs)?)
+    }
+}
+
+// A small version of the mnist-training example on random data, returns the losses and the
+// final weights.
+fn train(num_threads: usize) -> Result<(Vec<f32>, Vec<f32>)> {
+    let dev = &Device::CpuPool(CpuDeviceConfig::new(num_threads)?);
+    dev.set_seed(299792458)?;
+    let xs = Tensor::rand(0f32, 1., (16, 1, 28, 28), dev)?;
+    let ys = Tensor::rand(0f32, 10., 16, dev)?.to_dtype(DType::U32)?;
+    let varmap = VarMap::new();
+    let vs = VarBuilder::from_varmap(&varmap, DType::F32, dev);
+    let model = Mlp::new(vs)?;
+    let mut opt = candle_nn::AdamW::new_lr(varmap.all_vars(), 1e-3)?;
+    let mut losses = vec![];
+    for _step in 0..3 {
+        let logits = model.forward(&xs, true)?;
+        let log_sm = ops::log_softmax(&logits, D::Minus1)?;
+        let loss = loss::nll(&log_sm, &ys)?;
+        opt.backward_step(&loss)?;
+        losses.push(loss.to_vec0::<f32>()?);
+    }
+    let ws = model.fc1.weight().flatten_all()?.to_vec1::<f32>()?;
+    Ok((losses, ws))
+}
+
+#[test]
+fn deterministic_training() -> Result<()> {
+    candle::set_deterministic(true);
+    assert!(candle::is_deterministic());
+
+    // Each random op gets its own stream, the streams restart when setting the seed.
+    let dev = &Device::Cpu;
+    dev.set_seed(42)?;
+    let a = Tensor::randn(0f32, 1., 16, dev)?.to_vec1::<f32>()?;
+    let b = Tensor::randn(0f32, 1., 16, dev)?.to_vec1::<f32>()?;
+    dev.set_seed(42)?;
+    let c = Tensor::randn(0f32, 1., 16, dev)?.to_vec1::<f32>()?;
+    assert_ne!(a, b);
+    assert_eq!(a, c);
+
+    // Large matmuls give bit-identical results whatever the number of threads.
+    let lhs = Tensor::randn(0f32, 1., (2, 128, 256), dev)?;
+    let rhs = Tensor::randn(0f32, 1., (256, 192), dev)?;
+    let matmul = |num_threads| -> Result<Vec<u32>> {
+        let dev = &Device::CpuPool(CpuDeviceConfig::new(num_threads)?);
+        let prod = lhs.to_device(dev)?.broadcast_matmul(&rhs.to_device(dev)?)?;
+        let prod = prod.flatten_all()?.to_vec1::<f32>()?;
+        Ok(prod.iter().map(|v| v.to_bits()).collect())
+    };
+    let expected = matmul(1)?;
+    for num_threads in [2, 3, 8] {
+        assert!(matmul(num_threads)? == expected, "{num_threads}");
+    }
+
+    let (losses, ws) = train(1)?;
+    assert!(losses.iter().all(|l| l.is_finite()));
+    for num_threads in [2, 5] {
+        let (other_losses, other_ws) = train(num_threads)?;
+        let bits = |vs: &[f32]| vs.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&losses), bits(&other_losses));
+        assert!(bits(&ws) == bits(&other_ws), "{num_threads}");
+    }
+    candle::set_deterministic(false);
+    Ok(())
+}
@@ -42,5 +42,38 @@ fn layer_norm() -> Result<()> {
         test_utils::to_vec3_round(&std, 4)?,
         [[[1.7321], [1.7321], [1.7321]]]
     );
+    // The fused version of the layer gives the same result.
+    let fused = ln.with_fused(true).forward(&inp)?;
+    assert_eq!(
+        test_utils::to_vec3_round(&fused, 4)?,
+        test_utils::to_vec3_round(&res, 4)?
+    );
+    Ok(())
+}
+
+#[test]
+fn rms_norm() -> Result<()> {
+    let device = &Device::Cpu;
+    let xs = Tensor::new(&[[[1f32, 2., 3.], [4., -5., 6.]]], device)?;
+    let w = Tensor::new(&[1f32, 2., 0.5], device)?;
+    let expected = {
+        let norm = (xs.sqr()?.mean_keepdim(2)? + 1e-5)?.sqrt()?;
+        xs.broadcast_div(&norm)?.broadcast_mul(&w)?
+    };
+    for fused in [false, true] {
+        let rms = candle_nn::RmsNorm::new(w.clone(), 1e-5).with_fused(fused);
+        let res = rms.forward(&xs)?;
+        assert_eq!(
+            test_utils::to_vec3_round(&res, 4)?,
+            test_utils::to_vec3_round(&expected, 4)?
+        );
+        // Half precision inputs are normalized in f32 and converted back.
+        let w = w.to_dtype(candle::DType::BF16)?;
+        let rms = candle_nn::RmsNorm::new(w, 1e-5).with_fused(fused);
+        let res = rms.forward(&xs.to_dtype(candle::DType::BF16)?)?;
+        assert_eq!(res.dtype(), candle::DType::BF16);
+        let diff = (res.to_dtype(candle::DType::F32)? - &expected)?.abs()?;
+        assert!(diff.flatten_all()?.max(0)?.to_scalar::<f32>()? < 0.05);
+    }
     Ok(())
 }
@@ -62,3 +62,15 @@ fn softmax_numerical_stability() -> Result<()> {
     assert_eq!(softmax.to_vec1::<f32>()?, &[1f32, 0.]);
     Ok(())
 }
+
+#[test]
+fn silu() -> Result<()> {
+    let dev = &Device::Cpu;
+    let xs = Tensor::new(&[-2f32, -0.5, 0., 1., 3.], dev)?;
+    let silu = candle_nn::ops::silu(&xs)?;
+    let expected = (&xs / (xs.neg()?.exp()? + 1.)?)?;
+    assert_eq!(silu.to_vec1::<f32>()?, expected.to_vec1::<f32>()?);
+    let silu = candle_nn::ops::silu_fused(&xs)?;
+    assert_eq!(silu.to_vec1::<f32>()?, expected.to_vec1::<f32>()?);
+    Ok(())
+}
@@ -1,15 +1,19 @@
 use crate::onnx;
 use crate::onnx::attribute_proto::AttributeType;
 use crate::onnx::tensor_proto::DataType;
-use candle::{bail, DType, Device, Result, Tensor};
+use candle::{bail, DType, Device, PadMode, Result, Tensor};
 use std::collections::HashMap;
 
 pub type Value = Tensor;
 
 pub fn dtype(dt: DataType) -> Option<DType> {
     match dt {
+        DataType::Bool => Some(DType::Bool),
         DataType::Uint8 => Some(DType::U8),
         DataType::Uint32 => Some(DType::U32),
+        DataType::Int8 => Some(DType::I8),
+        DataType::Int16 => Some(DType::I16),
+        DataType::Int32 => Some(DType::I32),
         DataType::Int64 => Some(DType::I64),
         DataType::Float16 => Some(DType::F16),
         DataType::Float => Some(DType::F32),
@@ -101,18 +105,6 @@ fn get_attr_opt<'a, T: Attr + ?Sized>(
 pub fn get_tensor(t: &onnx::TensorProto, name: &str) -> Result<Tensor> {
     let dims: Vec<usize> = t.dims.iter().map(|&x| x as usize).collect();
     match DataType::try_from(t.data_type) {
-        Ok(DataType::Int32) => {
-            if t.int32_data.is_empty() {
-                let len = t.raw_data.len() / 4;
-                let data: &[i32] =
-                    unsafe { std::slice::from_raw_parts(t.raw_data.as_ptr() as *const i32, len) };
-                let data = data.iter().map(|v| *v as i64).collect::<Vec<_>>();
-                Tensor::from_vec(data, len, &Device::Cpu)
-            } else {
-                let data = t.int32_data.iter().map(|v| *v as i64).collect::<Vec<_>>();
-                Tensor::from_vec(data, t.int32_data.len(), &Device::Cpu)
-            }
-        }
         Ok(dt) => match dtype(dt) {
             Some(dt) => {
                 if dt == DType::F32 && !t.float_data.is_empty() {
@@ -121,6 +113,9 @@ pub fn get_tensor(t: &onnx::TensorProto, name: &str) -> Result<Tensor> {
                     Tensor::from_slice(&t.double_data, dims.as_slice(), &Device::Cpu)
                 } else if dt == DType::I64 && !t.int64_data.is_empty() {
                     Tensor::from_slice(&t.int64_data, dims.as_slice(), &Device::Cpu)
+                } else if !t.int32_data.is_empty() && (dt.is_int() || dt.is_bool()) {
+                    // The int32 field also stores the bool, uint8, int8 and int16 values.
+                    Tensor::from_slice(&t.int32_data, dims.as_slice(), &Device::Cpu)?.to_dtype(dt)
                 } else {
                     Tensor::from_raw_buffer(
                         t.raw_data.as_slice(),
@@ -727,7 +722,6 @@ pub fn simple_eval(
                 let input = get(&node.input[0])?;
                 let dt: i64 = *get_attr(node, "to")?;
                 let dtype = match DataType::try_from(dt as i32) {
-                    Ok(DataType::Int32) => DType::I64,
                     Ok(dt) => match dtype(dt) {
                         Some(dt) => dt,
                         None => {
@@ -760,6 +754,58 @@ pub fn simple_eval(
                 let output = input.cumsum(axis as usize)?;
                 values.insert(node.output[0].clone(), output);
             }
+            // https://github.com/onnx/onnx/blob/main/docs/Operators.md#Pad
+            "Pad" => {
+                let xs = get(&node.input[0])?;
+                let mode = match get_attr_opt::<str>(node, "mode")?.unwrap_or("constant") {
+                    "constant" => {
+                        let value = match node.input.get(2) {
+                            Some(name) if !name.is_empty() => get(name)?
+                                .to_dtype(DType::F64)?
+                                .flatten_all()?
+                                .to_vec1::<f64>()?
+                                .first()
+                                .copied()
+                                .unwrap_or(0.),
+                            _ => 0.,
+                        };
+                        PadMode::Constant(value)
+                    }
+                    "reflect" => PadMode::Reflect,
+                    "edge" => PadMode::Replicate,
+                    "wrap" => PadMode::Circular,
+                    mode => bail!("unsupported mode {mode} for pad {}", node.name),
+                };
+                let pads = get(&node.input[1])?.to_vec1::<i64>()?;
+                let axes = match node.input.get(3) {
+                    Some(name) if !name.is_empty() => get(name)?
+                        .to_vec1::<i64>()?
+                        .iter()
+                        .map(|&i| xs.normalize_axis(i))
+                        .collect::<Result<Vec<_>>>()?,
+                    _ => (0..xs.rank()).collect(),
+                };
+                if pads.len() != 2 * axes.len() {
+                    bail!(
+                        "pad {} expects {} pads for {} axes, got {}",
+                        node.name,
+                        2 * axes.len(),
+                        axes.len(),
+                        pads.len()
+                    )
+                }
+                // The pads are given as [begin_0, ..., begin_n, end_0, ..., end_n].
+                let mut pairs = vec![(0, 0); xs.rank()];
+                for (i, &axis) in axes.iter().enumerate() {
+                    let (before, after) = (pads[i], pads[i + axes.len()]);
+                    if before < 0 || after < 0 {
+                        bail!("negative pads are not supported in pad {}", node.name)
+                    }
+                    pairs[axis] = (before as usize, after as usize)
+                }
+                let output = xs.pad(&pairs, mode)?;
+                values.insert(node.output[0].clone(), output);
+            }
             op_type => bail!("unsupported op_type {op_type} for op {node:?}"),
         }
     }
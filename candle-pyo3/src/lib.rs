@@ -17,7 +17,7 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use ::candle::{quantized::QTensor, DType, Device, Module, Tensor, WithDType};
+use ::candle::{quantized::QTensor, DType, Device, ElemType, Module, Tensor};
 
 mod utils;
 use utils::wrap_err;
@@ -83,7 +83,7 @@ enum PyDevice {
 impl PyDevice {
     fn from_device(device: &Device) -> Self {
         match device {
-            Device::Cpu => Self::Cpu,
+            Device::Cpu | Device::CpuPool(_) => Self::Cpu,
             Device::Cuda(_) => Self::Cuda,
             Device::Metal(_) => Self::Metal,
         }
@@ -137,7 +137,7 @@ impl ToPyObject for PyDevice {
     }
 }
 
-trait PyWithDType: WithDType {
+trait PyWithDType: ElemType {
     fn to_py(&self, py: Python<'_>) -> PyObject;
 }
 
@@ -151,6 +151,10 @@ macro_rules! pydtype {
     };
 }
 
+pydtype!(bool, |v| v);
+pydtype!(i8, |v| v);
+pydtype!(i16, |v| v);
+pydtype!(i32, |v| v);
 pydtype!(i64, |v| v);
 pydtype!(u8, |v| v);
 pydtype!(u32, |v| v);
@@ -198,13 +202,20 @@ trait MapDType {
 
     fn map(&self, t: &Tensor) -> PyResult<Self::Output> {
         match t.dtype() {
+            DType::Bool => self.f::<bool>(t),
             DType::U8 => self.f::<u8>(t),
             DType::U32 => self.f::<u32>(t),
+            DType::I8 => self.f::<i8>(t),
+            DType::I16 => self.f::<i16>(t),
+            DType::I32 => self.f::<i32>(t),
             DType::I64 => self.f::<i64>(t),
             DType::BF16 => self.f::<bf16>(t),
             DType::F16 => self.f::<f16>(t),
             DType::F32 => self.f::<f32>(t),
             DType::F64 => self.f::<f64>(t),
+            dtype @ (DType::C64 | DType::C128) => Err(PyTypeError::new_err(format!(
+                "complex dtype {dtype:?} is not supported"
+            ))),
         }
     }
 }
@@ -1598,8 +1609,12 @@ fn candle(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyTensor>()?;
     m.add_class::<PyQTensor>()?;
     m.add_class::<PyDType>()?;
+    m.add("bool", PyDType(DType::Bool))?;
     m.add("u8", PyDType(DType::U8))?;
     m.add("u32", PyDType(DType::U32))?;
+    m.add("i8", PyDType(DType::I8))?;
+    m.add("i16", PyDType(DType::I16))?;
+    m.add("i32", PyDType(DType::I32))?;
     m.add("i64", PyDType(DType::I64))?;
     m.add("bf16", PyDType(DType::BF16))?;
     m.add("f16", PyDType(DType::F16))?;
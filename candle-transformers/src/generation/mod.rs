@@ -38,26 +38,27 @@ impl LogitsProcessor {
         Ok(next_token)
     }
 
-    fn sample_topp(&mut self, prs: &mut Vec<f32>, top_p: f32) -> Result<u32> {
+    fn sample_topp(&mut self, prs: &Tensor, top_p: f32) -> Result<u32> {
         // top-p sampling (or "nucleus sampling") samples from the smallest set of
         // tokens that exceed probability top_p. This way we never sample tokens that
         // have very low probabilities and are less likely to go "off the rails".
-        let mut argsort_indices = (0..prs.len()).collect::<Vec<_>>();
 
         // Sort by descending probability.
-        argsort_indices.sort_by(|&i, &j| prs[j].partial_cmp(&prs[i]).unwrap());
+        let argsort_indices = prs.arg_sort_last_dim(false)?.to_vec1::<u32>()?;
+        let mut prs: Vec<f32> = prs.to_vec1()?;
 
         // Clamp smaller probabilities to zero.
         let mut cumsum = 0.;
-        for index in &argsort_indices {
+        for &index in &argsort_indices {
+            let index = index as usize;
             if cumsum >= top_p {
-                prs[*index] = 0.0;
+                prs[index] = 0.0;
             } else {
-                cumsum += prs[*index];
+                cumsum += prs[index];
             }
         }
         // Sample with clamped probabilities.
-        self.sample_multinomial(prs)
+        self.sample_multinomial(&prs)
     }
 
     pub fn sample(&mut self, logits: &Tensor) -> Result<u32> {
@@ -67,14 +68,14 @@ impl LogitsProcessor {
             Some(temperature) => {
                 let logits = &(&logits / temperature)?;
                 let prs = candle_nn::ops::softmax_last_dim(logits)?;
-                let mut prs: Vec<f32> = prs.to_vec1()?;
                 let top_p = self.top_p.unwrap_or(1.);
                 if top_p <= 0.0 || top_p >= 1.0 {
                     // simply sample from the predicted probability distribution
+                    let prs: Vec<f32> = prs.to_vec1()?;
                     self.sample_multinomial(&prs)?
                 } else {
                     // top-p (nucleus) sampling, clamping the least likely tokens to zero
-                    self.sample_topp(&mut prs, top_p as f32)?
+                    self.sample_topp(&prs, top_p as f32)?
                 }
             }
         };
@@ -93,7 +93,7 @@ impl candle::CustomOp3 for Add3 {
                     }
                 }
             });
-        let dst = candle::WithDType::to_cpu_storage_owned(dst);
+        let dst = candle::ElemType::to_cpu_storage_owned(dst);
         Ok((dst, (b, q_h * q_w, k_h * k_w).into()))
     }
 }
@@ -1,15 +1,59 @@
-use candle::quantized::QTensor;
+use candle::quantized::{gguf_file, QTensor};
 use candle::{Device, Result, Shape};
 use std::sync::Arc;
 
+// The tensors are either all loaded in memory, or created on demand using the blocks of a memory
+// mapped file.
+enum Tensors {
+    Loaded(std::collections::HashMap<String, Arc<QTensor>>),
+    Mmaped(gguf_file::MmapedContent),
+}
+
+impl Tensors {
+    fn get(&self, name: &str) -> Result<Option<Arc<QTensor>>> {
+        match self {
+            Self::Loaded(data) => Ok(data.get(name).cloned()),
+            Self::Mmaped(mmaped) => {
+                if mmaped.content().tensor_infos.contains_key(name) {
+                    Ok(Some(Arc::new(mmaped.tensor(name)?)))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        match self {
+            Self::Loaded(data) => data.contains_key(name),
+            Self::Mmaped(mmaped) => mmaped.content().tensor_infos.contains_key(name),
+        }
+    }
+}
+
 // VarBuilder specialized for QTensors
 pub struct VarBuilder {
-    data: Arc<std::collections::HashMap<String, Arc<QTensor>>>,
+    data: Arc<Tensors>,
     path: Vec<String>,
     device: Device,
 }
 
 impl VarBuilder {
+    /// Creates a var builder that reads the tensors lazily from a memory mapped GGUF file, the
+    /// tensor blocks are used in place rather than being copied.
+    ///
+    /// # Safety
+    ///
+    /// The unsafe is inherited from [`gguf_file::MmapedContent::new`].
+    pub unsafe fn from_mmaped_gguf<P: AsRef<std::path::Path>>(p: P) -> Result<Self> {
+        let mmaped = gguf_file::MmapedContent::new(p)?;
+        Ok(Self {
+            data: Arc::new(Tensors::Mmaped(mmaped)),
+            path: Vec::new(),
+            device: Device::Cpu,
+        })
+    }
+
     pub fn from_gguf<P: AsRef<std::path::Path>>(p: P) -> Result<Self> {
         let mut file = std::fs::File::open(p)?;
         let content = candle::quantized::gguf_file::Content::read(&mut file)?;
@@ -19,7 +63,7 @@ impl VarBuilder {
             data.insert(tensor_name.to_string(), Arc::new(tensor));
         }
         Ok(Self {
-            data: Arc::new(data),
+            data: Arc::new(Tensors::Loaded(data)),
             path: Vec::new(),
             device: Device::Cpu,
         })
@@ -34,7 +78,7 @@ impl VarBuilder {
             data.insert(tensor_name.to_string(), Arc::new(tensor));
         }
         Ok(Self {
-            data: Arc::new(data),
+            data: Arc::new(Tensors::Loaded(data)),
             path: Vec::new(),
             device: Device::Cpu,
         })
@@ -60,7 +104,7 @@ impl VarBuilder {
 
     pub fn get<S: Into<Shape>>(&self, s: S, name: &str) -> Result<Arc<QTensor>> {
         let path = self.path(name);
-        match self.data.get(&path) {
+        match self.data.get(&path)? {
             None => {
                 candle::bail!("cannot find tensor {name}")
             }
@@ -72,18 +116,18 @@ impl VarBuilder {
                         qtensor.shape()
                     )
                 }
-                Ok(qtensor.clone())
+                Ok(qtensor)
             }
         }
     }
 
     pub fn get_no_shape(&self, name: &str) -> Result<Arc<QTensor>> {
         let path = self.path(name);
-        match self.data.get(&path) {
+        match self.data.get(&path)? {
             None => {
                 candle::bail!("cannot find tensor {name}")
             }
-            Some(qtensor) => Ok(qtensor.clone()),
+            Some(qtensor) => Ok(qtensor),
         }
     }
 